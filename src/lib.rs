@@ -3,15 +3,31 @@ pub mod lib {
     pub mod api {
         pub mod dryad;
         pub mod figshare;
+        pub mod ipfs;
+        #[cfg(test)]
+        pub mod test_remote;
         pub mod zenodo;
     }
+    pub mod archive;
     pub mod assets;
+    pub mod cache;
+    pub mod checksums;
+    pub mod client;
     pub mod download;
+    pub mod filter;
+    pub mod history;
+    pub mod http;
+    pub mod interrupt;
+    pub mod lock;
     pub mod macros;
+    pub mod output;
+    pub mod overwrite;
     pub mod progress;
     pub mod project;
     pub mod remote;
+    pub mod report;
     pub mod status;
+    pub mod sync_state;
     pub mod test_utilities;
     pub mod utils;
 }
@@ -4,34 +4,44 @@
 // FigShare's API design is, in my view, a bit awkward.
 // There are articles, files, and projects.
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 use futures_util::StreamExt;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use reqwest::{
-    header::{HeaderMap, HeaderValue},
+    header::{HeaderMap, HeaderValue, CONTENT_LENGTH},
     Method,
 };
-use reqwest::{Body, Client, Response};
+use reqwest::{Body, Response, StatusCode};
 use serde_derive::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::fs;
-use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
 use url::Url;
 
+use crate::lib::cache::ListingCache;
 use crate::lib::data::DataFile;
+use crate::lib::http::{self, HttpError};
+use crate::lib::interrupt::{self, Staged};
 use crate::lib::project::LocalMetadata;
-use crate::lib::remote::{AuthKeys, RemoteFile, RequestData};
+use crate::lib::remote::{
+    duplicate_ids, file_window, mark_duplicates, parse_remote_timestamp, AuthKeys, Quota,
+    RemoteFile, RequestData, UploadOutcome,
+};
+use crate::lib::utils::pluralize;
 #[allow(unused_imports)]
 use crate::{print_info, print_warn};
 
 pub const FIGSHARE_BASE_URL: &str = "https://api.figshare.com/v2/";
 
+// The items requested per page of a listing (FigShare's maximum); a page
+// with fewer is the last.
+const FIGSHARE_PAGE_SIZE: usize = 1000;
+
 // for testing:
 const TEST_TOKEN: &str = "test-token";
 
@@ -40,13 +50,69 @@ fn figshare_api_url() -> String {
     FIGSHARE_BASE_URL.to_string()
 }
 
+/// How a linked directory's files are stored on FigShare (see `sdf link
+/// --figshare-layout`).
+///
+/// FigShare mints one DOI per article, when the article is published.
+/// With `OneArticle` (the default), the directory is a single article, so
+/// its files share one DOI and are cited (and published) together. With
+/// `PerFile`, each file is its own article, titled '<name>: <file>', so
+/// each file gets its own DOI, and each article is published separately.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FigShareLayout {
+    #[default]
+    OneArticle,
+    PerFile,
+}
+
+impl FigShareLayout {
+    pub fn parse(layout: &str) -> Result<Self> {
+        match layout.to_lowercase().as_str() {
+            "one-article" => Ok(FigShareLayout::OneArticle),
+            "per-file" => Ok(FigShareLayout::PerFile),
+            _ => Err(anyhow!(
+                "Unknown FigShare layout '{}'; use 'one-article' or 'per-file'.",
+                layout
+            )),
+        }
+    }
+
+    fn is_one_article(&self) -> bool {
+        *self == FigShareLayout::OneArticle
+    }
+}
+
+/// The item types FigShare accepts as an article's `defined_type` (see
+/// `sdf link --type`).
+pub const FIGSHARE_RECORD_TYPES: &[&str] = &[
+    "figure",
+    "online resource",
+    "preprint",
+    "book",
+    "conference contribution",
+    "media",
+    "dataset",
+    "poster",
+    "journal contribution",
+    "presentation",
+    "thesis",
+    "software",
+];
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct FigShareAPI {
     #[serde(skip_serializing, skip_deserializing, default = "figshare_api_url")]
     base_url: String,
-    // one remote corresponds to a FigShare article
+    // one remote corresponds to a FigShare article (unless the layout is
+    // per-file, where articles are found by their titles)
     article_id: Option<u64>,
     name: String,
+    #[serde(default, skip_serializing_if = "FigShareLayout::is_one_article")]
+    layout: FigShareLayout,
+    // the articles' item type; 'dataset' if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record_type: Option<String>,
     #[serde(skip_serializing, skip_deserializing)]
     token: String,
 }
@@ -68,9 +134,14 @@ pub struct FigShareFile {
     name: String,
     size: u64,
     is_link_only: bool,
-    download_url: String,
+    // empty or null for files that can't be downloaded, e.g. embargoed ones
+    #[serde(default)]
+    download_url: Option<String>,
     supplied_md5: String,
     computed_md5: String,
+    // the article the file is in (set when listing)
+    #[serde(skip)]
+    article_id: u64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -120,16 +191,16 @@ impl<'a> FigShareUpload<'a> {
     async fn init_upload(
         &self,
         data_file: &DataFile,
+        size: u64,
+        article_id: u64,
     ) -> Result<(FigShareFile, FigSharePendingUploadInfo)> {
         debug!("initializing upload of '{:?}'", data_file);
-        // Requires: article ID, in FigShareArticle struct
         // (0) create URL and data
-        let article_id = self.api_instance.get_article_id()?;
         let url = format!("account/articles/{}/files", article_id);
         let data = FigShareNewUpload {
             name: data_file.basename()?,
             md5: data_file.md5.clone(),
-            size: data_file.size,
+            size,
         };
         // (1) issue POST to get location
         let response = self
@@ -154,7 +225,8 @@ impl<'a> FigShareUpload<'a> {
             .api_instance
             .issue_request::<HashMap<String, String>>(Method::GET, &location, None)
             .await?;
-        let upload_info: FigShareFile = response.json().await?;
+        let mut upload_info: FigShareFile = response.json().await?;
+        upload_info.article_id = article_id;
         debug!("upload info: {:?}", upload_info);
 
         // (4) Now, we need to issue another GET to initiate upload.
@@ -176,39 +248,62 @@ impl<'a> FigShareUpload<'a> {
         pending_upload_info: &FigSharePendingUploadInfo,
         path_context: &Path,
     ) -> Result<()> {
-        let full_path = path_context.join(&data_file.path);
+        let full_path = &path_context.join(&data_file.path);
         let url = &upload_info.upload_url;
-        let mut file = fs::File::open(full_path)?;
 
+        // each part is PUT to its own URL, so a failed part can be retried
+        // on its own without restarting the upload
+        let retry_policy = http::RetryPolicy::default();
+        let mut retried = Vec::new();
         for part in &pending_upload_info.parts {
             let start_offset = part.start_offset;
             let end_offset = part.end_offset;
 
-            // get the binary data between these offsets
-            file.seek(SeekFrom::Start(start_offset))?;
-            let mut data = vec![0u8; (end_offset - start_offset + 1) as usize];
-            file.read_exact(&mut data)?;
-
-            let part_url = format!("{}/{}", &url, part.part_no);
-            let _response = self
-                .api_instance
-                .issue_request::<HashMap<String, String>>(
-                    Method::PUT,
-                    &part_url,
-                    Some(RequestData::Binary(data)),
-                )
-                .await?;
+            // stream the data between these offsets, re-reading it for
+            // each attempt rather than keeping the part in memory
+            let part_url = &format!("{}/{}", &url, part.part_no);
+            let (_response, retries) = retry_policy
+                .run(|| async move {
+                    let window =
+                        file_window(full_path, start_offset, end_offset - start_offset + 1).await?;
+                    self.api_instance
+                        .issue_request::<HashMap<String, String>>(
+                            Method::PUT,
+                            part_url,
+                            Some(RequestData::Stream(window)),
+                        )
+                        .await
+                })
+                .await
+                .map_err(|err| {
+                    anyhow!(
+                        "Failed to upload part {} of '{}': {:?}",
+                        part.part_no,
+                        data_file.path,
+                        err
+                    )
+                })?;
+            if retries > 0 {
+                retried.push(format!("{} ({} attempts)", part.part_no, retries + 1));
+            }
             debug!(
                 "uploaded part {} (offsets {}:{})",
                 part.part_no, start_offset, end_offset
             )
         }
 
+        if !retried.is_empty() {
+            print_info!(
+                "uploaded '{}' after retrying part(s): {}",
+                data_file.path,
+                retried.join(", ")
+            );
+        }
         Ok(())
     }
 
     async fn complete_upload(&self, upload_info: &FigShareFile) -> Result<()> {
-        let article_id = self.api_instance.get_article_id()?;
+        let article_id = upload_info.article_id;
         let url = format!("account/articles/{}/files/{}", article_id, upload_info.id);
         let data = FigShareCompleteUpload {
             id: article_id,
@@ -226,7 +321,7 @@ impl<'a> FigShareUpload<'a> {
         data_file: &DataFile,
         path_context: &Path,
         overwrite: bool,
-    ) -> Result<()> {
+    ) -> Result<UploadOutcome> {
         if !data_file.is_alive(path_context) {
             return Err(anyhow!(
                 "Cannot upload: file '{}' does not exist lcoally.",
@@ -234,9 +329,14 @@ impl<'a> FigShareUpload<'a> {
             ));
         }
         // check if any files are associated with this article
-        let article_id = self.api_instance.get_article_id()?;
         let name = data_file.basename()?;
-        let existing_file = self.api_instance.file_exists(&name).await?;
+        let article_id = self.api_instance.upload_article_id(&name).await?;
+        let existing_file = self
+            .api_instance
+            .get_article_files(article_id)
+            .await?
+            .into_iter()
+            .find(|file| file.name == name);
         if let Some(file) = existing_file {
             if !overwrite {
                 print_info!(
@@ -246,6 +346,7 @@ impl<'a> FigShareUpload<'a> {
                     name,
                     article_id
                 );
+                return Ok(UploadOutcome::SkippedExists);
             } else {
                 info!(
                     "FigShare::upload() is deleting file '{}' since \
@@ -255,11 +356,48 @@ impl<'a> FigShareUpload<'a> {
                 self.api_instance.delete_article_file(&file).await?;
             }
         }
-        let (upload_info, pending_upload_info) = self.init_upload(data_file).await?;
+        let size = data_file.upload_size(path_context)?;
+        let (upload_info, pending_upload_info) =
+            self.init_upload(data_file, size, article_id).await?;
+        // if interrupted before the upload completes, delete the pending
+        // file so it is not left in the article
+        let api = self.api_instance.clone();
+        let pending = upload_info.clone();
+        let staged = interrupt::stage(Staged::Upload {
+            description: format!("FigShare upload of '{}'", name),
+            cancel: Box::new(move || {
+                Box::pin(async move { api.delete_article_file(&pending).await })
+            }),
+        });
         self.upload_parts(data_file, &upload_info, &pending_upload_info, path_context)
             .await?;
         self.complete_upload(&upload_info).await?;
-        Ok(())
+        drop(staged);
+        Ok(UploadOutcome::Uploaded)
+    }
+}
+
+// A file of a public article; the public API omits the upload fields.
+#[derive(Debug, Deserialize)]
+struct FigSharePublicFile {
+    name: String,
+    size: u64,
+    #[serde(default)]
+    download_url: Option<String>,
+    computed_md5: String,
+}
+
+impl From<FigSharePublicFile> for RemoteFile {
+    fn from(fgsh: FigSharePublicFile) -> Self {
+        RemoteFile {
+            name: fgsh.name,
+            md5: Some(fgsh.computed_md5),
+            size: Some(fgsh.size),
+            remote_service: "FigShare".to_string(),
+            url: fgsh.download_url.filter(|url| !url.is_empty()),
+            mod_time: None,
+            duplicate_ids: Vec::new(),
+        }
     }
 }
 
@@ -270,17 +408,68 @@ impl From<FigShareFile> for RemoteFile {
             md5: Some(fgsh.computed_md5),
             size: Some(fgsh.size),
             remote_service: "FigShare".to_string(),
-            url: Some(fgsh.download_url),
+            url: fgsh.download_url.filter(|url| !url.is_empty()),
+            mod_time: None,
+            duplicate_ids: Vec::new(),
         }
     }
 }
 
+// The part of the account details with its storage quota.
+#[derive(Debug, Deserialize)]
+struct FigShareAccount {
+    quota: u64,
+    used_quota: u64,
+}
+
+// The part of an article's details needed for its modification time.
+#[derive(Debug, Deserialize)]
+struct FigShareArticleModified {
+    modified_date: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct FigShareArticle {
     title: String,
     id: u64,
 }
 
+// One of the licenses FigShare offers (GET /account/licenses).
+#[derive(Debug, Deserialize)]
+struct FigShareLicense {
+    value: u64,
+    name: String,
+}
+
+// The value of FigShare's license for an SPDX identifier. FigShare names
+// its licenses differently (e.g. 'CC BY 4.0' for 'CC-BY-4.0', or 'CC0'
+// for 'CC0-1.0'), so names are compared by their letters and digits,
+// with a missing version taken to be 1.0.
+fn figshare_license(licenses: &[FigShareLicense], spdx_id: &str) -> Result<u64> {
+    let normalize = |name: &str| -> String {
+        name.chars()
+            .filter(|c| c.is_ascii_alphanumeric())
+            .collect::<String>()
+            .to_lowercase()
+    };
+    let spdx_id_normalized = normalize(spdx_id);
+    licenses
+        .iter()
+        .find(|license| {
+            let name = normalize(&license.name);
+            name == spdx_id_normalized || format!("{}10", name) == spdx_id_normalized
+        })
+        .map(|license| license.value)
+        .ok_or_else(|| {
+            let names: Vec<&str> = licenses.iter().map(|l| l.name.as_str()).collect();
+            anyhow!(
+                "FigShare has no license matching '{}' (its licenses are: {}).",
+                spdx_id,
+                names.join(", ")
+            )
+        })
+}
+
 impl FigShareAPI {
     pub fn new(name: &str, base_url: Option<String>) -> Result<Self> {
         // Note: this constructor is not called often, except through
@@ -297,12 +486,16 @@ impl FigShareAPI {
             auth_keys.temporary_add("figshare", TEST_TOKEN);
             auth_keys
         };
-        let token = auth_keys.get("figshare".to_string())?;
+        // the token may not be saved yet: Project::link() sets it, and
+        // only saves it once the service has accepted it
+        let token = auth_keys.get("figshare".to_string()).unwrap_or_default();
         let base_url = base_url.unwrap_or(FIGSHARE_BASE_URL.to_string());
         Ok(FigShareAPI {
             base_url,
             article_id: None,
             name: name.to_string(),
+            layout: FigShareLayout::default(),
+            record_type: None,
             token,
         })
     }
@@ -311,17 +504,63 @@ impl FigShareAPI {
         self.token = token;
     }
 
+    pub fn set_layout(&mut self, layout: FigShareLayout) {
+        self.layout = layout;
+    }
+
+    pub fn layout(&self) -> FigShareLayout {
+        self.layout
+    }
+
+    pub fn set_record_type(&mut self, record_type: &str) -> Result<()> {
+        let record_type = record_type.to_lowercase();
+        if !FIGSHARE_RECORD_TYPES.contains(&record_type.as_str()) {
+            return Err(anyhow!(
+                "Unknown FigShare item type '{}'; use one of: {}.",
+                record_type,
+                FIGSHARE_RECORD_TYPES.join(", ")
+            ));
+        }
+        self.record_type = Some(record_type);
+        Ok(())
+    }
+
+    pub fn record_type(&self) -> &str {
+        self.record_type.as_deref().unwrap_or("dataset")
+    }
+
+    pub fn has_token(&self) -> bool {
+        !self.token.is_empty()
+    }
+
     pub fn get_base_url(&self) -> String {
         self.base_url.clone()
     }
 
+    // Set the API base URL, e.g. for a mock server (the base URL is
+    // not saved in the manifest).
+    pub fn set_base_url(&mut self, base_url: String) {
+        self.base_url = base_url;
+    }
+
     async fn issue_request<T: serde::Serialize>(
         &self,
         method: Method,
         endpoint: &str,
         data: Option<RequestData<T>>,
     ) -> Result<Response> {
-        let mut headers = HeaderMap::new();
+        self.issue_request_with_headers(method, endpoint, None, data)
+            .await
+    }
+
+    async fn issue_request_with_headers<T: serde::Serialize>(
+        &self,
+        method: Method,
+        endpoint: &str,
+        extra_headers: Option<HeaderMap>,
+        data: Option<RequestData<T>>,
+    ) -> Result<Response> {
+        let mut headers = extra_headers.unwrap_or_default();
 
         // FigShare will give download links outside the API, so we handle
         // that possibility here.
@@ -333,14 +572,18 @@ impl FigShareAPI {
 
         trace!("request URL: {:?}", url);
 
-        let client = Client::new();
+        let client = http::client_for(&self.base_url)?;
+        let method_name = method.to_string();
         let mut request = client.request(method, &url);
 
         headers.insert(
             "Authorization",
             HeaderValue::from_str(&format!("token {}", self.token)).unwrap(),
         );
-        trace!("headers: {:?}", headers);
+        trace!(
+            "headers: {}",
+            http::mask_token(&format!("{:?}", headers), &self.token)
+        );
         request = request.headers(headers);
 
         let request = match data {
@@ -348,9 +591,11 @@ impl FigShareAPI {
             Some(RequestData::Binary(bin_data)) => request.body(bin_data),
             Some(RequestData::File(file)) => request.body(file),
             Some(RequestData::Stream(file)) => {
+                // set the length, since a streamed body is otherwise sent chunked
+                let length = file.limit();
                 let stream = tokio_util::io::ReaderStream::new(file);
                 let body = Body::wrap_stream(stream);
-                request.body(body)
+                request.header(CONTENT_LENGTH, length).body(body)
             }
             Some(RequestData::Empty) => {
                 request.json(&serde_json::Value::Object(serde_json::Map::new()))
@@ -358,17 +603,18 @@ impl FigShareAPI {
             None => request,
         };
 
-        let response = request.send().await?;
+        let response = request
+            .send()
+            .await
+            .map_err(http::send_error)
+            .with_context(|| format!("{} {}", method_name, url))?;
         let response_status = response.status();
-        if response_status.is_success() {
+        // 304 Not Modified is only returned for conditional requests
+        // (see ListingCache::conditional_get())
+        if response_status.is_success() || response_status == StatusCode::NOT_MODIFIED {
             Ok(response)
         } else {
-            Err(anyhow!(
-                "HTTP Error: {}\nurl: {:?}\n{:?}",
-                response_status,
-                url,
-                response.text().await?
-            ))
+            Err(HttpError::new(response_status, &url, response.text().await?).into())
         }
     }
 
@@ -376,7 +622,7 @@ impl FigShareAPI {
     // NOTE: Mostly deprecated due to trauma-based downloads.
     #[allow(dead_code)]
     async fn download_file(&self, url: &str, save_path: &Path) -> Result<()> {
-        let response = reqwest::get(url).await?;
+        let response = http::get(url).await?;
         let mut file = File::create(save_path).await?;
         let mut stream = response.bytes_stream();
         while let Some(chunk) = stream.next().await {
@@ -393,7 +639,7 @@ impl FigShareAPI {
         // (1) create the data for this article
         let mut data: HashMap<String, String> = HashMap::new();
         data.insert("title".to_string(), title.to_string());
-        data.insert("defined_type".to_string(), "dataset".to_string());
+        data.insert("defined_type".to_string(), self.record_type().to_string());
         debug!("creating data for article: {:?}", data);
 
         // (2) issue request and parse out the article ID from location
@@ -402,7 +648,7 @@ impl FigShareAPI {
             .await?;
         let data = response.json::<Value>().await?;
         let article_id_result = match data.get("location").and_then(|loc| loc.as_str()) {
-            Some(loc) => Ok(loc.split('/').last().unwrap_or_default().to_string()),
+            Some(loc) => Ok(loc.split('/').next_back().unwrap_or_default().to_string()),
             None => Err(anyhow!("Response does not have 'location' set!")),
         };
         let article_id: u64 = article_id_result?
@@ -422,12 +668,9 @@ impl FigShareAPI {
         data_file: &DataFile,
         path_context: &Path,
         overwrite: bool,
-    ) -> Result<bool> {
+    ) -> Result<UploadOutcome> {
         let this_upload = FigShareUpload::new(self);
-        this_upload
-            .upload(data_file, path_context, overwrite)
-            .await?;
-        Ok(true)
+        this_upload.upload(data_file, path_context, overwrite).await
     }
 
     pub fn authenticate_url(&self, url: &str) -> Result<String> {
@@ -454,18 +697,82 @@ impl FigShareAPI {
         }
     }
 
+    // The title of a file's article in the per-file layout.
+    fn file_article_title(&self, file_name: &str) -> String {
+        format!("{}: {}", self.name, file_name)
+    }
+
+    // The articles of the files, in the per-file layout.
+    async fn file_articles(&self) -> Result<Vec<FigShareArticle>> {
+        let prefix = self.file_article_title("");
+        Ok(self
+            .get_articles()
+            .await?
+            .into_iter()
+            .filter(|article| article.title.starts_with(&prefix))
+            .collect())
+    }
+
+    // The IDs of all the remote's articles.
+    async fn article_ids(&self) -> Result<Vec<u64>> {
+        match self.layout {
+            FigShareLayout::OneArticle => Ok(vec![self.get_article_id()?]),
+            FigShareLayout::PerFile => Ok(self
+                .file_articles()
+                .await?
+                .into_iter()
+                .map(|article| article.id)
+                .collect()),
+        }
+    }
+
+    // The article to upload a file to: the directory's article, or in the
+    // per-file layout the file's own article, which is created if needed.
+    async fn upload_article_id(&self, file_name: &str) -> Result<u64> {
+        if self.layout == FigShareLayout::OneArticle {
+            return self.get_article_id();
+        }
+        let title = self.file_article_title(file_name);
+        let mut matches_found: Vec<_> = self
+            .get_articles()
+            .await?
+            .into_iter()
+            .filter(|article| article.title == title)
+            .collect();
+        match matches_found.len() {
+            0 => Ok(self.create_article(&title).await?.id),
+            1 => Ok(matches_found.remove(0).id),
+            _ => Err(anyhow!(
+                "Found multiple FigShare Articles with the title '{}'",
+                title
+            )),
+        }
+    }
+
     // FigShare Remote initialization
     //
-    // This creates a FigShare article for the tracked directory.
+    // This creates a FigShare article for the tracked directory. In the
+    // per-file layout, articles are created as files are uploaded.
     #[allow(unused)]
     pub async fn remote_init(
         &mut self,
         local_metadata: LocalMetadata,
         link_only: bool,
     ) -> Result<()> {
+        if self.layout == FigShareLayout::PerFile {
+            if !link_only && !self.file_articles().await?.is_empty() {
+                return Err(anyhow!(
+                    "Existing FigShare Articles with titles starting '{}' \
+                     were found. Use --link-only to link.",
+                    self.file_article_title("")
+                ));
+            }
+            return Ok(());
+        }
         // (1) Let's make sure there is no Article that exists
         // with this same name
         let found_match = self.find_article().await?;
+        let created = found_match.is_none();
         let article = if let Some(existing_info) = found_match {
             if !link_only {
                 return Err(anyhow!(
@@ -483,33 +790,184 @@ impl FigShareAPI {
         // (3) Set the Article ID, which is the only state needed
         // for later queries
         self.article_id = Some(article.id);
+        // (4) FigShare only sets a license on existing articles
+        if let Some(license) = local_metadata.license.filter(|_| created) {
+            self.update_license(&license).await?;
+        }
         Ok(())
     }
 
-    // Get FigShare Articles as FigShareArticle
+    // Set the item type of the remote's existing articles to the
+    // record type (see 'sdf remote type').
+    pub async fn update_record_type(&self) -> Result<()> {
+        let mut data: HashMap<String, String> = HashMap::new();
+        data.insert("defined_type".to_string(), self.record_type().to_string());
+        for article_id in self.article_ids().await? {
+            let endpoint = format!("account/articles/{}", article_id);
+            self.issue_request(Method::PUT, &endpoint, Some(RequestData::Json(&data)))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Set the license of the remote's existing articles, to FigShare's
+    // license matching the SPDX identifier (see figshare_license()).
+    pub async fn update_license(&self, license: &str) -> Result<()> {
+        let response = self
+            .issue_request::<HashMap<String, String>>(Method::GET, "/account/licenses", None)
+            .await?;
+        let licenses: Vec<FigShareLicense> = response.json().await?;
+        let value = figshare_license(&licenses, license)?;
+        let data = serde_json::json!({ "license": value });
+        for article_id in self.article_ids().await? {
+            let endpoint = format!("account/articles/{}", article_id);
+            self.issue_request(Method::PUT, &endpoint, Some(RequestData::Json(&data)))
+                .await?;
+        }
+        Ok(())
+    }
+
+    // Get FigShare Articles as FigShareArticle, from every page of the
+    // listing.
     // TODO? does this get published data sets?
     async fn get_articles(&self) -> Result<Vec<FigShareArticle>> {
-        let url = "/account/articles";
+        http::fetch_pages(|page| async move {
+            let url = format!(
+                "/account/articles?page={}&page_size={}",
+                page, FIGSHARE_PAGE_SIZE
+            );
+            let response = self
+                .issue_request::<HashMap<String, String>>(Method::GET, &url, None)
+                .await?;
+            let articles: Vec<FigShareArticle> = response.json().await?;
+            let has_next = articles.len() == FIGSHARE_PAGE_SIZE;
+            Ok((articles, has_next))
+        })
+        .await
+    }
+
+    // Check the token and connection with a minimal read-only request,
+    // returning a summary of what the token can access.
+    pub async fn check_connection(&self) -> Result<String> {
+        let articles = self.get_articles().await?;
+        Ok(format!(
+            "the token can access {}",
+            pluralize(articles.len() as u64, "article")
+        ))
+    }
+
+    // Check that FigShare accepts the token with a cheap authenticated
+    // request (see Remote::validate_token()).
+    pub async fn validate_token(&self) -> Result<()> {
+        self.issue_request::<HashMap<String, String>>(
+            Method::GET,
+            "/account/articles?page_size=1",
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Check that each of the remote's articles can be accessed with the
+    // token. FigShare articles can still be changed once public (the
+    // changes are published as a new version).
+    pub async fn check_writable(&self) -> Result<String> {
+        let article_ids = self.article_ids().await?;
+        for article_id in &article_ids {
+            self.get_modified_time(*article_id)
+                .await
+                .with_context(|| format!("article {} could not be accessed", article_id))?;
+        }
+        Ok(format!(
+            "{} writable",
+            pluralize(article_ids.len() as u64, "article")
+        ))
+    }
+
+    pub async fn get_quota(&self) -> Result<Quota> {
+        let response = self
+            .issue_request::<HashMap<String, String>>(Method::GET, "/account", None)
+            .await?;
+        let account: FigShareAccount = response.json().await?;
+        Ok(Quota {
+            used: account.used_quota,
+            total: account.quota,
+        })
+    }
+
+    // Get the last modification time of an article. FigShare does not
+    // provide modification times for individual files.
+    pub async fn get_modified_time(&self, article_id: u64) -> Result<DateTime<Utc>> {
+        let url = format!("/account/articles/{}", article_id);
         let response = self
-            .issue_request::<HashMap<String, String>>(Method::GET, url, None)
+            .issue_request::<HashMap<String, String>>(Method::GET, &url, None)
             .await?;
-        let articles: Vec<FigShareArticle> = response.json().await?;
-        Ok(articles)
+        let article: FigShareArticleModified = response.json().await?;
+        parse_remote_timestamp(&article.modified_date)
     }
 
     pub async fn get_remote_files(&self) -> Result<Vec<RemoteFile>> {
-        let articles = self.get_files().await?;
-        let remote_files = articles.into_iter().map(RemoteFile::from).collect();
+        let mut remote_files = Vec::new();
+        let mut ids = Vec::new();
+        for article_id in self.article_ids().await? {
+            let files = self.get_article_files(article_id).await?;
+            // modification times are optional, so failing to get one is not an error
+            let mod_time = self.get_modified_time(article_id).await.ok();
+            ids.extend(files.iter().map(|file| file.id.to_string()));
+            remote_files.extend(files.into_iter().map(|file| RemoteFile {
+                mod_time,
+                ..RemoteFile::from(file)
+            }));
+        }
+        let duplicates = duplicate_ids(remote_files.iter().map(|file| file.name.as_str()).zip(ids));
+        mark_duplicates("FigShare", &duplicates, &mut remote_files);
         Ok(remote_files)
     }
 
+    // Get the files of the published article through the public API,
+    // which does not need an access token.
+    pub async fn get_public_files(&self) -> Result<Vec<RemoteFile>> {
+        if self.layout == FigShareLayout::PerFile {
+            return Err(anyhow!(
+                "Public downloads are not supported for the per-file FigShare layout."
+            ));
+        }
+        let article_id = self.get_article_id()?;
+        let url = format!(
+            "{}/articles/{}/files",
+            self.base_url.trim_end_matches('/'),
+            article_id
+        );
+        trace!("request URL: {:?}", &url);
+        let response = http::get(&url)
+            .await
+            .with_context(|| format!("GET {}", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(HttpError::new(status, &url, response.text().await?).into());
+        }
+        let files: Vec<FigSharePublicFile> = response.json().await?;
+        Ok(files.into_iter().map(RemoteFile::from).collect())
+    }
+
     // Get all files from a FigShare Article, in a HashMap
     // with file name as keys.
+    // Files with duplicate names (see duplicate_ids()) keep the first
+    // listed; pushes and pulls skip these files, so this only matters
+    // for deleting them.
     pub async fn get_files_hashmap(&self) -> Result<HashMap<String, FigShareFile>> {
-        let mut files: Vec<FigShareFile> = self.get_files().await?;
+        let files: Vec<FigShareFile> = self.get_files().await?;
+        let duplicates = duplicate_ids(
+            files
+                .iter()
+                .map(|file| (file.name.as_str(), file.id.to_string())),
+        );
+        if !duplicates.is_empty() {
+            debug!("FigShare listing has duplicate names: {:?}", duplicates);
+        }
         let mut files_hash: HashMap<String, FigShareFile> = HashMap::new();
-        for file in files.iter_mut() {
-            files_hash.insert(file.name.clone(), file.clone());
+        for file in files {
+            files_hash.entry(file.name.clone()).or_insert(file);
         }
         Ok(files_hash)
     }
@@ -521,6 +979,44 @@ impl FigShareAPI {
         Ok(files.get(name).cloned())
     }
 
+    // Delete a file from its article by name.
+    pub async fn delete_file(&self, name: &str) -> Result<()> {
+        let file = self
+            .file_exists(name)
+            .await?
+            .ok_or_else(|| anyhow!("File '{}' is not in the FigShare article.", name))?;
+        self.delete_article_file(&file).await
+    }
+
+    // Delete the probe file of 'sdf remote test --write-probe' (see
+    // Remote::write_probe()), and in the per-file layout, the article
+    // created for it, which would otherwise be left behind empty.
+    pub async fn delete_probe(&self, name: &str) -> Result<()> {
+        let file = self
+            .file_exists(name)
+            .await?
+            .ok_or_else(|| anyhow!("File '{}' is not in the FigShare article.", name))?;
+        self.delete_article_file(&file).await?;
+        if self.layout == FigShareLayout::PerFile {
+            self.delete_article(file.article_id).await?;
+        }
+        Ok(())
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // The article's public landing page, once it has been created (each
+    // file has its own in the per-file layout).
+    pub fn landing_url(&self) -> Option<String> {
+        if self.layout == FigShareLayout::PerFile {
+            return None;
+        }
+        self.article_id
+            .map(|id| format!("https://figshare.com/articles/dataset/_/{}", id))
+    }
+
     pub fn get_article_id(&self) -> Result<u64> {
         let article_id = self
             .article_id
@@ -528,31 +1024,64 @@ impl FigShareAPI {
         Ok(article_id)
     }
 
-    // Get all files from the FigShare Article
+    // Get all files from the FigShare Article (or Articles)
     pub async fn get_files(&self) -> Result<Vec<FigShareFile>> {
-        let article_id = self.get_article_id()?;
-        let url = format!("/account/articles/{}/files", article_id);
-        let response = self
-            .issue_request::<HashMap<String, String>>(Method::GET, &url, None)
-            .await?;
-        let files: Vec<FigShareFile> = response.json().await?;
+        let mut files = Vec::new();
+        for article_id in self.article_ids().await? {
+            files.extend(self.get_article_files(article_id).await?);
+        }
         Ok(files)
     }
 
-    // Delete Article
-    /* async fn delete_article(&self, article: &FigShareArticle) -> Result<()> {
-    let url = format!("account/articles/{}", article.id);
-    self.issue_request::<HashMap<String, String>>(Method::DELETE, &url, None).await?;
-    Ok(())
+    // Get all files from one FigShare Article, from every page of the
+    // listing.
+    async fn get_article_files(&self, article_id: u64) -> Result<Vec<FigShareFile>> {
+        // each page of the listing is cached, and only re-downloaded if it
+        // has changed
+        let cache = ListingCache::new()?;
+        let cache = &cache;
+        let mut files: Vec<FigShareFile> = http::fetch_pages(|page| async move {
+            let url = format!(
+                "/account/articles/{}/files?page={}&page_size={}",
+                article_id, page, FIGSHARE_PAGE_SIZE
+            );
+            let key = ListingCache::key("figshare", &format!("{}{}", self.base_url, url));
+            let body = cache
+                .conditional_get(&key, |headers| {
+                    self.issue_request_with_headers::<HashMap<String, String>>(
+                        Method::GET,
+                        &url,
+                        headers,
+                        None,
+                    )
+                })
+                .await?;
+            let files: Vec<FigShareFile> = serde_json::from_str(&body)?;
+            let has_next = files.len() == FIGSHARE_PAGE_SIZE;
+            Ok((files, has_next))
+        })
+        .await?;
+        for file in files.iter_mut() {
+            file.article_id = article_id;
+        }
+        Ok(files)
     }
-    */
 
-    // Delete the specified file from the FigShare Article
+    // Delete an (unpublished) article.
+    async fn delete_article(&self, article_id: u64) -> Result<()> {
+        let url = format!("account/articles/{}", article_id);
+        self.issue_request::<HashMap<String, String>>(Method::DELETE, &url, None)
+            .await?;
+        info!("deleted FigShare article (Article ID={})", article_id);
+        Ok(())
+    }
+
+    // Delete the specified file from its FigShare Article
     //
     // Note: we require a &FigShareFile as a way to enforce it exists,
     // e.g. is the result of a previous query.
     async fn delete_article_file(&self, file: &FigShareFile) -> Result<()> {
-        let article_id = self.get_article_id()?;
+        let article_id = file.article_id;
         let url = format!("account/articles/{}/files/{}", article_id, file.id);
         self.issue_request::<HashMap<String, String>>(Method::DELETE, &url, None)
             .await?;
@@ -567,6 +1096,8 @@ impl FigShareAPI {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lib::remote::Remote;
+    use crate::lib::test_utilities::check_error;
     use crate::logging_setup::setup;
     use httpmock::prelude::*;
     use serde_json::json;
@@ -584,10 +1115,7 @@ mod tests {
         let create_article_mock = server.mock(|when, then| {
             when.method(POST)
                 .path("/account/articles")
-                .header(
-                    "Authorization",
-                    &format!("token {}", TEST_TOKEN.to_string()),
-                )
+                .header("Authorization", format!("token {}", TEST_TOKEN))
                 .json_body(json!({
                     "title": title.to_string(),
                     "defined_type": "dataset"
@@ -605,7 +1133,7 @@ mod tests {
         let result = api.create_article(title).await;
 
         // Check the result
-        assert_eq!(result.is_ok(), true);
+        assert!(result.is_ok());
         let article = result.unwrap();
         assert_eq!(article.title, title);
         assert_eq!(article.id, expected_id);
@@ -613,4 +1141,460 @@ mod tests {
         // Verify that the mock was called exactly once
         create_article_mock.assert();
     }
+
+    #[tokio::test]
+    async fn test_check_connection() {
+        setup();
+        let server = MockServer::start();
+        let mut articles_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/account/articles")
+                .header("Authorization", format!("token {}", TEST_TOKEN));
+            then.status(200).json_body(json!([
+                { "title": "Project A", "id": 1 },
+                { "title": "Project B", "id": 2 }
+            ]));
+        });
+
+        let api = FigShareAPI::new("Test Article", Some(server.url(""))).unwrap();
+        let summary = api.check_connection().await.unwrap();
+        assert_eq!(summary, "the token can access 2 articles");
+        articles_mock.assert();
+        articles_mock.delete();
+
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles");
+            then.status(403).body("{\"message\": \"Invalid token\"}");
+        });
+        let result = api.check_connection().await;
+        assert!(result.unwrap_err().to_string().contains("403"));
+    }
+
+    #[tokio::test]
+    async fn test_validate_token_rejected() {
+        setup();
+        let server = MockServer::start();
+        let validate_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/account/articles")
+                .query_param("page_size", "1");
+            then.status(401).body("{\"message\": \"Invalid token\"}");
+        });
+        let create_mock = server.mock(|when, then| {
+            when.method(POST).path("/account/articles");
+            then.status(201).json_body(json!({ "entity_id": 1 }));
+        });
+
+        let mut remote =
+            Remote::FigShareAPI(FigShareAPI::new("Test Article", Some(server.url(""))).unwrap());
+        remote.set_token("s3cret-token".to_string()).unwrap();
+        let err = remote.validate_token().await.unwrap_err().to_string();
+        assert_eq!(
+            err,
+            "FigShare rejected the token (401). Check that the token has 'article' scope."
+        );
+        validate_mock.assert();
+        create_mock.assert_hits(0);
+    }
+
+    #[test]
+    fn test_layout_in_manifest() {
+        let mut api = FigShareAPI::new("Test", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+        // the default layout is left out, so older manifests are unchanged
+        assert!(!serde_yaml::to_string(&api).unwrap().contains("layout"));
+
+        api.set_layout(FigShareLayout::parse("per-file").unwrap());
+        let yaml = serde_yaml::to_string(&api).unwrap();
+        assert!(yaml.contains("layout: per-file"), "{}", yaml);
+        let loaded: FigShareAPI = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded.layout(), FigShareLayout::PerFile);
+
+        assert!(FigShareLayout::parse("per-article").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_record_type() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test Article", Some(server.url(""))).unwrap();
+        assert!(api.set_record_type("upload").is_err());
+        api.set_record_type("Software").unwrap();
+        let yaml = serde_yaml::to_string(&api).unwrap();
+        assert!(yaml.contains("record_type: software"), "{}", yaml);
+
+        let create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/account/articles")
+                .json_body(json!({
+                    "title": "Test Article",
+                    "defined_type": "software"
+                }));
+            then.status(201).json_body(json!({
+                "location": format!("{}account/articles/42", server.url(""))
+            }));
+        });
+        let article = api.create_article("Test Article").await.unwrap();
+        create_mock.assert();
+
+        // changing the type after linking updates the existing article
+        api.article_id = Some(article.id);
+        api.set_record_type("figure").unwrap();
+        let update_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/account/articles/42")
+                .json_body(json!({ "defined_type": "figure" }));
+            then.status(205);
+        });
+        api.update_record_type().await.unwrap();
+        update_mock.assert();
+    }
+
+    fn figshare_file(server: &MockServer, id: u64, name: &str, contents: &str) -> Value {
+        json!({
+            "upload_token": "", "upload_url": server.url("/upload/token"),
+            "status": "available", "preview_state": "", "viewer_type": "",
+            "is_attached_to_public_version": false, "id": id,
+            "name": name, "size": contents.len(), "is_link_only": false,
+            "download_url": server.url(format!("/files/{}", id)),
+            "supplied_md5": "", "computed_md5": format!("{:x}", md5::compute(contents))
+        })
+    }
+
+    #[tokio::test]
+    async fn test_upload_parts() {
+        setup();
+        let server = MockServer::start();
+        let api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("c.tsv"), "abcdefg").unwrap();
+        let data_file = DataFile::new("c.tsv".to_string(), None, dir.path())
+            .await
+            .unwrap();
+        let upload_info: FigShareFile =
+            serde_json::from_value(figshare_file(&server, 3, "c.tsv", "abcdefg")).unwrap();
+        let pending: FigSharePendingUploadInfo = serde_json::from_value(json!({
+            "token": "token", "md5": "", "size": 7, "name": "c.tsv", "status": "PENDING",
+            "parts": [
+                { "partNo": 1, "startOffset": 0, "endOffset": 3,
+                  "status": "PENDING", "locked": false },
+                { "partNo": 2, "startOffset": 4, "endOffset": 6,
+                  "status": "PENDING", "locked": false }
+            ]
+        }))
+        .unwrap();
+
+        // each part is sent with its own length, not chunked
+        let part_mocks: Vec<_> = [(1, "abcd"), (2, "efg")]
+            .into_iter()
+            .map(|(part_no, body)| {
+                server.mock(|when, then| {
+                    when.method(PUT)
+                        .path(format!("/upload/token/{}", part_no))
+                        .header("content-length", body.len().to_string())
+                        .body(body);
+                    then.status(200);
+                })
+            })
+            .collect();
+        FigShareUpload::new(&api)
+            .upload_parts(&data_file, &upload_info, &pending, dir.path())
+            .await
+            .unwrap();
+        for part_mock in part_mocks {
+            part_mock.assert();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_per_file_layout() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        api.set_layout(FigShareLayout::PerFile);
+
+        // linking creates no article
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles");
+            then.status(200).json_body(json!([
+                { "title": "Test: a.tsv", "id": 11 },
+                { "title": "Test: b.tsv", "id": 12 },
+                { "title": "Other", "id": 13 }
+            ]));
+        });
+        let create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/account/articles")
+                .json_body(json!({ "title": "Test: c.tsv", "defined_type": "dataset" }));
+            then.status(201).json_body(json!({
+                "location": server.url("/account/articles/14")
+            }));
+        });
+        api.remote_init(local_metadata(), true).await.unwrap();
+        create_mock.assert_hits(0);
+
+        // files are listed from each file's article
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/11/files");
+            then.status(200)
+                .json_body(json!([figshare_file(&server, 1, "a.tsv", "a\n")]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/12/files");
+            then.status(200)
+                .json_body(json!([figshare_file(&server, 2, "b.tsv", "b\n")]));
+        });
+        let mut names: Vec<String> = api
+            .get_remote_files()
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.tsv", "b.tsv"]);
+        assert_eq!(api.landing_url(), None);
+
+        // uploading a new file creates its article
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("c.tsv"), "c\n").unwrap();
+        let data_file = DataFile::new("c.tsv".to_string(), None, dir.path())
+            .await
+            .unwrap();
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/14/files");
+            then.status(200).json_body(json!([]));
+        });
+        let init_mock = server.mock(|when, then| {
+            when.method(POST).path("/account/articles/14/files");
+            then.status(201).json_body(json!({
+                "location": server.url("/account/articles/14/files/3")
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/14/files/3");
+            then.status(200)
+                .json_body(figshare_file(&server, 3, "c.tsv", "c\n"));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/upload/token");
+            then.status(200).json_body(json!({
+                "token": "token", "md5": "", "size": 2, "name": "c.tsv",
+                "status": "PENDING",
+                "parts": [{ "partNo": 1, "startOffset": 0, "endOffset": 1,
+                            "status": "PENDING", "locked": false }]
+            }));
+        });
+        let part_mock = server.mock(|when, then| {
+            when.method(PUT).path("/upload/token/1").body("c\n");
+            then.status(200);
+        });
+        let complete_mock = server.mock(|when, then| {
+            when.method(POST).path("/account/articles/14/files/3");
+            then.status(202);
+        });
+        let outcome = api.upload(&data_file, dir.path(), false).await.unwrap();
+        assert_eq!(outcome, UploadOutcome::Uploaded);
+        create_mock.assert();
+        init_mock.assert();
+        part_mock.assert();
+        complete_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_write_probe_per_file() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        api.set_layout(FigShareLayout::PerFile);
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles");
+            then.status(200)
+                .json_body(json!([{ "title": "Test: probe.txt", "id": 15 }]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/15/files");
+            then.status(200)
+                .json_body(json!([figshare_file(&server, 3, "probe.txt", "\n")]));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/account/articles/15/files");
+            then.status(201).json_body(json!({
+                "location": server.url("/account/articles/15/files/3")
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/15/files/3");
+            then.status(200)
+                .json_body(figshare_file(&server, 3, "probe.txt", "\n"));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/upload/token");
+            then.status(200).json_body(json!({
+                "token": "token", "md5": "", "size": 1, "name": "probe.txt",
+                "status": "PENDING",
+                "parts": [{ "partNo": 1, "startOffset": 0, "endOffset": 0,
+                            "status": "PENDING", "locked": false }]
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(PUT).path("/upload/token/1").body("\n");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/account/articles/15/files/3");
+            then.status(202);
+        });
+        let delete_file_mock = server.mock(|when, then| {
+            when.method(DELETE).path("/account/articles/15/files/3");
+            then.status(204);
+        });
+        let delete_article_mock = server.mock(|when, then| {
+            when.method(DELETE).path("/account/articles/15");
+            then.status(204);
+        });
+
+        // the probe's article is deleted with it
+        Remote::FigShareAPI(api)
+            .write_probe_as("probe.txt")
+            .await
+            .unwrap();
+        delete_file_mock.assert_hits(2);
+        delete_article_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_listing() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        api.article_id = Some(11);
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/11/files");
+            then.status(200).json_body(json!([
+                figshare_file(&server, 1, "a.tsv", "a\n"),
+                figshare_file(&server, 2, "b.tsv", "b\n"),
+                figshare_file(&server, 3, "a.tsv", "a\n")
+            ]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/11");
+            then.status(404);
+        });
+        let files = api.get_remote_files().await.unwrap();
+        for file in &files {
+            let expected: &[&str] = if file.name == "a.tsv" {
+                &["1", "3"]
+            } else {
+                &[]
+            };
+            assert_eq!(file.duplicate_ids, expected, "{}", file.name);
+        }
+
+        // maps by name keep the first file listed
+        let files = api.get_files_hashmap().await.unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files["a.tsv"].id, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_files_pages() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        api.article_id = Some(11);
+        let full_page: Vec<Value> = (1..=FIGSHARE_PAGE_SIZE as u64)
+            .map(|id| figshare_file(&server, id, &format!("{}.tsv", id), "a\n"))
+            .collect();
+        let first_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/account/articles/11/files")
+                .query_param("page", "1");
+            then.status(200).json_body(json!(full_page));
+        });
+        let last_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/account/articles/11/files")
+                .query_param("page", "2");
+            then.status(200)
+                .json_body(json!([figshare_file(&server, 1001, "last.tsv", "b\n")]));
+        });
+        let files = api.get_files().await.unwrap();
+        assert_eq!(files.len(), FIGSHARE_PAGE_SIZE + 1);
+        assert_eq!(files.last().unwrap().name, "last.tsv");
+        assert!(files.iter().all(|file| file.article_id == 11));
+        first_mock.assert();
+        last_mock.assert();
+    }
+
+    #[test]
+    fn test_figshare_license() {
+        let licenses: Vec<FigShareLicense> = serde_json::from_value(json!([
+            { "value": 1, "name": "CC BY 4.0", "url": "" },
+            { "value": 2, "name": "CC0", "url": "" },
+            { "value": 3, "name": "MIT", "url": "" },
+            { "value": 4, "name": "Apache 2.0", "url": "" }
+        ]))
+        .unwrap();
+        assert_eq!(figshare_license(&licenses, "CC-BY-4.0").unwrap(), 1);
+        assert_eq!(figshare_license(&licenses, "CC0-1.0").unwrap(), 2);
+        assert_eq!(figshare_license(&licenses, "MIT").unwrap(), 3);
+        assert_eq!(figshare_license(&licenses, "Apache-2.0").unwrap(), 4);
+        check_error(
+            figshare_license(&licenses, "GPL-3.0-only"),
+            "FigShare has no license matching 'GPL-3.0-only' (its licenses are: CC BY 4.0, CC0, MIT, Apache 2.0).",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_license() {
+        setup();
+        let server = MockServer::start();
+        let mut api = FigShareAPI::new("Test", Some(server.url(""))).unwrap();
+        api.article_id = Some(42);
+        server.mock(|when, then| {
+            when.method(GET).path("/account/licenses");
+            then.status(200)
+                .json_body(json!([{ "value": 7, "name": "CC BY 4.0", "url": "" }]));
+        });
+        let update_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/account/articles/42")
+                .json_body(json!({ "license": 7 }));
+            then.status(205);
+        });
+        api.update_license("CC-BY-4.0").await.unwrap();
+        update_mock.assert();
+    }
+
+    fn local_metadata() -> LocalMetadata {
+        LocalMetadata {
+            author_name: Some("Joan B. Scientist".to_string()),
+            title: Some("Test".to_string()),
+            email: None,
+            affiliation: None,
+            description: None,
+            orcid: None,
+            license: None,
+            co_authors: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_quota() {
+        setup();
+        let server = MockServer::start();
+        let account_mock = server.mock(|when, then| {
+            when.method(GET).path("/account");
+            then.status(200).json_body(json!({
+                "id": 7, "first_name": "Joan", "quota": 20_000, "used_quota": 5_000,
+                "used_quota_private": 5_000, "used_quota_public": 0
+            }));
+        });
+
+        let api = FigShareAPI::new("Test Article", Some(server.url(""))).unwrap();
+        let quota = api.get_quota().await.unwrap();
+        assert_eq!(quota.used, 5_000);
+        assert_eq!(quota.remaining(), 15_000);
+        account_mock.assert();
+    }
 }
@@ -0,0 +1,551 @@
+// IPFS API
+//
+// Notes:
+// IPFS has no notion of a data set, so each remote is a directory in the
+// node's Mutable File System (MFS), /scidataflow/<name>. Uploading a file
+// adds and pins it, and then copies its CID into the directory under the
+// file's name; listing the directory gives the name, size, and CID of each
+// file. This uses the Kubo RPC API, e.g. of a local node (the default) or
+// of a remote node set with api_url in the manifest.
+//
+// IPFS does not store MD5s, so remote files are compared by size only
+// (their status is "exists", as with other remotes without MD5s).
+
+use anyhow::{anyhow, Context, Result};
+use colored::Colorize;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use percent_encoding::utf8_percent_encode;
+use reqwest::multipart::{Form, Part};
+use reqwest::{Body, Response};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+use tokio_util::io::ReaderStream;
+
+use crate::lib::data::DataFile;
+use crate::lib::http::{self, HttpError};
+use crate::lib::project::LocalMetadata;
+use crate::lib::remote::{AuthKeys, RemoteFile, UploadOutcome};
+use crate::lib::utils::pluralize;
+#[allow(unused_imports)]
+use crate::{print_info, print_warn};
+
+pub const IPFS_API_URL: &str = "http://127.0.0.1:5001/api/v0";
+pub const IPFS_GATEWAY_URL: &str = "https://ipfs.io";
+
+// The MFS directory all remotes are created in.
+const MFS_ROOT: &str = "/scidataflow";
+
+// for testing:
+const TEST_TOKEN: &str = "test-token";
+
+// for serde deserialize default
+fn ipfs_api_url() -> String {
+    IPFS_API_URL.to_string()
+}
+
+fn ipfs_gateway_url() -> String {
+    IPFS_GATEWAY_URL.to_string()
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct IpfsAPI {
+    // Unlike other remotes, these are saved in the manifest, since the
+    // node (and its gateway) may differ between projects.
+    #[serde(default = "ipfs_api_url")]
+    api_url: String,
+    #[serde(default = "ipfs_gateway_url")]
+    gateway_url: String,
+    name: String,
+    // the MFS directory of this remote, set by remote_init()
+    directory: Option<String>,
+    // an optional bearer token, e.g. for Kubo's API.Authorizations
+    #[serde(skip_serializing, skip_deserializing)]
+    token: String,
+}
+
+/// The response from /add
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct IpfsAdded {
+    name: String,
+    hash: String,
+}
+
+/// The response from /files/ls?long=true
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct IpfsListing {
+    // null for an empty directory
+    entries: Option<Vec<IpfsEntry>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(rename_all = "PascalCase")]
+pub struct IpfsEntry {
+    name: String,
+    // 0 for files, 1 for directories
+    #[serde(rename = "Type")]
+    entry_type: u8,
+    size: u64,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct IpfsVersion {
+    version: String,
+}
+
+impl IpfsAPI {
+    pub fn new(name: &str, base_url: Option<String>) -> Result<Self> {
+        // As with the other remotes, a base_url means a mock server, with
+        // the test token. Otherwise, the token is optional.
+        let token = if base_url.is_none() {
+            AuthKeys::new().get("ipfs".to_string()).unwrap_or_default()
+        } else {
+            TEST_TOKEN.to_string()
+        };
+        Ok(IpfsAPI {
+            api_url: base_url.unwrap_or(IPFS_API_URL.to_string()),
+            gateway_url: IPFS_GATEWAY_URL.to_string(),
+            name: name.to_string(),
+            directory: None,
+            token,
+        })
+    }
+
+    pub fn set_token(&mut self, token: String) {
+        self.token = token;
+    }
+
+    pub fn has_token(&self) -> bool {
+        !self.token.is_empty()
+    }
+
+    pub fn set_base_url(&mut self, base_url: String) {
+        self.api_url = base_url;
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_directory(&self) -> Result<&str> {
+        self.directory
+            .as_deref()
+            .ok_or(anyhow!("Internal Error: IPFS directory not set."))
+    }
+
+    // The Kubo RPC API takes POSTs with the arguments in the query string.
+    async fn issue_request(
+        &self,
+        endpoint: &str,
+        args: &[(&str, &str)],
+        form: Option<Form>,
+    ) -> Result<Response> {
+        let url = format!(
+            "{}/{}",
+            self.api_url.trim_end_matches('/'),
+            endpoint.trim_start_matches('/')
+        );
+        trace!("request URL: {:?} {:?}", &url, args);
+
+        let client = http::client_for(&self.api_url)?;
+        let mut request = client.post(&url).query(args);
+        if self.has_token() {
+            request = request.bearer_auth(&self.token);
+        }
+        if let Some(form) = form {
+            request = request.multipart(form);
+        }
+
+        let response = request
+            .send()
+            .await
+            .map_err(http::send_error)
+            .with_context(|| format!("POST {}", url))?;
+        let response_status = response.status();
+        if response_status.is_success() {
+            Ok(response)
+        } else {
+            let text = response.text().await?;
+            Err(HttpError::new(response_status, &url, text).into())
+        }
+    }
+
+    // Check the node is reachable with a read-only request.
+    pub async fn check_connection(&self) -> Result<String> {
+        let response = self.issue_request("version", &[], None).await?;
+        let version: IpfsVersion = response.json().await?;
+        Ok(format!("the node is running IPFS {}", version.version))
+    }
+
+    // Check that the remote's MFS directory exists on the node.
+    pub async fn check_writable(&self) -> Result<String> {
+        let directory = self.get_directory()?;
+        if !self.directory_exists(directory).await? {
+            return Err(anyhow!(
+                "directory '{}' does not exist on the node",
+                directory
+            ));
+        }
+        Ok(format!("directory '{}' exists", directory))
+    }
+
+    async fn directory_exists(&self, directory: &str) -> Result<bool> {
+        match self
+            .issue_request("files/stat", &[("arg", directory)], None)
+            .await
+        {
+            Ok(_) => Ok(true),
+            Err(err) if err.to_string().contains("does not exist") => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    // Initialize the data collection on the Remote
+    //
+    // For IPFS, this creates the MFS directory of the remote.
+    pub async fn remote_init(
+        &mut self,
+        _local_metadata: LocalMetadata,
+        link_only: bool,
+    ) -> Result<()> {
+        let directory = format!("{}/{}", MFS_ROOT, self.name);
+        if self.directory_exists(&directory).await? {
+            if !link_only {
+                return Err(anyhow!(
+                    "An existing IPFS directory '{}' was found. Use --link-only to link.",
+                    directory
+                ));
+            }
+        } else {
+            self.issue_request(
+                "files/mkdir",
+                &[("arg", &directory), ("parents", "true")],
+                None,
+            )
+            .await?;
+        }
+        self.directory = Some(directory);
+        Ok(())
+    }
+
+    pub async fn get_files(&self) -> Result<Vec<IpfsEntry>> {
+        let directory = self.get_directory()?;
+        let response = self
+            .issue_request("files/ls", &[("arg", directory), ("long", "true")], None)
+            .await?;
+        let listing: IpfsListing = response.json().await?;
+        Ok(listing
+            .entries
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|entry| entry.entry_type == 0)
+            .collect())
+    }
+
+    pub async fn get_remote_files(&self) -> Result<Vec<RemoteFile>> {
+        let entries = self.get_files().await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| RemoteFile {
+                url: Some(self.gateway_url(&entry.hash, &entry.name)),
+                name: entry.name,
+                md5: None,
+                size: Some(entry.size),
+                remote_service: "IPFS".to_string(),
+                mod_time: None,
+                duplicate_ids: Vec::new(),
+            })
+            .collect())
+    }
+
+    // The gateway URL of a file, by its CID.
+    pub fn gateway_url(&self, cid: &str, name: &str) -> String {
+        format!(
+            "{}/ipfs/{}?filename={}",
+            self.gateway_url.trim_end_matches('/'),
+            cid,
+            utf8_percent_encode(name, http::FILENAME_ESCAPE)
+        )
+    }
+
+    // Gateways serve content by CID without authentication.
+    pub fn authenticate_url(&self, url: &str) -> Result<String> {
+        Ok(url.to_string())
+    }
+
+    // Remove a file from the remote's directory, keeping its content
+    // pinned.
+    async fn unlink_file(&self, name: &str) -> Result<()> {
+        let path = format!("{}/{}", self.get_directory()?, name);
+        self.issue_request("files/rm", &[("arg", &path)], None)
+            .await?;
+        Ok(())
+    }
+
+    // Remove a file from the remote's directory, and unpin it (unless it
+    // is pinned for another reason, e.g. another file with the same
+    // content).
+    async fn remove_file(&self, entry: &IpfsEntry) -> Result<()> {
+        self.unlink_file(&entry.name).await?;
+        if let Err(err) = self
+            .issue_request("pin/rm", &[("arg", &entry.hash)], None)
+            .await
+        {
+            debug!("could not unpin {}: {}", entry.hash, err);
+        }
+        info!("removed IPFS file '{}' (CID={})", entry.name, entry.hash);
+        Ok(())
+    }
+
+    // Delete a file from the remote's directory by name.
+    pub async fn delete_file(&self, name: &str) -> Result<()> {
+        let entry = self
+            .get_files_hashmap()
+            .await?
+            .remove(name)
+            .ok_or_else(|| anyhow!("File '{}' is not in the IPFS directory.", name))?;
+        self.remove_file(&entry).await
+    }
+
+    // Upload the file, replacing any existing file if overwrite is true.
+    pub async fn upload(
+        &self,
+        data_file: &DataFile,
+        path_context: &Path,
+        overwrite: bool,
+    ) -> Result<UploadOutcome> {
+        let directory = self.get_directory()?;
+        let name = data_file.basename()?;
+        let existing = self
+            .get_files()
+            .await?
+            .into_iter()
+            .find(|entry| entry.name == name);
+        if let Some(entry) = &existing {
+            if !overwrite {
+                print_info!(
+                    "IPFS directory '{}' already has file '{}' (CID={}); since \
+                     overwrite=false, it will not be replaced.",
+                    directory,
+                    name,
+                    entry.hash
+                );
+                return Ok(UploadOutcome::SkippedExists);
+            }
+        }
+
+        // add and pin the file, before removing any existing one, so a
+        // failed add leaves the remote's file in place
+        let size = data_file.upload_size(path_context)?;
+        let file = tokio::fs::File::open(data_file.full_path(path_context)?).await?;
+        let body = Body::wrap_stream(ReaderStream::new(file));
+        let part = Part::stream_with_length(body, size).file_name(name.clone());
+        let form = Form::new().part("file", part);
+        let response = self
+            .issue_request("add", &[("pin", "true"), ("cid-version", "1")], Some(form))
+            .await?;
+        let added: IpfsAdded = response.json().await?;
+        if let Some(entry) = existing {
+            // unchanged content stays pinned for the new file
+            if entry.hash == added.hash {
+                self.unlink_file(&entry.name).await?;
+            } else {
+                self.remove_file(&entry).await?;
+            }
+        }
+
+        // record it in the remote's directory under its name
+        let source = format!("/ipfs/{}", added.hash);
+        let path = format!("{}/{}", directory, name);
+        self.issue_request(
+            "files/cp",
+            &[("arg", &source), ("arg", &path), ("parents", "true")],
+            None,
+        )
+        .await?;
+        info!("added IPFS file '{}' (CID={})", added.name, added.hash);
+        Ok(UploadOutcome::Uploaded)
+    }
+
+    // Get all files in the remote's directory, in a HashMap with file
+    // name as keys.
+    pub async fn get_files_hashmap(&self) -> Result<HashMap<String, IpfsEntry>> {
+        let entries = self.get_files().await?;
+        debug!(
+            "IPFS directory has {}",
+            pluralize(entries.len() as u64, "file")
+        );
+        Ok(entries
+            .into_iter()
+            .map(|entry| (entry.name.clone(), entry))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logging_setup::setup;
+    use httpmock::prelude::*;
+    use serde_json::json;
+    use std::fs;
+    use tempfile::tempdir;
+
+    fn local_metadata() -> LocalMetadata {
+        LocalMetadata {
+            author_name: None,
+            title: None,
+            email: None,
+            affiliation: None,
+            description: None,
+            orcid: None,
+            license: None,
+            co_authors: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_init() {
+        setup();
+        let server = MockServer::start();
+        let stat = server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/stat")
+                .query_param("arg", "/scidataflow/test");
+            then.status(500)
+                .json_body(json!({"Message": "file does not exist", "Code": 0, "Type": "error"}));
+        });
+        let mkdir = server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/mkdir")
+                .query_param("arg", "/scidataflow/test")
+                .query_param("parents", "true")
+                .header("authorization", "Bearer test-token");
+            then.status(200);
+        });
+
+        let mut api = IpfsAPI::new("test", Some(server.url(""))).unwrap();
+        api.remote_init(local_metadata(), false).await.unwrap();
+        stat.assert();
+        mkdir.assert();
+        assert_eq!(api.get_directory().unwrap(), "/scidataflow/test");
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_list() {
+        setup();
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.tsv"), "a\tb\n").unwrap();
+        let data_file = DataFile::new("a.tsv".to_string(), None, dir.path())
+            .await
+            .unwrap();
+
+        let cid = "bafkreib3pe2xv5ivlgnoxbiwyehrlmxpfgl6rbhcbhnpxgf6ybazdvxv3y";
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/ls")
+                .query_param("arg", "/scidataflow/test");
+            then.status(200).json_body(json!({
+                "Entries": [
+                    {"Name": "a.tsv", "Type": 0, "Size": 4, "Hash": cid},
+                    {"Name": "sub", "Type": 1, "Size": 0, "Hash": "bafydir"}
+                ]
+            }));
+        });
+        let add = server.mock(|when, then| {
+            when.method(POST)
+                .path("/add")
+                .query_param("pin", "true")
+                .body_contains("a\tb\n");
+            then.status(200)
+                .json_body(json!({"Name": "a.tsv", "Hash": cid, "Size": "12"}));
+        });
+        let rm = server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/rm")
+                .query_param("arg", "/scidataflow/test/a.tsv");
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/pin/rm");
+            then.status(200);
+        });
+        let cp = server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/cp")
+                .query_param("arg", format!("/ipfs/{}", cid))
+                .query_param("arg", "/scidataflow/test/a.tsv");
+            then.status(200);
+        });
+
+        let mut api = IpfsAPI::new("test", Some(server.url(""))).unwrap();
+        api.directory = Some("/scidataflow/test".to_string());
+
+        // the file exists, so it's only replaced with overwrite
+        let outcome = api.upload(&data_file, dir.path(), false).await.unwrap();
+        assert_eq!(outcome, UploadOutcome::SkippedExists);
+        add.assert_hits(0);
+        let outcome = api.upload(&data_file, dir.path(), true).await.unwrap();
+        assert_eq!(outcome, UploadOutcome::Uploaded);
+        rm.assert();
+        add.assert();
+        cp.assert();
+
+        let files = api.get_remote_files().await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].name, "a.tsv");
+        assert_eq!(files[0].size, Some(4));
+        assert_eq!(files[0].md5, None);
+        assert_eq!(
+            files[0].url.as_deref(),
+            Some(format!("https://ipfs.io/ipfs/{}?filename=a.tsv", cid).as_str())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_add_fails() {
+        setup();
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.tsv"), "a\tb\n").unwrap();
+        let data_file = DataFile::new("a.tsv".to_string(), None, dir.path())
+            .await
+            .unwrap();
+        server.mock(|when, then| {
+            when.method(POST).path("/files/ls");
+            then.status(200).json_body(json!({
+                "Entries": [{"Name": "a.tsv", "Type": 0, "Size": 4, "Hash": "bafyold"}]
+            }));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/add");
+            then.status(500)
+                .json_body(json!({"Message": "node is offline", "Code": 0, "Type": "error"}));
+        });
+        let rm = server.mock(|when, then| {
+            when.method(POST).path("/files/rm");
+            then.status(200);
+        });
+
+        // the existing file is only removed once the new one is added
+        let mut api = IpfsAPI::new("test", Some(server.url(""))).unwrap();
+        api.directory = Some("/scidataflow/test".to_string());
+        assert!(api.upload(&data_file, dir.path(), true).await.is_err());
+        rm.assert_hits(0);
+    }
+
+    #[test]
+    fn test_gateway_url_encodes_name() {
+        let api = IpfsAPI::new("test", None).unwrap();
+        assert_eq!(
+            api.gateway_url("bafy", "my data #1&2.tsv"),
+            "https://ipfs.io/ipfs/bafy?filename=my%20data%20%231%262.tsv"
+        );
+    }
+}
@@ -0,0 +1,231 @@
+// An in-memory remote for tests (Remote::Test), so the push and pull
+// logic can be tested without mocking a service's API.
+//
+// Files are kept in a HashMap, and served for downloads by an in-process
+// mock HTTP server (pulls download through URLs, as with the real
+// services). Clones share the same files, so a test can keep a handle to
+// a remote after adding it to a DataCollection, to set up files and
+// failures and to check what was uploaded.
+
+use anyhow::{anyhow, Result};
+use httpmock::{Mock, MockServer};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::lib::data::DataFile;
+use crate::lib::remote::{duplicate_ids, mark_duplicates, RemoteFile, UploadOutcome};
+
+struct StoredFile {
+    contents: Vec<u8>,
+    // the mock serving the file's downloads
+    mock_id: usize,
+}
+
+#[derive(Default)]
+struct TestRemoteState {
+    files: HashMap<String, StoredFile>,
+    // whether listings include MD5s (some services' don't)
+    no_md5s: bool,
+    num_uploads: usize,
+    // the upload (counting from 1) that fails
+    fail_upload: Option<usize>,
+    fail_listing: bool,
+    // files listed twice, as services can after a retried upload
+    duplicated: Vec<String>,
+}
+
+#[derive(Clone)]
+pub struct TestRemote {
+    name: String,
+    server: Arc<MockServer>,
+    state: Arc<Mutex<TestRemoteState>>,
+}
+
+impl fmt::Debug for TestRemote {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestRemote")
+            .field("name", &self.name)
+            .field("files", &self.file_names())
+            .finish()
+    }
+}
+
+impl PartialEq for TestRemote {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl TestRemote {
+    pub fn new(name: &str) -> Self {
+        TestRemote {
+            name: name.to_string(),
+            server: Arc::new(MockServer::start()),
+            state: Arc::new(Mutex::new(TestRemoteState::default())),
+        }
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // Add (or replace) a file on the remote.
+    pub fn put_file(&self, name: &str, contents: &[u8]) {
+        self.put_slow_file(name, contents, Duration::ZERO);
+    }
+
+    // Add (or replace) a file whose downloads only start after a delay,
+    // e.g. to interrupt a pull while it's downloading.
+    pub fn put_slow_file(&self, name: &str, contents: &[u8], delay: Duration) {
+        let mock_id = self
+            .server
+            .mock(|when, then| {
+                when.method("GET").path(format!("/files/{}", name));
+                then.status(200).body(contents).delay(delay);
+            })
+            .id;
+        let old = self.state.lock().unwrap().files.insert(
+            name.to_string(),
+            StoredFile {
+                contents: contents.to_vec(),
+                mock_id,
+            },
+        );
+        if let Some(old) = old {
+            Mock::new(old.mock_id, &self.server).delete();
+        }
+    }
+
+    pub fn delete_file(&self, name: &str) -> Result<()> {
+        let removed = self.state.lock().unwrap().files.remove(name);
+        let removed = removed.ok_or_else(|| anyhow!("No file '{}' on the test remote.", name))?;
+        Mock::new(removed.mock_id, &self.server).delete();
+        Ok(())
+    }
+
+    pub fn file(&self, name: &str) -> Option<Vec<u8>> {
+        let state = self.state.lock().unwrap();
+        state.files.get(name).map(|file| file.contents.clone())
+    }
+
+    pub fn file_names(&self) -> Vec<String> {
+        let mut names: Vec<_> = self.state.lock().unwrap().files.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    pub fn num_uploads(&self) -> usize {
+        self.state.lock().unwrap().num_uploads
+    }
+
+    // Leave MD5s out of listings, as for services that don't report them.
+    pub fn set_no_md5s(&self, no_md5s: bool) {
+        self.state.lock().unwrap().no_md5s = no_md5s;
+    }
+
+    // Fail the nth upload attempt (counting from 1).
+    pub fn fail_upload(&self, nth: usize) {
+        self.state.lock().unwrap().fail_upload = Some(nth);
+    }
+
+    pub fn set_fail_listing(&self, fail: bool) {
+        self.state.lock().unwrap().fail_listing = fail;
+    }
+
+    // List a file twice, under different IDs.
+    pub fn duplicate_listing(&self, name: &str) {
+        self.state.lock().unwrap().duplicated.push(name.to_string());
+    }
+
+    pub async fn get_remote_files(&self) -> Result<Vec<RemoteFile>> {
+        let state = self.state.lock().unwrap();
+        if state.fail_listing {
+            return Err(anyhow!("Test remote listing failed."));
+        }
+        let mut remote_files: Vec<RemoteFile> = state
+            .files
+            .iter()
+            .chain(
+                state
+                    .files
+                    .iter()
+                    .filter(|(name, _)| state.duplicated.contains(name)),
+            )
+            .map(|(name, file)| RemoteFile {
+                name: name.clone(),
+                md5: (!state.no_md5s).then(|| format!("{:x}", md5::compute(&file.contents))),
+                size: Some(file.contents.len() as u64),
+                remote_service: "Test".to_string(),
+                url: Some(self.server.url(format!("/files/{}", name))),
+                mod_time: None,
+                duplicate_ids: Vec::new(),
+            })
+            .collect();
+        let duplicates = duplicate_ids(
+            remote_files
+                .iter()
+                .enumerate()
+                .map(|(id, file)| (file.name.as_str(), id.to_string())),
+        );
+        mark_duplicates("Test", &duplicates, &mut remote_files);
+        Ok(remote_files)
+    }
+
+    pub async fn upload(
+        &self,
+        data_file: &DataFile,
+        path_context: &Path,
+        overwrite: bool,
+    ) -> Result<UploadOutcome> {
+        let name = data_file.basename()?;
+        {
+            let mut state = self.state.lock().unwrap();
+            state.num_uploads += 1;
+            if state.fail_upload == Some(state.num_uploads) {
+                return Err(anyhow!("Test remote upload {} failed.", state.num_uploads));
+            }
+            if state.files.contains_key(&name) && !overwrite {
+                return Ok(UploadOutcome::SkippedExists);
+            }
+        }
+        let contents = fs::read(data_file.full_path(path_context)?)?;
+        self.put_file(&name, &contents);
+        Ok(UploadOutcome::Uploaded)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_test_remote() {
+        let remote = TestRemote::new("test");
+        remote.put_file("a.tsv", b"first\n");
+        remote.put_file("a.tsv", b"second\n");
+        let files = remote.get_remote_files().await.unwrap();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].md5,
+            Some(format!("{:x}", md5::compute("second\n")))
+        );
+
+        // the replaced file is served
+        let url = files[0].url.clone().unwrap();
+        let body = reqwest::get(&url).await.unwrap().text().await.unwrap();
+        assert_eq!(body, "second\n");
+
+        // clones share files
+        let clone = remote.clone();
+        clone.delete_file("a.tsv").unwrap();
+        assert!(remote.file_names().is_empty());
+        assert_eq!(reqwest::get(&url).await.unwrap().status(), 404);
+
+        remote.set_fail_listing(true);
+        assert!(remote.get_remote_files().await.is_err());
+    }
+}
@@ -1,12 +1,14 @@
 use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
 use colored::Colorize;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
+use percent_encoding::utf8_percent_encode;
 use reqwest::{
     header::{HeaderMap, HeaderValue, CONTENT_LENGTH, CONTENT_TYPE},
     Method,
 };
-use reqwest::{Body, Client, Response};
+use reqwest::{Body, Response, StatusCode};
 use serde_derive::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryInto;
@@ -16,15 +18,39 @@ use tokio_util::io::ReaderStream;
 #[allow(unused_imports)]
 use crate::{print_info, print_warn};
 
-use crate::lib::remote::{AuthKeys, RemoteFile, RequestData};
-use crate::lib::utils::{shorten, ISSUE_URL};
-use crate::lib::{data::DataFile, project::LocalMetadata};
+use crate::lib::cache::ListingCache;
+use crate::lib::data::{Author, DataFile};
+use crate::lib::http::{self, HttpError};
+use crate::lib::project::LocalMetadata;
+use crate::lib::remote::{
+    duplicate_ids, file_window, mark_duplicates, parse_remote_timestamp, AuthKeys, RemoteFile,
+    RequestData, UploadOutcome,
+};
+use crate::lib::utils::{compute_md5, pluralize, shorten, ISSUE_URL};
 
 const BASE_URL: &str = "https://zenodo.org/api";
 
+// The depositions requested per page of the listing.
+const ZENODO_PAGE_SIZE: usize = 100;
+
 // for testing:
 const TEST_TOKEN: &str = "test-token";
 
+/// The upload types Zenodo accepts for a deposition's `upload_type` (see
+/// `sdf link --type`).
+pub const ZENODO_RECORD_TYPES: &[&str] = &[
+    "publication",
+    "poster",
+    "presentation",
+    "dataset",
+    "image",
+    "video",
+    "software",
+    "lesson",
+    "physicalobject",
+    "other",
+];
+
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
 pub struct ZenodoDeposition {
     conceptrecid: String,
@@ -57,6 +83,32 @@ pub struct ZenodoFileUpload {
     delete_marker: bool,
 }
 
+// Zenodo deposition files are flat, so a file's path relative to its
+// linked directory is stored with its '/' separators encoded as "%2F"
+// (e.g. 'raw/a.tsv' is stored as 'raw%2Fa.tsv'), which keeps files in
+// subdirectories from colliding on their basenames. Plain basenames are
+// stored unchanged.
+const PATH_SEPARATOR: &str = "%2F";
+
+fn encode_filename(relative_path: &str) -> String {
+    relative_path.replace('/', PATH_SEPARATOR)
+}
+
+fn decode_filename(filename: &str) -> String {
+    filename.replace(PATH_SEPARATOR, "/")
+}
+
+// A file's path relative to its linked directory, or its basename if it
+// is not in it (e.g. an archive built elsewhere).
+fn relative_name(data_file: &DataFile, tracked_dir: &str) -> Result<String> {
+    match Path::new(&data_file.path).strip_prefix(tracked_dir) {
+        Ok(relative) if !tracked_dir.is_empty() => {
+            Ok(relative.to_string_lossy().replace('\\', "/"))
+        }
+        _ => data_file.basename(),
+    }
+}
+
 // NOTE: this is a shim to address issue #16. This is caused by an upstream
 // bug in Zenodo.
 fn deserialize_filesize<'de, D>(deserializer: D) -> Result<usize, D::Error>
@@ -77,14 +129,61 @@ pub struct ZenodoFile {
     links: ZenodoLinks,
 }
 
+// The part of a deposition needed for its modification time.
+#[derive(Debug, Deserialize)]
+struct ZenodoDepositionModified {
+    modified: String,
+}
+
+// A file of a published record, as listed by the public records API
+// (which, unlike the deposition API, does not need an access token).
+#[derive(Debug, Deserialize)]
+struct ZenodoRecordFile {
+    key: String,
+    size: u64,
+    // prefixed with the algorithm, e.g. "md5:<hex digest>"
+    checksum: String,
+    links: ZenodoRecordFileLinks,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZenodoRecordFileLinks {
+    #[serde(rename = "self")]
+    download: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZenodoRecord {
+    files: Vec<ZenodoRecordFile>,
+}
+
+impl From<ZenodoRecordFile> for RemoteFile {
+    fn from(file: ZenodoRecordFile) -> Self {
+        RemoteFile {
+            name: decode_filename(&file.key),
+            md5: file
+                .checksum
+                .strip_prefix("md5:")
+                .map(|md5| md5.to_string()),
+            size: Some(file.size),
+            remote_service: "Zenodo".to_string(),
+            url: Some(file.links.download),
+            mod_time: None,
+            duplicate_ids: Vec::new(),
+        }
+    }
+}
+
 impl From<ZenodoFile> for RemoteFile {
     fn from(znd: ZenodoFile) -> Self {
         RemoteFile {
-            name: znd.filename,
+            name: decode_filename(&znd.filename),
             md5: Some(znd.checksum),
             size: Some(znd.filesize as u64),
             remote_service: "Zenodo".to_string(),
             url: znd.links.download,
+            mod_time: None,
+            duplicate_ids: Vec::new(),
         }
     }
 }
@@ -108,6 +207,20 @@ pub struct ZenodoLinks {
 struct Creator {
     name: String,
     affiliation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    orcid: Option<String>,
+}
+
+// Zenodo's creators have no contact information, so authors' emails
+// are not sent.
+impl From<Author> for Creator {
+    fn from(author: Author) -> Self {
+        Creator {
+            name: author.name,
+            affiliation: author.affiliation,
+            orcid: author.orcid,
+        }
+    }
 }
 
 // We need this wrapper to provide the metadata
@@ -125,15 +238,19 @@ struct ZenodoMetadata {
     upload_type: Option<String>,
     description: Option<String>,
     creators: Option<Vec<Creator>>,
+    // Zenodo's license ID, the lowercased SPDX identifier (e.g. 'cc-by-4.0')
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    license: Option<String>,
 }
 
 impl TryInto<ZenodoDepositionData> for LocalMetadata {
     type Error = anyhow::Error;
 
     fn try_into(self) -> Result<ZenodoDepositionData> {
-        let name = self
-            .author_name
-            .ok_or_else(|| anyhow!("Author name is required"))?;
+        if self.author_name.is_none() {
+            return Err(anyhow!("Author name is required"));
+        }
+        let creators: Vec<Creator> = self.authors().into_iter().map(Creator::from).collect();
         // TODO? Warn user of default description?
         let description = self
             .description
@@ -150,10 +267,8 @@ impl TryInto<ZenodoDepositionData> for LocalMetadata {
                 ))?,
                 upload_type: Some("dataset".to_string()),
                 description: Some(description),
-                creators: Some(vec![Creator {
-                    name,
-                    affiliation: self.affiliation,
-                }]),
+                creators: Some(creators),
+                license: self.license.map(|license| license.to_lowercase()),
             },
         })
     }
@@ -196,6 +311,13 @@ pub struct ZenodoAPI {
     // this is rather lengthy.
     deposition_id: Option<u64>,
     bucket_url: Option<String>,
+    // The DOI Zenodo reserves when the deposition is created, which
+    // becomes active once it is published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    doi: Option<String>,
+    // the deposition's upload type; 'dataset' if not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    record_type: Option<String>,
 }
 
 impl ZenodoAPI {
@@ -214,7 +336,9 @@ impl ZenodoAPI {
             auth_keys.temporary_add("zenodo", TEST_TOKEN);
             auth_keys
         };
-        let token = auth_keys.get("zenodo".to_string())?;
+        // the token may not be saved yet: Project::link() sets it, and
+        // only saves it once the service has accepted it
+        let token = auth_keys.get("zenodo".to_string()).unwrap_or_default();
         let base_url = base_url.unwrap_or(BASE_URL.to_string());
         Ok(ZenodoAPI {
             base_url,
@@ -222,6 +346,8 @@ impl ZenodoAPI {
             token,
             deposition_id: None,
             bucket_url: None,
+            doi: None,
+            record_type: None,
         })
     }
 
@@ -229,6 +355,33 @@ impl ZenodoAPI {
         self.token = token;
     }
 
+    pub fn has_token(&self) -> bool {
+        !self.token.is_empty()
+    }
+
+    pub fn set_record_type(&mut self, record_type: &str) -> Result<()> {
+        let record_type = record_type.to_lowercase();
+        if !ZENODO_RECORD_TYPES.contains(&record_type.as_str()) {
+            return Err(anyhow!(
+                "Unknown Zenodo upload type '{}'; use one of: {}.",
+                record_type,
+                ZENODO_RECORD_TYPES.join(", ")
+            ));
+        }
+        self.record_type = Some(record_type);
+        Ok(())
+    }
+
+    pub fn record_type(&self) -> &str {
+        self.record_type.as_deref().unwrap_or("dataset")
+    }
+
+    // Set the API base URL, e.g. for a mock server or another
+    // Invenio instance (the base URL is not saved in the manifest).
+    pub fn set_base_url(&mut self, base_url: String) {
+        self.base_url = base_url;
+    }
+
     // issue request
     // TODO: this is the same as FigShareAPI's issue_request().
     // Since APIs can have different authentication routines, we
@@ -240,15 +393,21 @@ impl ZenodoAPI {
         headers: Option<HeaderMap>,
         data: Option<RequestData<T>>,
     ) -> Result<Response> {
+        // the endpoint may have its own query string
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
         let url = format!(
-            "{}/{}?access_token={}",
+            "{}/{}{}access_token={}",
             self.base_url.trim_end_matches('/'),
             endpoint.trim_start_matches('/'),
+            separator,
             self.token
         );
-        trace!("request URL: {:?}", &url);
+        // the token is in the URL, so it's masked in logs and errors
+        let masked_url = http::mask_token(&url, &self.token);
+        trace!("request URL: {:?}", &masked_url);
 
-        let client = Client::new();
+        let client = http::client_for(&self.base_url)?;
+        let method_name = method.to_string();
         let mut request = client.request(method, &url);
         if let Some(h) = headers {
             request = request.headers(h);
@@ -269,34 +428,80 @@ impl ZenodoAPI {
             None => request,
         };
 
-        trace!("request (before send): {:?}", request);
-        let response = request.send().await?;
+        trace!(
+            "request (before send): {}",
+            http::mask_token(&format!("{:?}", request), &self.token)
+        );
+        let response = request
+            .send()
+            .await
+            .map_err(http::send_error)
+            .with_context(|| format!("{} {}", method_name, masked_url))?;
 
         let response_status = response.status();
-        if response_status.is_success() {
+        // 304 Not Modified is only returned for conditional requests
+        // (see ListingCache::conditional_get())
+        if response_status.is_success() || response_status == StatusCode::NOT_MODIFIED {
             Ok(response)
         } else {
-            let text = &response.text().await?;
-            Err(anyhow!(
-                "HTTP Error: {}\nurl: {:?}\n{:?}",
-                response_status,
-                &url,
-                text
-            ))
+            let text = response.text().await?;
+            Err(HttpError::new(response_status, &masked_url, text).into())
         }
     }
 
+    // Get the depositions, following the listing's next links.
     pub async fn get_depositions(&self) -> Result<Vec<ZenodoDeposition>> {
-        let response = self
-            .issue_request::<HashMap<String, String>>(
-                Method::GET,
-                "/deposit/depositions",
-                None,
-                None,
-            )
-            .await?;
-        let info: Vec<ZenodoDeposition> = response.json().await?;
-        Ok(info)
+        http::fetch_pages(|page| async move {
+            let url = format!(
+                "/deposit/depositions?page={}&size={}",
+                page, ZENODO_PAGE_SIZE
+            );
+            let response = self
+                .issue_request::<HashMap<String, String>>(Method::GET, &url, None, None)
+                .await?;
+            let has_next = http::has_next_link(&response);
+            let info: Vec<ZenodoDeposition> = response.json().await?;
+            Ok((info, has_next))
+        })
+        .await
+    }
+
+    // Check the token and connection with a minimal read-only request,
+    // returning a summary of what the token can access.
+    pub async fn check_connection(&self) -> Result<String> {
+        let depositions = self.get_depositions().await?;
+        Ok(format!(
+            "the token can access {}",
+            pluralize(depositions.len() as u64, "deposition")
+        ))
+    }
+
+    // Check that Zenodo accepts the token with a cheap authenticated
+    // request (see Remote::validate_token()).
+    pub async fn validate_token(&self) -> Result<()> {
+        self.issue_request::<HashMap<String, String>>(
+            Method::GET,
+            "/deposit/depositions?size=1",
+            None,
+            None,
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Check that the deposition exists and its files can still be
+    // changed: once published (submitted), they are locked.
+    pub async fn check_writable(&self) -> Result<String> {
+        let id = self.get_deposition_id()?;
+        let deposition = self.get_deposition(id.try_into()?).await?;
+        if deposition.submitted {
+            return Err(anyhow!(
+                "deposition {} is published, so its files cannot be changed \
+                 (create a new version of it on Zenodo)",
+                id
+            ));
+        }
+        Ok(format!("deposition {} is writable", id))
     }
 
     pub async fn get_deposition_exists(&self) -> Result<bool> {
@@ -324,18 +529,114 @@ impl ZenodoAPI {
                 // We need to do one more API call, to get the full listing
                 // with the bucket URL.
                 let partial_deposition = matches_found.remove(0);
-                let url = format!("deposit/depositions/{}", partial_deposition.id);
-                let response = self
-                    .issue_request::<HashMap<String, String>>(Method::GET, &url, None, None)
-                    .await?;
-                let deposition: ZenodoDeposition = response.json().await?;
-                Ok(Some(deposition))
+                Ok(Some(self.get_deposition(partial_deposition.id).await?))
             }
         } else {
             Ok(None)
         }
     }
 
+    // Get the full listing of a deposition, including the bucket URL.
+    pub async fn get_deposition(&self, id: u32) -> Result<ZenodoDeposition> {
+        let url = format!("deposit/depositions/{}", id);
+        let response = self
+            .issue_request::<HashMap<String, String>>(Method::GET, &url, None, None)
+            .await?;
+        let deposition: ZenodoDeposition = response.json().await?;
+        Ok(deposition)
+    }
+
+    // Set the upload type of the remote's deposition to the record type
+    // (see 'sdf remote type').
+    pub async fn update_record_type(&self) -> Result<()> {
+        self.update_metadata("upload_type", self.record_type())
+            .await
+    }
+
+    // Set the license of the remote's deposition (an SPDX identifier; see
+    // 'sdf metadata --license').
+    pub async fn update_license(&self, license: &str) -> Result<()> {
+        self.update_metadata("license", &license.to_lowercase())
+            .await
+    }
+
+    // Set one field of the deposition's metadata. Zenodo replaces all of a
+    // deposition's metadata on updates, so the rest of it is sent back
+    // unchanged.
+    async fn update_metadata(&self, field: &str, value: &str) -> Result<()> {
+        let id = self.get_deposition_id()?;
+        let url = format!("deposit/depositions/{}", id);
+        let deposition: serde_json::Value = self
+            .issue_request::<HashMap<String, String>>(Method::GET, &url, None, None)
+            .await?
+            .json()
+            .await?;
+        let mut metadata = deposition
+            .get("metadata")
+            .cloned()
+            .ok_or(anyhow!("Zenodo deposition {} has no metadata.", id))?;
+        metadata[field] = serde_json::Value::from(value);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let data = serde_json::json!({ "metadata": metadata });
+        self.issue_request(
+            Method::PUT,
+            &url,
+            Some(headers),
+            Some(RequestData::Json(data)),
+        )
+        .await?;
+        Ok(())
+    }
+
+    // Delete an unpublished deposition.
+    pub async fn delete_deposition(&self, id: u32) -> Result<()> {
+        let url = format!("deposit/depositions/{}", id);
+        self.issue_request::<HashMap<String, String>>(Method::DELETE, &url, None, None)
+            .await?;
+        info!("deleted Zenodo deposition (ID={})", id);
+        Ok(())
+    }
+
+    // After creating a deposition, re-list the depositions with this title
+    // in case another process created one at the same time. The oldest
+    // deposition wins: if ours is newer, it is deleted and the oldest is
+    // adopted (and the other process keeps its deposition, or does the same).
+    async fn resolve_concurrent_creation(
+        &self,
+        created: ZenodoDeposition,
+    ) -> Result<ZenodoDeposition> {
+        let mut matches_found: Vec<_> = self
+            .get_depositions()
+            .await?
+            .into_iter()
+            .filter(|a| a.title == self.name)
+            .collect();
+        matches_found.sort_by(|a, b| (&a.created, a.id).cmp(&(&b.created, b.id)));
+        let oldest_id = match matches_found.first() {
+            Some(oldest) if oldest.id != created.id => oldest.id,
+            _ => return Ok(created),
+        };
+
+        let conflict = format!(
+            "Another Zenodo Deposition with the title '{}' (ID={}) was created \
+             at the same time as this one (ID={})",
+            self.name, oldest_id, created.id
+        );
+        self.delete_deposition(created.id).await.context(format!(
+            "{}, and deleting this one failed. Delete one of them on Zenodo, \
+             then link again with --link-only.",
+            conflict
+        ))?;
+        print_warn!("{}; deleted this one and linked the other.", conflict);
+        self.get_deposition(oldest_id).await.context(format!(
+            "{}; this one was deleted, but the other could not be retrieved. \
+             Link again with --link-only.",
+            conflict
+        ))
+    }
+
     // Create a new Zenodo Deposition
     //
     // Note that this uses LocalMetadata to propagate some of the Zenodo metadata fields
@@ -351,7 +652,8 @@ impl ZenodoAPI {
         let mut metadata_copy = local_metadata.clone();
         metadata_copy.title = Some(self.name.clone());
 
-        let deposition_data: ZenodoDepositionData = metadata_copy.try_into()?;
+        let mut deposition_data: ZenodoDepositionData = metadata_copy.try_into()?;
+        deposition_data.metadata.upload_type = Some(self.record_type().to_string());
 
         let data = Some(RequestData::Json(deposition_data));
         let response = self
@@ -382,8 +684,10 @@ impl ZenodoAPI {
             }
             existing_info
         } else {
-            // Step 2: Create a new deposition if none exists
-            self.create_deposition(local_metadata).await?
+            // Step 2: Create a new deposition if none exists, and check
+            // that no other process created one at the same time
+            let created = self.create_deposition(local_metadata).await?;
+            self.resolve_concurrent_creation(created).await?
         };
 
         self.deposition_id = Some(info.id as u64);
@@ -392,6 +696,10 @@ impl ZenodoAPI {
             return Err(anyhow!("Internal Error: ZenodoAPI::find_deposition() did not return an entry with a bucket_url."));
         }
         self.bucket_url = bucket_url;
+        self.doi = info
+            .metadata
+            .prereserve_doi
+            .map(|prereserved| prereserved.doi);
 
         Ok(())
     }
@@ -399,9 +707,36 @@ impl ZenodoAPI {
     // Check if file exists, returning None if not,
     // and the ZenodoFile if so
     // TODO: could be part of higher Remote API, e.g. through generics?
+    // Get a file of the deposition by its (decoded) name.
     pub async fn file_exists(&self, name: &str) -> Result<Option<ZenodoFile>> {
         let files = self.get_files_hashmap().await?;
-        Ok(files.get(name).cloned())
+        Ok(files.get(&encode_filename(name)).cloned())
+    }
+
+    // Delete a file from the deposition by its name.
+    pub async fn delete_file(&self, name: &str) -> Result<()> {
+        let file = self
+            .file_exists(name)
+            .await?
+            .ok_or_else(|| anyhow!("File '{}' is not in the Zenodo deposition.", name))?;
+        self.delete_article_file(&file).await
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    // The record's public landing page (once published), on the same
+    // Zenodo instance as the API (e.g. the sandbox).
+    pub fn landing_url(&self) -> Option<String> {
+        let site = self.base_url.trim_end_matches('/').trim_end_matches("/api");
+        self.deposition_id
+            .map(|id| format!("{}/records/{}", site, id))
+    }
+
+    // The prereserved DOI, if Zenodo reserved one.
+    pub fn doi(&self) -> Option<&str> {
+        self.doi.as_deref()
     }
 
     pub fn get_deposition_id(&self) -> Result<u64> {
@@ -428,21 +763,23 @@ impl ZenodoAPI {
     pub async fn upload(
         &self,
         data_file: &DataFile,
+        tracked_dir: &str,
         path_context: &Path,
         overwrite: bool,
-    ) -> Result<bool> {
+    ) -> Result<UploadOutcome> {
         // (1) First, let's make sure that data_file isn't empty
-        if data_file.size == 0 {
+        let file_size = data_file.upload_size(path_context)?;
+        if file_size == 0 {
             return Err(anyhow!(
                 "ZenodoAPI::upload() was called to upload an empty file: '{:?}'",
                 data_file.full_path(path_context)?
             ));
         }
 
-        // (2) Get local file info
+        // (2) Get local file info; the file is stored under its path
+        // relative to the linked directory (see encode_filename())
         let full_path = path_context.join(&data_file.path);
-        let name = data_file.basename()?;
-        let file_size = data_file.size;
+        let name = relative_name(data_file, tracked_dir)?;
 
         // (3) Find the bucket url.
         let bucket_url = self.bucket_url.as_ref().ok_or(anyhow!(
@@ -463,7 +800,7 @@ impl ZenodoAPI {
                     name,
                     id
                 );
-                return Ok(false);
+                return Ok(UploadOutcome::SkippedExists);
             } else {
                 info!(
                     "FigShare::upload() is deleting file '{}' since \
@@ -490,10 +827,19 @@ impl ZenodoAPI {
         // (7) we need to take the Zenodo bucket_url, remove the base since
         // issue_request adds it
         let bucket_endpoint = remove_base_url(bucket_url)?;
-        let bucket_endpoint = format!("{}/{}", bucket_endpoint, name);
+        let bucket_endpoint = format!(
+            "{}/{}",
+            bucket_endpoint,
+            utf8_percent_encode(&encode_filename(&name), http::FILENAME_ESCAPE)
+        );
 
-        // (8) Prepare the file upload
-        let file = tokio::fs::File::open(full_path).await?;
+        // (8) Prepare the file upload; the remote's MD5 is compared to that
+        // of the file as it's uploaded, which may have changed since the
+        // manifest's MD5 was computed
+        let local_md5 = compute_md5(&full_path)
+            .await?
+            .ok_or_else(|| anyhow!("Could not compute the MD5 of {:?}.", full_path))?;
+        let file = file_window(&full_path, 0, file_size).await?;
         let response = self
             .issue_request::<HashMap<String, String>>(
                 Method::PUT,
@@ -515,7 +861,6 @@ impl ZenodoAPI {
             .strip_prefix("md5:")
             .expect(&err_msg)
             .to_owned();
-        let local_md5 = data_file.md5.clone();
 
         let msg = format!(
             "After upload, the local ({}) and remote ({}) MD5s differed.\n\
@@ -544,38 +889,97 @@ impl ZenodoAPI {
                                          trying to delete the file.",
                         msg
                     ))?;
-                    Ok(false)
+                    print_warn!("{}", msg.trim_end());
+                    Ok(UploadOutcome::FailedChecksum)
                 }
             }
         } else {
             // we did the upload, MD5s match
-            Ok(true)
+            Ok(UploadOutcome::Uploaded)
         }
     }
 
+    // Get the deposition's files. Unlike the depositions listing, this
+    // is not paginated: all of a deposition's files are returned at once.
     pub async fn get_files(&self) -> Result<Vec<ZenodoFile>> {
         let id = self.get_deposition_id()?;
         let url = format!("{}/{}/files", "/deposit/depositions", id);
+        // the listing is cached, and only re-downloaded if it has changed
+        let cache = ListingCache::new()?;
+        let key = ListingCache::key("zenodo", &format!("{}{}", self.base_url, url));
+        let body = cache
+            .conditional_get(&key, |headers| {
+                self.issue_request::<HashMap<String, String>>(Method::GET, &url, headers, None)
+            })
+            .await?;
+        let files: Vec<ZenodoFile> = serde_json::from_str(&body)?;
+        Ok(files)
+    }
+
+    // Get the last modification time of the deposition. Zenodo does not
+    // provide modification times for individual files.
+    pub async fn get_modified_time(&self) -> Result<DateTime<Utc>> {
+        let id = self.get_deposition_id()?;
+        let url = format!("{}/{}", "/deposit/depositions", id);
         let response = self
             .issue_request::<HashMap<String, String>>(Method::GET, &url, None, None)
             .await?;
-        let files: Vec<ZenodoFile> = response.json().await?;
-        Ok(files)
+        let deposition: ZenodoDepositionModified = response.json().await?;
+        parse_remote_timestamp(&deposition.modified)
     }
 
     pub async fn get_remote_files(&self) -> Result<Vec<RemoteFile>> {
         let articles = self.get_files().await?;
-        let remote_files: Vec<RemoteFile> = articles.into_iter().map(RemoteFile::from).collect();
+        // modification times are optional, so failing to get one is not an error
+        let mod_time = self.get_modified_time().await.ok();
+        let ids: Vec<String> = articles.iter().map(|file| file.id.clone()).collect();
+        let mut remote_files: Vec<RemoteFile> = articles
+            .into_iter()
+            .map(|file| RemoteFile {
+                mod_time,
+                ..RemoteFile::from(file)
+            })
+            .collect();
+        let duplicates = duplicate_ids(remote_files.iter().map(|file| file.name.as_str()).zip(ids));
+        mark_duplicates("Zenodo", &duplicates, &mut remote_files);
         Ok(remote_files)
     }
 
+    // Get the files of the published record through the public records
+    // API, which does not need an access token.
+    pub async fn get_public_files(&self) -> Result<Vec<RemoteFile>> {
+        let id = self.get_deposition_id()?;
+        let url = format!("{}/records/{}", self.base_url.trim_end_matches('/'), id);
+        trace!("request URL: {:?}", &url);
+        let response = http::get(&url)
+            .await
+            .with_context(|| format!("GET {}", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(HttpError::new(status, &url, response.text().await?).into());
+        }
+        let record: ZenodoRecord = response.json().await?;
+        Ok(record.files.into_iter().map(RemoteFile::from).collect())
+    }
+
     // Get all files from a Zenodo Deposition, in a HashMap
     // with file name as keys.
+    // Files with duplicate names (see duplicate_ids()) keep the first
+    // listed; pushes and pulls skip these files, so this only matters
+    // for deleting them.
     pub async fn get_files_hashmap(&self) -> Result<HashMap<String, ZenodoFile>> {
-        let mut files: Vec<ZenodoFile> = self.get_files().await?;
+        let files: Vec<ZenodoFile> = self.get_files().await?;
+        let duplicates = duplicate_ids(
+            files
+                .iter()
+                .map(|file| (file.filename.as_str(), file.id.clone())),
+        );
+        if !duplicates.is_empty() {
+            debug!("Zenodo listing has duplicate names: {:?}", duplicates);
+        }
         let mut files_hash: HashMap<String, ZenodoFile> = HashMap::new();
-        for file in files.iter_mut() {
-            files_hash.insert(file.filename.clone(), file.clone());
+        for file in files {
+            files_hash.entry(file.filename.clone()).or_insert(file);
         }
         Ok(files_hash)
     }
@@ -588,10 +992,74 @@ impl ZenodoAPI {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lib::data::PushSummary;
+    use crate::lib::remote::Remote;
+    use crate::lib::test_utilities::check_error;
     use crate::logging_setup::setup;
     use httpmock::prelude::*;
-    use serde_json::json;
+    use serde_json::{json, Value};
     use std::io::Write;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_encode_filename() {
+        assert_eq!(encode_filename("a.tsv"), "a.tsv");
+        assert_eq!(encode_filename("raw/2023/a.tsv"), "raw%2F2023%2Fa.tsv");
+        for path in ["a.tsv", "raw/a.tsv", "raw/2023/a.tsv"] {
+            assert_eq!(decode_filename(&encode_filename(path)), path);
+        }
+
+        // listed files are named by their relative paths
+        let file: ZenodoFile = serde_json::from_value(json!({
+            "checksum": "abc",
+            "filename": "raw%2Fa.tsv",
+            "filesize": 4.0,
+            "id": "1",
+            "links": {}
+        }))
+        .unwrap();
+        assert_eq!(RemoteFile::from(file).name, "raw/a.tsv");
+    }
+
+    #[tokio::test]
+    async fn test_validate_token() {
+        setup();
+        let server = MockServer::start();
+        let mut validate_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/deposit/depositions")
+                .query_param("size", "1");
+            then.status(401).json_body(json!({ "status": 401 }));
+        });
+        let create_mock = server.mock(|when, then| {
+            when.method(POST).path("/deposit/depositions");
+            then.status(201).json_body(json!({ "id": 1 }));
+        });
+
+        let mut remote = Remote::ZenodoAPI(ZenodoAPI::new("test", Some(server.url(""))).unwrap());
+        remote.set_token("s3cret-token".to_string()).unwrap();
+        let err = remote.validate_token().await.unwrap_err().to_string();
+        assert!(
+            err.starts_with("Zenodo rejected the token (401)."),
+            "{}",
+            err
+        );
+        assert!(err.contains("deposit:write"));
+        validate_mock.assert();
+        create_mock.assert_hits(0);
+        validate_mock.delete();
+
+        // other errors are passed on, with the token in the URL masked
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(500);
+        });
+        let err = format!("{:?}", remote.validate_token().await.unwrap_err());
+        assert!(err.contains("500"), "{}", err);
+        assert!(err.contains("access_token=****"), "{}", err);
+        assert!(!err.contains("s3cret-token"), "{}", err);
+        create_mock.assert_hits(0);
+    }
 
     #[tokio::test]
     async fn test_remote_init_success() {
@@ -611,6 +1079,9 @@ mod tests {
             description: Some(
                 "Let's build infrastructure so science can build off itself.".to_string(),
             ),
+            orcid: None,
+            license: None,
+            co_authors: Vec::new(),
         };
 
         // Create a mock deposition endpoint with a simulated success response
@@ -676,13 +1147,328 @@ mod tests {
         let _result = api.remote_init(local_metadata, false).await;
         //info!("result: {:?}", result);
 
-        // ensure the specified mocks were called exactly one time (or fail).
-        deposition_get_mock.assert();
+        // ensure the specified mocks were called exactly one time (or fail);
+        // the depositions are listed again after creating one.
+        deposition_get_mock.assert_hits(2);
         deposition_mock.assert();
 
         // Assert that the deposition_id and bucket_url have been set correctly
         assert_eq!(api.deposition_id, Some(expected_id as u64));
         assert_eq!(api.bucket_url, Some(expected_bucket_url.to_string()));
+        assert_eq!(api.doi(), Some("10.5281/zenodo.8266448"));
+    }
+
+    #[tokio::test]
+    async fn test_remote_init_link_only_doi() {
+        setup();
+        let server = MockServer::start();
+        let mut deposition = deposition_json(
+            7,
+            "2023-08-20T01:31:12.406094+00:00",
+            "http://zenodo.com/api/bucket-7",
+        );
+        deposition["metadata"]["prereserve_doi"] = json!({ "doi": "10.5281/zenodo.7", "recid": 7 });
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(200).json_body(json!([deposition.clone()]));
+        });
+        let get_mock = server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7");
+            then.status(200).json_body(deposition.clone());
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        api.remote_init(local_metadata(), true).await.unwrap();
+        get_mock.assert();
+        assert_eq!(api.deposition_id, Some(7));
+        assert_eq!(api.doi(), Some("10.5281/zenodo.7"));
+    }
+
+    fn deposition_json(id: u32, created: &str, bucket_url: &str) -> serde_json::Value {
+        json!({
+            "conceptrecid": "8266447",
+            "created": created,
+            "id": id,
+            "links": { "bucket": bucket_url },
+            "metadata": { "title": "test" },
+            "modified": created,
+            "owner": 110965,
+            "record_id": id,
+            "state": "unsubmitted",
+            "submitted": false,
+            "title": "test"
+        })
+    }
+
+    #[tokio::test]
+    async fn test_record_type() {
+        setup();
+        let server = MockServer::start();
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        assert!(api.set_record_type("figure").is_err());
+        api.set_record_type("software").unwrap();
+
+        let create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/deposit/depositions")
+                .json_body_partial(r#"{ "metadata": { "upload_type": "software" } }"#);
+            then.status(200).json_body(deposition_json(
+                7,
+                "2023-08-20T01:31:12.406094+00:00",
+                "http://zenodo.com/api/bucket-7",
+            ));
+        });
+        let metadata = LocalMetadata {
+            author_name: Some("Joan B. Scientist".to_string()),
+            title: None,
+            email: None,
+            affiliation: None,
+            description: None,
+            orcid: None,
+            license: None,
+            co_authors: Vec::new(),
+        };
+        let deposition = api.create_deposition(metadata).await.unwrap();
+        create_mock.assert();
+
+        // changing the type after linking keeps the rest of the metadata
+        api.deposition_id = Some(deposition.id as u64);
+        api.set_record_type("dataset").unwrap();
+        let get_mock = server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7");
+            then.status(200).json_body(deposition_json(
+                7,
+                "2023-08-20T01:31:12.406094+00:00",
+                "http://zenodo.com/api/bucket-7",
+            ));
+        });
+        let update_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/deposit/depositions/7")
+                .json_body(json!({ "metadata": { "title": "test", "upload_type": "dataset" } }));
+            then.status(200).json_body(json!({}));
+        });
+        api.update_record_type().await.unwrap();
+        get_mock.assert();
+        update_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_license() {
+        setup();
+        let server = MockServer::start();
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        let create_mock = server.mock(|when, then| {
+            when.method(POST)
+                .path("/deposit/depositions")
+                .json_body_partial(r#"{ "metadata": { "license": "cc-by-4.0" } }"#);
+            then.status(200).json_body(deposition_json(
+                7,
+                "2023-08-20T01:31:12.406094+00:00",
+                "http://zenodo.com/api/bucket-7",
+            ));
+        });
+        let metadata = LocalMetadata {
+            orcid: None,
+            license: Some("CC-BY-4.0".to_string()),
+            co_authors: Vec::new(),
+            ..local_metadata()
+        };
+        let deposition = api.create_deposition(metadata).await.unwrap();
+        create_mock.assert();
+
+        // setting it later keeps the rest of the metadata
+        api.deposition_id = Some(deposition.id as u64);
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7");
+            then.status(200).json_body(deposition_json(
+                7,
+                "2023-08-20T01:31:12.406094+00:00",
+                "http://zenodo.com/api/bucket-7",
+            ));
+        });
+        let update_mock = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/deposit/depositions/7")
+                .json_body(json!({ "metadata": { "title": "test", "license": "cc0-1.0" } }));
+            then.status(200).json_body(json!({}));
+        });
+        api.update_license("CC0-1.0").await.unwrap();
+        update_mock.assert();
+    }
+
+    // Mock two processes creating a deposition at the same time: the
+    // first listing is empty, and later ones include both depositions.
+    // The matcher can't capture state, so each test has its own flag.
+    fn mock_concurrent_creation(
+        server: &MockServer,
+        first_listing: fn(&HttpMockRequest) -> bool,
+        ours: u32,
+    ) {
+        let older = "2023-08-20T01:31:12.406094+00:00";
+        let newer = "2023-08-20T01:31:13.102312+00:00";
+        server.mock(|when, then| {
+            when.method(GET)
+                .path("/deposit/depositions")
+                .matches(first_listing);
+            then.status(200).json_body(json!([]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(200).json_body(json!([
+                deposition_json(1, older, "http://zenodo.com/api/bucket-1"),
+                deposition_json(2, newer, "http://zenodo.com/api/bucket-2"),
+            ]));
+        });
+        server.mock(|when, then| {
+            when.method(POST).path("/deposit/depositions");
+            let created = if ours == 1 { older } else { newer };
+            let bucket_url = format!("http://zenodo.com/api/bucket-{}", ours);
+            then.status(201)
+                .json_body(deposition_json(ours, created, &bucket_url));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/1");
+            then.status(200)
+                .json_body(deposition_json(1, older, "http://zenodo.com/api/bucket-1"));
+        });
+    }
+
+    fn creators_json(metadata: LocalMetadata) -> Value {
+        let data: ZenodoDepositionData = metadata.try_into().unwrap();
+        serde_json::to_value(data).unwrap()["metadata"]["creators"].clone()
+    }
+
+    #[test]
+    fn test_creators_one_author() {
+        let metadata = LocalMetadata {
+            title: Some("Test".to_string()),
+            email: Some("joan@berkeley.edu".to_string()),
+            affiliation: Some("UC Berkeley".to_string()),
+            orcid: Some("0000-0002-1825-0097".to_string()),
+            ..local_metadata()
+        };
+        // the email is not sent, since Zenodo's creators have no contact information
+        assert_eq!(
+            creators_json(metadata),
+            json!([{
+                "name": "Joan B. Scientist",
+                "affiliation": "UC Berkeley",
+                "orcid": "0000-0002-1825-0097"
+            }])
+        );
+    }
+
+    #[test]
+    fn test_creators_three_authors() {
+        let metadata = LocalMetadata {
+            title: Some("Test".to_string()),
+            co_authors: vec![
+                Author {
+                    name: "Ada Lovelace".to_string(),
+                    email: Some("ada@example.org".to_string()),
+                    affiliation: None,
+                    orcid: Some("0000-0002-9079-593X".to_string()),
+                },
+                Author {
+                    name: "Alan Turing".to_string(),
+                    email: None,
+                    affiliation: Some("Cambridge".to_string()),
+                    orcid: None,
+                },
+            ],
+            ..local_metadata()
+        };
+        assert_eq!(
+            creators_json(metadata),
+            json!([
+                { "name": "Joan B. Scientist", "affiliation": null },
+                { "name": "Ada Lovelace", "affiliation": null, "orcid": "0000-0002-9079-593X" },
+                { "name": "Alan Turing", "affiliation": "Cambridge" }
+            ])
+        );
+
+        // the configured user is required
+        let metadata = LocalMetadata {
+            author_name: None,
+            ..local_metadata()
+        };
+        let result: Result<ZenodoDepositionData> = metadata.try_into();
+        check_error(result, "Author name is required");
+    }
+
+    fn local_metadata() -> LocalMetadata {
+        LocalMetadata {
+            author_name: Some("Joan B. Scientist".to_string()),
+            title: None,
+            email: None,
+            affiliation: None,
+            description: None,
+            orcid: None,
+            license: None,
+            co_authors: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_remote_init_concurrent_creation() {
+        setup();
+        static LISTED: AtomicBool = AtomicBool::new(false);
+        let server = MockServer::start();
+        mock_concurrent_creation(&server, |_| !LISTED.swap(true, Ordering::SeqCst), 2);
+        let delete_mock = server.mock(|when, then| {
+            when.method(DELETE).path("/deposit/depositions/2");
+            then.status(204);
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        api.remote_init(local_metadata(), false).await.unwrap();
+
+        // ours was newer, so it was deleted and the older one adopted
+        delete_mock.assert();
+        assert_eq!(api.deposition_id, Some(1));
+        assert_eq!(
+            api.bucket_url,
+            Some("http://zenodo.com/api/bucket-1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remote_init_concurrent_creation_older() {
+        setup();
+        static LISTED: AtomicBool = AtomicBool::new(false);
+        let server = MockServer::start();
+        mock_concurrent_creation(&server, |_| !LISTED.swap(true, Ordering::SeqCst), 1);
+        let delete_mock = server.mock(|when, then| {
+            when.method(DELETE);
+            then.status(204);
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        api.remote_init(local_metadata(), false).await.unwrap();
+
+        // ours was older, so it is kept (the other process deletes its own)
+        delete_mock.assert_hits(0);
+        assert_eq!(api.deposition_id, Some(1));
+    }
+
+    #[tokio::test]
+    async fn test_remote_init_concurrent_creation_delete_fails() {
+        setup();
+        static LISTED: AtomicBool = AtomicBool::new(false);
+        let server = MockServer::start();
+        mock_concurrent_creation(&server, |_| !LISTED.swap(true, Ordering::SeqCst), 2);
+        server.mock(|when, then| {
+            when.method(DELETE).path("/deposit/depositions/2");
+            then.status(403);
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        let err = api.remote_init(local_metadata(), false).await.unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("(ID=1)"), "{}", message);
+        assert!(message.contains("(ID=2)"), "{}", message);
+        assert_eq!(api.deposition_id, None);
     }
 
     #[tokio::test]
@@ -731,6 +1517,40 @@ mod tests {
         delete_file_mock.assert();
     }
 
+    #[tokio::test]
+    async fn test_get_remote_files_mod_time() {
+        setup();
+        let server = MockServer::start();
+        let expected_deposition_id = 1234564;
+
+        let remote_files = vec![ZenodoFile {
+            checksum: "fake-checksum".to_string(),
+            filename: "fake_data.tsv".to_string(),
+            id: "56789".to_string(),
+            links: ZenodoLinks::default(),
+            filesize: 11,
+        }];
+        let get_files_mock = setup_get_files_mock(&server, expected_deposition_id, &remote_files);
+        let deposition_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path(format!("/deposit/depositions/{}", expected_deposition_id));
+            then.status(200)
+                .json_body(json!({ "id": expected_deposition_id, "modified": "2023-08-20T01:31:12.406103+00:00" }));
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        api.deposition_id = Some(expected_deposition_id);
+
+        let files = api.get_remote_files().await.unwrap();
+        get_files_mock.assert();
+        deposition_mock.assert();
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].mod_time,
+            Some(parse_remote_timestamp("2023-08-20T01:31:12.406103Z").unwrap())
+        );
+    }
+
     fn setup_get_files_mock<'a>(
         server: &'a MockServer,
         expected_deposition_id: u64,
@@ -807,7 +1627,7 @@ mod tests {
     // things quite a bit. The issue is that the vector remote_files will need to change
     // mid-call to ZenodoAPI::upload(), since the file was uploaded but has wrong MD5,
     // and the upload() method then retrieves it
-    async fn test_upload(file_exists: bool, overwrite: bool) -> Result<bool> {
+    async fn test_upload(file_exists: bool, overwrite: bool) -> Result<UploadOutcome> {
         setup();
         // Start a mock server
         let server = MockServer::start();
@@ -819,16 +1639,20 @@ mod tests {
         // Get the path to the temporary file
         let temp_file_path = temp_file.path().to_owned();
 
-        // (note: MD5s are fake, no checking with the mock server)
+        // the MD5 of the file's contents, which the mock server returns
         let temp_filename = temp_file_path.to_string_lossy().to_string();
-        let md5 = "2942bfabb3d05332b66eb128e0842cff";
+        let md5 = "aadd2e9f886b40b62dec296fff1e6823";
         let size = 28;
         let data_file = DataFile {
             path: temp_filename.clone(),
             tracked: true,
             md5: md5.to_string(),
             size,
-            url: None,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
         };
 
         let path_context = Path::new("path/to/datafile");
@@ -856,8 +1680,7 @@ mod tests {
 
         // Mock for the upload method
         // NOTE: this mock does not test for binary files
-        let upload_file_mock =
-            setup_upload_file_mock(&server, &bucket_endpoint, &md5, size as usize);
+        let upload_file_mock = setup_upload_file_mock(&server, bucket_endpoint, md5, size as usize);
 
         // Mock for the delete_article_file method
         let delete_file_mock = if file_exists && overwrite {
@@ -876,7 +1699,7 @@ mod tests {
         api.bucket_url = Some(bucket_url.to_string());
 
         // Main call to test
-        let result = api.upload(&data_file, &path_context, overwrite).await;
+        let result = api.upload(&data_file, "", path_context, overwrite).await;
 
         //println!("get_files_mock={:}?, upload_file_mock={:?}, delete_file_mock={:?}",
         //         get_files_mock.hits(), upload_file_mock.hits(), delete_file_mock.unwrap().hits());
@@ -891,16 +1714,64 @@ mod tests {
         if file_exists && overwrite {
             delete_file_mock.unwrap().assert();
         }
-        return result;
+        result
+    }
+
+    #[tokio::test]
+    async fn test_upload_stale_manifest_size() {
+        let server = MockServer::start();
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("data.tsv"), "Some test data for the file\n").unwrap();
+        let md5 = "aadd2e9f886b40b62dec296fff1e6823";
+        // the file has grown since it was added to the manifest, so the
+        // manifest's MD5 is stale; the upload is checked against the MD5
+        // of what was uploaded
+        let data_file = DataFile {
+            path: "data.tsv".to_string(),
+            tracked: true,
+            md5: format!("{:x}", md5::compute("Some test\n")),
+            size: 10,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+
+        let expected_deposition_id = 1234564;
+        let remote_files = Vec::new();
+        let get_files_mock = setup_get_files_mock(&server, expected_deposition_id, &remote_files);
+        let upload_mock = server.mock(|when, then| {
+            when.method("PUT")
+                .path("/files/bucket/data.tsv")
+                .header("Content-Length", "28");
+            then.status(201).json_body(json!({
+                "key": "data.tsv", "mimetype": "text/tab-separated-values",
+                "checksum": format!("md5:{}", md5), "version_id": "1", "size": 28,
+                "created": "2020-02-26T14:20:53.805734+00:00",
+                "updated": "2020-02-26T14:20:53.811817+00:00",
+                "links": {}, "is_head": true, "delete_marker": false
+            }));
+        });
+
+        let mut api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        api.deposition_id = Some(expected_deposition_id);
+        api.bucket_url = Some(format!("{}/files/bucket", BASE_URL));
+        let result = api.upload(&data_file, "", dir.path(), false).await.unwrap();
+        assert_eq!(result, UploadOutcome::Uploaded);
+        get_files_mock.assert();
+        upload_mock.assert();
     }
 
     #[tokio::test]
     async fn test_upload_no_overwrite_no_remote_files() -> Result<()> {
         let result = test_upload(false, false).await?;
         assert!(
-            result,
+            result == UploadOutcome::Uploaded,
             "Zenodo::upload() failed (file_exists={:?}, overwrite={:?}0. Result: {:?}",
-            false, false, result
+            false,
+            false,
+            result
         );
         Ok(())
     }
@@ -908,22 +1779,95 @@ mod tests {
     #[tokio::test]
     async fn test_upload_no_overwrite_with_remote_files() -> Result<()> {
         let result = test_upload(true, false).await?;
-        // result should return false since no upload was done.
+        // result should be SkippedExists since no upload was done.
         assert!(
-            !result,
+            result == UploadOutcome::SkippedExists,
             "Zenodo::upload() failed (file_exists={:?}, overwrite={:?}). Result: {:?}",
-            true, false, result
+            true,
+            false,
+            result
         );
+
+        // and push's summary should count it as skipped, not uploaded
+        let mut summary = PushSummary::default();
+        assert!(!summary.record("data/file.tsv", result));
+        assert_eq!(summary.num_uploaded, 0);
+        assert_eq!(summary.exists_skipped, vec!["data/file.tsv".to_string()]);
+        assert!(summary.checksum_failed.is_empty());
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_get_depositions_pages() {
+        const CREATED: &str = "2023-08-20T01:31:12+00:00";
+        setup();
+        let server = MockServer::start();
+        let api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        let first_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/deposit/depositions")
+                .query_param("page", "1");
+            then.status(200)
+                .header(
+                    "Link",
+                    "<https://zenodo.org/api/deposit/depositions?page=2>; rel=\"next\"",
+                )
+                .json_body(json!([
+                    deposition_json(1, CREATED, ""),
+                    deposition_json(2, CREATED, "")
+                ]));
+        });
+        let last_mock = server.mock(|when, then| {
+            when.method(GET)
+                .path("/deposit/depositions")
+                .query_param("page", "2");
+            then.status(200)
+                .json_body(json!([deposition_json(3, CREATED, "")]));
+        });
+        let depositions = api.get_depositions().await.unwrap();
+        let ids: Vec<u32> = depositions.iter().map(|deposition| deposition.id).collect();
+        assert_eq!(ids, vec![1, 2, 3]);
+        first_mock.assert();
+        last_mock.assert();
+    }
+
+    #[tokio::test]
+    async fn test_check_connection() {
+        const CREATED: &str = "2023-08-20T01:31:12+00:00";
+        setup();
+        let server = MockServer::start();
+        let api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        let mock = server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(200).json_body(json!([
+                deposition_json(1, CREATED, ""),
+                deposition_json(2, CREATED, "")
+            ]));
+        });
+        let message = api.check_connection().await.unwrap();
+        assert_eq!(message, "the token can access 2 depositions");
+        mock.assert();
+
+        // a rejected token is an error
+        let server = MockServer::start();
+        let api = ZenodoAPI::new("test", Some(server.url("/"))).unwrap();
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(401);
+        });
+        let err = api.check_connection().await.unwrap_err();
+        assert_eq!(http::error_status(&err), Some(401));
+    }
+
     #[tokio::test]
     async fn test_upload_overwrite_with_remote_files() -> Result<()> {
         let result = test_upload(true, true).await?;
         assert!(
-            result,
+            result == UploadOutcome::Uploaded,
             "Zenodo::upload() failed (file_exists={:?}, overwrite={:?}). Result: {:?}",
-            true, true, result
+            true,
+            true,
+            result
         );
         Ok(())
     }
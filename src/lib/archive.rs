@@ -0,0 +1,216 @@
+//! Reproducible archives of a linked directory's tracked files.
+//!
+//! `sdf push --archive <dir>` uploads a directory as one tar.gz rather than
+//! as loose files. The archive is built reproducibly (members in sorted
+//! order, fixed mtimes and modes, no owner information) so that the same
+//! files always give the same archive and MD5. The archive's MD5 and the
+//! MD5 of each member are recorded in the manifest, and `sdf pull --archive
+//! <dir>` checks both when unpacking.
+
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use flate2::{Compression, GzBuilder};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+use crate::lib::interrupt::{self, Staged};
+
+/// An archive of a linked directory, as recorded in the manifest.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Archive {
+    /// The archive's file name on the remote.
+    pub name: String,
+    pub md5: String,
+    pub size: u64,
+    /// MD5s of the archived files, keyed by path relative to the directory.
+    pub members: BTreeMap<String, String>,
+}
+
+/// The archive file name for a linked directory, e.g. data_raw.tar.gz
+/// for data/raw.
+pub fn archive_name(dir: &str) -> String {
+    let name = dir.replace(['/', '\\'], "_");
+    let name = if name.is_empty() { "data" } else { &name };
+    format!("{}.tar.gz", name)
+}
+
+/// Build a reproducible tar.gz at `dest` of the `members` (paths relative
+/// to `dir`, which is relative to `path_context`).
+pub fn build_archive(
+    path_context: &Path,
+    dir: &str,
+    members: &BTreeMap<String, String>,
+    dest: &Path,
+) -> Result<()> {
+    let output = File::create(dest).context(format!("Could not create archive {:?}.", dest))?;
+    // no file name or timestamp in the gzip header
+    let encoder = GzBuilder::new()
+        .mtime(0)
+        .write(output, Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    builder.mode(tar::HeaderMode::Deterministic);
+
+    // BTreeMap keys iterate in sorted order, so member order is stable
+    for member in members.keys() {
+        let path = path_context.join(dir).join(member);
+        let mut file = File::open(&path).context(format!("Could not open {:?}.", path))?;
+        let size = file.metadata()?.len();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(size);
+        header.set_mode(0o644);
+        header.set_mtime(0);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_entry_type(tar::EntryType::Regular);
+        builder.append_data(&mut header, member, &mut file)?;
+    }
+    builder.into_inner()?.finish()?.flush()?;
+    Ok(())
+}
+
+// Check an archive member's path stays inside the directory it is
+// unpacked to.
+fn safe_member_path(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Unpack the members of an archive into `dest_dir`, checking each
+/// against its MD5 in `expected`. Members are written to a temporary
+/// file and only moved into place once verified. Members for which
+/// `skip` returns true are not unpacked. Returns the members unpacked.
+pub fn unpack_archive<F>(
+    archive: &Path,
+    dest_dir: &Path,
+    expected: &BTreeMap<String, String>,
+    skip: F,
+) -> Result<Vec<String>>
+where
+    F: Fn(&str) -> bool,
+{
+    let input = File::open(archive).context(format!("Could not open archive {:?}.", archive))?;
+    let mut tar = tar::Archive::new(GzDecoder::new(input));
+    let mut unpacked = Vec::new();
+    let mut mismatches = Vec::new();
+    for entry in tar.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_path_buf();
+        let member = path.to_string_lossy().to_string();
+        if !safe_member_path(&path) {
+            return Err(anyhow!("Archive member '{}' has an unsafe path.", member));
+        }
+        let expected_md5 = expected
+            .get(&member)
+            .ok_or_else(|| anyhow!("Archive member '{}' is not in the manifest.", member))?;
+        if skip(&member) {
+            continue;
+        }
+
+        let dest = dest_dir.join(&path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let tmp_path = PathBuf::from(format!("{}.tmp", dest.display()));
+        let _staged = interrupt::stage(Staged::Partial(tmp_path.clone()));
+        let md5 = write_with_md5(&mut entry, &tmp_path)?;
+        if &md5 != expected_md5 {
+            fs::remove_file(&tmp_path)?;
+            mismatches.push(member);
+            continue;
+        }
+        fs::rename(&tmp_path, &dest)?;
+        unpacked.push(member);
+    }
+    if !mismatches.is_empty() {
+        return Err(anyhow!(
+            "Archive members did not match their manifest MD5s, and were not unpacked: {}",
+            mismatches.join(", ")
+        ));
+    }
+    Ok(unpacked)
+}
+
+// Write a reader to a file, returning its MD5.
+fn write_with_md5<R: Read>(reader: &mut R, dest: &Path) -> Result<String> {
+    let mut output = File::create(dest)?;
+    let mut context = md5::Context::new();
+    let mut buffer = [0; 8192];
+    loop {
+        let num_read = reader.read(&mut buffer)?;
+        if num_read == 0 {
+            break;
+        }
+        context.consume(&buffer[..num_read]);
+        output.write_all(&buffer[..num_read])?;
+    }
+    output.flush()?;
+    Ok(format!("{:x}", context.compute()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn md5_of(contents: &str) -> String {
+        format!("{:x}", md5::compute(contents))
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/raw/sub")).unwrap();
+        fs::write(path_context.join("data/raw/a.tsv"), "a\n").unwrap();
+        fs::write(path_context.join("data/raw/sub/b.tsv"), "b\n").unwrap();
+        let members = BTreeMap::from([
+            ("a.tsv".to_string(), md5_of("a\n")),
+            ("sub/b.tsv".to_string(), md5_of("b\n")),
+        ]);
+        assert_eq!(archive_name("data/raw"), "data_raw.tar.gz");
+
+        // rebuilding the same files gives the same archive
+        let first = path_context.join("first.tar.gz");
+        let second = path_context.join("second.tar.gz");
+        build_archive(path_context, "data/raw", &members, &first).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        fs::write(path_context.join("data/raw/a.tsv"), "a\n").unwrap();
+        build_archive(path_context, "data/raw", &members, &second).unwrap();
+        assert_eq!(fs::read(&first).unwrap(), fs::read(&second).unwrap());
+
+        let out = path_context.join("out");
+        let unpacked = unpack_archive(&first, &out, &members, |_| false).unwrap();
+        assert_eq!(unpacked, vec!["a.tsv", "sub/b.tsv"]);
+        assert_eq!(fs::read_to_string(out.join("sub/b.tsv")).unwrap(), "b\n");
+
+        let skipped = unpack_archive(&first, &out, &members, |m| m == "a.tsv").unwrap();
+        assert_eq!(skipped, vec!["sub/b.tsv"]);
+    }
+
+    #[test]
+    fn test_unpack_archive_mismatch() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data")).unwrap();
+        fs::write(path_context.join("data/a.tsv"), "a\n").unwrap();
+        let members = BTreeMap::from([("a.tsv".to_string(), md5_of("a\n"))]);
+        let archive = path_context.join("data.tar.gz");
+        build_archive(path_context, "data", &members, &archive).unwrap();
+
+        let out = path_context.join("out");
+        let wrong = BTreeMap::from([("a.tsv".to_string(), md5_of("changed\n"))]);
+        let result = unpack_archive(&archive, &out, &wrong, |_| false);
+        assert!(result.unwrap_err().to_string().contains("did not match"));
+        assert!(!out.join("a.tsv").exists());
+        assert!(!out.join("a.tsv.tmp").exists());
+
+        let result = unpack_archive(&archive, &out, &BTreeMap::new(), |_| false);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("not in the manifest"));
+    }
+}
@@ -0,0 +1,248 @@
+//! Cache of remote file listings.
+//!
+//! Listings are stored with the ETag the remote returned for them, so later
+//! fetches can make a conditional request (If-None-Match); if the remote
+//! has not changed, it returns 304 Not Modified and the cached listing is
+//! used instead of downloading it again.
+//!
+//! The cache in the user's cache directory is only used once it is
+//! enabled with [`enable()`], as the sdf binary does at startup, so that
+//! library users (such as the tests) don't write to it.
+
+use anyhow::{anyhow, Result};
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use reqwest::header::{HeaderMap, HeaderValue, ETAG, IF_NONE_MATCH};
+use reqwest::{Response, StatusCode};
+use serde_derive::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::future::Future;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+const CACHE_DIR: &str = "scidataflow";
+const LISTINGS_DIR: &str = "remote_listings";
+
+// Whether ListingCache::new() uses the user's cache directory.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Use the listing cache in the user's cache directory.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+// Resolve the cache directory following XDG conventions:
+// $XDG_CACHE_HOME/scidataflow if set (and absolute), else ~/.cache/scidataflow.
+fn resolve_cache_dir(xdg_cache_home: Option<String>, home: Option<PathBuf>) -> Result<PathBuf> {
+    let base = match xdg_cache_home.map(PathBuf::from) {
+        Some(xdg) if xdg.is_absolute() => xdg,
+        _ => home
+            .ok_or_else(|| anyhow!("Cannot load home directory!"))?
+            .join(".cache"),
+    };
+    Ok(base.join(CACHE_DIR))
+}
+
+pub fn cache_dir() -> Result<PathBuf> {
+    resolve_cache_dir(env::var("XDG_CACHE_HOME").ok(), dirs::home_dir())
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct CachedListing {
+    pub etag: String,
+    pub body: String,
+}
+
+pub struct ListingCache {
+    dir: PathBuf,
+    // if not, listings are neither loaded nor stored
+    enabled: bool,
+}
+
+impl ListingCache {
+    /// The cache in the user's cache directory, which is only used if
+    /// it has been enabled (see [`enable()`]).
+    pub fn new() -> Result<Self> {
+        Ok(ListingCache {
+            dir: cache_dir()?.join(LISTINGS_DIR),
+            enabled: ENABLED.load(Ordering::Relaxed),
+        })
+    }
+
+    pub fn at(dir: PathBuf) -> Self {
+        ListingCache { dir, enabled: true }
+    }
+
+    pub fn dir(&self) -> &PathBuf {
+        &self.dir
+    }
+
+    // The cache key for a listing: a hash of the service and listing URL
+    // (which should not contain the access token), so each remote gets
+    // its own entry.
+    pub fn key(service: &str, url: &str) -> String {
+        format!("{:x}", md5::compute(format!("{}:{}", service, url)))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", key))
+    }
+
+    pub fn load(&self, key: &str) -> Option<CachedListing> {
+        if !self.enabled {
+            return None;
+        }
+        let contents = fs::read_to_string(self.path(key)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    pub fn store(&self, key: &str, listing: &CachedListing) -> Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        fs::write(self.path(key), serde_json::to_string(listing)?)?;
+        Ok(())
+    }
+
+    /// Remove all cached listings, returning how many were removed.
+    pub fn purge(&self) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let mut num_removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                fs::remove_file(path)?;
+                num_removed += 1;
+            }
+        }
+        Ok(num_removed)
+    }
+
+    // Get a listing with a conditional request. The send closure issues the
+    // request with the given extra headers (If-None-Match, if there is a
+    // cached ETag). A 304 response returns the cached body; otherwise the
+    // body is returned, and cached if the response has an ETag.
+    pub async fn conditional_get<F, Fut>(&self, key: &str, send: F) -> Result<String>
+    where
+        F: FnOnce(Option<HeaderMap>) -> Fut,
+        Fut: Future<Output = Result<Response>>,
+    {
+        let cached = self.load(key);
+        let headers = match &cached {
+            Some(listing) => {
+                let mut headers = HeaderMap::new();
+                headers.insert(IF_NONE_MATCH, HeaderValue::from_str(&listing.etag)?);
+                Some(headers)
+            }
+            None => None,
+        };
+
+        let response = send(headers).await?;
+        if response.status() == StatusCode::NOT_MODIFIED {
+            trace!("remote listing not modified, using cache entry {}", key);
+            return cached.map(|listing| listing.body).ok_or_else(|| {
+                anyhow!("Remote returned 304 Not Modified for an uncached listing.")
+            });
+        }
+
+        let etag = response
+            .headers()
+            .get(ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(|etag| etag.to_string());
+        let body = response.text().await?;
+        if let Some(etag) = etag {
+            let listing = CachedListing {
+                etag,
+                body: body.clone(),
+            };
+            // the cache is an optimization, so failing to write it is not an error
+            if let Err(err) = self.store(key, &listing) {
+                debug!("could not cache remote listing: {}", err);
+            }
+        }
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_cache_dir() {
+        let home = Some(PathBuf::from("/home/joan"));
+        let dir = resolve_cache_dir(Some("/xdg/cache".to_string()), home.clone()).unwrap();
+        assert_eq!(dir, PathBuf::from("/xdg/cache/scidataflow"));
+        let dir = resolve_cache_dir(None, home.clone()).unwrap();
+        assert_eq!(dir, PathBuf::from("/home/joan/.cache/scidataflow"));
+        // relative paths are ignored, per the XDG spec
+        let dir = resolve_cache_dir(Some("cache".to_string()), home).unwrap();
+        assert_eq!(dir, PathBuf::from("/home/joan/.cache/scidataflow"));
+    }
+
+    #[tokio::test]
+    async fn test_conditional_get() {
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        let cache = ListingCache::at(dir.path().join("listings"));
+        let url = server.url("/files");
+        let key = ListingCache::key("zenodo", &url);
+
+        let mut first = server.mock(|when, then| {
+            when.method(GET).path("/files");
+            then.status(200)
+                .header("ETag", "\"v1\"")
+                .body("[\"file.tsv\"]");
+        });
+        let send = |headers: Option<HeaderMap>| {
+            let mut request = reqwest::Client::new().get(&url);
+            if let Some(headers) = headers {
+                request = request.headers(headers);
+            }
+            async move { Ok(request.send().await?) }
+        };
+
+        let body = cache.conditional_get(&key, send).await.unwrap();
+        assert_eq!(body, "[\"file.tsv\"]");
+        first.assert();
+        assert_eq!(cache.load(&key).unwrap().etag, "\"v1\"");
+        first.delete();
+
+        // unchanged remote: 304, served from the cache
+        let not_modified = server.mock(|when, then| {
+            when.method(GET)
+                .path("/files")
+                .header("If-None-Match", "\"v1\"");
+            then.status(304);
+        });
+        let body = cache.conditional_get(&key, send).await.unwrap();
+        assert_eq!(body, "[\"file.tsv\"]");
+        not_modified.assert();
+
+        assert_eq!(cache.purge().unwrap(), 1);
+        assert!(cache.load(&key).is_none());
+    }
+
+    #[test]
+    fn test_disabled_cache() {
+        let dir = tempdir().unwrap();
+        let cache = ListingCache {
+            dir: dir.path().join("listings"),
+            enabled: false,
+        };
+        let listing = CachedListing {
+            etag: "\"v1\"".to_string(),
+            body: "[]".to_string(),
+        };
+        cache.store("key", &listing).unwrap();
+        assert!(!cache.dir().exists());
+        assert!(cache.load("key").is_none());
+    }
+}
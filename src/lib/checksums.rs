@@ -0,0 +1,292 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lib::utils::{compute_digests, normalize_path, FileDigests};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha256,
+}
+
+impl ChecksumAlgorithm {
+    // Infer the algorithm from the length of a hex digest.
+    fn from_digest(digest: &str) -> Option<ChecksumAlgorithm> {
+        match digest.len() {
+            32 => Some(ChecksumAlgorithm::Md5),
+            64 => Some(ChecksumAlgorithm::Sha256),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "MD5",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+        }
+    }
+}
+
+/// A sidecar checksum file, e.g. the CHECKSUMS.md5 or SHA256SUMS files
+/// published alongside many datasets, in the `<hash>  <filename>` format
+/// written by md5sum and sha256sum.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChecksumFile {
+    pub algorithm: ChecksumAlgorithm,
+    /// The directory filenames are relative to (the checksum file's).
+    pub directory: PathBuf,
+    /// Digests (lowercase hex), keyed by filename.
+    pub digests: BTreeMap<String, String>,
+}
+
+impl ChecksumFile {
+    pub fn load(path: &Path) -> Result<ChecksumFile> {
+        let contents = fs::read_to_string(path).context(format!(
+            "Could not read checksum file '{}'.",
+            path.display()
+        ))?;
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+        ChecksumFile::parse(&contents, directory).context(format!(
+            "Could not parse checksum file '{}'.",
+            path.display()
+        ))
+    }
+
+    pub fn parse(contents: &str, directory: PathBuf) -> Result<ChecksumFile> {
+        let mut algorithm = None;
+        let mut digests = BTreeMap::new();
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim_end();
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+            // md5sum separates the digest and filename with a space and
+            // then a mode character: ' ' for text, '*' for binary
+            let (digest, filename) = line
+                .split_once(' ')
+                .map(|(digest, rest)| (digest, rest.strip_prefix([' ', '*']).unwrap_or(rest)))
+                .filter(|(_, filename)| !filename.is_empty())
+                .ok_or_else(|| anyhow!("Line {} is not in '<hash>  <filename>' format.", i + 1))?;
+
+            let line_algorithm = ChecksumAlgorithm::from_digest(digest)
+                .filter(|_| digest.chars().all(|c| c.is_ascii_hexdigit()))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Line {} does not begin with an MD5 or SHA-256 digest.",
+                        i + 1
+                    )
+                })?;
+            match algorithm {
+                None => algorithm = Some(line_algorithm),
+                Some(algorithm) if algorithm != line_algorithm => {
+                    return Err(anyhow!(
+                        "Line {} has a {} digest, but earlier lines are {}.",
+                        i + 1,
+                        line_algorithm.name(),
+                        algorithm.name()
+                    ));
+                }
+                _ => {}
+            }
+            let filename = normalize_path(Path::new(filename))
+                .to_string_lossy()
+                .to_string();
+            digests.insert(filename, digest.to_lowercase());
+        }
+        let algorithm = algorithm.ok_or_else(|| anyhow!("No checksums found."))?;
+        Ok(ChecksumFile {
+            algorithm,
+            directory,
+            digests,
+        })
+    }
+
+    /// The paths of the listed files, joined onto the checksum file's directory.
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.digests
+            .keys()
+            .map(|filename| self.directory.join(filename))
+            .collect()
+    }
+
+    /// The expected digest for a file, if it is listed.
+    pub fn expected(&self, path: &Path) -> Option<&String> {
+        let filename = path.strip_prefix(&self.directory).ok()?;
+        self.digests
+            .get(normalize_path(filename).to_string_lossy().as_ref())
+    }
+
+    /// The digest from a file's computed digests to compare against the
+    /// expected one.
+    pub fn actual<'a>(&self, digests: &'a FileDigests) -> Option<&'a String> {
+        match self.algorithm {
+            ChecksumAlgorithm::Md5 => Some(&digests.md5),
+            ChecksumAlgorithm::Sha256 => digests.sha256.as_ref(),
+        }
+    }
+}
+
+/// Parse an expected SHA-256 digest (e.g. from `sdf get --sha256`),
+/// lowercasing it.
+pub fn parse_sha256(digest: &str) -> Result<String> {
+    let digest = digest.trim();
+    let is_sha256 = ChecksumAlgorithm::from_digest(digest) == Some(ChecksumAlgorithm::Sha256)
+        && digest.chars().all(|c| c.is_ascii_hexdigit());
+    if !is_sha256 {
+        return Err(anyhow!(
+            "'{}' is not a SHA-256 digest (64 hexadecimal characters).",
+            digest
+        ));
+    }
+    Ok(digest.to_lowercase())
+}
+
+/// Check a file's SHA-256 against the expected (lowercase) digest,
+/// independent of the manifest's MD5.
+pub async fn check_sha256(path: &Path, expected: &str) -> Result<()> {
+    check_sha256_as(path, path, expected).await
+}
+
+// Check the SHA-256 of the file at path, naming it name in errors.
+async fn check_sha256_as(path: &Path, name: &Path, expected: &str) -> Result<()> {
+    let actual = compute_digests(path, true)
+        .await?
+        .and_then(|digests| digests.sha256)
+        .ok_or_else(|| anyhow!("Could not compute the SHA-256 of '{}'.", name.display()))?;
+    if actual != expected {
+        return Err(anyhow!(
+            "The SHA-256 of '{}' ({}) does not match the expected SHA-256 ({}).",
+            name.display(),
+            actual,
+            expected
+        ));
+    }
+    Ok(())
+}
+
+/// Where a file whose SHA-256 is to be checked is downloaded to, so that
+/// it only replaces any existing file once it is verified.
+pub fn unverified_path(path: &str) -> String {
+    format!("{}.unverified", path)
+}
+
+/// Check the SHA-256 of a file downloaded to `download` (see
+/// [`unverified_path`]), and move it to `destination` if it matches. If it
+/// does not, the download is deleted, so a corrupt download is neither
+/// kept nor added to the manifest, and any file at `destination` is left
+/// as it was.
+pub async fn verify_download_sha256(
+    download: &Path,
+    destination: &Path,
+    expected: &str,
+) -> Result<()> {
+    if !download.exists() {
+        return Err(anyhow!("'{}' was not downloaded.", destination.display()));
+    }
+    if let Err(err) = check_sha256_as(download, destination, expected).await {
+        fs::remove_file(download).context(format!(
+            "Failed to delete the download '{}'.",
+            download.display()
+        ))?;
+        return Err(anyhow!("{:#} The download was discarded.", err));
+    }
+    fs::rename(download, destination).context(format!(
+        "Failed to move the download '{}' to '{}'.",
+        download.display(),
+        destination.display()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_md5() {
+        let contents = "\
+# checksums for the reference
+d41d8cd98f00b204e9800998ecf8427e  genome.fa
+900150983CD24FB0D6963F7D28E17F72 *chr/chr1.fa.gz
+
+";
+        let checksums = ChecksumFile::parse(contents, PathBuf::from("data/ref")).unwrap();
+        assert_eq!(checksums.algorithm, ChecksumAlgorithm::Md5);
+        assert_eq!(
+            checksums.expected(Path::new("data/ref/genome.fa")).unwrap(),
+            "d41d8cd98f00b204e9800998ecf8427e"
+        );
+        // binary mode marker is stripped and digests are lowercased
+        assert_eq!(
+            checksums
+                .expected(Path::new("data/ref/chr/chr1.fa.gz"))
+                .unwrap(),
+            "900150983cd24fb0d6963f7d28e17f72"
+        );
+        assert!(checksums.expected(Path::new("data/genome.fa")).is_none());
+        assert_eq!(
+            checksums.paths(),
+            vec![
+                PathBuf::from("data/ref/chr/chr1.fa.gz"),
+                PathBuf::from("data/ref/genome.fa")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_sha256() {
+        let contents =
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  ./file.tsv\n";
+        let checksums = ChecksumFile::parse(contents, PathBuf::new()).unwrap();
+        assert_eq!(checksums.algorithm, ChecksumAlgorithm::Sha256);
+        assert!(checksums.expected(Path::new("file.tsv")).is_some());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let dir = PathBuf::new();
+        assert!(ChecksumFile::parse("", dir.clone()).is_err());
+        assert!(ChecksumFile::parse("d41d8cd98f00b204e9800998ecf8427e\n", dir.clone()).is_err());
+        assert!(ChecksumFile::parse("not-a-digest  file.tsv\n", dir.clone()).is_err());
+        let mixed = "d41d8cd98f00b204e9800998ecf8427e  a.tsv\n\
+            ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  b.tsv\n";
+        assert!(ChecksumFile::parse(mixed, dir).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_verify_download_sha256() {
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        assert_eq!(parse_sha256(&abc.to_uppercase()).unwrap(), abc);
+        assert!(parse_sha256("d41d8cd98f00b204e9800998ecf8427e").is_err());
+        assert!(parse_sha256(&"g".repeat(64)).is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("file.tsv");
+        let download = PathBuf::from(unverified_path(&path.to_string_lossy()));
+        fs::write(&download, "abc").unwrap();
+        verify_download_sha256(&download, &path, abc).await.unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+        assert!(!download.exists());
+
+        // a mismatched download is deleted, leaving the existing file
+        fs::write(&download, "abcd").unwrap();
+        let err = verify_download_sha256(&download, &path, abc)
+            .await
+            .unwrap_err();
+        let msg = format!("{:#}", err);
+        assert!(
+            msg.contains("does not match the expected SHA-256"),
+            "{}",
+            msg
+        );
+        assert!(!msg.contains("unverified"), "{}", msg);
+        assert!(!download.exists());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "abc");
+
+        let err = verify_download_sha256(&download, &path, abc)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("was not downloaded"));
+    }
+}
@@ -0,0 +1,571 @@
+//! A client for reading published SciDataFlow projects.
+//!
+//! [`RemoteClient`] lists and downloads the files of a project from its
+//! remotes (Zenodo or FigShare) given only its data manifest, without a
+//! local project directory. Public records need no access token; if one
+//! is given with [`RemoteClient::with_token`], the authenticated APIs are
+//! used instead, so unpublished drafts can be read too.
+//!
+//! ```
+//! use httpmock::prelude::*;
+//! use scidataflow::lib::client::RemoteClient;
+//!
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! // A mock Zenodo server, holding a project's manifest and a record with one file.
+//! let server = MockServer::start();
+//! let manifest = server.mock(|when, then| {
+//!     when.method(GET).path("/project/data_manifest.yml");
+//!     then.status(200).body(
+//!         "files: []\n\
+//!          remotes:\n  \
+//!            data/raw: !ZenodoAPI\n    \
+//!              name: Example project\n    \
+//!              deposition_id: 1234\n    \
+//!              bucket_url: null\n\
+//!          metadata:\n  \
+//!            title: Example project\n",
+//!     );
+//! });
+//! let record = server.mock(|when, then| {
+//!     when.method(GET).path("/api/records/1234");
+//!     then.status(200).json_body(serde_json::json!({
+//!         "files": [{
+//!             "key": "counts.tsv",
+//!             "size": 9,
+//!             "checksum": format!("md5:{:x}", md5::compute("a\t1\nb\t2\n")),
+//!             "links": { "self": server.url("/api/records/1234/files/counts.tsv") }
+//!         }]
+//!     }));
+//! });
+//! let file = server.mock(|when, then| {
+//!     when.method(GET).path("/api/records/1234/files/counts.tsv");
+//!     then.status(200).body("a\t1\nb\t2\n");
+//! });
+//!
+//! let client = RemoteClient::from_manifest_url(&server.url("/project/data_manifest.yml"))
+//!     .await?
+//!     // only needed here to point at the mock server
+//!     .with_base_url("zenodo", &server.url("/api"));
+//!
+//! let files = client.list_files().await?;
+//! assert_eq!(files[0].path, "data/raw/counts.tsv");
+//!
+//! let dest = tempfile::tempdir()?;
+//! let path = client.download("data/raw/counts.tsv", dest.path()).await?;
+//! assert_eq!(std::fs::read_to_string(path)?, "a\t1\nb\t2\n");
+//! # manifest.assert();
+//! # record.assert_hits(2);
+//! # file.assert();
+//! # Ok(())
+//! # }
+//! ```
+
+use anyhow::{anyhow, Context, Result};
+use futures::StreamExt;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::lib::data::DataCollection;
+use crate::lib::filter::PathFilter;
+use crate::lib::http;
+use crate::lib::interrupt::{self, Staged};
+use crate::lib::remote::{Remote, RemoteFile};
+use crate::lib::utils::{check_contained_path, compute_md5, is_readonly, set_readonly};
+
+/// A file on one of a project's remotes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PublishedFile {
+    /// The path in the project, i.e. the linked directory and the file name.
+    pub path: String,
+    /// The remote's listing for the file (name, size, MD5, and URL).
+    pub remote: RemoteFile,
+}
+
+/// What [`RemoteClient::pull`] did with each file, by path.
+#[derive(Debug, Default, PartialEq)]
+pub struct PullSummary {
+    pub downloaded: Vec<String>,
+    /// Local files with the remote's MD5, which were left as they are.
+    pub current: Vec<String>,
+    /// Local files that differ from (or cannot be compared to) the
+    /// remote's, which were left as they are since overwrite was not set.
+    pub overwrite_skipped: Vec<String>,
+}
+
+/// Lists and downloads the files of a project from its remotes.
+pub struct RemoteClient {
+    data: DataCollection,
+    // access tokens, keyed by lowercase service name (as in the auth keys)
+    tokens: HashMap<String, String>,
+}
+
+impl RemoteClient {
+    /// Create a client for the remotes in a loaded data manifest.
+    pub fn new(data: DataCollection) -> Self {
+        RemoteClient {
+            data,
+            tokens: HashMap::new(),
+        }
+    }
+
+    /// Create a client from the contents of a data manifest. Since the
+    /// manifest may come from anywhere, its paths are checked to stay
+    /// within the directory files are downloaded to.
+    pub fn from_manifest_str(manifest: &str) -> Result<Self> {
+        let data: DataCollection =
+            serde_yaml::from_str(manifest).context("Failed to parse the data manifest.")?;
+        for path in data.files.keys().chain(data.remotes.keys()) {
+            check_contained_path(path).context("The data manifest has an invalid path.")?;
+        }
+        Ok(RemoteClient::new(data))
+    }
+
+    /// Create a client from a data manifest at a URL, e.g. the raw
+    /// data_manifest.yml of a project's Git repository.
+    pub async fn from_manifest_url(url: &str) -> Result<Self> {
+        let response = http::get(url)
+            .await
+            .context(format!("Failed to fetch data manifest from '{}'.", url))?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Failed to fetch data manifest from '{}' (HTTP {}).",
+                url,
+                status
+            ));
+        }
+        RemoteClient::from_manifest_str(&response.text().await?)
+    }
+
+    /// Use an access token for a service ("zenodo" or "figshare"). The
+    /// token is used for listings and added to download URLs.
+    pub fn with_token(mut self, service: &str, token: &str) -> Self {
+        let service = service.to_lowercase();
+        for remote in self.data.remotes.values_mut() {
+            if remote.name().to_lowercase() == service {
+                // only Dryad, which is not implemented, can fail here
+                let _ = remote.set_token(token.to_string());
+            }
+        }
+        self.tokens.insert(service, token.to_string());
+        self
+    }
+
+    /// Use a different API base URL for a service, e.g. a mock server.
+    pub fn with_base_url(mut self, service: &str, base_url: &str) -> Self {
+        let service = service.to_lowercase();
+        for remote in self.data.remotes.values_mut() {
+            if remote.name().to_lowercase() == service {
+                let _ = remote.set_base_url(base_url.to_string());
+            }
+        }
+        self
+    }
+
+    /// The manifest the client reads from.
+    pub fn manifest(&self) -> &DataCollection {
+        &self.data
+    }
+
+    /// Take the manifest the client reads from.
+    pub fn into_manifest(self) -> DataCollection {
+        self.data
+    }
+
+    fn has_token(&self, remote: &Remote) -> bool {
+        self.tokens.contains_key(&remote.name().to_lowercase())
+    }
+
+    /// List the files on all of the project's remotes, sorted by path.
+    pub async fn list_files(&self) -> Result<Vec<PublishedFile>> {
+        let mut files = Vec::new();
+        for (directory, remote) in &self.data.remotes {
+            let remote_files = if self.has_token(remote) {
+                remote.get_files().await
+            } else {
+                remote.get_public_files().await
+            }
+            .context(format!(
+                "Failed to list the files of the {} remote for '{}'.",
+                remote.name(),
+                directory
+            ))?;
+            for remote_file in remote_files {
+                check_contained_path(&remote_file.name).context(format!(
+                    "The {} remote for '{}' lists an invalid file name.",
+                    remote.name(),
+                    directory
+                ))?;
+                files.push(PublishedFile {
+                    path: Path::new(directory)
+                        .join(&remote_file.name)
+                        .to_string_lossy()
+                        .to_string(),
+                    remote: remote_file,
+                });
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(files)
+    }
+
+    // Find a file by its path, or by its name if that is unambiguous.
+    async fn find_file(&self, name: &str) -> Result<(PublishedFile, &Remote)> {
+        let files = self.list_files().await?;
+        let mut matches: Vec<PublishedFile> = files
+            .iter()
+            .filter(|file| file.path == name)
+            .cloned()
+            .collect();
+        if matches.is_empty() {
+            matches = files
+                .into_iter()
+                .filter(|file| file.remote.name == name)
+                .collect();
+        }
+        let file = match matches.len() {
+            0 => {
+                return Err(anyhow!(
+                    "No file '{}' found on the project's remotes.",
+                    name
+                ))
+            }
+            1 => matches.remove(0),
+            _ => {
+                let paths: Vec<String> = matches.into_iter().map(|file| file.path).collect();
+                return Err(anyhow!(
+                    "File name '{}' is ambiguous; use one of its paths: {}",
+                    name,
+                    paths.join(", ")
+                ));
+            }
+        };
+        let remote = self.remote_for(&file)?;
+        Ok((file, remote))
+    }
+
+    // The remote a file was listed from (that of its directory).
+    fn remote_for(&self, file: &PublishedFile) -> Result<&Remote> {
+        let directory = Path::new(&file.path)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.data
+            .remotes
+            .get(&directory)
+            .ok_or_else(|| anyhow!("No remote found for '{}'.", file.path))
+    }
+
+    /// Download a file, given its path (as from [`RemoteClient::list_files`])
+    /// or its file name if that is unambiguous. If `dest` is a directory
+    /// the file is saved in it under its file name; otherwise it is saved
+    /// to `dest`. The download is checked against the remote's MD5, if it
+    /// has one. Returns the path the file was saved to.
+    pub async fn download(&self, name: &str, dest: &Path) -> Result<PathBuf> {
+        let (file, remote) = self.find_file(name).await?;
+        let save_path = if dest.is_dir() {
+            dest.join(&file.remote.name)
+        } else {
+            dest.to_path_buf()
+        };
+        self.fetch(&file, remote, &save_path).await?;
+        Ok(save_path)
+    }
+
+    /// Download the files in the manifest (that pass `limit`) into `dir`,
+    /// at their paths in the project, e.g. to refresh a local copy of a
+    /// published project's data. Local files with the remote's MD5 are
+    /// left as they are, as are other existing files unless `overwrite`
+    /// is set. With `readonly`, downloaded files are made read-only; files
+    /// that were read-only before being replaced are too.
+    pub async fn pull(
+        &self,
+        dir: &Path,
+        overwrite: bool,
+        readonly: bool,
+        limit: &PathFilter,
+    ) -> Result<PullSummary> {
+        let mut summary = PullSummary::default();
+        let files = self.list_files().await?;
+        for file in files
+            .into_iter()
+            .filter(|file| self.data.files.contains_key(&file.path) && limit.matches(&file.path))
+        {
+            let save_path = dir.join(&file.path);
+            if save_path.exists() {
+                let md5 = compute_md5(&save_path).await?;
+                if md5.is_some() && md5 == file.remote.md5 {
+                    summary.current.push(file.path);
+                    continue;
+                }
+                if !overwrite {
+                    summary.overwrite_skipped.push(file.path);
+                    continue;
+                }
+            }
+            if let Some(parent) = save_path.parent() {
+                std::fs::create_dir_all(parent)
+                    .context(format!("Failed to create directory {:?}.", parent))?;
+            }
+            let was_readonly = save_path.exists() && is_readonly(&save_path)?;
+            if was_readonly {
+                set_readonly(&save_path, false)?;
+            }
+            let remote = self.remote_for(&file)?;
+            let fetched = self.fetch(&file, remote, &save_path).await;
+            if (readonly || was_readonly) && save_path.exists() {
+                set_readonly(&save_path, true)?;
+            }
+            fetched?;
+            summary.downloaded.push(file.path);
+        }
+        Ok(summary)
+    }
+
+    // Download a file to save_path, checking it against the remote's MD5.
+    async fn fetch(&self, file: &PublishedFile, remote: &Remote, save_path: &Path) -> Result<()> {
+        let url = file
+            .remote
+            .url
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cannot download '{}'; download URL not set.", file.path))?;
+        let url = if self.has_token(remote) {
+            remote.authenticate_url(url)?
+        } else {
+            url.clone()
+        };
+
+        trace!("downloading '{}' to {:?}", file.path, save_path);
+        let staged = interrupt::stage(Staged::Partial(save_path.to_path_buf()));
+        let response = http::get(&url).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Failed to download '{}' (HTTP {}).",
+                file.path,
+                status
+            ));
+        }
+        let mut output =
+            File::create(save_path).context(format!("Failed to create file {:?}.", save_path))?;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            output.write_all(&chunk?)?;
+        }
+        output.flush()?;
+        drop(staged);
+
+        if let Some(expected) = &file.remote.md5 {
+            let md5 = compute_md5(save_path).await?.unwrap_or_default();
+            if &md5 != expected {
+                return Err(anyhow!(
+                    "Downloaded file {:?} has MD5 {}, but the remote's is {}.",
+                    save_path,
+                    md5,
+                    expected
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use serde_json::json;
+    use tempfile::tempdir;
+
+    fn figshare_manifest() -> String {
+        "files: []\n\
+         remotes:\n  \
+           data/supplement: !FigShareAPI\n    \
+             article_id: 42\n    \
+             name: Example project\n\
+         metadata:\n  \
+           title: null\n"
+            .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_download_with_token() {
+        let server = MockServer::start();
+        let contents = "x\ty\n";
+        let listing = server.mock(|when, then| {
+            when.method(GET)
+                .path("/account/articles/42/files")
+                .header("Authorization", "token secret");
+            then.status(200).json_body(json!([{
+                "upload_token": "", "upload_url": "", "status": "available",
+                "preview_state": "", "viewer_type": "",
+                "is_attached_to_public_version": false, "id": 1,
+                "name": "table.tsv", "size": 4, "is_link_only": false,
+                "download_url": server.url("/files/1"),
+                "supplied_md5": "", "computed_md5": format!("{:x}", md5::compute(contents))
+            }]));
+        });
+        let download = server.mock(|when, then| {
+            when.method(GET)
+                .path("/files/1")
+                .query_param("token", "secret");
+            then.status(200).body(contents);
+        });
+
+        let client = RemoteClient::from_manifest_str(&figshare_manifest())
+            .unwrap()
+            .with_base_url("figshare", &server.url(""))
+            .with_token("FigShare", "secret");
+        let dir = tempdir().unwrap();
+        let dest = dir.path().join("renamed.tsv");
+        let path = client.download("table.tsv", &dest).await.unwrap();
+        assert_eq!(path, dest);
+        assert_eq!(std::fs::read_to_string(&dest).unwrap(), contents);
+        listing.assert();
+        download.assert();
+    }
+
+    #[tokio::test]
+    async fn test_download_md5_mismatch() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/articles/42/files");
+            then.status(200).json_body(json!([{
+                "id": 1, "name": "table.tsv", "size": 4, "is_link_only": false,
+                "download_url": server.url("/files/1"),
+                "supplied_md5": "", "computed_md5": "00000000000000000000000000000000"
+            }]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/files/1");
+            then.status(200).body("x\ty\n");
+        });
+
+        let client = RemoteClient::from_manifest_str(&figshare_manifest())
+            .unwrap()
+            .with_base_url("figshare", &server.url(""));
+        let dir = tempdir().unwrap();
+        let result = client
+            .download("data/supplement/table.tsv", dir.path())
+            .await;
+        assert!(result.unwrap_err().to_string().contains("MD5"));
+
+        let result = client.download("missing.tsv", dir.path()).await;
+        assert!(result.unwrap_err().to_string().contains("No file"));
+    }
+
+    #[tokio::test]
+    async fn test_malicious_paths() {
+        // manifest paths and remote file names that would escape the
+        // download directory are rejected
+        for path in ["../../.bashrc", "/etc/x"] {
+            let manifest = figshare_manifest().replace(
+                "files: []\n",
+                &format!(
+                    "files:\n- path: {}\n  tracked: false\n  md5: ''\n  size: 4\n  \
+                     url: http://localhost/x\n",
+                    path
+                ),
+            );
+            let err = RemoteClient::from_manifest_str(&manifest).err().unwrap();
+            assert!(format!("{:#}", err).contains(path), "{:#}", err);
+        }
+        let manifest = figshare_manifest().replace("data/supplement", "../outside");
+        assert!(RemoteClient::from_manifest_str(&manifest).is_err());
+
+        let server = MockServer::start();
+        let download = server.mock(|when, then| {
+            when.method(GET).path("/files/1");
+            then.status(200).body("echo pwned\n");
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/articles/42/files");
+            then.status(200).json_body(json!([{
+                "id": 1, "name": "../../.bashrc", "size": 11, "is_link_only": false,
+                "download_url": server.url("/files/1"),
+                "supplied_md5": "", "computed_md5": ""
+            }]));
+        });
+        let client = RemoteClient::from_manifest_str(&figshare_manifest())
+            .unwrap()
+            .with_base_url("figshare", &server.url(""));
+        let dir = tempdir().unwrap();
+        let project = dir.path().join("project");
+        std::fs::create_dir(&project).unwrap();
+        let err = client
+            .pull(&project, true, false, &PathFilter::new())
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("../../.bashrc"), "{:#}", err);
+        assert!(client.download("../../.bashrc", &project).await.is_err());
+        assert!(!dir.path().join(".bashrc").exists());
+        download.assert_hits(0);
+    }
+
+    #[tokio::test]
+    async fn test_pull() {
+        let server = MockServer::start();
+        let contents = "x\ty\n";
+        let md5 = format!("{:x}", md5::compute(contents));
+        server.mock(|when, then| {
+            when.method(GET).path("/articles/42/files");
+            then.status(200).json_body(json!([
+                {
+                    "id": 1, "name": "table.tsv", "size": 4, "is_link_only": false,
+                    "download_url": server.url("/files/1"),
+                    "supplied_md5": "", "computed_md5": md5
+                },
+                {
+                    "id": 2, "name": "other.tsv", "size": 4, "is_link_only": false,
+                    "download_url": server.url("/files/2"),
+                    "supplied_md5": "", "computed_md5": md5
+                }
+            ]));
+        });
+        let download = server.mock(|when, then| {
+            when.method(GET).path("/files/1");
+            then.status(200).body(contents);
+        });
+
+        // only files in the manifest are pulled
+        let manifest = figshare_manifest().replace(
+            "files: []\n",
+            &format!(
+                "files:\n- path: data/supplement/table.tsv\n  tracked: true\n  \
+                 md5: {}\n  size: 4\n",
+                md5
+            ),
+        );
+        let client = RemoteClient::from_manifest_str(&manifest)
+            .unwrap()
+            .with_base_url("figshare", &server.url(""));
+        let dir = tempdir().unwrap();
+        let local = dir.path().join("data/supplement/table.tsv");
+        let limit = PathFilter::new();
+        let summary = client.pull(dir.path(), false, false, &limit).await.unwrap();
+        assert_eq!(summary.downloaded, vec!["data/supplement/table.tsv"]);
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), contents);
+
+        let summary = client.pull(dir.path(), false, false, &limit).await.unwrap();
+        assert_eq!(summary.current, vec!["data/supplement/table.tsv"]);
+
+        // changed local files are only replaced with overwrite
+        std::fs::write(&local, "changed\n").unwrap();
+        let summary = client.pull(dir.path(), false, false, &limit).await.unwrap();
+        assert_eq!(summary.overwrite_skipped, vec!["data/supplement/table.tsv"]);
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), "changed\n");
+        let summary = client.pull(dir.path(), true, false, &limit).await.unwrap();
+        assert_eq!(summary.downloaded, vec!["data/supplement/table.tsv"]);
+        assert_eq!(std::fs::read_to_string(&local).unwrap(), contents);
+        download.assert_hits(2);
+
+        let limit = PathFilter::with_directories(vec!["data/other".to_string()]);
+        let summary = client.pull(dir.path(), true, false, &limit).await.unwrap();
+        assert_eq!(summary, PullSummary::default());
+    }
+}
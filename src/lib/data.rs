@@ -1,35 +1,49 @@
+use crate::lib::archive::{archive_name, build_archive, unpack_archive, Archive};
 use crate::lib::data::serde::{Deserializer, Serializer};
-use crate::lib::download::Downloads;
+use crate::lib::download::{confirm_download, Downloads};
+use crate::lib::filter::PathFilter;
+use crate::lib::http;
+use crate::lib::interrupt::{self, Staged};
+use crate::lib::output::{json, quiet};
+use crate::lib::overwrite::{backup_local_file, print_overwrite_skipped, OverwriteAction};
 use anyhow::{anyhow, Result};
 use chrono::prelude::*;
 use colored::*;
-use futures::future::join_all;
-use futures::stream::FuturesUnordered;
+use futures::stream::{self, FuturesUnordered};
 use futures::StreamExt;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use serde;
 use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::fs::metadata;
 use std::path::{Path, PathBuf};
-
-use crate::lib::progress::Progress;
-use crate::lib::remote::{authenticate_remote, Remote, RemoteFile, RemoteStatusCode};
-use crate::lib::utils::{compute_md5, format_mod_time, md5_status, pluralize};
+use std::sync::Mutex;
+
+use crate::lib::progress::{Progress, ProgressGroup};
+use crate::lib::remote::{
+    authenticate_remote, Quota, Remote, RemoteFile, RemoteStatusCode, UploadOutcome,
+};
+use crate::lib::sync_state::SyncState;
+use crate::lib::utils::{
+    case_collisions, compute_md5, format_bytes, format_mod_time, is_readonly, md5_status,
+    normalize_path_key, pluralize, set_readonly, strict_paths,
+};
 use crate::{print_info, print_warn};
 
 // The status of a local data file, *conditioned* on it being in the manifest.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub enum LocalStatusCode {
     Current,  // The MD5s between the file and manifest agree
     Modified, // The MD5s disagree
     Deleted,  // The file is in the manifest but not file system
+    Unhashed, // The file was added without an MD5 (sdf add --no-hash)
     Invalid,  // Invalid state
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct StatusEntry {
     pub name: String,
     pub local_status: Option<LocalStatusCode>,
@@ -40,6 +54,24 @@ pub struct StatusEntry {
     pub remote_md5: Option<String>,
     pub manifest_md5: Option<String>,
     pub local_mod_time: Option<DateTime<Utc>>,
+    pub remote_mod_time: Option<DateTime<Utc>>,
+    // Size in bytes: the manifest's, or the remote's for remote-only files.
+    pub size: Option<u64>,
+    // Size in bytes of the local file, if it exists.
+    pub local_size: Option<u64>,
+    // Set (to a short reason) if the file's remote could not be fetched.
+    pub remote_unavailable: Option<String>,
+    // The remote's MD5 as last seen (see DataFile::remote_md5).
+    pub last_remote_md5: Option<String>,
+}
+
+// Which side of a file that differs from its remote version changed
+// since the remote's MD5 was last seen (see StatusEntry::changed_side()).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangedSide {
+    Local,
+    Remote,
+    Both,
 }
 
 impl StatusEntry {
@@ -62,7 +94,49 @@ impl StatusEntry {
     fn include_remotes(&self) -> bool {
         self.remote_service.is_some()
     }
+    // Whether the manifest's size is stale while its MD5 still matches the
+    // file's (e.g. from editing the manifest by hand).
+    pub fn size_changed(&self) -> bool {
+        self.local_status == Some(LocalStatusCode::Modified)
+            && self.local_md5.is_some()
+            && self.local_md5 == self.manifest_md5
+            && self.local_size.is_some()
+            && self.local_size != self.size
+    }
+    // For a file that differs from its remote version, whether the local
+    // file was modified after the remote (None if this cannot be
+    // determined). Remotes only report when their record (e.g. a Zenodo
+    // deposition) last changed, which may have been for another file, so
+    // only a newer local file says which copy to keep.
+    pub fn local_is_newer(&self) -> Option<bool> {
+        if self.remote_status != Some(RemoteStatusCode::Different) {
+            return None;
+        }
+        match (self.local_mod_time, self.remote_mod_time) {
+            (Some(local), Some(remote)) => Some(local > remote),
+            _ => None,
+        }
+    }
+    // For a file that differs from its remote version, which side changed
+    // since the remote's MD5 was last seen (None if it was never recorded).
+    pub fn changed_side(&self) -> Option<ChangedSide> {
+        if self.remote_status != Some(RemoteStatusCode::Different) {
+            return None;
+        }
+        let last_seen = self.last_remote_md5.as_ref()?;
+        let local_changed = self.local_md5.as_ref() != Some(last_seen);
+        let remote_changed = self.remote_md5.as_ref() != Some(last_seen);
+        match (local_changed, remote_changed) {
+            (true, false) => Some(ChangedSide::Local),
+            (false, true) => Some(ChangedSide::Remote),
+            (true, true) => Some(ChangedSide::Both),
+            (false, false) => None,
+        }
+    }
     pub fn color(&self, line: String) -> String {
+        if self.remote_unavailable.is_some() {
+            return line.yellow().to_string();
+        }
         // color is polymorphic on whether remote_status is None.
         let tracked = self.tracked;
         let local_status = &self.local_status;
@@ -89,6 +163,7 @@ impl StatusEntry {
 
             (Some(true), Some(LocalStatusCode::Modified), _) => line.red().to_string(),
             (Some(false), Some(LocalStatusCode::Modified), _) => line.red().to_string(),
+            (_, Some(LocalStatusCode::Unhashed), _) => line.yellow().to_string(),
             (Some(true), Some(LocalStatusCode::Current), Some(RemoteStatusCode::Different)) => {
                 line.yellow().to_string()
             }
@@ -105,7 +180,7 @@ impl StatusEntry {
             }
         }
     }
-    pub fn columns(&self, abbrev: Option<i32>) -> Vec<String> {
+    pub fn columns(&self, abbrev: Option<i32>, show_size: bool) -> Vec<String> {
         let local_status = &self.local_status;
 
         let md5_string = self
@@ -116,9 +191,11 @@ impl StatusEntry {
 
         // append a local status message column
         let local_status_msg = match local_status {
+            Some(LocalStatusCode::Modified) if self.size_changed() => "size changed",
             Some(LocalStatusCode::Current) => "current",
             Some(LocalStatusCode::Modified) => "changed",
             Some(LocalStatusCode::Deleted) => "deleted",
+            Some(LocalStatusCode::Unhashed) => "not yet hashed",
             Some(LocalStatusCode::Invalid) => "invalid",
             _ => "no file",
         };
@@ -133,10 +210,19 @@ impl StatusEntry {
             self.name.clone(),
             format!("{}{}", local_status_msg, tracked),
             md5_string,
-            mod_time_pretty,
         ];
+        if show_size {
+            columns.push(self.size.map(format_bytes).unwrap_or_default());
+        }
+        columns.push(mod_time_pretty);
 
-        if self.include_remotes() {
+        if let Some(reason) = &self.remote_unavailable {
+            if reason == NOT_AUTHENTICATED {
+                columns.push(reason.clone());
+            } else {
+                columns.push(format!("remote unavailable: {}", reason));
+            }
+        } else if self.include_remotes() {
             let remote_status_msg = match &self.remote_status {
                 Some(RemoteStatusCode::Current) => "identical remote".to_string(),
                 Some(RemoteStatusCode::MessyLocal) => "messy local".to_string(),
@@ -144,12 +230,21 @@ impl StatusEntry {
                     let remote_md5 = self
                         .remote_md5_column(abbrev)
                         .expect("Internal Error: StatusEntry::remote_md5_column().");
-                    format!("different remote version ({:})", remote_md5)
+                    let newer = match (self.changed_side(), self.local_is_newer()) {
+                        (Some(ChangedSide::Local), _) => ", changed locally (push)",
+                        (Some(ChangedSide::Remote), _) => ", changed on remote (pull)",
+                        (Some(ChangedSide::Both), _) => ", changed on both",
+                        (None, Some(true)) => ", local newer (push)",
+                        (None, Some(false)) => ", remote record modified later",
+                        (None, None) => "",
+                    };
+                    format!("different remote version ({:}){}", remote_md5, newer)
                 }
                 Some(RemoteStatusCode::NotExists) => "not on remote".to_string(),
                 Some(RemoteStatusCode::NoLocal) => "unknown (messy remote)".to_string(),
                 Some(RemoteStatusCode::Exists) => "exists, no remote MD5".to_string(),
                 Some(RemoteStatusCode::DeletedLocal) => "exists on remote".to_string(),
+                Some(RemoteStatusCode::DuplicateRemote) => "duplicated on remote".to_string(),
                 _ => "invalid".to_string(),
             };
             columns.push(remote_status_msg.to_string());
@@ -158,13 +253,539 @@ impl StatusEntry {
     }
 }
 
+// What 'sdf clean' deleted (or would delete, with --dry-run), and the
+// files it kept, with why.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CleanReport {
+    pub deleted: Vec<String>,
+    // the total size of the deleted files
+    pub bytes: u64,
+    pub kept: Vec<(String, String)>,
+}
+
+impl CleanReport {
+    fn keep(&mut self, path: &str, reason: &str) {
+        self.kept.push((path.to_string(), reason.to_string()));
+    }
+}
+
+// What a push did, beyond the files it skipped before uploading.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PushSummary {
+    pub num_uploaded: usize,
+    // Files the remote already had when uploading (and overwrite was not set).
+    pub exists_skipped: Vec<String>,
+    // Files whose remote MD5 differed from the local one after upload.
+    pub checksum_failed: Vec<String>,
+    // Files not pushed because they match their directory's push_exclude
+    // patterns (see sdf remote exclude).
+    pub excluded: Vec<String>,
+}
+
+impl PushSummary {
+    // Count an upload's outcome, returning whether the file was uploaded.
+    pub fn record(&mut self, path: &str, outcome: UploadOutcome) -> bool {
+        match outcome {
+            UploadOutcome::Uploaded => self.num_uploaded += 1,
+            UploadOutcome::SkippedExists => self.exists_skipped.push(path.to_string()),
+            UploadOutcome::FailedChecksum => self.checksum_failed.push(path.to_string()),
+        }
+        outcome == UploadOutcome::Uploaded
+    }
+}
+
+// Why a push or pull skipped a file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SkipReason {
+    // the local and remote files are identical
+    Current,
+    // the destination has a different version, and overwrite was not set
+    Exists,
+    // the local file differs from the manifest (see MessyFile)
+    Messy,
+    // the file is not tracked (push)
+    Untracked,
+    // the file matches its directory's push_exclude patterns (push)
+    Excluded,
+    // the remote lists more than one file with this name
+    Duplicate,
+    // the remote's record was deleted on the service (see RemoteFailure)
+    RemoteGone,
+    // transferred by the interrupted push or pull being resumed
+    Resumed,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: SkipReason,
+    // e.g. how to resolve a messy file
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FailedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+// What a push or pull does with a file, given its status (see
+// push_decision() and pull_decision(), and 'sdf why').
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransferDecision {
+    // upload (push) or download (pull) the file
+    Transfer,
+    Skip(SkipReason),
+    // there is nothing to transfer, e.g. a push of a deleted file
+    Nothing,
+    // the status should not occur here; an internal error
+    Invalid,
+}
+
+impl TransferDecision {
+    // Explain the decision, e.g. "skip (identical remote)", where verb is
+    // "upload" or "download" (which also replaces files with --backup).
+    pub fn describe(&self, verb: &str, status: &RemoteStatusCode) -> String {
+        let no_md5 = *status == RemoteStatusCode::Exists;
+        let replace = if verb == "download" {
+            "--overwrite or --backup"
+        } else {
+            "--overwrite"
+        };
+        match self {
+            TransferDecision::Transfer => {
+                let why = match status {
+                    RemoteStatusCode::NotExists => "not on the remote".to_string(),
+                    RemoteStatusCode::DeletedLocal => "missing locally".to_string(),
+                    RemoteStatusCode::Exists => {
+                        format!("remote has no MD5 to compare, with {}", replace)
+                    }
+                    RemoteStatusCode::Different => format!("remote differs, with {}", replace),
+                    _ => String::new(),
+                };
+                format!("{} ({})", verb, why)
+            }
+            TransferDecision::Skip(reason) => {
+                let why = match reason {
+                    SkipReason::Current => "identical remote".to_string(),
+                    SkipReason::Exists if no_md5 => {
+                        format!("remote has no MD5 to compare, requires {}", replace)
+                    }
+                    SkipReason::Exists => format!("remote differs, requires {}", replace),
+                    SkipReason::Messy => "local file differs from the manifest".to_string(),
+                    SkipReason::Untracked => "not tracked; see 'sdf track'".to_string(),
+                    SkipReason::Excluded => {
+                        "matches its directory's push_exclude patterns".to_string()
+                    }
+                    SkipReason::Duplicate => "duplicated on the remote".to_string(),
+                    SkipReason::RemoteGone => "the remote's record was deleted".to_string(),
+                    SkipReason::Resumed => "already transferred".to_string(),
+                };
+                format!("skip ({})", why)
+            }
+            TransferDecision::Nothing => {
+                let why = match status {
+                    RemoteStatusCode::DeletedLocal => "deleted locally",
+                    _ => "no local file",
+                };
+                format!("nothing ({})", why)
+            }
+            TransferDecision::Invalid => {
+                format!("error (unexpected {:?} status; please report)", status)
+            }
+        }
+    }
+}
+
+// Whether a push uploads a tracked file (not excluded from pushes) with
+// this status.
+pub fn push_decision(status: &RemoteStatusCode, overwrite: bool) -> TransferDecision {
+    match status {
+        RemoteStatusCode::NotExists => TransferDecision::Transfer,
+        RemoteStatusCode::Current => TransferDecision::Skip(SkipReason::Current),
+        // TODO if remote supports modification times, could do extra
+        // comparison here
+        RemoteStatusCode::Exists | RemoteStatusCode::Different => replace_decision(overwrite),
+        RemoteStatusCode::MessyLocal => TransferDecision::Skip(SkipReason::Messy),
+        // warned about when fetching (see mark_duplicates())
+        RemoteStatusCode::DuplicateRemote => TransferDecision::Skip(SkipReason::Duplicate),
+        // a remote-only or deleted file has nothing to upload
+        RemoteStatusCode::NoLocal | RemoteStatusCode::DeletedLocal => TransferDecision::Nothing,
+        RemoteStatusCode::Invalid => TransferDecision::Invalid,
+    }
+}
+
+// Whether a pull downloads a file in the manifest with a remote copy,
+// with this status; replace is --overwrite or --backup. Messy local files
+// are always skipped, so local changes not yet in the manifest are never
+// discarded.
+pub fn pull_decision(status: &RemoteStatusCode, replace: bool) -> TransferDecision {
+    match status {
+        RemoteStatusCode::NotExists | RemoteStatusCode::DeletedLocal => TransferDecision::Transfer,
+        RemoteStatusCode::Current => TransferDecision::Skip(SkipReason::Current),
+        RemoteStatusCode::Exists | RemoteStatusCode::Different => replace_decision(replace),
+        RemoteStatusCode::MessyLocal => TransferDecision::Skip(SkipReason::Messy),
+        RemoteStatusCode::DuplicateRemote => TransferDecision::Skip(SkipReason::Duplicate),
+        // pulls only consider files with local and remote copies (see
+        // MergedFile::can_download())
+        RemoteStatusCode::NoLocal | RemoteStatusCode::Invalid => TransferDecision::Invalid,
+    }
+}
+
+// A transfer whose destination has a different version.
+fn replace_decision(overwrite: bool) -> TransferDecision {
+    if OverwriteAction::new(true, overwrite).transfers() {
+        TransferDecision::Transfer
+    } else {
+        TransferDecision::Skip(SkipReason::Exists)
+    }
+}
+
+// How a push is done (see DataCollection::push() and Project::push()).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PushOptions {
+    // Replace remote files that differ from the local ones.
+    pub overwrite: bool,
+    // Check the uploaded files against the remotes' listings after.
+    pub verify: bool,
+    // Upload to several remotes at once.
+    pub parallel_remotes: bool,
+    // Skip the files an interrupted push already uploaded.
+    pub resume: bool,
+    // Also upload README.md files as record files (Project::push() only).
+    pub include_readme: bool,
+    // Push even if over the size budget (Project::push() only).
+    pub force: bool,
+}
+
+// How a pull is done (see DataCollection::pull() and Project::pull()).
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct PullOptions {
+    // Replace local files that differ from the remote ones.
+    pub overwrite: bool,
+    // Replace them, after renaming the local file to a backup.
+    pub backup: bool,
+    // Make the pulled files read-only.
+    pub readonly: bool,
+    // Skip the files an interrupted pull already downloaded.
+    pub resume: bool,
+    // Pull the files with URLs rather than from remotes, or with all,
+    // both (Project::pull() only).
+    pub urls: bool,
+    pub all: bool,
+}
+
+// What a push or pull did with each file, printed as JSON by --json.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct TransferReport {
+    pub uploaded: Vec<String>,
+    pub downloaded: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+    pub failed: Vec<FailedFile>,
+}
+
+impl TransferReport {
+    pub fn skip(&mut self, paths: &[String], reason: SkipReason) {
+        self.skipped.extend(paths.iter().map(|path| SkippedFile {
+            path: path.clone(),
+            reason,
+            detail: None,
+        }));
+    }
+
+    pub fn fail(&mut self, path: &str, reason: &str) {
+        self.failed.push(FailedFile {
+            path: path.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    // The paths of the files skipped for a reason.
+    pub fn skipped_for(&self, reason: SkipReason) -> Vec<&str> {
+        self.skipped
+            .iter()
+            .filter(|skipped| skipped.reason == reason)
+            .map(|skipped| skipped.path.as_str())
+            .collect()
+    }
+
+    // An error if any files failed (e.g. "2 files could not be downloaded.").
+    pub fn failures(&self, action: &str) -> Result<()> {
+        if self.failed.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "{} could not be {}.",
+            pluralize(self.failed.len() as u64, "file"),
+            action
+        ))
+    }
+
+    // Add the files of another report (e.g. sdf pull --all's URL pull).
+    pub fn extend(&mut self, other: TransferReport) {
+        self.uploaded.extend(other.uploaded);
+        self.downloaded.extend(other.downloaded);
+        self.skipped.extend(other.skipped);
+        self.failed.extend(other.failed);
+    }
+
+    // The report, with the number of files in each list.
+    pub fn to_json(&self) -> Result<String> {
+        let mut value = serde_json::to_value(self)?;
+        value["totals"] = serde_json::json!({
+            "uploaded": self.uploaded.len(),
+            "downloaded": self.downloaded.len(),
+            "skipped": self.skipped.len(),
+            "failed": self.failed.len(),
+        });
+        Ok(serde_json::to_string_pretty(&value)?)
+    }
+}
+
+// The files stored on a remote, for sdf stats --remote.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteUsage {
+    pub directory: String,
+    pub service: String,
+    pub num_files: usize,
+    pub total_bytes: u64,
+    // files the remote did not report a size for
+    pub num_unknown_size: usize,
+    pub quota: Option<Quota>,
+}
+
+// How a file compares with its copy on the remote, for sdf verify --remote.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Reconciliation {
+    // the local and remote MD5s match
+    Synced,
+    // the local file has changed since it was added
+    LocalDiffers,
+    // the remote file differs from the one in the manifest
+    RemoteDiffers,
+    // a tracked file that is not on the remote
+    MissingRemote,
+    // a remote file that is not in the manifest, or not on disk
+    MissingLocal,
+    // a file without an MD5 on one side (e.g. not yet hashed, or on a
+    // remote that does not report MD5s)
+    Unverifiable,
+}
+
+impl Reconciliation {
+    pub fn is_discrepancy(&self) -> bool {
+        !matches!(self, Reconciliation::Synced | Reconciliation::Unverifiable)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Reconciliation::Synced => "synced",
+            Reconciliation::LocalDiffers => "local file differs",
+            Reconciliation::RemoteDiffers => "remote file differs",
+            Reconciliation::MissingRemote => "missing on the remote",
+            Reconciliation::MissingLocal => "missing locally",
+            Reconciliation::Unverifiable => "no MD5 to compare",
+        }
+    }
+}
+
+// Files at least this large get a warning before sdf status --deep-verify
+// downloads them.
+const DEEP_VERIFY_WARN_SIZE: u64 = 1024 * 1024 * 1024;
+
+// The reason shown for the files of a remote without a stored token.
+pub const NOT_AUTHENTICATED: &str = "not authenticated (run sdf link or add a token)";
+
+// A remote whose files could not be fetched, e.g. because its
+// token expired.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteFailure {
+    pub service: String,
+    pub directory: String,
+    pub error: String,
+    // no token could be found for the remote
+    pub unauthenticated: bool,
+    // the remote's record (e.g. a Zenodo deposition) was deleted on the
+    // service, so its listing is 404 Not Found (or 410 Gone)
+    pub gone: bool,
+}
+
+impl RemoteFailure {
+    fn new(service: &str, directory: &str, err: &anyhow::Error) -> Self {
+        RemoteFailure {
+            service: service.to_string(),
+            directory: directory.to_string(),
+            error: format!("{:#}", err),
+            unauthenticated: false,
+            gone: false,
+        }
+    }
+
+    // A remote whose listing failed, which is gone if the service says its
+    // record does not exist, rather than a raw 404 error.
+    fn listing(remote: &Remote, directory: &str, err: &anyhow::Error) -> Self {
+        let record = remote.record_label();
+        match (http::error_status(err), record) {
+            (Some(404 | 410), Some(record)) => RemoteFailure {
+                gone: true,
+                ..RemoteFailure::new(
+                    remote.name(),
+                    directory,
+                    &anyhow!(
+                        "the linked {} {} no longer exists; link '{}' to a new one with \
+                         'sdf link --relink {} {} <TOKEN>'",
+                        remote.name(),
+                        record,
+                        directory,
+                        directory,
+                        remote.name().to_lowercase()
+                    ),
+                )
+            },
+            _ => RemoteFailure::new(remote.name(), directory, err),
+        }
+    }
+
+    fn unauthenticated(service: &str, directory: &str, err: &anyhow::Error) -> Self {
+        RemoteFailure {
+            unauthenticated: true,
+            ..RemoteFailure::new(service, directory, err)
+        }
+    }
+
+    // A short reason for the failure: the first line of the error,
+    // e.g. "HTTP Error: 403 Forbidden".
+    pub fn reason(&self) -> &str {
+        if self.unauthenticated {
+            return NOT_AUTHENTICATED;
+        }
+        self.error.lines().next().unwrap_or_default()
+    }
+
+    pub fn into_error(self) -> anyhow::Error {
+        anyhow!(
+            "Could not fetch files from the {} remote for '{}': {}",
+            self.service,
+            self.directory,
+            self.error
+        )
+    }
+}
+
+// A file (by manifest path) on more than one remote, whose copies have
+// different MD5s (see DataCollection::remote_conflicts()).
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteConflict {
+    pub path: String,
+    // (linked directory, service, MD5) of each copy, by directory
+    pub copies: Vec<(String, String, String)>,
+}
+
+impl RemoteConflict {
+    pub fn message(&self) -> String {
+        let copies: Vec<String> = self
+            .copies
+            .iter()
+            .map(|(directory, service, md5)| {
+                format!(
+                    "'{}' ({}, MD5 {})",
+                    directory,
+                    service,
+                    &md5[..md5.len().min(8)]
+                )
+            })
+            .collect();
+        format!(
+            "'{}' differs between remotes: {}. Push the intended version to each, \
+             or rename one of them.",
+            self.path,
+            copies.join(", ")
+        )
+    }
+}
+
+// The result of checking a linked directory's remote (see
+// DataCollection::test_remotes()): a summary if it passed, or why it
+// failed.
+#[derive(Debug)]
+pub struct RemoteCheck {
+    pub service: String,
+    pub directory: String,
+    pub result: Result<String>,
+}
+
+impl RemoteCheck {
+    pub fn passed(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    pub fn line(&self) -> String {
+        match &self.result {
+            Ok(summary) => format!("PASS  {} ({}): {}", self.directory, self.service, summary),
+            Err(err) => format!("FAIL  {} ({}): {:#}", self.directory, self.service, err),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct DataFile {
     pub path: String,
     pub tracked: bool,
+    // Empty for files not yet hashed (see DataFile::unhashed()).
     pub md5: String,
     pub size: u64,
-    pub url: Option<String>, //modified: Option<DateTime<Utc>>,
+    // Download URLs: the first, then mirrors to try in order if it fails.
+    // Written as a single URL (or null) unless there are mirrors, so
+    // manifests without mirrors are unchanged.
+    #[serde(rename = "url", default, with = "url_list")]
+    pub urls: Vec<String>,
+    // Provenance, set when the file is registered (see
+    // DataCollection::register()); older manifests lack these.
+    // Where the file came from: its download URL, or "local".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_at: Option<DateTime<Utc>>,
+    // The user's name from the config (sdf config --name).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub added_by: Option<String>,
+    // The remote's MD5 as last seen by 'sdf update --remote' (and kept
+    // current by pushes and pulls), so that a file that differs from the
+    // remote's can be told apart as changed locally or on the remote.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_md5: Option<String>,
+}
+
+// (De)serialize DataFile::urls as null, a URL, or a list of URLs.
+mod url_list {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Urls {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    pub fn serialize<S: Serializer>(urls: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error> {
+        match urls.as_slice() {
+            [] => serializer.serialize_none(),
+            [url] => serializer.serialize_str(url),
+            urls => urls.serialize(serializer),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(match Option::<Urls>::deserialize(deserializer)? {
+            None => Vec::new(),
+            Some(Urls::One(url)) => vec![url],
+            Some(Urls::Many(urls)) => urls,
+        })
+    }
 }
 
 // A merged DataFile and RemoteFile
@@ -224,7 +845,8 @@ impl MergedFile {
     }
 
     pub async fn local_md5(&self, path_context: &Path) -> Option<String> {
-        if let Some(local) = &self.local {
+        // files not yet hashed are hashed by 'sdf update', not here
+        if let Some(local) = self.local.as_ref().filter(|local| local.is_hashed()) {
             if let Ok(md5_result) = local.get_md5(path_context).await {
                 return md5_result;
             }
@@ -243,6 +865,13 @@ impl MergedFile {
         self.local.as_ref().map(|local| local.md5.clone())
     }
 
+    pub fn size(&self) -> Option<u64> {
+        match &self.local {
+            Some(local) => Some(local.size),
+            None => self.remote.as_ref().and_then(|remote| remote.size),
+        }
+    }
+
     pub async fn local_remote_md5_mismatch(&self, path_context: &Path) -> Option<bool> {
         let local_md5 = self.local_md5(path_context).await;
         let remote_md5 = self.remote_md5();
@@ -252,10 +881,15 @@ impl MergedFile {
         }
     }
 
-    pub fn local_mod_time(&self, path_context: &Path) -> Option<DateTime<Utc>> {
-        self.local
-            .as_ref()
-            .and_then(|data_file| data_file.get_mod_time(path_context).ok())
+    // The local file's modification time, None if there is no local file.
+    pub fn local_mod_time(&self, path_context: &Path) -> Result<Option<DateTime<Utc>>> {
+        let Some(data_file) = &self.local else {
+            return Ok(None);
+        };
+        if !data_file.full_path(path_context)?.exists() {
+            return Ok(None);
+        }
+        data_file.get_mod_time(path_context).map(Some)
     }
 
     pub async fn status(&self, path_context: &Path) -> Result<RemoteStatusCode> {
@@ -276,6 +910,14 @@ impl MergedFile {
         if !self.has_remote().unwrap_or(false) {
             return Ok(RemoteStatusCode::NotExists);
         }
+        // which of the remote's copies is meant is unknown
+        if self
+            .remote
+            .as_ref()
+            .is_some_and(|remote| !remote.duplicate_ids.is_empty())
+        {
+            return Ok(RemoteStatusCode::DuplicateRemote);
+        }
 
         // MergedFile has a remote, so get the remote status.
         let status = match (&local_status, &md5_mismatch) {
@@ -307,12 +949,61 @@ impl MergedFile {
                 // the manifest and tracked a pull would pull it in.
                 RemoteStatusCode::DeletedLocal
             }
+            (Some(LocalStatusCode::Unhashed), _) => {
+                // There's no manifest MD5 to compare to the remote's.
+                RemoteStatusCode::Exists
+            }
             (_, _) => RemoteStatusCode::Invalid,
         };
 
         Ok(status)
     }
 
+    // For a file whose remote has no MD5, download the remote copy and
+    // hash it, updating the status entry to Current or Different. If the
+    // download fails, the entry is left as it is, with a warning.
+    pub async fn deep_verify(&self, remote: &Remote, entry: &mut StatusEntry) -> Result<()> {
+        let url = match self
+            .remote
+            .as_ref()
+            .and_then(|remote_file| remote_file.url.as_ref())
+        {
+            Some(url) => remote.authenticate_url(url)?,
+            None => return Ok(()),
+        };
+        let size = self
+            .remote
+            .as_ref()
+            .and_then(|remote_file| remote_file.size);
+        if let Some(size) = size.filter(|size| *size >= DEEP_VERIFY_WARN_SIZE) {
+            print_warn!(
+                "Downloading '{}' ({}) from {} to verify it.",
+                entry.name,
+                format_bytes(size),
+                remote.name()
+            );
+        }
+        match http::get_md5(&url).await {
+            Ok(md5) => {
+                entry.remote_status = Some(if entry.manifest_md5.as_ref() == Some(&md5) {
+                    RemoteStatusCode::Current
+                } else {
+                    RemoteStatusCode::Different
+                });
+                entry.remote_md5 = Some(md5);
+            }
+            Err(err) => {
+                print_warn!(
+                    "Could not download '{}' from {} to verify it: {:#}",
+                    entry.name,
+                    remote.name(),
+                    err
+                );
+            }
+        }
+        Ok(())
+    }
+
     // Create a StatusEntry, for printing the status to the user.
     pub async fn status_entry(
         &self,
@@ -354,7 +1045,18 @@ impl MergedFile {
             local_md5: self.local_md5(path_context).await,
             remote_md5: self.remote_md5(),
             manifest_md5: self.manifest_md5(),
-            local_mod_time: self.local_mod_time(path_context),
+            local_mod_time: self.local_mod_time(path_context)?,
+            remote_mod_time: self.remote.as_ref().and_then(|remote| remote.mod_time),
+            size: self.size(),
+            local_size: self
+                .local
+                .as_ref()
+                .and_then(|local| local.get_size(path_context).ok()),
+            remote_unavailable: None,
+            last_remote_md5: self
+                .local
+                .as_ref()
+                .and_then(|local| local.remote_md5.clone()),
         })
     }
 }
@@ -369,23 +1071,56 @@ impl DataFile {
             Some(md5) => md5,
             None => return Err(anyhow!("Could not compute MD5 as file does not exist")),
         };
+        DataFile::with_md5(path, md5, url, path_context)
+    }
+
+    // Create a DataFile with an already computed MD5 (e.g. computed
+    // along with another digest, in a single pass over the file).
+    pub fn with_md5(
+        path: String,
+        md5: String,
+        url: Option<&str>,
+        path_context: &Path,
+    ) -> Result<DataFile> {
+        let full_path = path_context.join(&path);
         let size = metadata(full_path)
             .map_err(|err| anyhow!("Failed to get metadata for file {:?}: {}", path, err))?
             .len();
-        let maybe_url: Option<String> = url.map(|s| s.to_string());
         Ok(DataFile {
             path,
             tracked: false,
             md5,
             size,
-            url: maybe_url,
+            urls: url.map(|url| url.to_string()).into_iter().collect(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
         })
     }
 
+    // Create a DataFile with only its size, deferring computing the MD5
+    // (sdf add --no-hash) to a later 'sdf update'.
+    pub fn unhashed(path: String, path_context: &Path) -> Result<DataFile> {
+        if !path_context.join(&path).exists() {
+            return Err(anyhow!("File '{}' does not exist.", path));
+        }
+        DataFile::with_md5(path, String::new(), None, path_context)
+    }
+
+    pub fn is_hashed(&self) -> bool {
+        !self.md5.is_empty()
+    }
+
     pub fn full_path(&self, path_context: &Path) -> Result<PathBuf> {
         Ok(path_context.join(self.path.clone()))
     }
 
+    /// The file's (first) download URL, if it has one.
+    pub fn url(&self) -> Option<&str> {
+        self.urls.first().map(|url| url.as_str())
+    }
+
     pub fn basename(&self) -> Result<String> {
         let path = Path::new(&self.path);
         match path.file_name() {
@@ -422,12 +1157,41 @@ impl DataFile {
         Ok(size)
     }
 
+    // Whether the file's size on disk differs from the manifest's (false if
+    // it does not exist).
+    pub fn is_size_changed(&self, path_context: &Path) -> bool {
+        self.get_size(path_context)
+            .is_ok_and(|size| size != self.size)
+    }
+
+    // The size to upload the file with: its size on disk, since the
+    // manifest's may be stale (e.g. when a modified file is pushed with
+    // --overwrite). A warning is shown if the two differ.
+    pub fn upload_size(&self, path_context: &Path) -> Result<u64> {
+        let size = self.get_size(path_context)?;
+        if size != self.size {
+            print_warn!(
+                "'{}' is {} bytes on disk, but {} bytes in the manifest; uploading it \
+                 as it is on disk. Use 'sdf update {}' to update the manifest.",
+                self.path,
+                size,
+                self.size,
+                self.path
+            );
+        }
+        Ok(size)
+    }
+
     pub fn is_alive(&self, path_context: &Path) -> bool {
         path_context.join(&self.path).exists()
     }
 
-    // Returns true if the file does not exist.
+    // Returns true if the file does not exist. A file whose size differs
+    // from the manifest's is changed, without hashing it.
     pub async fn is_changed(&self, path_context: &Path) -> Result<bool> {
+        if self.is_size_changed(path_context) {
+            return Ok(true);
+        }
         match self.get_md5(path_context).await? {
             Some(new_md5) => Ok(new_md5 != self.md5),
             None => Ok(true),
@@ -436,6 +1200,13 @@ impl DataFile {
 
     pub async fn status(&self, path_context: &Path) -> Result<LocalStatusCode> {
         let is_alive = self.is_alive(path_context);
+        if !self.is_hashed() {
+            return Ok(if is_alive {
+                LocalStatusCode::Unhashed
+            } else {
+                LocalStatusCode::Deleted
+            });
+        }
         let is_changed = self.is_changed(path_context).await?;
         let local_status = match (is_changed, is_alive) {
             (false, true) => LocalStatusCode::Current,
@@ -449,10 +1220,12 @@ impl DataFile {
         Ok(local_status)
     }
 
-    pub async fn update(&mut self, path_context: &Path) -> Result<()> {
+    // Rehash the file and update its size, returning whether its MD5 changed.
+    pub async fn update(&mut self, path_context: &Path) -> Result<bool> {
+        let old_md5 = self.md5.clone();
         self.update_md5(path_context).await?;
         self.update_size(path_context)?;
-        Ok(())
+        Ok(self.md5 != old_md5)
     }
 
     pub fn update_size(&mut self, path_context: &Path) -> Result<()> {
@@ -493,10 +1266,36 @@ impl DataFile {
     }
 }
 
+// An author of the data besides the configured user.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone)]
+pub struct Author {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub affiliation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orcid: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
 pub struct DataCollectionMetadata {
     pub title: Option<String>,
     pub description: Option<String>,
+    // an SPDX license identifier, e.g. 'CC-BY-4.0' (see 'sdf metadata --license')
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    // a cap on the total size of the files, in bytes (see 'sdf metadata --size-budget')
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size_budget: Option<u64>,
+    // the authors after the configured user, in order, as sent to new
+    // Zenodo depositions (set in data_manifest.yml)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub authors: Vec<Author>,
+    // whether link and push require a title, author, and license (see
+    // 'sdf metadata --require-metadata')
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_metadata: bool,
 }
 
 /// DataCollection structure for managing the data manifest
@@ -506,13 +1305,38 @@ pub struct DataCollection {
     pub files: HashMap<String, DataFile>,
     pub remotes: HashMap<String, Remote>, // key is tracked directory
     pub metadata: DataCollectionMetadata,
+    // Sub-manifests (relative to the project root) whose files and
+    // remotes are merged into this collection; see Project::load().
+    pub includes: Vec<String>,
+    // Files (e.g. a README) uploaded to remotes alongside the data, but
+    // not tracked as manifest entries; see record_files_for().
+    pub record_files: Vec<String>,
+    // Linked directories pushed as a single archive (sdf push --archive),
+    // keyed by directory.
+    pub archives: BTreeMap<String, Archive>,
+    // Glob patterns (relative to the linked directory) of files that are
+    // never pushed to a directory's remote, keyed by directory.
+    pub push_exclude: BTreeMap<String, Vec<String>>,
+    // Linked directories whose pulled files are made read-only (as with
+    // sdf pull --readonly), keyed by directory.
+    pub readonly_on_pull: BTreeMap<String, bool>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq, Clone)]
 pub struct MinimalDataCollection {
+    #[serde(default, rename = "include", skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
     pub files: Vec<DataFile>,
-    pub remotes: HashMap<String, Remote>,
+    pub remotes: BTreeMap<String, Remote>,
     pub metadata: DataCollectionMetadata,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub record_files: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub archives: BTreeMap<String, Archive>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub push_exclude: BTreeMap<String, Vec<String>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub readonly_on_pull: BTreeMap<String, bool>,
 }
 
 impl serde::Serialize for DataCollection {
@@ -520,17 +1344,7 @@ impl serde::Serialize for DataCollection {
     where
         S: Serializer,
     {
-        // Serialize `files` as a sorted vector
-        let sorted_files: Vec<DataFile> = self.files.values().cloned().collect();
-
-        // Construct a new struct to hold the serializable parts
-        let to_serialize = MinimalDataCollection {
-            files: sorted_files,
-            remotes: self.remotes.clone(),
-            metadata: self.metadata.clone(),
-        };
-
-        to_serialize.serialize(serializer)
+        self.to_minimal().serialize(serializer)
     }
 }
 
@@ -542,17 +1356,58 @@ impl<'de> serde::Deserialize<'de> for DataCollection {
         // Deserialize into a temporary struct
         let temp = MinimalDataCollection::deserialize(deserializer)?;
 
-        // Build the HashMap for files based on the path
+        // Build the HashMap for files based on the path. Paths and remote
+        // directories are normalized, since older manifests could contain
+        // keys like "./data/" (these are migrated when the manifest is saved).
         let files = temp
             .files
             .into_iter()
-            .map(|df| (df.path.clone(), df))
+            .map(|mut df| {
+                df.path = normalize_path_key(&df.path);
+                (df.path.clone(), df)
+            })
             .collect();
 
+        let mut remotes = HashMap::new();
+        for (dir, remote) in temp.remotes {
+            let key = normalize_path_key(&dir);
+            if remotes.insert(key.clone(), remote).is_some() {
+                return Err(serde::de::Error::custom(format!(
+                    "multiple remotes in the data manifest are linked to directory '{}'",
+                    key
+                )));
+            }
+        }
+
         Ok(DataCollection {
             files,
-            remotes: temp.remotes,
+            remotes,
             metadata: temp.metadata,
+            includes: temp
+                .includes
+                .iter()
+                .map(|include| normalize_path_key(include))
+                .collect(),
+            record_files: temp
+                .record_files
+                .iter()
+                .map(|path| normalize_path_key(path))
+                .collect(),
+            archives: temp
+                .archives
+                .into_iter()
+                .map(|(dir, archive)| (normalize_path_key(&dir), archive))
+                .collect(),
+            push_exclude: temp
+                .push_exclude
+                .into_iter()
+                .map(|(dir, patterns)| (normalize_path_key(&dir), patterns))
+                .collect(),
+            readonly_on_pull: temp
+                .readonly_on_pull
+                .into_iter()
+                .map(|(dir, readonly)| (normalize_path_key(&dir), readonly))
+                .collect(),
         })
     }
 }
@@ -565,7 +1420,190 @@ impl DataCollection {
             files: HashMap::new(),
             remotes: HashMap::new(),
             metadata: DataCollectionMetadata::default(),
+            includes: Vec::new(),
+            record_files: Vec::new(),
+            archives: BTreeMap::new(),
+            push_exclude: BTreeMap::new(),
+            readonly_on_pull: BTreeMap::new(),
+        }
+    }
+
+    // Recover what can be read from a manifest that no longer loads (e.g.
+    // after a bad manual edit), for 'sdf init --force'. Each file entry and
+    // remote, and each other section, is read on its own, so one broken
+    // entry doesn't lose the rest. Returns the collection, the number of
+    // file entries and remotes that could not be read, and the paths of
+    // the unreadable file entries whose path could still be read.
+    pub fn salvage(contents: &str) -> Result<(DataCollection, usize, Vec<String>)> {
+        let value: serde_yaml::Value = serde_yaml::from_str(contents)
+            .map_err(|err| anyhow!("The manifest is not valid YAML: {}", err))?;
+        let section = |key: &str| value.get(key).cloned();
+        let mut data = DataCollection::new();
+        let mut num_dropped = 0;
+        let mut dropped_paths = Vec::new();
+
+        let files = section("files").and_then(|files| files.as_sequence().cloned());
+        for entry in files.unwrap_or_default() {
+            let path = entry.get("path").and_then(|path| path.as_str());
+            let path = path.map(normalize_path_key);
+            match serde_yaml::from_value::<DataFile>(entry) {
+                Ok(mut data_file) => {
+                    data_file.path = normalize_path_key(&data_file.path);
+                    data.files.insert(data_file.path.clone(), data_file);
+                }
+                Err(_) => {
+                    num_dropped += 1;
+                    dropped_paths.extend(path);
+                }
+            }
+        }
+        let remotes = section("remotes").and_then(|remotes| remotes.as_mapping().cloned());
+        for (dir, remote) in remotes.unwrap_or_default() {
+            match (dir.as_str(), serde_yaml::from_value::<Remote>(remote)) {
+                (Some(dir), Ok(remote)) => {
+                    data.remotes.insert(normalize_path_key(dir), remote);
+                }
+                _ => num_dropped += 1,
+            }
+        }
+
+        // the other sections are optional, so are left out if unreadable
+        fn read<T: serde::de::DeserializeOwned + Default>(value: Option<serde_yaml::Value>) -> T {
+            value
+                .and_then(|value| serde_yaml::from_value(value).ok())
+                .unwrap_or_default()
+        }
+        data.metadata = read(section("metadata"));
+        let includes: Vec<String> = read(section("include"));
+        data.includes = includes
+            .iter()
+            .map(|path| normalize_path_key(path))
+            .collect();
+        let record_files: Vec<String> = read(section("record_files"));
+        data.record_files = record_files
+            .iter()
+            .map(|path| normalize_path_key(path))
+            .collect();
+        data.archives = read(section("archives"));
+        data.push_exclude = read(section("push_exclude"));
+        data.readonly_on_pull = read(section("readonly_on_pull"));
+        Ok((data, num_dropped, dropped_paths))
+    }
+
+    // The serializable parts of the collection, with files sorted by path
+    // and remotes by directory, so that the same collection always
+    // serializes the same way.
+    fn to_minimal(&self) -> MinimalDataCollection {
+        let mut files: Vec<DataFile> = self.files.values().cloned().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        MinimalDataCollection {
+            includes: self.includes.clone(),
+            files,
+            remotes: self
+                .remotes
+                .iter()
+                .map(|(dir, remote)| (dir.clone(), remote.clone()))
+                .collect(),
+            metadata: self.metadata.clone(),
+            record_files: self.record_files.clone(),
+            archives: self.archives.clone(),
+            push_exclude: self.push_exclude.clone(),
+            readonly_on_pull: self.readonly_on_pull.clone(),
+        }
+    }
+
+    // A SHA-256 (in hex) of the canonical serialization of the collection
+    // (see to_minimal()), for detecting changes to the data definition.
+    // When and by whom files were added is left out, so re-adding an
+    // unchanged file doesn't change the hash, as is the last-seen remote
+    // MD5, which records syncing rather than the data.
+    pub fn manifest_hash(&self) -> Result<String> {
+        let mut minimal = self.to_minimal();
+        for data_file in minimal.files.iter_mut() {
+            data_file.added_at = None;
+            data_file.added_by = None;
+            data_file.remote_md5 = None;
+        }
+        let canonical = serde_yaml::to_string(&minimal)?;
+        Ok(format!("{:x}", Sha256::digest(canonical.as_bytes())))
+    }
+
+    // The directory (relative to the project root) an included
+    // sub-manifest covers, i.e. the directory it is in.
+    pub fn include_directory(include: &str) -> String {
+        let dir = Path::new(include).parent().unwrap_or(Path::new(""));
+        normalize_path_key(&dir.to_string_lossy())
+    }
+
+    // The included sub-manifest that owns a path (file or remote directory),
+    // i.e. the one whose directory is the longest prefix of the path. None
+    // means the path belongs in the root manifest.
+    pub fn owning_include(&self, path: &str) -> Option<&String> {
+        self.includes
+            .iter()
+            .filter(|include| Path::new(path).starts_with(Self::include_directory(include)))
+            .max_by_key(|include| Self::include_directory(include).len())
+    }
+
+    // Split into the root manifest's collection and one collection per
+    // included sub-manifest, with each sub-manifest's paths made relative
+    // to its directory.
+    pub fn split_includes(&self) -> Result<(DataCollection, Vec<(String, DataCollection)>)> {
+        let mut root = DataCollection {
+            metadata: self.metadata.clone(),
+            includes: self.includes.clone(),
+            record_files: self.record_files.clone(),
+            archives: self.archives.clone(),
+            push_exclude: self.push_exclude.clone(),
+            readonly_on_pull: self.readonly_on_pull.clone(),
+            ..Default::default()
+        };
+        let mut subs: HashMap<String, DataCollection> = self
+            .includes
+            .iter()
+            .map(|include| (include.clone(), DataCollection::new()))
+            .collect();
+
+        let rerooted = |path: &str, include: &str| -> Result<String> {
+            let dir = Self::include_directory(include);
+            Ok(Path::new(path)
+                .strip_prefix(&dir)
+                .map_err(|_| anyhow!("Internal error: '{}' is not in '{}'.", path, dir))?
+                .to_string_lossy()
+                .to_string())
+        };
+
+        for (key, data_file) in &self.files {
+            match self.owning_include(key) {
+                Some(include) => {
+                    let path = rerooted(key, include)?;
+                    let mut data_file = data_file.clone();
+                    data_file.path = path.clone();
+                    subs.get_mut(include).unwrap().files.insert(path, data_file);
+                }
+                None => {
+                    root.files.insert(key.clone(), data_file.clone());
+                }
+            }
+        }
+        for (dir, remote) in &self.remotes {
+            match self.owning_include(dir) {
+                Some(include) => {
+                    let dir = rerooted(dir, include)?;
+                    subs.get_mut(include)
+                        .unwrap()
+                        .remotes
+                        .insert(dir, remote.clone());
+                }
+                None => {
+                    root.remotes.insert(dir.clone(), remote.clone());
+                }
+            }
         }
+
+        let mut subs: Vec<_> = subs.into_iter().collect();
+        subs.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok((root, subs))
     }
 
     // Authenticate all remotes, if there are any.
@@ -579,8 +1617,18 @@ impl DataCollection {
         Ok(())
     }
 
-    pub fn register(&mut self, data_file: DataFile) -> Result<()> {
+    pub fn register(&mut self, mut data_file: DataFile) -> Result<()> {
+        // e.g. "./data/x.tsv" and "data/x.tsv" are the same key
+        data_file.path = normalize_path_key(&data_file.path);
         let path = data_file.path.clone();
+        self.check_case_collision(&path, strict_paths())?;
+        if data_file.source.is_none() {
+            let source = data_file.url().unwrap_or("local").to_string();
+            data_file.source = Some(source);
+        }
+        if data_file.added_at.is_none() {
+            data_file.added_at = Some(Utc::now());
+        }
         if let std::collections::hash_map::Entry::Vacant(e) = self.files.entry(path) {
             e.insert(data_file);
             Ok(())
@@ -593,14 +1641,87 @@ impl DataCollection {
         }
     }
 
-    pub async fn contains(&self, filename: &str) -> Result<bool> {
-        Ok(self.files.contains_key(filename))
-    }
-
-    pub async fn remove(&mut self, filename: &str) -> bool {
-        if self.files.contains_key(filename) {
-            self.files.remove(filename);
-            true
+    // Check whether a new path differs only by case from a file already in
+    // the manifest. On case-insensitive filesystems these are the same file,
+    // so this warns (or errors, if strict).
+    pub fn check_case_collision(&self, path: &str, strict: bool) -> Result<()> {
+        let lowercase = path.to_lowercase();
+        let existing = self
+            .files
+            .keys()
+            .find(|key| key.as_str() != path && key.to_lowercase() == lowercase);
+        if let Some(existing) = existing {
+            let msg = format!(
+                "File '{}' differs only by case from '{}', which is already in the manifest.\n\
+                 On case-insensitive filesystems (e.g. macOS and Windows defaults) these are the same file.",
+                path, existing
+            );
+            if strict {
+                return Err(anyhow!(msg));
+            }
+            print_warn!("{}", msg);
+        }
+        Ok(())
+    }
+
+    // Check the whole manifest for paths that differ only by case. This warns
+    // (or errors, if strict), since on case-insensitive filesystems these
+    // entries clobber each other.
+    pub fn check_case_collisions(&self, strict: bool) -> Result<()> {
+        let collisions = case_collisions(self.files.keys().map(|key| key.as_str()));
+        if collisions.is_empty() {
+            return Ok(());
+        }
+        let groups: Vec<String> = collisions
+            .iter()
+            .map(|group| format!("  {}", group.join(", ")))
+            .collect();
+        let msg = format!(
+            "The manifest has entries that differ only by case:\n{}\n\
+             On case-insensitive filesystems (e.g. macOS and Windows defaults) these are the same file;\n\
+             rename them with 'sdf mv' so each path is distinct.",
+            groups.join("\n")
+        );
+        if strict {
+            return Err(anyhow!(msg));
+        }
+        print_warn!("{}", msg);
+        Ok(())
+    }
+
+    /// Whether the file is in the manifest; see registered_key().
+    pub async fn contains(&self, filename: &str, path_context: &Path) -> Result<bool> {
+        Ok(self.registered_key(filename, path_context).is_some())
+    }
+
+    /// The manifest key the file is registered under: its own (normalized)
+    /// path, or, if it exists locally, a differently spelled key that
+    /// resolves to the same file, e.g. through a symlinked directory or by
+    /// case on a case-insensitive filesystem. Only keys with the same
+    /// basename (ignoring case) are checked this way, so a file registered
+    /// under a symlink with a different name is not found.
+    pub fn registered_key(&self, filename: &str, path_context: &Path) -> Option<String> {
+        let key = normalize_path_key(filename);
+        if self.files.contains_key(&key) {
+            return Some(key);
+        }
+        let target = fs::canonicalize(path_context.join(&key)).ok()?;
+        let basename = |path: &str| {
+            Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_lowercase())
+        };
+        let target_name = basename(&key);
+        self.files
+            .keys()
+            .filter(|path| basename(path) == target_name)
+            .find(|path| fs::canonicalize(path_context.join(path)).ok().as_ref() == Some(&target))
+            .cloned()
+    }
+
+    pub async fn remove(&mut self, filename: &str) -> bool {
+        if self.files.remove(&normalize_path_key(filename)).is_some() {
+            true
         } else {
             println!(
                 "File '{}' is not registered in the manifest, so it was not removed.",
@@ -610,11 +1731,13 @@ impl DataCollection {
         }
     }
 
-    pub async fn update(&mut self, filename: Option<&String>, path_context: &Path) -> Result<()> {
+    // Rehash a file (or all files), returning whether any MD5 changed.
+    pub async fn update(&mut self, filename: Option<&String>, path_context: &Path) -> Result<bool> {
+        let mut changed = false;
         match filename {
             Some(file) => {
-                if let Some(data_file) = self.files.get_mut(file) {
-                    data_file.update(path_context).await?;
+                if let Some(data_file) = self.files.get_mut(&normalize_path_key(file)) {
+                    changed = data_file.update(path_context).await?;
                     debug!("rehashed file {:?}", data_file.path);
                 } else {
                     return Err(anyhow!("File '{}' does not exist.", file));
@@ -625,22 +1748,31 @@ impl DataCollection {
                 let all_files: Vec<_> = self.files.keys().cloned().collect();
                 for file in all_files {
                     if let Some(data_file) = self.files.get_mut(&file) {
-                        data_file.update(path_context).await?;
+                        changed |= data_file.update(path_context).await?;
                         debug!("rehashed file {:?}", data_file.path);
                     }
                 }
             }
         }
-        Ok(())
+        Ok(changed)
+    }
+
+    // Get the registered DataFiles in a directory (including its subdirectories).
+    pub fn files_in_directory(&self, dir: &str) -> Vec<&DataFile> {
+        self.files
+            .values()
+            .filter(|data_file| Path::new(&data_file.path).starts_with(dir))
+            .collect()
     }
 
     // Validate the directory as being tracked by a remote,
     // i.e. no nesting.
-    pub fn validate_remote_directory(&self, dir: &String) -> Result<()> {
+    pub fn validate_remote_directory(&self, dir: &str) -> Result<()> {
+        let dir = &normalize_path_key(dir);
         let dir_path = Path::new(dir);
         // check if the directory itself is already tracked.
         if self.remotes.contains_key(dir) {
-            return Err(anyhow!("Directory '{}' is already tracked in the data manifest. You can manually delete it and re-add.", dir));
+            return Err(anyhow!("Directory '{}' is already tracked in the data manifest. Use 'sdf link --relink' to link it to a different remote.", dir));
         }
 
         // check if the provided directory is a parent of any directory in remotes.
@@ -666,7 +1798,7 @@ impl DataCollection {
     }
 
     pub fn get_this_files_remote(&self, data_file: &DataFile) -> Result<Option<String>> {
-        let path = data_file.directory()?;
+        let path = normalize_path_key(&data_file.directory()?);
         let res: Vec<String> = self
             .remotes
             .iter()
@@ -681,23 +1813,194 @@ impl DataCollection {
         }
     }
 
+    // The record files uploaded to the remote linked to a directory: those
+    // directly in the directory, and those outside of any linked directory
+    // (e.g. a README at the project root), unless the directory has its
+    // own record file with the same name.
+    pub fn record_files_for(&self, dir: &str) -> Vec<&String> {
+        let basename = |path: &str| {
+            Path::new(path)
+                .file_name()
+                .map(|name| name.to_string_lossy().to_string())
+        };
+        let own: Vec<&String> = self
+            .record_files
+            .iter()
+            .filter(|path| self.linked_directory(path).as_deref() == Some(dir))
+            .collect();
+        let shared = self.record_files.iter().filter(|path| {
+            !self
+                .remotes
+                .keys()
+                .any(|linked| Path::new(path).starts_with(linked))
+                && !own.iter().any(|own| basename(own) == basename(path))
+        });
+        own.iter().copied().chain(shared).collect()
+    }
+
+    // The record file a file on the remote linked to dir corresponds to.
+    pub fn record_file_for(&self, dir: &str, name: &str) -> Option<&String> {
+        self.record_files_for(dir)
+            .into_iter()
+            .find(|path| Path::new(path).file_name() == Some(name.as_ref()))
+    }
+
+    // Whether a remote file, by its path in the project, is a record
+    // file of its linked directory.
+    fn is_record_file(&self, path: &str) -> bool {
+        let name = Path::new(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.linked_directory(path)
+            .is_some_and(|dir| self.record_file_for(&dir, &name).is_some())
+    }
+
+    // Add README.md record files for each linked directory: the
+    // directory's own README.md if it has one, otherwise the project's.
+    // Returns the newly added record files.
+    pub fn add_readme_record_files(&mut self, path_context: &Path) -> Result<Vec<String>> {
+        let mut readmes = Vec::new();
+        for dir in self.remotes.keys() {
+            let own = normalize_path_key(&Path::new(dir).join("README.md").to_string_lossy());
+            let readme = if path_context.join(&own).is_file() {
+                own
+            } else if path_context.join("README.md").is_file() {
+                "README.md".to_string()
+            } else {
+                return Err(anyhow!(
+                    "No README.md found in '{}' or the project directory.",
+                    dir
+                ));
+            };
+            if self.files.contains_key(&readme) {
+                // already pushed as a tracked file
+                continue;
+            }
+            if !readmes.contains(&readme) && !self.record_files.contains(&readme) {
+                readmes.push(readme);
+            }
+        }
+        readmes.sort();
+        self.record_files.extend(readmes.iter().cloned());
+        Ok(readmes)
+    }
+
+    // Remove the remote-only status entries of record files, which are
+    // not manifest entries, returning a map of (directory, name) to their
+    // remote MD5.
+    pub fn take_record_statuses(
+        &self,
+        statuses: &mut BTreeMap<String, Vec<StatusEntry>>,
+    ) -> HashMap<(String, String), Option<String>> {
+        let mut records = HashMap::new();
+        for (dir, entries) in statuses.iter_mut() {
+            entries.retain(|entry| {
+                let name = Path::new(&entry.name)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let is_record = entry.local_status.is_none()
+                    && entry.tracked.is_none()
+                    && self.record_file_for(dir, &name).is_some();
+                if is_record {
+                    records.insert((dir.clone(), name), entry.remote_md5.clone());
+                }
+                !is_record
+            });
+        }
+        statuses.retain(|_, entries| !entries.is_empty());
+        records
+    }
+
+    // Get the linked directory a file is pushed from, i.e. its parent
+    // directory if that is registered in remotes (see track_file()).
+    // The remote linked to a directory or to the closest of its parents.
+    pub fn remote_for_directory(&self, dir: &str) -> Option<&Remote> {
+        self.remotes
+            .iter()
+            .filter(|(linked, _)| Path::new(dir).starts_with(linked))
+            .max_by_key(|(linked, _)| linked.len())
+            .map(|(_, remote)| remote)
+    }
+
+    pub fn linked_directory(&self, filepath: &str) -> Option<String> {
+        let dir = normalize_path_key(&Path::new(filepath).parent()?.to_string_lossy());
+        self.remotes.contains_key(&dir).then_some(dir)
+    }
+
+    // The total size of the manifest's files.
+    pub fn total_size(&self) -> u64 {
+        self.files.values().map(|data_file| data_file.size).sum()
+    }
+
+    // The total size of the tracked files in linked directories matching
+    // the filter, i.e. what a push would put on the remotes.
+    pub fn remote_size(&self, filter: &PathFilter) -> u64 {
+        self.files
+            .values()
+            .filter(|data_file| {
+                data_file.tracked
+                    && filter.matches(&data_file.path)
+                    && self.linked_directory(&data_file.path).is_some()
+            })
+            .map(|data_file| data_file.size)
+            .sum()
+    }
+
+    // The files in linked directories worth tracking (untracked, and at
+    // least min_size bytes) and worth untracking (tracked, and smaller),
+    // sorted by path (see the config's auto_track_min_size).
+    pub fn tracking_suggestions(&self, min_size: u64) -> (Vec<&DataFile>, Vec<&DataFile>) {
+        let mut files: Vec<&DataFile> = self
+            .files
+            .values()
+            .filter(|data_file| self.linked_directory(&data_file.path).is_some())
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        files
+            .into_iter()
+            .filter(|data_file| data_file.tracked != (data_file.size >= min_size))
+            .partition(|data_file| !data_file.tracked)
+    }
+
     // Register the remote
     //
     // This can overwrite existing entries.
-    pub fn register_remote(&mut self, dir: &String, remote: Remote) -> Result<()> {
-        self.validate_remote_directory(dir)?;
-        self.remotes.insert(dir.to_string(), remote);
+    pub fn register_remote(&mut self, dir: &str, remote: Remote) -> Result<()> {
+        let dir = normalize_path_key(dir);
+        self.validate_remote_directory(&dir)?;
+        self.remotes.insert(dir, remote);
         Ok(())
     }
 
-    pub fn get_remote(&mut self, dir: &String) -> Result<&Remote> {
-        match self.remotes.get(dir) {
+    // Replace the remote of an already-linked directory, returning the old
+    // one. Files' tracked flags are left as they are, but the MD5s last
+    // seen on the old remote (see DataFile::remote_md5) are forgotten.
+    pub fn replace_remote(&mut self, dir: &str, remote: Remote) -> Result<Remote> {
+        let dir = normalize_path_key(dir);
+        let existing = self
+            .remotes
+            .get_mut(&dir)
+            .ok_or_else(|| anyhow!("Directory '{}' is not linked to a remote.", dir))?;
+        let old = std::mem::replace(existing, remote);
+        for data_file in self.files.values_mut() {
+            if Path::new(&data_file.path).starts_with(&dir) {
+                data_file.remote_md5 = None;
+            }
+        }
+        Ok(old)
+    }
+
+    pub fn get_remote(&mut self, dir: &str) -> Result<&Remote> {
+        match self.remotes.get(&normalize_path_key(dir)) {
             Some(remote) => Ok(remote),
             None => Err(anyhow!("No such remote")),
         }
     }
-    pub fn track_file(&mut self, filepath: &String, path_context: &Path) -> Result<()> {
+    pub fn track_file(&mut self, filepath: &str, path_context: &Path) -> Result<()> {
         trace!("complete files: {:?}", self.files);
+        let filepath = &normalize_path_key(filepath);
         let data_file = self.files.get_mut(filepath);
 
         // extract the directory from the filepath
@@ -706,10 +2009,8 @@ impl DataCollection {
             .ok_or_else(|| anyhow!("Failed to get directory for file '{}'", filepath))?;
 
         // check if the directory exists in self.remotes
-        if !self
-            .remotes
-            .contains_key(dir_path.to_str().unwrap_or_default())
-        {
+        let dir_key = normalize_path_key(&dir_path.to_string_lossy());
+        if !self.remotes.contains_key(&dir_key) {
             return Err(anyhow!(
                 "Directory '{}' is not registered in remotes.",
                 dir_path.display()
@@ -718,8 +2019,7 @@ impl DataCollection {
 
         match data_file {
             None => Err(anyhow!(
-                "Data file '{}' is not in the data manifest. Add it first using:\n \
-                                $ sdf track {}\n",
+                "Data file '{}' is not in the data manifest. Add it first using:\n  $ sdf add {}",
                 filepath,
                 filepath
             )),
@@ -738,11 +2038,10 @@ impl DataCollection {
         }
     }
     pub fn untrack_file(&mut self, filepath: &String) -> Result<()> {
-        let data_file = self.files.get_mut(filepath);
+        let data_file = self.files.get_mut(&normalize_path_key(filepath));
         match data_file {
             None => Err(anyhow!(
-                "Cannot untrack data file '{}' since it was never added to\
-                                the data manifest.",
+                "Cannot untrack data file '{}' since it is not in the data manifest.",
                 filepath
             )),
             Some(file) => file.set_untracked(),
@@ -768,42 +2067,279 @@ impl DataCollection {
     pub async fn fetch(
         &mut self,
     ) -> Result<HashMap<(String, String), HashMap<String, RemoteFile>>> {
-        self.authenticate_remotes()?;
+        let (all_remote_files, failures) = self.fetch_all().await?;
+        match failures.into_iter().next() {
+            Some(failure) => Err(failure.into_error()),
+            None => Ok(all_remote_files),
+        }
+    }
 
-        let mut all_remote_files = HashMap::new();
-        let pb = Progress::new(self.remotes.len() as u64)?;
-        pb.bar.set_message("Fetching remote files...");
+    // Sum the sizes of the files on each remote, sorted by directory, with
+    // the account's quota where the service reports one.
+    pub async fn remote_usage(&mut self) -> Result<(Vec<RemoteUsage>, Vec<RemoteFailure>)> {
+        let (all_remote_files, failures) = self.fetch_all().await?;
+        let mut usages = Vec::new();
+        for ((service, directory), files) in all_remote_files {
+            // the quota is extra information, so failing to get it is not an error
+            let quota = match self.remotes[&directory].get_quota().await {
+                Ok(quota) => quota,
+                Err(err) => {
+                    debug!("could not get {} quota: {}", service, err);
+                    None
+                }
+            };
+            usages.push(RemoteUsage {
+                num_files: files.len(),
+                total_bytes: files.values().filter_map(|file| file.size).sum(),
+                num_unknown_size: files.values().filter(|file| file.size.is_none()).count(),
+                directory,
+                service,
+                quota,
+            });
+        }
+        usages.sort_by(|a, b| a.directory.cmp(&b.directory));
+        Ok((usages, failures))
+    }
 
-        // Convert remotes into Futures, so that they can be awaited in parallel
-        let fetch_futures: Vec<_> = self
-            .remotes
-            .iter()
-            .map(|(path, remote)| {
-                let remote_name = remote.name().to_string();
-                let path_clone = path.clone();
-                async move {
-                    let remote_files = remote.get_files_hashmap().await?;
-                    Ok(((remote_name, path_clone), remote_files))
+    // Compare every file in a linked directory with its copy on the
+    // remote, grouping the (sorted) file names by how they compare. Record
+    // files (e.g. a README) are not manifest entries, so are left out.
+    // This is read-only: nothing is changed locally or on the remotes.
+    pub async fn verify_remote(
+        &mut self,
+        path_context: &Path,
+    ) -> Result<BTreeMap<Reconciliation, Vec<String>>> {
+        let all_files = self.merge(true).await?;
+        let mut report: BTreeMap<Reconciliation, Vec<String>> = BTreeMap::new();
+        for files in all_files.values() {
+            for (name, merged_file) in files {
+                let supported = self
+                    .remotes
+                    .iter()
+                    .filter(|(dir, _)| Path::new(name).starts_with(dir))
+                    .all(|(_, remote)| remote.is_supported());
+                if merged_file.remote_service.is_none() || !supported {
+                    continue;
                 }
-            })
-            .collect();
+                let reconciliation = match (&merged_file.local, &merged_file.remote) {
+                    // untracked files are not expected on the remote
+                    (Some(local), None) if !local.tracked => continue,
+                    (Some(_), None) => Reconciliation::MissingRemote,
+                    (Some(local), Some(_)) if !local.full_path(path_context)?.exists() => {
+                        Reconciliation::MissingLocal
+                    }
+                    (Some(local), Some(_)) => {
+                        match merged_file.local_remote_md5_mismatch(path_context).await {
+                            Some(false) => Reconciliation::Synced,
+                            Some(true) => {
+                                // the remote is as last seen, so the local file changed
+                                let remote_unchanged = local.remote_md5.is_some()
+                                    && local.remote_md5 == merged_file.remote_md5();
+                                if remote_unchanged {
+                                    Reconciliation::LocalDiffers
+                                } else if merged_file.local_md5(path_context).await.as_ref()
+                                    == Some(&local.md5)
+                                {
+                                    Reconciliation::RemoteDiffers
+                                } else {
+                                    Reconciliation::LocalDiffers
+                                }
+                            }
+                            None => Reconciliation::Unverifiable,
+                        }
+                    }
+                    (None, Some(_)) if self.is_record_file(name) => continue,
+                    (None, Some(_)) => Reconciliation::MissingLocal,
+                    (None, None) => continue,
+                };
+                report.entry(reconciliation).or_default().push(name.clone());
+            }
+        }
+        for names in report.values_mut() {
+            names.sort();
+        }
+        Ok(report)
+    }
+
+    // Check each linked remote, or just that of dir (see Remote::test()),
+    // in directory order; a failure is recorded rather than stopping the
+    // rest.
+    pub async fn test_remotes(
+        &mut self,
+        dir: Option<&str>,
+        write_probe: bool,
+    ) -> Result<Vec<RemoteCheck>> {
+        if let Some(dir) = dir {
+            if !self.remotes.contains_key(dir) {
+                return Err(anyhow!("Directory '{}' is not linked to a remote.", dir));
+            }
+        }
+        if self.remotes.is_empty() {
+            return Err(anyhow!(
+                "No directories are linked to remotes; use 'sdf link' first."
+            ));
+        }
+        let mut dirs: Vec<String> = match dir {
+            Some(dir) => vec![dir.to_string()],
+            None => self.remotes.keys().cloned().collect(),
+        };
+        dirs.sort();
+        let mut checks = Vec::new();
+        for path in dirs {
+            let remote = self.remotes.get_mut(&path).unwrap();
+            // remotes created with a token (e.g. in tests) are already authenticated
+            let result = if remote.has_token() {
+                Ok(())
+            } else {
+                authenticate_remote(remote)
+            };
+            let result = match result {
+                Ok(()) => remote.test(write_probe).await,
+                Err(err) => Err(err.context(NOT_AUTHENTICATED)),
+            };
+            checks.push(RemoteCheck {
+                service: remote.name().to_string(),
+                directory: path,
+                result,
+            });
+        }
+        Ok(checks)
+    }
+
+    // Record the remote's current MD5 of each of these files that is in a
+    // linked directory (see DataFile::remote_md5), or None if the remote
+    // doesn't have the file or doesn't list MD5s. Returns how many files
+    // were in linked directories.
+    pub async fn record_remote_md5s(&mut self, filepaths: &[String]) -> Result<usize> {
+        let all_files = self.merge(true).await?;
+        let mut num_recorded = 0;
+        for (key, merged_file) in all_files.values().flatten() {
+            if merged_file.remote_service.is_none() || !filepaths.contains(key) {
+                continue;
+            }
+            if let Some(data_file) = self.files.get_mut(key) {
+                data_file.remote_md5 = merged_file.remote_md5();
+                num_recorded += 1;
+            }
+        }
+        Ok(num_recorded)
+    }
+
+    // After a push or pull, the local and remote copies of each transferred
+    // file agree, so update its last-seen remote MD5 to the given one; only
+    // files with one recorded (by 'sdf update --remote') are changed.
+    fn sync_remote_md5s<'a, I>(&mut self, synced: I)
+    where
+        I: IntoIterator<Item = (&'a String, String)>,
+    {
+        for (path, md5) in synced {
+            if let Some(data_file) = self.files.get_mut(path) {
+                if data_file.remote_md5.is_some() {
+                    data_file.remote_md5 = Some(md5);
+                }
+            }
+        }
+    }
+
+    // Whether any of these files has a last-seen remote MD5, i.e. whether
+    // the manifest needs saving after they were transferred.
+    pub fn has_remote_md5s(&self, paths: &[String]) -> bool {
+        paths.iter().any(|path| {
+            self.files
+                .get(path)
+                .is_some_and(|data_file| data_file.remote_md5.is_some())
+        })
+    }
+
+    // Fetch the files of all remotes, returning those fetched and the
+    // remotes that failed (rather than stopping at the first failure).
+    pub async fn fetch_all(
+        &mut self,
+    ) -> Result<(
+        HashMap<(String, String), HashMap<String, RemoteFile>>,
+        Vec<RemoteFailure>,
+    )> {
+        let mut failures = Vec::new();
+        let mut authenticated = Vec::new();
+        for (path, remote) in self.remotes.iter_mut() {
+            if !remote.is_supported() {
+                print_warn!(
+                    "Skipping '{}': its remote ({}) is not supported by this version of sdf.",
+                    path,
+                    remote.name()
+                );
+                continue;
+            }
+            // remotes created with a token (e.g. in tests) are already authenticated
+            let result = if remote.has_token() {
+                Ok(())
+            } else {
+                authenticate_remote(remote)
+            };
+            match result {
+                Ok(()) => authenticated.push(path.clone()),
+                Err(e) => failures.push(RemoteFailure::unauthenticated(remote.name(), path, &e)),
+            }
+        }
+
+        let mut all_remote_files = HashMap::new();
+        let pb = Progress::new(authenticated.len() as u64)?;
+        pb.bar.set_message("Fetching remote files...");
 
-        let results = join_all(fetch_futures).await;
+        // Convert remotes into Futures, so that (up to remote_concurrency()
+        // of) them can be awaited in parallel
+        let fetch_futures = authenticated.iter().map(|path| {
+            let remote = &self.remotes[path];
+            let remote_name = remote.name().to_string();
+            let path_clone = path.clone();
+            async move {
+                let result = remote.get_files_hashmap().await;
+                ((remote_name, path_clone), result)
+            }
+        });
+        let mut results = stream::iter(fetch_futures).buffer_unordered(http::remote_concurrency());
 
-        for result in results {
+        while let Some((key, result)) = results.next().await {
             match result {
-                Ok((key, value)) => {
+                Ok(value) => {
                     pb.bar
                         .set_message(format!("Fetching remote files...   {} done.", key.0));
                     all_remote_files.insert(key, value);
-                    pb.bar.inc(1);
                 }
-                Err(e) => return Err(e), // Handle errors as needed
+                Err(e) => failures.push(RemoteFailure::listing(&self.remotes[&key.1], &key.1, &e)),
             }
+            pb.bar.inc(1);
         }
 
         pb.bar.finish_with_message("Fetching completed.");
-        Ok(all_remote_files)
+        failures.sort_by(|a, b| a.directory.cmp(&b.directory));
+        Ok((all_remote_files, failures))
+    }
+
+    // Merge a single local file with its remote counterpart (if any),
+    // fetching only the remote for the file's linked directory.
+    pub async fn merge_file(&mut self, filepath: &str) -> Result<MergedFile> {
+        let local = self
+            .files
+            .get(filepath)
+            .cloned()
+            .ok_or_else(|| anyhow!("Data file '{}' is not in the data manifest.", filepath))?;
+        let dir = self
+            .linked_directory(filepath)
+            .ok_or_else(|| anyhow!("Directory of '{}' is not registered in remotes.", filepath))?;
+        let remote = self
+            .remotes
+            .get_mut(&dir)
+            .ok_or_else(|| anyhow!("Directory '{}' is not registered in remotes.", dir))?;
+        if !remote.has_token() {
+            authenticate_remote(remote)?;
+        }
+        let remote_files = remote.get_files_hashmap().await?;
+        Ok(MergedFile {
+            remote: remote_files.get(&local.basename()?).cloned(),
+            remote_service: Some(remote.name().to_string()),
+            local: Some(local),
+        })
     }
 
     // Merge all local and remote files.
@@ -817,6 +2353,75 @@ impl DataCollection {
         &mut self,
         include_remotes: bool,
     ) -> Result<HashMap<String, HashMap<String, MergedFile>>> {
+        let (result, failures) = self.merge_available(include_remotes).await?;
+        match failures.into_iter().next() {
+            Some(failure) => Err(failure.into_error()),
+            None => Ok(result),
+        }
+    }
+
+    // Merge as merge(), but leave out the directories whose remote's record
+    // was deleted on the service (see RemoteFailure::gone), warning about
+    // each. Their tracked files are returned as skipped, for push and pull.
+    pub async fn merge_linked(
+        &mut self,
+    ) -> Result<(
+        HashMap<String, HashMap<String, MergedFile>>,
+        Vec<SkippedFile>,
+    )> {
+        let (mut result, failures) = self.merge_available(true).await?;
+        let mut skipped = Vec::new();
+        for failure in failures {
+            if !failure.gone {
+                return Err(failure.into_error());
+            }
+            print_warn!("Skipping '{}': {}", failure.directory, failure.error);
+            let in_directory = |dir: &String| Path::new(dir).starts_with(&failure.directory);
+            for (_, files) in result.iter().filter(|(dir, _)| in_directory(dir)) {
+                skipped.extend(
+                    files
+                        .values()
+                        .filter_map(|merged_file| merged_file.local.as_ref())
+                        .filter(|data_file| data_file.tracked)
+                        .map(|data_file| SkippedFile {
+                            path: data_file.path.clone(),
+                            reason: SkipReason::RemoteGone,
+                            detail: Some(failure.error.clone()),
+                        }),
+                );
+            }
+            result.retain(|dir, _| !in_directory(dir));
+        }
+        skipped.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok((result, skipped))
+    }
+
+    // Like merge(), but remotes that cannot be fetched are returned as
+    // failures instead of an error, and their files are merged as if
+    // they had no remote files.
+    pub async fn merge_available(
+        &mut self,
+        include_remotes: bool,
+    ) -> Result<(
+        HashMap<String, HashMap<String, MergedFile>>,
+        Vec<RemoteFailure>,
+    )> {
+        let (result, failures, _) = self.merge_compared(include_remotes).await?;
+        Ok((result, failures))
+    }
+
+    // Merge as merge_available(), also returning the files whose copies
+    // differ between remotes (see remote_conflicts()), which sdf status
+    // reports.
+    #[allow(clippy::type_complexity)]
+    async fn merge_compared(
+        &mut self,
+        include_remotes: bool,
+    ) -> Result<(
+        HashMap<String, HashMap<String, MergedFile>>,
+        Vec<RemoteFailure>,
+        Vec<RemoteConflict>,
+    )> {
         // directory -> {(filename -> MergedFile), ...}
         let mut result: HashMap<String, HashMap<String, MergedFile>> = HashMap::new();
 
@@ -839,24 +2444,50 @@ impl DataCollection {
         }
 
         if !include_remotes {
-            return Ok(result);
+            return Ok((result, Vec::new(), Vec::new()));
         }
 
         // iterate through each remote and retrieve remote files
-        let all_remote_files = self.fetch().await?;
+        let (all_remote_files, failures) = self.fetch_all().await?;
+        let conflicts = self.remote_conflicts(&all_remote_files);
+        DataCollection::merge_remote_files(&mut result, &all_remote_files);
+        Ok((result, failures, conflicts))
+    }
+
+    // Merge remote files into the local files of merge().
+    //
+    // Remote files are matched to local files by their path relative to
+    // the linked directory. Some remotes (e.g. FigShare) flatten names to
+    // basenames, so files in subdirectories of a linked directory would
+    // not match; these are matched by basename within the linked
+    // directory instead, if that is unambiguous.
+    fn merge_remote_files(
+        result: &mut HashMap<String, HashMap<String, MergedFile>>,
+        all_remote_files: &HashMap<(String, String), HashMap<String, RemoteFile>>,
+    ) {
         for ((remote_service, tracked_dir), remote_files) in all_remote_files.iter() {
             // merge remote files with local files
             for (name, remote_file) in remote_files {
-                // try to get the tracked directory; it doesn't exist make it
-                let path_key = PathBuf::from(tracked_dir)
-                    .join(name)
-                    .to_str()
-                    .unwrap()
-                    .to_string();
-                if let Some(merged_file) = result
-                    .entry(tracked_dir.clone())
-                    .or_default()
-                    .get_mut(&path_key)
+                // remotes may use either path separator
+                let name = name.replace('\\', "/");
+                let path_key =
+                    normalize_path_key(PathBuf::from(tracked_dir).join(&name).to_str().unwrap());
+                let dir = Path::new(&path_key)
+                    .parent()
+                    .map(|dir| dir.to_string_lossy().to_string())
+                    .unwrap_or_default();
+
+                let location = if result
+                    .get(&dir)
+                    .is_some_and(|files| files.contains_key(&path_key))
+                {
+                    Some((dir.clone(), path_key.clone()))
+                } else {
+                    DataCollection::match_by_basename(result, tracked_dir, &name, remote_service)
+                };
+
+                if let Some(merged_file) =
+                    location.and_then(|(dir, key)| result.get_mut(&dir)?.get_mut(&key))
                 {
                     // we have a local and a remote file
                     // set the joined remote file and the service
@@ -864,8 +2495,8 @@ impl DataCollection {
                     merged_file.remote_service = Some(remote_service.to_string());
                 } else {
                     // no local file, but we have a remote
-                    result.entry(tracked_dir.clone()).or_default().insert(
-                        path_key.to_string(),
+                    result.entry(dir).or_default().insert(
+                        path_key,
                         MergedFile {
                             local: None,
                             remote: Some(remote_file.clone()),
@@ -875,7 +2506,201 @@ impl DataCollection {
                 }
             }
         }
-        Ok(result)
+    }
+
+    // Group the fetched remote files by their manifest path across
+    // remotes, and return those on more than one remote with different
+    // MD5s, i.e. mirrors that have diverged (a file in a linked directory
+    // nested in another is on both directories' remotes). Copies without
+    // an MD5 can't be compared, and record files (e.g. each directory's
+    // README) are expected to differ.
+    fn remote_conflicts(
+        &self,
+        all_remote_files: &HashMap<(String, String), HashMap<String, RemoteFile>>,
+    ) -> Vec<RemoteConflict> {
+        let mut by_path: BTreeMap<String, Vec<(String, String, String)>> = BTreeMap::new();
+        for ((remote_service, tracked_dir), remote_files) in all_remote_files {
+            let records = self.record_files_for(tracked_dir);
+            for (name, remote_file) in remote_files {
+                let name = name.replace('\\', "/");
+                let is_record = records
+                    .iter()
+                    .any(|record| Path::new(record).file_name() == Path::new(&name).file_name());
+                let path =
+                    normalize_path_key(&Path::new(tracked_dir).join(&name).to_string_lossy());
+                if let (Some(md5), false) = (remote_file.get_md5(), is_record) {
+                    by_path.entry(path).or_default().push((
+                        tracked_dir.clone(),
+                        remote_service.clone(),
+                        md5,
+                    ));
+                }
+            }
+        }
+        by_path
+            .into_iter()
+            .filter(|(_, copies)| copies.iter().any(|copy| copy.2 != copies[0].2))
+            .map(|(path, mut copies)| {
+                copies.sort();
+                RemoteConflict { path, copies }
+            })
+            .collect()
+    }
+
+    // Find the local file a flattened remote file name corresponds to: the
+    // only local file in the linked directory (or its subdirectories) with
+    // that basename, that is not already matched to a remote file. Returns
+    // its (directory, key) in the merge() results.
+    fn match_by_basename(
+        result: &HashMap<String, HashMap<String, MergedFile>>,
+        tracked_dir: &str,
+        name: &str,
+        remote_service: &str,
+    ) -> Option<(String, String)> {
+        let basename = Path::new(name).file_name()?;
+        let candidates: Vec<(String, String)> = result
+            .iter()
+            .filter(|(dir, _)| Path::new(dir).starts_with(tracked_dir))
+            .flat_map(|(dir, files)| {
+                files
+                    .iter()
+                    .filter(|(key, merged_file)| {
+                        merged_file.local.is_some()
+                            && merged_file.remote.is_none()
+                            && Path::new(key).file_name() == Some(basename)
+                    })
+                    .map(move |(key, _)| (dir.clone(), key.clone()))
+            })
+            .collect();
+        match candidates.len() {
+            0 => None,
+            1 => candidates.into_iter().next(),
+            _ => {
+                let mut paths: Vec<String> = candidates.into_iter().map(|(_, key)| key).collect();
+                paths.sort();
+                print_warn!(
+                    "{} file '{}' (linked to '{}') could be any of these local files, \
+                     since {} does not keep subdirectories: {}",
+                    remote_service,
+                    name,
+                    tracked_dir,
+                    remote_service,
+                    paths.join(", ")
+                );
+                None
+            }
+        }
+    }
+
+    // Upload each remote's record files (see record_files_for()) if the
+    // remote does not have them or their MD5 has changed. Record files are
+    // not manifest entries, so removing one from the manifest leaves the
+    // remote copy alone.
+    async fn push_record_files(
+        &self,
+        all_files: &HashMap<String, HashMap<String, MergedFile>>,
+        path_context: &Path,
+        filter: &PathFilter,
+        summary: &mut PushSummary,
+    ) -> Result<Vec<(String, DataFile)>> {
+        let mut uploaded = Vec::new();
+        for (dir, remote) in &self.remotes {
+            for record in self.record_files_for(dir) {
+                if !filter.matches(record) || self.files.contains_key(record) {
+                    continue;
+                }
+                let data_file = match DataFile::new(record.clone(), None, path_context).await {
+                    Ok(data_file) => data_file,
+                    Err(_) => {
+                        print_warn!(
+                            "Record file '{}' does not exist, so it was not uploaded to {}.",
+                            record,
+                            remote.name()
+                        );
+                        continue;
+                    }
+                };
+                let key = normalize_path_key(
+                    &Path::new(dir).join(data_file.basename()?).to_string_lossy(),
+                );
+                let remote_md5 = all_files
+                    .get(dir)
+                    .and_then(|files| files.get(&key))
+                    .and_then(|merged_file| merged_file.remote.as_ref())
+                    .and_then(|remote_file| remote_file.md5.as_ref());
+                if remote_md5 == Some(&data_file.md5) {
+                    continue;
+                }
+                print_info!("uploading record file {:?} to {}", record, remote.name());
+                let outcome = remote.upload(&data_file, dir, path_context, true).await?;
+                if summary.record(record, outcome) {
+                    uploaded.push((dir.clone(), data_file));
+                }
+            }
+        }
+        Ok(uploaded)
+    }
+
+    // Check just-uploaded files against a fresh listing of each remote
+    // (one listing per remote), since some upload paths (e.g. FigShare's)
+    // never confirm the stored file matches the local one. Files that
+    // don't match are moved from the report's uploaded to its failed.
+    async fn verify_uploads(
+        &self,
+        uploaded: &BTreeMap<String, Vec<DataFile>>,
+        report: &mut TransferReport,
+    ) -> Result<()> {
+        let mut mismatches = Vec::new();
+        for (dir, data_files) in uploaded {
+            let remote = self
+                .remotes
+                .get(dir)
+                .ok_or_else(|| anyhow!("No remote found for '{}'.", dir))?;
+            let remote_files = remote.get_files_hashmap().await.map_err(|err| {
+                anyhow!(
+                    "Could not fetch the {} listing for '{}' to verify the upload: {}",
+                    remote.name(),
+                    dir,
+                    err
+                )
+            })?;
+            for data_file in data_files {
+                let remote_file = remote_files.get(&data_file.basename()?);
+                if let Some(problem) = upload_mismatch(data_file, remote_file) {
+                    report.uploaded.retain(|path| path != &data_file.path);
+                    report.fail(
+                        &data_file.path,
+                        &format!("did not match the remote after upload: {}", problem),
+                    );
+                    mismatches.push(format!(
+                        "{} ({}): {}",
+                        data_file.path,
+                        remote.name(),
+                        problem
+                    ));
+                }
+            }
+        }
+        if json() {
+            return Ok(());
+        }
+        if mismatches.is_empty() {
+            println!("Verified uploads against the remote listings.");
+            return Ok(());
+        }
+        println!(
+            "{}",
+            format!(
+                "{} did not match the remote after upload:",
+                pluralize(mismatches.len() as u64, "file")
+            )
+            .red()
+            .bold()
+        );
+        for mismatch in &mismatches {
+            println!("   - {}", mismatch);
+        }
+        Ok(())
     }
 
     // Get the status of the DataCollection, optionally with remotes.
@@ -886,20 +2711,61 @@ impl DataCollection {
         path_context: &Path,
         include_remotes: bool,
     ) -> Result<BTreeMap<String, Vec<StatusEntry>>> {
-        let merged_files = self.merge(include_remotes).await?;
+        let (statuses, _) = self
+            .status_with(path_context, include_remotes, false)
+            .await?;
+        Ok(statuses)
+    }
+
+    // The status, as status(), optionally downloading the remote copies of
+    // files whose remotes have no MD5 to compare them (sdf status
+    // --deep-verify), and the files whose copies differ between remotes
+    // (see remote_conflicts()).
+    pub async fn status_with(
+        &mut self,
+        path_context: &Path,
+        include_remotes: bool,
+        deep_verify: bool,
+    ) -> Result<(BTreeMap<String, Vec<StatusEntry>>, Vec<RemoteConflict>)> {
+        let (merged_files, failures, conflicts) = self.merge_compared(include_remotes).await?;
+        for failure in &failures {
+            if failure.unauthenticated {
+                print_warn!(
+                    "The {} remote for '{}' is {}; its files' remote status is not shown.",
+                    failure.service,
+                    failure.directory,
+                    failure.reason()
+                );
+            } else {
+                print_warn!(
+                    "The {} remote for '{}' is unavailable ({}); its files' remote status is not shown.",
+                    failure.service,
+                    failure.directory,
+                    failure.reason()
+                );
+            }
+        }
 
         let mut statuses_futures = FuturesUnordered::new();
 
         for (directory, inner_map) in merged_files.into_iter() {
             // this clone is to prevent a borrow issue due to async move below
             let files: Vec<_> = inner_map.values().cloned().collect();
+            let remote = self
+                .remote_for_directory(&directory)
+                .filter(|_| deep_verify);
             for mf in files {
                 let directory_clone = directory.clone();
                 statuses_futures.push(async move {
-                    let status_entry = mf
-                        .status_entry(path_context, include_remotes)
-                        .await
-                        .map_err(anyhow::Error::from)?;
+                    let mut status_entry = mf.status_entry(path_context, include_remotes).await?;
+                    if let Some(remote) = remote {
+                        let unverified = status_entry.remote_status
+                            == Some(RemoteStatusCode::Exists)
+                            && status_entry.local_status == Some(LocalStatusCode::Current);
+                        if unverified {
+                            mf.deep_verify(remote, &mut status_entry).await?;
+                        }
+                    }
                     Ok::<(String, StatusEntry), anyhow::Error>((directory_clone, status_entry))
                 });
             }
@@ -922,25 +2788,136 @@ impl DataCollection {
         }
 
         pb.bar.finish_with_message("MD5 comparison complete.");
-        Ok(statuses)
+
+        // mark the files of remotes that could not be fetched
+        for (directory, entries) in statuses.iter_mut() {
+            let failure = failures
+                .iter()
+                .find(|failure| Path::new(directory).starts_with(&failure.directory));
+            if let Some(failure) = failure {
+                for entry in entries.iter_mut() {
+                    entry.remote_unavailable = Some(failure.reason().to_string());
+                }
+            }
+        }
+        Ok((statuses, conflicts))
+    }
+
+    // Upload the queued files of each remote (by tracked directory), with a
+    // progress bar for each remote. Remotes are uploaded to one at a time,
+    // or with parallel_remotes, up to remote_concurrency() at once. This
+    // stops at the first upload that errors.
+    async fn upload_queued(
+        &self,
+        queued: &BTreeMap<String, Vec<DataFile>>,
+        path_context: &Path,
+        overwrite: bool,
+        parallel_remotes: bool,
+        state: &Mutex<SyncState>,
+    ) -> Result<Vec<(String, DataFile, UploadOutcome)>> {
+        let group = ProgressGroup::new();
+        let mut remote_uploads = Vec::new();
+        for (tracked_dir, data_files) in queued {
+            let remote = self.remotes.get(tracked_dir).ok_or_else(|| {
+                anyhow!("Directory '{}' is not registered in remotes.", tracked_dir)
+            })?;
+            let label = format!("{} ({})", tracked_dir, remote.name());
+            let pb = group.add(&label, data_files.len() as u64)?;
+            remote_uploads.push(async move {
+                let mut outcomes = Vec::new();
+                for data_file in data_files {
+                    info!("uploading file {:?} to {}", data_file.path, remote.name());
+                    pb.bar.set_message(data_file.path.clone());
+                    let outcome = remote
+                        .upload(data_file, tracked_dir, path_context, overwrite)
+                        .await?;
+                    if outcome == UploadOutcome::Uploaded {
+                        // so an interrupted push can be resumed
+                        let mut state = state.lock().expect("sync state lock");
+                        state.complete(data_file);
+                        state.save(path_context)?;
+                    }
+                    outcomes.push((tracked_dir.clone(), data_file.clone(), outcome));
+                    pb.bar.inc(1);
+                }
+                pb.bar.finish_with_message("uploaded.");
+                Ok::<_, anyhow::Error>(outcomes)
+            });
+        }
+        let concurrency = if parallel_remotes {
+            http::remote_concurrency()
+        } else {
+            1
+        };
+        let mut results = stream::iter(remote_uploads).buffered(concurrency);
+        let mut outcomes = Vec::new();
+        while let Some(result) = results.next().await {
+            outcomes.extend(result?);
+        }
+        Ok(outcomes)
     }
 
-    pub async fn push(&mut self, path_context: &Path, overwrite: bool) -> Result<()> {
+    // Push the tracked files to their remotes. With parallel_remotes,
+    // several remotes are uploaded to at once (see upload_queued()). With
+    // resume, files uploaded by an interrupted push aren't checked again
+    // (see sync_state.rs).
+    pub async fn push(
+        &mut self,
+        path_context: &Path,
+        filter: &PathFilter,
+        options: &PushOptions,
+    ) -> Result<TransferReport> {
+        let PushOptions {
+            overwrite,
+            verify,
+            parallel_remotes,
+            resume,
+            ..
+        } = *options;
         // TODO before any push, we need to make sure that the project
         // status is "clean" e.g. nothing out of data.
+        self.check_hashed(|data_file| data_file.tracked && filter.matches(&data_file.path))?;
 
-        // Fetch all files as MergedFiles
+        // Fetch all files as MergedFiles, skipping directories whose
+        // remote no longer exists
         // note: this authenticates
-        let all_files = self.merge(true).await?;
-
-        let mut num_uploaded = 0;
+        let (all_files, gone_skipped) = self.merge_linked().await?;
+        let gone_skipped: Vec<SkippedFile> = gone_skipped
+            .into_iter()
+            .filter(|skipped| filter.matches(&skipped.path))
+            .collect();
+
+        // files to upload, and those uploaded, by tracked directory (the
+        // latter to verify against the remotes)
+        let mut queued: BTreeMap<String, Vec<DataFile>> = BTreeMap::new();
+        let mut uploaded: BTreeMap<String, Vec<DataFile>> = BTreeMap::new();
+        let mut summary = PushSummary::default();
         let mut current_skipped = Vec::new();
         let mut messy_skipped = Vec::new();
         let mut overwrite_skipped = Vec::new();
         let mut untracked_skipped = Vec::new();
+        let mut duplicate_skipped = Vec::new();
+        let state = if resume {
+            SyncState::resume(path_context, "push")
+        } else {
+            SyncState::new("push")
+        };
+        let mut resumed_skipped = Vec::new();
 
         for (tracked_dir, files) in all_files.iter() {
+            if self.archives.contains_key(tracked_dir) {
+                print_info!(
+                    "'{}' is pushed as an archive; use 'sdf push --archive {}' to update it.",
+                    tracked_dir,
+                    tracked_dir
+                );
+                continue;
+            }
             if let Some(remote) = self.remotes.get(tracked_dir) {
+                // skipped with a warning when fetching (see fetch_all())
+                if !remote.is_supported() {
+                    continue;
+                }
                 for merged_file in files.values() {
                     let name = merged_file.name()?;
                     let path = PathBuf::from(tracked_dir)
@@ -948,224 +2925,821 @@ impl DataCollection {
                         .to_str()
                         .unwrap()
                         .to_string();
+                    if !filter.matches(&path) {
+                        continue;
+                    }
                     let local = merged_file.local.clone();
 
-                    // if the file is not tracked or is remote-only,
-                    // we do not do anything
-                    if local.as_ref().map_or(false, |mf| !mf.tracked) {
-                        untracked_skipped.push(path);
+                    match self.push_skipped(local.as_ref(), &path)? {
+                        Some(SkipReason::Untracked) => {
+                            untracked_skipped.push(path);
+                            continue;
+                        }
+                        Some(_) => {
+                            summary.excluded.push(path);
+                            continue;
+                        }
+                        None => {}
+                    }
+
+                    if local
+                        .as_ref()
+                        .is_some_and(|local| state.is_completed(local, path_context))
+                    {
+                        resumed_skipped.push(path);
                         continue;
                     }
 
                     // now we need to figure out whether to push the file,
                     // which depends on the RemoteStatusCode and whether
-                    // we should overwrite (TODO)
-                    let do_upload = match merged_file.status(path_context).await? {
-                        RemoteStatusCode::NoLocal => {
-                            // A file exists on the remote, but not locally: there
-                            // is nothing to push in this case (or count!)
-                            false
-                        }
-                        RemoteStatusCode::Current => {
+                    // we should overwrite
+                    let status = merged_file.status(path_context).await?;
+                    let do_upload = match push_decision(&status, overwrite) {
+                        TransferDecision::Transfer => true,
+                        TransferDecision::Skip(SkipReason::Current) => {
                             current_skipped.push(path);
                             false
                         }
-                        RemoteStatusCode::Exists => {
-                            // it exists on the remote, but we cannot
-                            // compare MD5s. Push only if overwrite is true.
-                            if !overwrite {
-                                overwrite_skipped.push(path);
-                            }
-                            overwrite
+                        TransferDecision::Skip(SkipReason::Messy) => {
+                            messy_skipped.push(MessyFile::new(merged_file, path_context).await?);
+                            false
                         }
-                        RemoteStatusCode::MessyLocal => {
-                            messy_skipped.push(path);
+                        TransferDecision::Skip(SkipReason::Duplicate) => {
+                            duplicate_skipped.push(path);
                             false
                         }
-                        RemoteStatusCode::Invalid => {
-                            return Err(anyhow!("A file ({:}) with RemoteStatusCode::Invalid was encountered. Please report.", path));
+                        TransferDecision::Skip(_) => {
+                            overwrite_skipped.push(path);
+                            false
                         }
-                        RemoteStatusCode::Different => {
-                            // TODO if remote supports modification times,
-                            // could do extra comparison here
-                            info!("skipping {:} {:}", path, overwrite);
-                            if !overwrite {
-                                overwrite_skipped.push(path);
+                        TransferDecision::Nothing => {
+                            if status == RemoteStatusCode::DeletedLocal {
+                                print_warn!(
+                                    "A file ({:}) was skipped because it was deleted.",
+                                    path
+                                );
                             }
-                            overwrite
-                        }
-                        RemoteStatusCode::DeletedLocal => {
-                            // there is nothing to upload
-                            print_warn!("A file ({:}) was skipped because it was deleted.", path);
                             false
                         }
-                        RemoteStatusCode::NotExists => true,
+                        TransferDecision::Invalid => {
+                            return Err(anyhow!("A file ({:}) with RemoteStatusCode::{:?} was encountered. Please report.", path, status));
+                        }
                     };
 
                     if do_upload {
                         let data_file = local.ok_or(anyhow!("Internal error (do_upload() with MergedFile.local = None): please report."))?;
-                        print_info!("uploading file {:?} to {}", data_file.path, remote.name());
-                        remote.upload(&data_file, path_context, overwrite).await?;
-                        num_uploaded += 1;
+                        queued
+                            .entry(tracked_dir.clone())
+                            .or_default()
+                            .push(data_file);
                     }
                 }
             }
         }
-        println!("Uploaded {}.", pluralize(num_uploaded as u64, "file"));
+        let state = Mutex::new(state);
+        let outcomes = self
+            .upload_queued(&queued, path_context, overwrite, parallel_remotes, &state)
+            .await?;
+        for (tracked_dir, data_file, outcome) in outcomes {
+            if summary.record(&data_file.path, outcome) {
+                uploaded.entry(tracked_dir).or_default().push(data_file);
+            }
+        }
+        for (dir, data_file) in self
+            .push_record_files(&all_files, path_context, filter, &mut summary)
+            .await?
+        {
+            uploaded.entry(dir).or_default().push(data_file);
+        }
+        let num_uploaded = summary.num_uploaded;
+        overwrite_skipped.extend(summary.exists_skipped.iter().cloned());
         let num_skipped = overwrite_skipped.len()
             + current_skipped.len()
             + messy_skipped.len()
-            + untracked_skipped.len();
-        let punc = if num_skipped > 0 { "." } else { ":" };
-        println!("Skipped {}{}", pluralize(num_skipped as u64, "file"), punc);
-        if !untracked_skipped.is_empty() {
+            + untracked_skipped.len()
+            + duplicate_skipped.len()
+            + gone_skipped.len()
+            + summary.excluded.len()
+            + resumed_skipped.len();
+
+        let mut report = TransferReport {
+            uploaded: uploaded
+                .values()
+                .flatten()
+                .map(|f| f.path.clone())
+                .collect(),
+            ..Default::default()
+        };
+        report.skip(&current_skipped, SkipReason::Current);
+        report.skip(&overwrite_skipped, SkipReason::Exists);
+        report
+            .skipped
+            .extend(messy_skipped.iter().map(MessyFile::skipped));
+        report.skip(&untracked_skipped, SkipReason::Untracked);
+        report.skip(&summary.excluded, SkipReason::Excluded);
+        report.skip(&duplicate_skipped, SkipReason::Duplicate);
+        report.skip(&resumed_skipped, SkipReason::Resumed);
+        report.skipped.extend(gone_skipped);
+        for path in &summary.checksum_failed {
+            report.fail(path, "remote MD5 did not match the local MD5 after upload");
+        }
+
+        if json() {
+            // the report is printed as JSON by Project::push()
+        } else if quiet() {
             println!(
-                "  Untracked: {}",
-                pluralize(untracked_skipped.len() as u64, "file")
+                "Uploaded {}; skipped {}.",
+                pluralize(num_uploaded as u64, "file"),
+                pluralize(num_skipped as u64, "file")
             );
-            for path in untracked_skipped {
-                println!("   - {:}", path);
+        } else {
+            println!("Uploaded {}.", pluralize(num_uploaded as u64, "file"));
+            let punc = if num_skipped > 0 { "." } else { ":" };
+            println!("Skipped {}{}", pluralize(num_skipped as u64, "file"), punc);
+            if !untracked_skipped.is_empty() {
+                println!(
+                    "  Untracked: {}",
+                    pluralize(untracked_skipped.len() as u64, "file")
+                );
+                for path in untracked_skipped {
+                    println!("   - {:}", path);
+                }
+            }
+            if !summary.excluded.is_empty() {
+                println!(
+                    "  Excluded by pattern: {}",
+                    pluralize(summary.excluded.len() as u64, "file")
+                );
+                for path in &summary.excluded {
+                    println!("   - {:}", path);
+                }
+            }
+            if !current_skipped.is_empty() {
+                println!(
+                    "  Remote file is indentical to local file: {}",
+                    pluralize(current_skipped.len() as u64, "file")
+                );
+                for path in current_skipped {
+                    println!("   - {:}", path);
+                }
             }
+            print_overwrite_skipped("push", &overwrite_skipped);
+            print_messy_skipped(&messy_skipped);
+            print_duplicate_skipped(&duplicate_skipped);
+            print_resumed_skipped("push", &resumed_skipped);
         }
-        if !current_skipped.is_empty() {
+        if !summary.checksum_failed.is_empty() && !json() {
             println!(
-                "  Remote file is indentical to local file: {}",
-                pluralize(current_skipped.len() as u64, "file")
+                "{}",
+                format!(
+                    "Failed: {} did not match the local MD5 after upload (the remote copies were deleted):",
+                    pluralize(summary.checksum_failed.len() as u64, "file")
+                )
+                .red()
+                .bold()
             );
-            for path in current_skipped {
+            for path in &summary.checksum_failed {
                 println!("   - {:}", path);
             }
         }
-        if !overwrite_skipped.is_empty() {
-            println!(
-                "  Would overwrite (use --overwrite to push): {}",
-                pluralize(overwrite_skipped.len() as u64, "file")
-            );
-            for path in overwrite_skipped {
-                println!("   - {:}", path);
+        if verify && num_uploaded > 0 {
+            self.verify_uploads(&uploaded, &mut report).await?;
+        }
+        let synced: Vec<(&String, String)> = report
+            .uploaded
+            .iter()
+            .filter_map(|path| Some((path, self.files.get(path)?.md5.clone())))
+            .collect();
+        self.sync_remote_md5s(synced);
+        if report.failed.is_empty() {
+            SyncState::remove(path_context)?;
+        }
+
+        Ok(report)
+    }
+
+    // Whether a file matches the push_exclude patterns of the directory
+    // linked to its remote. Patterns are matched against the path relative
+    // to the linked directory, and (as with --include/--exclude) '*' also
+    // matches '/', so '*.bam' excludes BAM files in subdirectories too.
+    // Why a push skips a file before checking its status: it is not
+    // tracked, or matches its directory's push_exclude patterns (excluded
+    // files are never pushed, even with --overwrite). Remote-only files
+    // (without a local DataFile) are never skipped here.
+    pub fn push_skipped(&self, local: Option<&DataFile>, path: &str) -> Result<Option<SkipReason>> {
+        match local {
+            Some(local) if !local.tracked => Ok(Some(SkipReason::Untracked)),
+            Some(_) if self.push_excluded(path)? => Ok(Some(SkipReason::Excluded)),
+            _ => Ok(None),
+        }
+    }
+
+    pub fn push_excluded(&self, path: &str) -> Result<bool> {
+        let path = normalize_path_key(path);
+        let linked = self
+            .push_exclude
+            .iter()
+            .filter(|(dir, _)| Path::new(&path).starts_with(dir))
+            .max_by_key(|(dir, _)| dir.len());
+        let Some((dir, patterns)) = linked else {
+            return Ok(false);
+        };
+        let relative = Path::new(&path).strip_prefix(dir)?.to_string_lossy();
+        for pattern in patterns {
+            let compiled = glob::Pattern::new(pattern).map_err(|err| {
+                anyhow!(
+                    "Invalid push_exclude pattern '{}' for '{}': {}",
+                    pattern,
+                    dir,
+                    err
+                )
+            })?;
+            if compiled.matches(&relative) {
+                return Ok(true);
             }
         }
-        if !messy_skipped.is_empty() {
-            println!(
-                "  Local is \"messy\" (manifest and file disagree): {}",
-                pluralize(messy_skipped.len() as u64, "file")
-            );
-            println!("  Use 'sdf update <FILE>' to add the current version to the manifest.");
-            for path in messy_skipped {
-                println!("   - {:}", path);
+        Ok(false)
+    }
+
+    // Add a pattern of files (relative to the linked directory) never to
+    // push to a directory's remote, returning false if it was already there.
+    pub fn add_push_exclude(&mut self, dir: &str, pattern: &str) -> Result<bool> {
+        let dir = normalize_path_key(dir);
+        if !self.remotes.contains_key(&dir) {
+            return Err(anyhow!(
+                "Directory '{}' is not linked to a remote; use 'sdf link' first.",
+                dir
+            ));
+        }
+        glob::Pattern::new(pattern)
+            .map_err(|err| anyhow!("Invalid pattern '{}': {}", pattern, err))?;
+        let patterns = self.push_exclude.entry(dir).or_default();
+        if patterns.iter().any(|existing| existing == pattern) {
+            return Ok(false);
+        }
+        patterns.push(pattern.to_string());
+        Ok(true)
+    }
+
+    // Whether files pulled to a path are made read-only, i.e. whether the
+    // (innermost) directory containing it has readonly_on_pull set.
+    pub fn readonly_on_pull(&self, path: &str) -> bool {
+        self.readonly_on_pull
+            .iter()
+            .filter(|(dir, _)| Path::new(path).starts_with(dir))
+            .max_by_key(|(dir, _)| dir.len())
+            .is_some_and(|(_, readonly)| *readonly)
+    }
+
+    // Set whether files pulled to a linked directory are made read-only.
+    pub fn set_readonly_on_pull(&mut self, dir: &str, readonly: bool) -> Result<()> {
+        let dir = normalize_path_key(dir);
+        if !self.remotes.contains_key(&dir) {
+            return Err(anyhow!(
+                "Directory '{}' is not linked to a remote; use 'sdf link' first.",
+                dir
+            ));
+        }
+        if readonly {
+            self.readonly_on_pull.insert(dir, true);
+        } else {
+            self.readonly_on_pull.remove(&dir);
+        }
+        Ok(())
+    }
+
+    // Get a linked directory's remote, authenticated.
+    fn authenticated_remote(&mut self, dir: &str) -> Result<&Remote> {
+        let remote = self
+            .remotes
+            .get_mut(dir)
+            .ok_or_else(|| anyhow!("Directory '{}' is not linked to a remote.", dir))?;
+        if !remote.has_token() {
+            authenticate_remote(remote)?;
+        }
+        Ok(remote)
+    }
+
+    // Error if any of the selected files have not been hashed yet
+    // (i.e. were added with 'sdf add --no-hash').
+    pub fn check_hashed<F>(&self, select: F) -> Result<()>
+    where
+        F: Fn(&DataFile) -> bool,
+    {
+        let mut unhashed: Vec<_> = self
+            .files
+            .values()
+            .filter(|data_file| !data_file.is_hashed() && select(data_file))
+            .map(|data_file| data_file.path.clone())
+            .collect();
+        if unhashed.is_empty() {
+            return Ok(());
+        }
+        unhashed.sort();
+        Err(anyhow!(
+            "{} not been hashed yet (see 'sdf add --no-hash'); run 'sdf update' first:\n  {}",
+            if unhashed.len() == 1 {
+                "1 file has".to_string()
+            } else {
+                format!("{} files have", unhashed.len())
+            },
+            unhashed.join("\n  ")
+        ))
+    }
+
+    // Push all tracked files in a linked directory as a single reproducible
+    // archive (see archive.rs), recording it in the manifest. The archive is
+    // not rebuilt or uploaded if no member has changed and the remote's copy
+    // is current.
+    pub async fn push_archive(&mut self, dir: &str, path_context: &Path) -> Result<()> {
+        let dir = normalize_path_key(dir);
+
+        // the members' MD5s are recorded for verifying pulls, so local
+        // files must match the manifest
+        let mut members = BTreeMap::new();
+        let mut messy = Vec::new();
+        for (path, data_file) in &self.files {
+            let member = match Path::new(path).strip_prefix(&dir) {
+                Ok(member) if data_file.tracked => member.to_string_lossy().to_string(),
+                _ => continue,
+            };
+            let md5 = compute_md5(&path_context.join(path)).await?;
+            if md5.as_ref() != Some(&data_file.md5) {
+                messy.push(path.clone());
+                continue;
+            }
+            members.insert(member, data_file.md5.clone());
+        }
+        if !messy.is_empty() {
+            messy.sort();
+            return Err(anyhow!(
+                "Cannot archive '{}': these files are missing or do not match the manifest \
+                 (use 'sdf update <FILE>'): {}",
+                dir,
+                messy.join(", ")
+            ));
+        }
+        if members.is_empty() {
+            return Err(anyhow!("No tracked files in '{}' to archive.", dir));
+        }
+
+        let name = archive_name(&dir);
+        let recorded = self.archives.get(&dir).cloned();
+        let remote = self.authenticated_remote(&dir)?;
+        let remote_md5 = remote
+            .get_files_hashmap()
+            .await?
+            .get(&name)
+            .and_then(|remote_file| remote_file.md5.clone());
+        if let Some(recorded) = &recorded {
+            if recorded.members == members && remote_md5.as_ref() == Some(&recorded.md5) {
+                println!("Archive '{}' is current; nothing to push.", name);
+                return Ok(());
+            }
+        }
+
+        let build_dir = tempfile::tempdir()?;
+        let _staged = interrupt::stage(Staged::Partial(build_dir.path().to_path_buf()));
+        let archive_path = build_dir.path().join(&name);
+        build_archive(path_context, &dir, &members, &archive_path)?;
+        let data_file = DataFile::new(
+            archive_path.to_string_lossy().to_string(),
+            None,
+            Path::new(""),
+        )
+        .await?;
+        print_info!(
+            "uploading archive '{}' ({}) to {}",
+            name,
+            pluralize(members.len() as u64, "file"),
+            remote.name()
+        );
+        if remote.upload(&data_file, &dir, Path::new(""), true).await? != UploadOutcome::Uploaded {
+            return Err(anyhow!(
+                "Archive '{}' did not match its MD5 after upload; try pushing again.",
+                name
+            ));
+        }
+
+        self.archives.insert(
+            dir,
+            Archive {
+                name,
+                md5: data_file.md5,
+                size: data_file.size,
+                members,
+            },
+        );
+        Ok(())
+    }
+
+    // Download a linked directory's archive and unpack it, checking the
+    // archive and each member against the manifest's MD5s.
+    pub async fn pull_archive(
+        &mut self,
+        dir: &str,
+        path_context: &Path,
+        overwrite: bool,
+    ) -> Result<()> {
+        let dir = normalize_path_key(dir);
+        let archive = self.archives.get(&dir).cloned().ok_or_else(|| {
+            anyhow!(
+                "No archive is recorded for '{}'; push one with 'sdf push --archive {}'.",
+                dir,
+                dir
+            )
+        })?;
+        // members are checked against the manifest entries if they exist,
+        // otherwise against the archive's record
+        let expected: BTreeMap<String, String> = archive
+            .members
+            .iter()
+            .map(|(member, md5)| {
+                let path = normalize_path_key(&Path::new(&dir).join(member).to_string_lossy());
+                let md5 = self
+                    .files
+                    .get(&path)
+                    .map_or(md5, |data_file| &data_file.md5);
+                (member.clone(), md5.clone())
+            })
+            .collect();
+
+        let remote = self.authenticated_remote(&dir)?;
+        let remote_file = remote
+            .get_files_hashmap()
+            .await?
+            .remove(&archive.name)
+            .ok_or_else(|| anyhow!("Archive '{}' was not found on the remote.", archive.name))?;
+        let url = remote_file
+            .url
+            .as_ref()
+            .ok_or_else(|| anyhow!("Cannot download '{}'; download URL not set.", archive.name))?;
+        let url = remote.authenticate_url(url)?;
+
+        let download_dir = tempfile::tempdir()?;
+        let _staged = interrupt::stage(Staged::Partial(download_dir.path().to_path_buf()));
+        let archive_path = download_dir.path().join(&archive.name);
+        print_info!("downloading archive '{}'", archive.name);
+        let response = http::get(&url).await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(anyhow!(
+                "Failed to download '{}' (HTTP {}).",
+                archive.name,
+                status
+            ));
+        }
+        // streamed to disk, hashing as it's written, since archives can be
+        // larger than memory
+        let mut output = fs::File::create(&archive_path)?;
+        let mut context = md5::Context::new();
+        let mut stream = response.bytes_stream();
+        while let Some(chunk) = stream.next().await {
+            let chunk = chunk.map_err(http::send_error)?;
+            context.consume(&chunk);
+            std::io::Write::write_all(&mut output, &chunk)?;
+        }
+        drop(output);
+        let md5 = format!("{:x}", context.compute());
+        if md5 != archive.md5 {
+            fs::remove_file(&archive_path)?;
+            return Err(anyhow!(
+                "Downloaded archive '{}' has MD5 {}, but the manifest's is {}.",
+                archive.name,
+                md5,
+                archive.md5
+            ));
+        }
+
+        // skip members that are current, or that differ if not overwriting
+        let dest_dir = path_context.join(&dir);
+        let mut skipped = Vec::new();
+        let mut overwrite_skipped = Vec::new();
+        for (member, md5) in &expected {
+            let local_md5 = compute_md5(&dest_dir.join(member)).await?;
+            match local_md5 {
+                Some(local_md5) if &local_md5 == md5 => skipped.push(member.clone()),
+                Some(_) if !OverwriteAction::new(true, overwrite).transfers() => {
+                    overwrite_skipped.push(member.clone())
+                }
+                _ => {}
             }
         }
+        let unpacked = unpack_archive(&archive_path, &dest_dir, &expected, |member| {
+            skipped
+                .iter()
+                .chain(&overwrite_skipped)
+                .any(|m| m == member)
+        })?;
 
+        println!(
+            "Unpacked {} from '{}' ({} already current).",
+            pluralize(unpacked.len() as u64, "file"),
+            archive.name,
+            skipped.len()
+        );
+        let overwrite_skipped: Vec<String> = overwrite_skipped
+            .iter()
+            .map(|member| Path::new(&dir).join(member).to_string_lossy().to_string())
+            .collect();
+        print_overwrite_skipped("pull", &overwrite_skipped);
         Ok(())
     }
 
-    pub async fn pull_urls(&mut self, path_context: &Path, overwrite: bool) -> Result<()> {
+    pub async fn pull_urls(
+        &mut self,
+        path_context: &Path,
+        overwrite: bool,
+        readonly: bool,
+        limit: &PathFilter,
+    ) -> Result<TransferReport> {
         let mut downloads = Downloads::new();
-        let mut filepaths = Vec::new();
+        let mut readonly_pulls = ReadonlyPulls::default();
+        // (download filename, manifest path) of each queued download
+        let mut queued = Vec::new();
         let mut skipped = Vec::new();
+        // (directory, size) of each queued download, and whether it's
+        // made read-only
+        let mut sizes = Vec::new();
+        let mut to_replace = Vec::new();
         let mut num_downloaded = 0;
-        for data_file in self.files.values() {
-            if let Some(url) = &data_file.url {
+        for data_file in self
+            .files
+            .values()
+            .filter(|data_file| limit.matches(&data_file.path))
+        {
+            if !data_file.urls.is_empty() {
                 let full_path = data_file.full_path(path_context)?;
-                let download =
-                    downloads.add(url.clone(), Some(&full_path.to_string_lossy()), overwrite)?;
+                let download = downloads.add_with_mirrors(
+                    &data_file.urls,
+                    Some(&full_path.to_string_lossy()),
+                    overwrite,
+                )?;
                 if let Some(dl) = download {
-                    let filepath = dl.filename.clone();
-                    filepaths.push(filepath);
+                    queued.push((dl.filename.clone(), data_file.path.clone()));
                     num_downloaded += 1;
+                    sizes.push((data_file.directory()?, data_file.size));
+                    let readonly = readonly || self.readonly_on_pull(&data_file.path);
+                    to_replace.push((full_path, readonly));
                 } else {
-                    skipped.push(url.clone());
+                    skipped.push(data_file.path.clone());
                 }
             }
         }
 
-        if num_downloaded > 0 {
+        confirm_download(&sizes)?;
+        for (full_path, readonly) in &to_replace {
+            readonly_pulls.replacing(full_path, *readonly)?;
+        }
+        if num_downloaded > 0 && !quiet() {
             println!("Downloaded:");
         }
         // grab all the files
-        downloads.retrieve(Some(" - {}"), None, false).await?;
+        let retrieved = downloads
+            .retrieve_with_failures(Some(" - {}"), None, false, None)
+            .await;
+        readonly_pulls.apply()?;
+        let mut report = download_report(&queued, &retrieved?);
 
-        let num_skipped = skipped.len();
-        println!(
-            "{} files were downloaded.\n\
-                  {} files were skipped because they existed (and --overwrite was not specified).",
-            num_downloaded, num_skipped
-        );
-        Ok(())
+        if !json() {
+            println!(
+                "Downloaded {}; skipped {}.",
+                pluralize(report.downloaded.len() as u64, "file"),
+                pluralize(skipped.len() as u64, "file")
+            );
+            print_overwrite_skipped("pull --urls", &skipped);
+        }
+        report.skip(&skipped, SkipReason::Exists);
+        Ok(report)
     }
 
     // Download all files
     //
     // TODO: code redundancy with the push method's tracking of
     // why stuff is skipped; split out info enum, etc.
-    pub async fn pull(&mut self, path_context: &Path, overwrite: bool) -> Result<()> {
-        let all_files = self.merge(true).await?;
-
+    //
+    // With backup, files are replaced as with overwrite, but local files
+    // are first renamed to <name>.local.<timestamp>. With readonly (or
+    // the directory's readonly_on_pull), pulled files are made read-only.
+    // With resume, files downloaded by an interrupted pull aren't checked
+    // again (see sync_state.rs).
+    pub async fn pull(
+        &mut self,
+        path_context: &Path,
+        limit: &PathFilter,
+        options: &PullOptions,
+    ) -> Result<TransferReport> {
+        let PullOptions {
+            overwrite,
+            backup,
+            readonly,
+            resume,
+            ..
+        } = *options;
+        let (all_files, gone_skipped) = self.merge_linked().await?;
+
+        let mut report = TransferReport::default();
+        report.skipped.extend(
+            gone_skipped
+                .into_iter()
+                .filter(|skipped| limit.matches(&skipped.path)),
+        );
+        let num_gone = report.skipped.len();
+        // (download filename, manifest path) of each queued download
+        let mut queued = Vec::new();
         let mut downloads = Downloads::new();
+        let mut readonly_pulls = ReadonlyPulls::default();
+        let replace = overwrite || backup;
+        let mut backed_up = Vec::new();
 
         let mut current_skipped = Vec::new();
         let mut messy_skipped = Vec::new();
         let mut overwrite_skipped = Vec::new();
+        // files without a download URL (e.g. embargoed), with the reason
+        let mut unavailable_skipped = Vec::new();
+        let mut duplicate_skipped = Vec::new();
+        // (directory, size) of each queued download, and its local file,
+        // whether it's made read-only, and its name
+        let mut sizes = Vec::new();
+        let mut to_replace = Vec::new();
+        let mut state = if resume {
+            SyncState::resume(path_context, "pull")
+        } else {
+            SyncState::new("pull")
+        };
+        let mut resumed_skipped = Vec::new();
 
         for (dir, merged_files) in all_files.iter() {
             // can_download() is true only if local and remote are not None.
             // (local file can be deleted, but will only be None if not in manifest also)
-            for merged_file in merged_files.values().filter(|f| f.can_download()) {
+            let merged_files = merged_files.iter().filter(|(path_key, merged_file)| {
+                limit.matches(path_key) && merged_file.can_download()
+            });
+            for (path_key, merged_file) in merged_files {
                 let path = merged_file.name()?;
+                if merged_file
+                    .local
+                    .as_ref()
+                    .is_some_and(|local| state.is_completed(local, path_context))
+                {
+                    report.skip(std::slice::from_ref(path_key), SkipReason::Resumed);
+                    resumed_skipped.push(path);
+                    continue;
+                }
 
-                let do_download = match merged_file.status(path_context).await? {
-                    RemoteStatusCode::NoLocal => {
-                        return Err(anyhow!("Internal error: execution should not have reached this point, please report.\n\
-                                           'sdf pull' filtered by MergedFile.can_download() but found a RemoteStatusCode::NoLocal status."));
-                    }
-                    RemoteStatusCode::Current => {
-                        current_skipped.push(path);
+                let status = merged_file.status(path_context).await?;
+                let messy = if status == RemoteStatusCode::MessyLocal {
+                    Some(MessyFile::new(merged_file, path_context).await?)
+                } else {
+                    None
+                };
+                let do_download = match pull_decision(&status, replace) {
+                    TransferDecision::Transfer => true,
+                    TransferDecision::Skip(SkipReason::Messy) => {
+                        if let Some(messy) = messy {
+                            report.skipped.push(SkippedFile {
+                                path: path_key.clone(),
+                                ..messy.skipped()
+                            });
+                            messy_skipped.push(messy);
+                        }
                         false
                     }
-                    RemoteStatusCode::Exists => {
-                        // it exists on the remote, but we cannot
-                        // compare MD5s. Push only if overwrite is true.
-                        if !overwrite {
-                            overwrite_skipped.push(path);
+                    TransferDecision::Skip(reason) => {
+                        report.skip(std::slice::from_ref(path_key), reason);
+                        match reason {
+                            SkipReason::Current => current_skipped.push(path),
+                            SkipReason::Duplicate => duplicate_skipped.push(path),
+                            _ => overwrite_skipped.push(path),
                         }
-                        overwrite
-                    }
-                    RemoteStatusCode::MessyLocal => {
-                        messy_skipped.push(path);
                         false
                     }
-                    RemoteStatusCode::Invalid => {
-                        return Err(anyhow!("A file ({:}) with RemoteStatusCode::Invalid was encountered. Please report.", path));
-                    }
-                    RemoteStatusCode::Different => {
-                        // TODO if remote supports modification times,
-                        // could do extra comparison here
-                        info!("skipping {:} {:}", path, overwrite);
-                        if !overwrite {
-                            overwrite_skipped.push(path);
-                        }
-                        overwrite
+                    TransferDecision::Nothing => false,
+                    TransferDecision::Invalid => {
+                        return Err(anyhow!("A file ({:}) with RemoteStatusCode::{:?} was encountered. Please report.", path, status));
                     }
-                    RemoteStatusCode::DeletedLocal => true,
-                    RemoteStatusCode::NotExists => true,
                 };
 
                 if do_download {
                     if let Some(remote) = self.remotes.get(dir) {
+                        // skip just this file, so the rest still download
                         let download =
-                            remote.get_download_info(merged_file, path_context, overwrite)?;
+                            match remote.get_download_info(merged_file, path_context, replace) {
+                                Ok(download) => download,
+                                Err(err) => {
+                                    let reason = format!("{:#}", err);
+                                    report.fail(path_key, &reason);
+                                    unavailable_skipped.push((merged_file.name()?, reason));
+                                    continue;
+                                }
+                            };
+                        let readonly = readonly || self.readonly_on_pull(path_key);
+                        to_replace.push((
+                            PathBuf::from(&download.filename),
+                            readonly,
+                            merged_file.name()?,
+                        ));
+                        // the remote's size is what's downloaded
+                        let size = merged_file
+                            .remote
+                            .as_ref()
+                            .and_then(|remote| remote.size)
+                            .or_else(|| merged_file.size())
+                            .unwrap_or(0);
+                        sizes.push((dir.clone(), size));
+                        queued.push((download.filename.clone(), path_key.clone()));
+                        let label = format!("{} ({})", dir, remote.name());
+                        downloads.set_label(&download.filename, &label);
                         downloads.queue.push(download);
                     }
                 }
             }
         }
 
-        // now retrieve all the files in the queue.
-        downloads
-            .retrieve(Some(" - {}"), Some("No files downloaded."), true)
-            .await?;
+        // only once the pull is confirmed are local files changed
+        confirm_download(&sizes)?;
+        for (full_path, readonly, name) in &to_replace {
+            readonly_pulls.replacing(full_path, *readonly)?;
+            if backup && full_path.exists() {
+                let backup_path = backup_local_file(full_path)?;
+                let backup_path = backup_path
+                    .strip_prefix(path_context)
+                    .unwrap_or(&backup_path)
+                    .to_string_lossy()
+                    .to_string();
+                backed_up.push((name.clone(), backup_path));
+            }
+        }
+
+        // now retrieve all the files in the queue, recording each as it
+        // finishes so an interrupted pull can be resumed
+        let mut save_error = None;
+        let mut record_download = |filename: &str| {
+            let data_file = queued
+                .iter()
+                .find(|(queued_filename, _)| queued_filename == filename)
+                .and_then(|(_, path_key)| self.files.get(path_key));
+            if let Some(data_file) = data_file {
+                state.complete(data_file);
+                if let Err(err) = state.save(path_context) {
+                    save_error.get_or_insert(err);
+                }
+            }
+        };
+        let retrieved = downloads
+            .retrieve_each(
+                Some(" - {}"),
+                Some("No files downloaded."),
+                true,
+                None,
+                &mut record_download,
+            )
+            .await;
+        readonly_pulls.apply()?;
+        report.extend(download_report(&queued, &retrieved?));
+        if let Some(err) = save_error {
+            return Err(err);
+        }
+        if report.failed.is_empty() {
+            SyncState::remove(path_context)?;
+        }
+        let synced: Vec<(&String, String)> = all_files
+            .values()
+            .flatten()
+            .filter(|(key, _)| report.downloaded.contains(key))
+            .filter_map(|(key, merged_file)| Some((key, merged_file.remote_md5()?)))
+            .collect();
+        self.sync_remote_md5s(synced);
+
+        let num_skipped = overwrite_skipped.len()
+            + current_skipped.len()
+            + messy_skipped.len()
+            + unavailable_skipped.len()
+            + duplicate_skipped.len()
+            + resumed_skipped.len()
+            + num_gone;
+        if json() {
+            return Ok(report);
+        }
+        if quiet() {
+            println!(
+                "Downloaded {}; skipped {}.",
+                pluralize(report.downloaded.len() as u64, "file"),
+                pluralize(num_skipped as u64, "file")
+            );
+            return Ok(report);
+        }
+
+        if !backed_up.is_empty() {
+            println!(
+                "Renamed {} before replacing them:",
+                pluralize(backed_up.len() as u64, "local file")
+            );
+            for (path, backup_path) in &backed_up {
+                println!(" - {} -> {}", path, backup_path);
+            }
+        }
 
-        let num_skipped = overwrite_skipped.len() + current_skipped.len() + messy_skipped.len();
         println!("Skipped {} files. Reasons:", num_skipped);
         if !current_skipped.is_empty() {
             println!(
@@ -1176,66 +3750,326 @@ impl DataCollection {
                 println!("   - {:}", path);
             }
         }
-        if !overwrite_skipped.is_empty() {
+        print_overwrite_skipped("pull", &overwrite_skipped);
+        print_messy_skipped(&messy_skipped);
+        print_duplicate_skipped(&duplicate_skipped);
+        print_resumed_skipped("pull", &resumed_skipped);
+        if !unavailable_skipped.is_empty() {
             println!(
-                "  Would overwrite (use --overwrite to push): {}",
-                pluralize(overwrite_skipped.len() as u64, "file")
+                "  No download URL / access denied: {}",
+                pluralize(unavailable_skipped.len() as u64, "file")
             );
-            for path in overwrite_skipped {
-                println!("   - {:}", path);
+            for (path, reason) in &unavailable_skipped {
+                println!("   - {:}: {}", path, reason);
             }
         }
-        if !messy_skipped.is_empty() {
-            println!(
-                "  Local is \"messy\" (manifest and file disagree): {}",
-                pluralize(messy_skipped.len() as u64, "file")
-            );
-            println!("  Use 'sdf update <FILE>' to add the current version to the manifest.");
-            for path in messy_skipped {
-                println!("   - {:}", path);
+        Ok(report)
+    }
+
+    // Delete the local copies of tracked files that are identical on their
+    // remote (RemoteStatusCode::Current), to free disk space. The manifest
+    // is not changed, so 'sdf pull' restores them. Files whose remote has
+    // no MD5 (RemoteStatusCode::Exists) are only deleted with force, and
+    // then only if the remote's size (when given) matches. With dry_run,
+    // the report lists what would be deleted, and nothing is.
+    pub async fn clean(
+        &mut self,
+        path_context: &Path,
+        force: bool,
+        dry_run: bool,
+        limit: &PathFilter,
+    ) -> Result<CleanReport> {
+        let all_files = self.merge(true).await?;
+        let mut report = CleanReport::default();
+        for (dir, merged_files) in all_files.iter() {
+            // skipped with a warning when fetching (see fetch_all())
+            if !self
+                .remotes
+                .get(dir)
+                .is_some_and(|remote| remote.is_supported())
+            {
+                continue;
+            }
+            let mut merged_files: Vec<_> = merged_files
+                .iter()
+                .filter(|(path_key, _)| limit.matches(path_key))
+                .collect();
+            merged_files.sort_by_key(|(path_key, _)| *path_key);
+            for (path_key, merged_file) in merged_files {
+                let local = match &merged_file.local {
+                    Some(local) => local,
+                    None => continue,
+                };
+                if !local.tracked {
+                    report.keep(path_key, "not tracked");
+                    continue;
+                }
+                // there's no MD5 to compare to the remote's, even with --force
+                if !local.is_hashed() {
+                    report.keep(path_key, "not hashed yet (run sdf update)");
+                    continue;
+                }
+                let remote_size = merged_file.remote.as_ref().and_then(|remote| remote.size);
+                let kept = match merged_file.status(path_context).await? {
+                    RemoteStatusCode::Current => None,
+                    RemoteStatusCode::Exists if !force => {
+                        Some("the remote has no MD5 to verify it (use --force)")
+                    }
+                    RemoteStatusCode::Exists => match remote_size {
+                        Some(size) if size != local.size => Some("the remote's size differs"),
+                        _ => None,
+                    },
+                    RemoteStatusCode::NotExists => Some("not on the remote"),
+                    RemoteStatusCode::Different => Some("differs from the remote's version"),
+                    RemoteStatusCode::MessyLocal => Some("changed since the manifest"),
+                    RemoteStatusCode::DuplicateRemote => Some("duplicated on the remote"),
+                    // already gone locally
+                    RemoteStatusCode::DeletedLocal | RemoteStatusCode::NoLocal => continue,
+                    RemoteStatusCode::Invalid => Some("invalid status"),
+                };
+                if let Some(reason) = kept {
+                    report.keep(path_key, reason);
+                    continue;
+                }
+                if !dry_run {
+                    let full_path = local.full_path(path_context)?;
+                    fs::remove_file(&full_path).map_err(|err| {
+                        anyhow!("Could not delete '{}': {}", full_path.display(), err)
+                    })?;
+                }
+                report.deleted.push(path_key.clone());
+                report.bytes += local.size;
             }
         }
+        Ok(report)
+    }
+}
+
+// The files a pull makes read-only once they are downloaded: those pulled
+// with readonly, and those that were read-only before being replaced.
+#[derive(Default)]
+struct ReadonlyPulls {
+    paths: Vec<PathBuf>,
+}
+
+impl ReadonlyPulls {
+    // Note a file about to be downloaded, making it writable if it is
+    // read-only so it can be replaced.
+    fn replacing(&mut self, path: &Path, readonly: bool) -> Result<()> {
+        let was_readonly = path.exists() && is_readonly(path)?;
+        if was_readonly {
+            set_readonly(path, false)?;
+        }
+        if readonly || was_readonly {
+            self.paths.push(path.to_path_buf());
+        }
+        Ok(())
+    }
 
+    // Make the downloaded files read-only.
+    fn apply(&self) -> Result<()> {
+        for path in self.paths.iter().filter(|path| path.exists()) {
+            set_readonly(path, true)?;
+        }
         Ok(())
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::lib::api::figshare::{FigShareAPI, FIGSHARE_BASE_URL};
-    use crate::lib::remote::Remote;
-    use crate::lib::test_utilities::check_error;
+// The report of a pull's downloads, given the (download filename,
+// manifest path) of each queued download and the (filename, error) of
+// those that failed.
+fn download_report(queued: &[(String, String)], failed: &[(String, String)]) -> TransferReport {
+    let mut report = TransferReport::default();
+    for (filename, path) in queued {
+        match failed.iter().find(|(name, _)| name == filename) {
+            Some((_, err)) => report.fail(path, err),
+            None => report.downloaded.push(path.clone()),
+        }
+    }
+    report
+}
 
-    use super::{DataCollection, DataFile};
-    use std::io::Write;
-    use std::path::Path;
-    use tempfile::NamedTempFile;
+/// A file skipped by a push or pull because it is "messy", i.e. the local
+/// file differs from the manifest. How to resolve it depends on which of
+/// the three MD5s agree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessyFile {
+    pub path: String,
+    pub manifest_md5: Option<String>,
+    pub local_md5: Option<String>,
+    // None if the remote doesn't report MD5s
+    pub remote_md5: Option<String>,
+}
 
-    fn mock_data_file() -> NamedTempFile {
-        let temp_file = NamedTempFile::new().unwrap();
-        temp_file
+impl MessyFile {
+    pub async fn new(merged_file: &MergedFile, path_context: &Path) -> Result<Self> {
+        Ok(MessyFile {
+            path: merged_file.name()?,
+            manifest_md5: merged_file.manifest_md5(),
+            local_md5: merged_file.local_md5(path_context).await,
+            remote_md5: merged_file.remote_md5(),
+        })
     }
 
-    #[tokio::test]
-    async fn test_datafile_new_with_nonexistent_path() {
-        let nonexistent_path = "some/nonexistent/path".to_string();
-        let path_context = Path::new("");
+    fn skipped(&self) -> SkippedFile {
+        SkippedFile {
+            path: self.path.clone(),
+            reason: SkipReason::Messy,
+            detail: Some(self.hint()),
+        }
+    }
 
-        let result = DataFile::new(nonexistent_path, None, &path_context).await;
-        match result {
-            Ok(_) => assert!(false, "Expected an error, but got Ok"),
-            Err(err) => {
-                assert!(
-                    err.to_string().contains("does not exist"),
-                    "Unexpected error: {:?}",
-                    err
-                );
-            }
-        };
+    /// Whether the remote has the manifest's version, so only the local
+    /// file changed.
+    pub fn remote_matches_manifest(&self) -> bool {
+        self.remote_md5.is_some() && self.remote_md5 == self.manifest_md5
     }
 
-    #[tokio::test]
-    async fn test_md5() {
+    /// What state the file is in, and the commands that resolve it.
+    pub fn hint(&self) -> String {
+        let path = &self.path;
+        if self.remote_md5.is_some() && self.remote_md5 == self.local_md5 {
+            format!(
+                "local matches the remote, but the manifest is out of date; \
+                 run 'sdf update {}'",
+                path
+            )
+        } else if self.remote_matches_manifest() {
+            format!(
+                "local changed since the manifest, which agrees with the remote; \
+                 run 'sdf update {0}' then 'sdf push --overwrite' to keep the local \
+                 changes, or delete it and run 'sdf pull' to discard them",
+                path
+            )
+        } else if self.remote_md5.is_some() {
+            format!(
+                "local, manifest, and remote all differ; run 'sdf update {}' then \
+                 'sdf push --overwrite' to replace the remote's version with the local one",
+                path
+            )
+        } else {
+            format!(
+                "local changed since the manifest (the remote has no MD5 to compare); \
+                 run 'sdf update {}' then 'sdf push --overwrite'",
+                path
+            )
+        }
+    }
+}
+
+fn print_messy_skipped(messy_skipped: &[MessyFile]) {
+    if messy_skipped.is_empty() {
+        return;
+    }
+    println!(
+        "  Local is \"messy\" (manifest and file disagree): {}",
+        pluralize(messy_skipped.len() as u64, "file")
+    );
+    for messy in messy_skipped {
+        println!("   - {}: {}", messy.path, messy.hint());
+    }
+}
+
+fn print_duplicate_skipped(duplicate_skipped: &[String]) {
+    if duplicate_skipped.is_empty() {
+        return;
+    }
+    println!(
+        "  Remote lists more than one file with this name (delete the extra copies): {}",
+        pluralize(duplicate_skipped.len() as u64, "file")
+    );
+    for path in duplicate_skipped {
+        println!("   - {:}", path);
+    }
+}
+
+// Files transferred by the interrupted push or pull being resumed are
+// only counted, since there may be many.
+fn print_resumed_skipped(operation: &str, resumed_skipped: &[String]) {
+    if resumed_skipped.is_empty() {
+        return;
+    }
+    println!(
+        "  Already transferred by the interrupted {} (resumed): {}",
+        operation,
+        pluralize(resumed_skipped.len() as u64, "file")
+    );
+}
+
+// Compare an uploaded file to the remote's listing of it, returning a
+// description of the problem if they disagree. MD5s are only compared
+// when the remote reports one.
+fn upload_mismatch(data_file: &DataFile, remote_file: Option<&RemoteFile>) -> Option<String> {
+    let remote_file = match remote_file {
+        Some(remote_file) => remote_file,
+        None => return Some("missing from the remote listing".to_string()),
+    };
+    if let Some(size) = remote_file.size {
+        if size != data_file.size {
+            return Some(format!(
+                "remote size is {} bytes, local is {} bytes",
+                size, data_file.size
+            ));
+        }
+    }
+    match &remote_file.md5 {
+        Some(md5) if !md5.is_empty() && md5 != &data_file.md5 => {
+            Some(format!("remote MD5 is {}, local is {}", md5, data_file.md5))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lib::api::figshare::{FigShareAPI, FIGSHARE_BASE_URL};
+    use crate::lib::api::test_remote::TestRemote;
+    use crate::lib::api::zenodo::ZenodoAPI;
+    use crate::lib::archive::{build_archive, Archive};
+    use crate::lib::filter::PathFilter;
+    use crate::lib::remote::{Remote, RemoteFile, RemoteStatusCode, UploadOutcome};
+    use crate::lib::sync_state::SyncState;
+    use crate::lib::test_utilities::check_error;
+
+    use super::{
+        pull_decision, push_decision, upload_mismatch, ChangedSide, DataCollection, DataFile,
+        LocalStatusCode, MergedFile, MessyFile, PullOptions, PushOptions, Reconciliation,
+        RemoteFailure, SkipReason, SkippedFile, StatusEntry, TransferDecision, TransferReport,
+        NOT_AUTHENTICATED,
+    };
+    use chrono::prelude::*;
+    use httpmock::prelude::*;
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, HashMap};
+    use std::fs;
+    use std::io::Write;
+    use std::path::Path;
+    use tempfile::{tempdir, NamedTempFile};
+
+    fn mock_data_file() -> NamedTempFile {
+        NamedTempFile::new().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_datafile_new_with_nonexistent_path() {
+        let nonexistent_path = "some/nonexistent/path".to_string();
+        let path_context = Path::new("");
+
+        let result = DataFile::new(nonexistent_path, None, path_context).await;
+        match result {
+            Ok(_) => panic!("Expected an error, but got Ok"),
+            Err(err) => {
+                assert!(
+                    err.to_string().contains("does not exist"),
+                    "Unexpected error: {:?}",
+                    err
+                );
+            }
+        };
+    }
+
+    #[tokio::test]
+    async fn test_md5() {
         let path_context = Path::new("");
         let mut file = mock_data_file();
 
@@ -1244,11 +4078,11 @@ mod tests {
 
         // Make a DataFile
         let path = file.path().to_string_lossy().to_string();
-        let data_file = DataFile::new(path, None, &path_context).await.unwrap();
+        let data_file = DataFile::new(path, None, path_context).await.unwrap();
 
         // Compare MD5s
         let expected_md5 = "d3feb335769173b2db573413b0f6abf4".to_string();
-        let observed_md5 = data_file.get_md5(&path_context).await.unwrap().unwrap();
+        let observed_md5 = data_file.get_md5(path_context).await.unwrap().unwrap();
         assert!(observed_md5 == expected_md5, "MD5 mismatch!");
     }
 
@@ -1262,7 +4096,7 @@ mod tests {
 
         // Make a DataFile
         let path = file.path().to_string_lossy().to_string();
-        let data_file = DataFile::new(path, None, &path_context).await.unwrap();
+        let data_file = DataFile::new(path, None, path_context).await.unwrap();
 
         // Let's also check size
         assert!(
@@ -1283,14 +4117,14 @@ mod tests {
 
         // Make a DataFile
         let path = file.path().to_string_lossy().to_string();
-        let mut data_file = DataFile::new(path, None, &path_context).await.unwrap();
+        let mut data_file = DataFile::new(path, None, path_context).await.unwrap();
 
         // Now, we change the data.
         writeln!(file, "Modified mock data.").unwrap();
 
         // Make sure the file MD5 is right
         let expected_md5 = "c6526ab1de615b49e53398ae5588bd00".to_string();
-        let observed_md5 = data_file.get_md5(&path_context).await.unwrap().unwrap();
+        let observed_md5 = data_file.get_md5(path_context).await.unwrap().unwrap();
         assert!(observed_md5 == expected_md5);
 
         // Make sure the old MD5 is in the DataFile
@@ -1315,7 +4149,7 @@ mod tests {
 
         // Make a DataFile
         let path = file.path().to_string_lossy().to_string();
-        let mut data_file = DataFile::new(path, None, &path_context).await.unwrap();
+        let mut data_file = DataFile::new(path, None, path_context).await.unwrap();
 
         // Now, we change the data.
         writeln!(file, "Modified mock data.").unwrap();
@@ -1326,6 +4160,243 @@ mod tests {
         assert!(data_file.size == 31, "DataFile.update_size() wrong!");
     }
 
+    #[test]
+    fn test_salvage() {
+        let contents = "\
+files:
+- path: ./data/a.tsv
+  tracked: true
+  md5: 60b725f10c9c85c70d97880dfe8191b3
+  size: 2
+- path: data/b.tsv
+  tracked: [broken]
+remotes:
+  data: !FigShareAPI
+    article_id: not-a-number
+metadata:
+  title: A project
+push_exclude:
+  data:
+  - '*.tmp'
+";
+        let (data, num_dropped, dropped_paths) = DataCollection::salvage(contents).unwrap();
+        assert_eq!(num_dropped, 2);
+        assert_eq!(dropped_paths, vec!["data/b.tsv"]);
+        assert_eq!(data.files.keys().collect::<Vec<_>>(), vec!["data/a.tsv"]);
+        assert!(data.remotes.is_empty());
+        assert_eq!(data.metadata.title.as_deref(), Some("A project"));
+        assert_eq!(data.push_exclude["data"], vec!["*.tmp"]);
+        assert!(DataCollection::salvage("files: [unclosed").is_err());
+    }
+
+    #[test]
+    fn test_push_decision() {
+        use RemoteStatusCode::*;
+        let transfer = TransferDecision::Transfer;
+        let skip = TransferDecision::Skip;
+        // (status, overwrite, decision)
+        let cases = [
+            (NotExists, false, transfer),
+            (NotExists, true, transfer),
+            (Current, false, skip(SkipReason::Current)),
+            (Current, true, skip(SkipReason::Current)),
+            (Exists, false, skip(SkipReason::Exists)),
+            (Exists, true, transfer),
+            (Different, false, skip(SkipReason::Exists)),
+            (Different, true, transfer),
+            (MessyLocal, false, skip(SkipReason::Messy)),
+            (MessyLocal, true, skip(SkipReason::Messy)),
+            (DuplicateRemote, true, skip(SkipReason::Duplicate)),
+            (NoLocal, true, TransferDecision::Nothing),
+            (DeletedLocal, true, TransferDecision::Nothing),
+            (Invalid, false, TransferDecision::Invalid),
+        ];
+        for (status, overwrite, decision) in cases {
+            assert_eq!(
+                push_decision(&status, overwrite),
+                decision,
+                "push of {:?} (overwrite: {})",
+                status,
+                overwrite
+            );
+        }
+    }
+
+    #[test]
+    fn test_pull_decision() {
+        use RemoteStatusCode::*;
+        let transfer = TransferDecision::Transfer;
+        let skip = TransferDecision::Skip;
+        // (status, replace, decision)
+        let cases = [
+            (NotExists, false, transfer),
+            (DeletedLocal, false, transfer),
+            (Current, true, skip(SkipReason::Current)),
+            (Exists, false, skip(SkipReason::Exists)),
+            (Exists, true, transfer),
+            (Different, false, skip(SkipReason::Exists)),
+            (Different, true, transfer),
+            (MessyLocal, false, skip(SkipReason::Messy)),
+            (MessyLocal, true, skip(SkipReason::Messy)),
+            (DuplicateRemote, true, skip(SkipReason::Duplicate)),
+            (NoLocal, false, TransferDecision::Invalid),
+            (Invalid, true, TransferDecision::Invalid),
+        ];
+        for (status, replace, decision) in cases {
+            assert_eq!(
+                pull_decision(&status, replace),
+                decision,
+                "pull of {:?} (replace: {})",
+                status,
+                replace
+            );
+        }
+    }
+
+    #[test]
+    fn test_transfer_decision_describe() {
+        use RemoteStatusCode::*;
+        let cases = [
+            (
+                push_decision(&Current, false),
+                Current,
+                "skip (identical remote)",
+            ),
+            (
+                pull_decision(&Different, false),
+                Different,
+                "skip (remote differs, requires --overwrite or --backup)",
+            ),
+            (
+                pull_decision(&Different, true),
+                Different,
+                "download (remote differs, with --overwrite or --backup)",
+            ),
+            (
+                push_decision(&Exists, false),
+                Exists,
+                "skip (remote has no MD5 to compare, requires --overwrite)",
+            ),
+            (
+                push_decision(&NotExists, false),
+                NotExists,
+                "upload (not on the remote)",
+            ),
+            (
+                push_decision(&DeletedLocal, false),
+                DeletedLocal,
+                "nothing (deleted locally)",
+            ),
+            (
+                TransferDecision::Skip(SkipReason::Untracked),
+                NotExists,
+                "skip (not tracked; see 'sdf track')",
+            ),
+        ];
+        for (decision, status, expected) in cases {
+            let verb = if expected.starts_with("download") || expected.contains("--backup") {
+                "download"
+            } else {
+                "upload"
+            };
+            assert_eq!(decision.describe(verb, &status), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_status_size_changed() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::write(path_context.join("a.tsv"), "a\n").unwrap();
+        let mut dc = DataCollection::new();
+        let data_file = DataFile::new("a.tsv".to_string(), None, path_context)
+            .await
+            .unwrap();
+        // the manifest's size is wrong, though its MD5 is right
+        dc.register(DataFile {
+            size: 3,
+            ..data_file
+        })
+        .unwrap();
+
+        let data_file = &dc.files["a.tsv"];
+        assert!(data_file.is_size_changed(path_context));
+        assert!(data_file.is_changed(path_context).await.unwrap());
+        let statuses = dc.status(path_context, false).await.unwrap();
+        let entry = &statuses[""][0];
+        assert_eq!(entry.local_status, Some(LocalStatusCode::Modified));
+        assert!(entry.size_changed());
+        assert_eq!(entry.columns(None, true)[1], "size changed");
+
+        // 'sdf update' refreshes the size, and the MD5 is unchanged
+        assert!(!dc.update(None, path_context).await.unwrap());
+        assert_eq!(dc.files["a.tsv"].size, 2);
+        let statuses = dc.status(path_context, false).await.unwrap();
+        assert_eq!(statuses[""][0].local_status, Some(LocalStatusCode::Current));
+
+        // a file whose contents changed is still just changed
+        fs::write(path_context.join("a.tsv"), "abc\n").unwrap();
+        let statuses = dc.status(path_context, false).await.unwrap();
+        assert!(!statuses[""][0].size_changed());
+        assert_eq!(statuses[""][0].columns(None, true)[1], "changed");
+    }
+
+    #[tokio::test]
+    async fn test_register_provenance() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::write(path_context.join("local.tsv"), "a\n").unwrap();
+        fs::write(path_context.join("fetched.tsv"), "b\n").unwrap();
+        let url = "https://example.com/fetched.tsv".to_string();
+
+        let mut dc = DataCollection::new();
+        let local = DataFile::new("local.tsv".to_string(), None, path_context)
+            .await
+            .unwrap();
+        dc.register(local).unwrap();
+        let fetched = DataFile::new("fetched.tsv".to_string(), Some(&url), path_context)
+            .await
+            .unwrap();
+        dc.register(fetched).unwrap();
+
+        let local = dc.files.get("local.tsv").unwrap();
+        assert_eq!(local.source.as_deref(), Some("local"));
+        assert!(local.added_at.is_some());
+        assert!(local.added_by.is_none());
+        let fetched = dc.files.get("fetched.tsv").unwrap();
+        assert_eq!(fetched.source.as_ref(), Some(&url));
+
+        // manifests written before provenance was tracked still load
+        let old: DataFile = serde_yaml::from_str(
+            "path: old.tsv\ntracked: true\nmd5: 00000000000000000000000000000000\nsize: 2\n",
+        )
+        .unwrap();
+        assert!(old.source.is_none() && old.added_at.is_none() && old.added_by.is_none());
+        let yaml = serde_yaml::to_string(&old).unwrap();
+        assert!(!yaml.contains("source") && !yaml.contains("added_"));
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+    async fn test_status_many_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path_context = dir.path();
+        let mut dc = DataCollection::new();
+        let num_files = 64;
+        for i in 0..num_files {
+            let name = format!("file_{}.tsv", i);
+            std::fs::write(path_context.join(&name), format!("data {}\n", i)).unwrap();
+            let data_file = DataFile::new(name, None, path_context).await.unwrap();
+            dc.register(data_file).unwrap();
+        }
+
+        let statuses = dc.status(path_context, false).await.unwrap();
+        let entries: Vec<_> = statuses.values().flatten().collect();
+        assert_eq!(entries.len(), num_files);
+        assert!(entries
+            .iter()
+            .all(|entry| entry.local_status == Some(LocalStatusCode::Current)));
+    }
+
     #[test]
     fn test_register_remote_figshare() {
         let mut dc = DataCollection::new();
@@ -1354,4 +4425,2668 @@ mod tests {
         let result = dc.register_remote(&dir, Remote::FigShareAPI(figshare));
         check_error(result, "already tracked");
     }
+
+    #[test]
+    fn test_register_remote_normalizes_directory() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/supplement")).unwrap();
+        fs::write(path_context.join("data/supplement/file.tsv"), "a\tb\n").unwrap();
+
+        for spelling in ["./data/supplement/", "data/supplement"] {
+            let mut dc = DataCollection::new();
+            let figshare =
+                FigShareAPI::new("Test remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+            dc.register_remote(spelling, Remote::FigShareAPI(figshare))
+                .unwrap();
+            assert!(dc.remotes.contains_key("data/supplement"));
+
+            // the other spelling refers to the same directory
+            let figshare =
+                FigShareAPI::new("Another remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+            let result = dc.register_remote("data/supplement/", Remote::FigShareAPI(figshare));
+            check_error(result, "already tracked");
+
+            dc.files.insert(
+                "data/supplement/file.tsv".to_string(),
+                DataFile {
+                    path: "data/supplement/file.tsv".to_string(),
+                    tracked: false,
+                    md5: "".to_string(),
+                    size: 4,
+                    urls: Vec::new(),
+                    source: None,
+                    added_at: None,
+                    added_by: None,
+                    remote_md5: None,
+                },
+            );
+            dc.track_file("./data/supplement/file.tsv", path_context)
+                .unwrap();
+            assert!(dc.files["data/supplement/file.tsv"].tracked);
+        }
+    }
+
+    #[test]
+    fn test_replace_remote() {
+        let mut dc = DataCollection::new();
+        let figshare =
+            FigShareAPI::new("Test remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+        dc.register_remote("data/supplement", Remote::FigShareAPI(figshare))
+            .unwrap();
+        dc.files.insert(
+            "data/supplement/file.tsv".to_string(),
+            DataFile {
+                path: "data/supplement/file.tsv".to_string(),
+                tracked: true,
+                md5: "".to_string(),
+                size: 4,
+                urls: Vec::new(),
+                source: None,
+                added_at: None,
+                added_by: None,
+                remote_md5: None,
+            },
+        );
+
+        let zenodo = ZenodoAPI::new("Test remote", Some("http://localhost".to_string())).unwrap();
+        let old = dc
+            .replace_remote("./data/supplement/", Remote::ZenodoAPI(zenodo))
+            .unwrap();
+        assert_eq!(old.name(), "FigShare");
+        assert_eq!(dc.remotes["data/supplement"].name(), "Zenodo");
+        assert!(dc.files["data/supplement/file.tsv"].tracked);
+
+        let zenodo = ZenodoAPI::new("Test remote", Some("http://localhost".to_string())).unwrap();
+        let result = dc.replace_remote("data/raw", Remote::ZenodoAPI(zenodo));
+        check_error(result, "not linked");
+    }
+
+    // After a relink, the MD5s last seen on the old remote aren't compared
+    // against the new remote's.
+    #[tokio::test]
+    async fn test_status_after_relink() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7/files");
+            then.status(200).json_body(json!([{
+                "checksum": format!("{:x}", md5::compute("zenodo\n")),
+                "filename": "a.tsv", "filesize": 7, "id": "1", "links": {}
+            }]));
+        });
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/supplement")).unwrap();
+        fs::write(path_context.join("data/supplement/a.tsv"), "a\n").unwrap();
+        let mut dc = mock_manifest(
+            &server,
+            &[("data/supplement/a.tsv", "a\n", true)],
+            &[("data/supplement", FIGSHARE_REMOTE)],
+            "",
+        );
+        // last synced with FigShare
+        let local_md5 = format!("{:x}", md5::compute("a\n"));
+        dc.files
+            .get_mut("data/supplement/a.tsv")
+            .unwrap()
+            .remote_md5 = Some(local_md5);
+
+        let mut zenodo: Remote = serde_yaml::from_str(&zenodo_remote(&server, 7)).unwrap();
+        zenodo.set_base_url(server.url("")).unwrap();
+        zenodo.set_token("secret".to_string()).unwrap();
+        dc.replace_remote("data/supplement", zenodo).unwrap();
+        assert_eq!(dc.files["data/supplement/a.tsv"].remote_md5, None);
+
+        let statuses = dc.status(path_context, true).await.unwrap();
+        let entry = &statuses["data/supplement"][0];
+        assert_eq!(entry.remote_status, Some(RemoteStatusCode::Different));
+        assert_eq!(entry.last_remote_md5, None);
+        // not "changed on the remote", from comparing with FigShare's MD5
+        assert_eq!(entry.changed_side(), None);
+    }
+
+    #[test]
+    fn test_deserialize_normalizes_keys() {
+        let mut dc = DataCollection::new();
+        let figshare =
+            FigShareAPI::new("Test remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+        // bypass register_remote() to mimic a manifest from an older version
+        dc.remotes.insert(
+            "./data/supplement/".to_string(),
+            Remote::FigShareAPI(figshare),
+        );
+        let yaml = serde_yaml::to_string(&dc).unwrap();
+
+        let loaded: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        assert!(loaded.remotes.contains_key("data/supplement"));
+        assert_eq!(loaded.remotes.len(), 1);
+
+        // two spellings of the same directory cannot be merged
+        let figshare =
+            FigShareAPI::new("Other remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+        dc.remotes
+            .insert("data/supplement".to_string(), Remote::FigShareAPI(figshare));
+        let yaml = serde_yaml::to_string(&dc).unwrap();
+        let result: Result<DataCollection, _> = serde_yaml::from_str(&yaml);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_zenodo_doi_round_trip() {
+        let manifest = |doi: &str| {
+            format!(
+                "files: []\n\
+                 remotes:\n  \
+                   data/supplement: !ZenodoAPI\n    \
+                     name: Test remote\n    \
+                     deposition_id: 1234\n    \
+                     bucket_url: https://zenodo.org/api/files/abc\n{}\
+                 metadata:\n  \
+                   title: null\n",
+                doi
+            )
+        };
+        let yaml = manifest("    doi: 10.5281/zenodo.1234\n");
+        let dc: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(
+            dc.remotes["data/supplement"].doi(),
+            Some("10.5281/zenodo.1234")
+        );
+        let saved = serde_yaml::to_string(&dc).unwrap();
+        assert!(saved.contains("doi: 10.5281/zenodo.1234"), "{}", saved);
+        let reloaded: DataCollection = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.remotes, dc.remotes);
+
+        // manifests from before DOIs were stored still load, and stay
+        // without one
+        let dc: DataCollection = serde_yaml::from_str(&manifest("")).unwrap();
+        assert_eq!(dc.remotes["data/supplement"].doi(), None);
+        assert!(!serde_yaml::to_string(&dc).unwrap().contains("doi"));
+    }
+
+    // A manifest with a remote from a service this version doesn't know,
+    // as a newer sdf would write it.
+    const UNKNOWN_REMOTE_MANIFEST: &str = "\
+files:
+- path: data/future/a.tsv
+  tracked: true
+  md5: 60b725f10c9c85c70d97880dfe8191b3
+  size: 2
+  url: null
+remotes:
+  data/future: !FutureAPI
+    name: Future remote
+    record:
+      id: 42
+      tags:
+      - raw
+      - yes
+    endpoint: null
+metadata:
+  title: null
+  description: null
+";
+
+    #[test]
+    fn test_unknown_remote_round_trip() {
+        let dc: DataCollection = serde_yaml::from_str(UNKNOWN_REMOTE_MANIFEST).unwrap();
+        let remote = &dc.remotes["data/future"];
+        assert!(!remote.is_supported());
+        assert_eq!(remote.name(), "FutureAPI");
+        assert_eq!(serde_yaml::to_string(&dc).unwrap(), UNKNOWN_REMOTE_MANIFEST);
+
+        // known remotes that fail to deserialize are still errors
+        let broken = UNKNOWN_REMOTE_MANIFEST
+            .replace("!FutureAPI", "!FigShareAPI")
+            .replace("name: Future remote", "article_id: not-a-number");
+        assert!(serde_yaml::from_str::<DataCollection>(&broken).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_remote_skipped() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/future")).unwrap();
+        fs::write(path_context.join("data/future/a.tsv"), "a\n").unwrap();
+        let mut dc: DataCollection = serde_yaml::from_str(UNKNOWN_REMOTE_MANIFEST).unwrap();
+
+        let (fetched, failures) = dc.fetch_all().await.unwrap();
+        assert!(fetched.is_empty());
+        assert!(failures.is_empty());
+
+        // nothing is uploaded, and push succeeds
+        let summary = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(summary, TransferReport::default());
+        dc.pull(path_context, &PathFilter::new(), &PullOptions::default())
+            .await
+            .unwrap();
+        assert!(dc.verify_remote(path_context).await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_report_json() {
+        let mut report = TransferReport {
+            uploaded: vec!["data/a.tsv".to_string()],
+            ..Default::default()
+        };
+        report.skip(
+            &["data/b.tsv".to_string(), "data/c.tsv".to_string()],
+            SkipReason::Current,
+        );
+        report.skipped.push(SkippedFile {
+            path: "data/d.tsv".to_string(),
+            reason: SkipReason::Messy,
+            detail: Some("run 'sdf update data/d.tsv'".to_string()),
+        });
+        report.fail("data/e.tsv", "access denied");
+        assert_eq!(
+            report.skipped_for(SkipReason::Current),
+            vec!["data/b.tsv", "data/c.tsv"]
+        );
+        check_error(report.failures("pushed"), "1 file could not be pushed.");
+
+        let value: serde_json::Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(
+            value["totals"],
+            json!({ "uploaded": 1, "downloaded": 0, "skipped": 3, "failed": 1 })
+        );
+        assert_eq!(
+            value["skipped"][0],
+            json!({ "path": "data/b.tsv", "reason": "current" })
+        );
+        assert_eq!(value["skipped"][2]["reason"], "messy");
+        assert_eq!(
+            value["failed"][0],
+            json!({ "path": "data/e.tsv", "reason": "access denied" })
+        );
+        assert!(TransferReport::default().failures("pushed").is_ok());
+    }
+
+    #[test]
+    fn test_manifest_hash() {
+        let data_file = |path: String| DataFile {
+            path,
+            tracked: false,
+            md5: "abc".to_string(),
+            size: 4,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        let paths: Vec<String> = (0..50).map(|i| format!("data/{}.tsv", i)).collect();
+
+        // the same files, registered in opposite orders (into maps with
+        // different iteration orders)
+        let mut forward = DataCollection::new();
+        let mut reverse = DataCollection::new();
+        for path in &paths {
+            forward.register(data_file(path.clone())).unwrap();
+        }
+        for path in paths.iter().rev() {
+            reverse.register(data_file(path.clone())).unwrap();
+        }
+        let mut sorted = paths.clone();
+        sorted.sort();
+        let saved: Vec<String> = reverse
+            .to_minimal()
+            .files
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        assert_eq!(saved, sorted);
+        // (their added_at times differ, which the hash leaves out)
+        let hash = forward.manifest_hash().unwrap();
+        assert_eq!(hash.len(), 64);
+        assert_eq!(reverse.manifest_hash().unwrap(), hash);
+
+        // provenance is left out, but content changes are not
+        let data_file = reverse.files.get_mut("data/7.tsv").unwrap();
+        data_file.added_at = Some(Utc::now());
+        data_file.added_by = Some("Joan B. Scientist".to_string());
+        assert_eq!(reverse.manifest_hash().unwrap(), hash);
+        reverse.files.get_mut("data/7.tsv").unwrap().md5 = "abd".to_string();
+        assert_ne!(reverse.manifest_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn test_url_list_serialization() {
+        let manifest = |url: &str| {
+            format!(
+                "files:\n- path: data/a.tsv\n  tracked: false\n  md5: abc\n  size: 1\n  url:{}\n\
+                 remotes: {{}}\nmetadata:\n  title: null\n  description: null\n",
+                url
+            )
+        };
+        let urls = |url: &str| -> Vec<String> {
+            let dc: DataCollection = serde_yaml::from_str(&manifest(url)).unwrap();
+            let urls = dc.files["data/a.tsv"].urls.clone();
+            // the manifest is saved as it was loaded
+            assert_eq!(serde_yaml::to_string(&dc).unwrap(), manifest(url));
+            urls
+        };
+        assert!(urls(" null").is_empty());
+        assert_eq!(urls(" https://a.org/a.tsv"), vec!["https://a.org/a.tsv"]);
+        assert_eq!(
+            urls("\n  - https://a.org/a.tsv\n  - https://b.org/a.tsv"),
+            vec!["https://a.org/a.tsv", "https://b.org/a.tsv"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_equivalent_path_keys() {
+        let data_file = |path: &str| DataFile {
+            path: path.to_string(),
+            tracked: false,
+            md5: "".to_string(),
+            size: 4,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        let mut dc = DataCollection::new();
+        dc.register(data_file("./data/x.tsv")).unwrap();
+        assert!(dc.files.contains_key("data/x.tsv"));
+        for spelling in [
+            "data/x.tsv",
+            "data//x.tsv",
+            "data/raw/../x.tsv",
+            "./data/./x.tsv",
+        ] {
+            assert!(dc.register(data_file(spelling)).is_err(), "{}", spelling);
+            assert!(
+                dc.contains(spelling, Path::new(".")).await.unwrap(),
+                "{}",
+                spelling
+            );
+        }
+        assert_eq!(dc.files.len(), 1);
+
+        dc.files.get_mut("data/x.tsv").unwrap().tracked = true;
+        dc.untrack_file(&"./data/x.tsv".to_string()).unwrap();
+        assert!(!dc.files["data/x.tsv"].tracked);
+        assert!(dc.remove("data/raw/../x.tsv").await);
+        assert!(dc.files.is_empty());
+    }
+
+    #[test]
+    fn test_track_untrack_errors() {
+        let dir = tempdir().unwrap();
+        let mut dc = DataCollection::new();
+        let figshare =
+            FigShareAPI::new("Test remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+        dc.register_remote("data", Remote::FigShareAPI(figshare))
+            .unwrap();
+
+        // unregistered files point to 'sdf add' (not 'sdf track')
+        let err = dc.track_file("data/x.tsv", dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Data file 'data/x.tsv' is not in the data manifest. Add it first using:\n  $ sdf add data/x.tsv"
+        );
+        let err = dc.untrack_file(&"data/x.tsv".to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Cannot untrack data file 'data/x.tsv' since it is not in the data manifest."
+        );
+
+        // files outside linked directories can be untracked, but not tracked
+        dc.files.insert(
+            "other/y.tsv".to_string(),
+            DataFile {
+                path: "other/y.tsv".to_string(),
+                tracked: true,
+                md5: "".to_string(),
+                size: 4,
+                urls: Vec::new(),
+                source: None,
+                added_at: None,
+                added_by: None,
+                remote_md5: None,
+            },
+        );
+        dc.untrack_file(&"other/y.tsv".to_string()).unwrap();
+        let err = dc.untrack_file(&"other/y.tsv".to_string()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "file 'other/y.tsv' is already not tracked on remote."
+        );
+        let err = dc.track_file("other/y.tsv", dir.path()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Directory 'other' is not registered in remotes."
+        );
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let data_file = |path: &str| DataFile {
+            path: path.to_string(),
+            tracked: false,
+            md5: "".to_string(),
+            size: 4,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        let mut dc = DataCollection::new();
+        // bypass register() to construct a colliding manifest directly
+        for path in ["data/results.tsv", "data/Results.tsv", "data/other.tsv"] {
+            dc.files.insert(path.to_string(), data_file(path));
+        }
+        assert!(dc.check_case_collisions(false).is_ok());
+        let result = dc.check_case_collisions(true);
+        check_error(result, "differ only by case");
+
+        dc.files.remove("data/Results.tsv");
+        assert!(dc.check_case_collisions(true).is_ok());
+        let result = dc.check_case_collision("DATA/results.tsv", true);
+        check_error(result, "differs only by case from 'data/results.tsv'");
+        assert!(dc.check_case_collision("DATA/results.tsv", false).is_ok());
+        assert!(dc.check_case_collision("data/results.tsv", true).is_ok());
+    }
+
+    #[test]
+    fn test_upload_mismatch() {
+        let data_file = DataFile {
+            path: "data/raw/counts.tsv".to_string(),
+            tracked: true,
+            md5: "d3feb335769173b2db573413b0f6abf4".to_string(),
+            size: 11,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        let remote_file = |md5: &str, size| RemoteFile {
+            name: "counts.tsv".to_string(),
+            md5: Some(md5.to_string()),
+            size: Some(size),
+            remote_service: "FigShare".to_string(),
+            url: None,
+            mod_time: None,
+            duplicate_ids: Vec::new(),
+        };
+
+        let same = remote_file(&data_file.md5, 11);
+        assert_eq!(upload_mismatch(&data_file, Some(&same)), None);
+        // FigShare reports an empty MD5 until it has computed one
+        let pending = remote_file("", 11);
+        assert_eq!(upload_mismatch(&data_file, Some(&pending)), None);
+
+        let truncated = remote_file(&data_file.md5, 4);
+        assert!(upload_mismatch(&data_file, Some(&truncated))
+            .unwrap()
+            .contains("size"));
+        let corrupted = remote_file("00000000000000000000000000000000", 11);
+        assert!(upload_mismatch(&data_file, Some(&corrupted))
+            .unwrap()
+            .contains("MD5"));
+        assert!(upload_mismatch(&data_file, None)
+            .unwrap()
+            .contains("missing"));
+    }
+
+    #[test]
+    fn test_record_files() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/raw")).unwrap();
+        fs::create_dir_all(path_context.join("data/supplement")).unwrap();
+
+        let mut dc = DataCollection::new();
+        for linked in ["data/raw", "data/supplement"] {
+            let figshare =
+                FigShareAPI::new("Test remote", Some(FIGSHARE_BASE_URL.to_string())).unwrap();
+            dc.register_remote(linked, Remote::FigShareAPI(figshare))
+                .unwrap();
+        }
+
+        // no README anywhere
+        check_error(
+            dc.add_readme_record_files(path_context),
+            "No README.md found",
+        );
+
+        // the project README goes to data/supplement, which has none
+        fs::write(path_context.join("README.md"), "# Project\n").unwrap();
+        fs::write(path_context.join("data/raw/README.md"), "# Raw\n").unwrap();
+        let added = dc.add_readme_record_files(path_context).unwrap();
+        assert_eq!(added, vec!["README.md", "data/raw/README.md"]);
+        assert!(dc.add_readme_record_files(path_context).unwrap().is_empty());
+
+        dc.record_files.push("LICENSE".to_string());
+        assert_eq!(
+            dc.record_files_for("data/raw"),
+            vec!["data/raw/README.md", "LICENSE"]
+        );
+        assert_eq!(
+            dc.record_files_for("data/supplement"),
+            vec!["README.md", "LICENSE"]
+        );
+        assert_eq!(
+            dc.record_file_for("data/supplement", "README.md").unwrap(),
+            "README.md"
+        );
+
+        // record files round trip through the manifest
+        let yaml = serde_yaml::to_string(&dc).unwrap();
+        let loaded: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(loaded.record_files, dc.record_files);
+
+        // and are taken out of the status entries
+        let entry = |name: &str, local_status| StatusEntry {
+            name: name.to_string(),
+            local_status,
+            remote_status: Some(RemoteStatusCode::NoLocal),
+            tracked: None,
+            remote_service: Some("FigShare".to_string()),
+            local_md5: None,
+            remote_md5: Some("abc".to_string()),
+            manifest_md5: None,
+            local_mod_time: None,
+            remote_mod_time: None,
+            size: None,
+            local_size: None,
+            remote_unavailable: None,
+            last_remote_md5: None,
+        };
+        let mut statuses = BTreeMap::from([(
+            "data/raw".to_string(),
+            vec![entry("README.md", None), entry("other.tsv", None)],
+        )]);
+        let records = dc.take_record_statuses(&mut statuses);
+        assert_eq!(
+            records[&("data/raw".to_string(), "README.md".to_string())],
+            Some("abc".to_string())
+        );
+        assert_eq!(statuses["data/raw"].len(), 1);
+        assert_eq!(statuses["data/raw"][0].name, "other.tsv");
+    }
+
+    #[test]
+    fn test_remote_failure_unauthenticated() {
+        let err = anyhow::anyhow!("Expected Zenodo access token not found.\n\nIf you used...");
+        let failure = RemoteFailure::new("Zenodo", "data/raw", &err);
+        assert_eq!(failure.reason(), "Expected Zenodo access token not found.");
+        let failure = RemoteFailure::unauthenticated("Zenodo", "data/raw", &err);
+        assert_eq!(failure.reason(), NOT_AUTHENTICATED);
+
+        // files of unauthenticated remotes are shown with local status only
+        let entry = StatusEntry {
+            name: "data/raw/b.tsv".to_string(),
+            local_status: Some(LocalStatusCode::Current),
+            remote_status: None,
+            tracked: Some(true),
+            remote_service: None,
+            local_md5: Some("abc".to_string()),
+            remote_md5: None,
+            manifest_md5: Some("abc".to_string()),
+            local_mod_time: None,
+            remote_mod_time: None,
+            size: None,
+            local_size: None,
+            last_remote_md5: None,
+            remote_unavailable: Some(failure.reason().to_string()),
+        };
+        let columns = entry.columns(None, true);
+        assert_eq!(columns.last().unwrap(), NOT_AUTHENTICATED);
+    }
+
+    #[tokio::test]
+    async fn test_remote_usage() {
+        let server = MockServer::start();
+        let yaml = "files: []\n\
+             remotes:\n  \
+               data/supplement: !FigShareAPI\n    \
+                 article_id: 42\n    \
+                 name: Test remote\n\
+             metadata:\n  \
+               title: null\n";
+        let mut dc: DataCollection = serde_yaml::from_str(yaml).unwrap();
+        let remote = dc.remotes.get_mut("data/supplement").unwrap();
+        remote.set_base_url(server.url("")).unwrap();
+        remote.set_token("secret".to_string()).unwrap();
+
+        let file = |id: u64, name: &str, size: u64| {
+            json!({
+                "upload_token": "", "upload_url": "", "status": "available",
+                "preview_state": "", "viewer_type": "",
+                "is_attached_to_public_version": false, "id": id,
+                "name": name, "size": size, "is_link_only": false,
+                "download_url": "", "supplied_md5": "", "computed_md5": ""
+            })
+        };
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200)
+                .json_body(json!([file(1, "a.tsv", 1024), file(2, "b.tsv", 2048)]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account");
+            then.status(200)
+                .json_body(json!({ "quota": 10_240, "used_quota": 3_072 }));
+        });
+
+        let (usages, failures) = dc.remote_usage().await.unwrap();
+        assert!(failures.is_empty());
+        assert_eq!(usages.len(), 1);
+        let usage = &usages[0];
+        assert_eq!(usage.directory, "data/supplement");
+        assert_eq!((usage.num_files, usage.total_bytes), (2, 3072));
+        assert_eq!(usage.quota.as_ref().unwrap().remaining(), 7_168);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_many_remotes() {
+        // more remotes than are fetched at once, all on the same service
+        let server = MockServer::start();
+        let num_remotes = crate::lib::http::DEFAULT_REMOTE_CONCURRENCY as u64 + 4;
+        let mut dc = DataCollection::new();
+        for id in 1..=num_remotes {
+            server.mock(|when, then| {
+                when.method(GET)
+                    .path(format!("/account/articles/{}/files", id));
+                then.status(200)
+                    .delay(std::time::Duration::from_millis(100))
+                    .json_body(json!([{
+                        "upload_token": "", "upload_url": "", "status": "available",
+                        "preview_state": "", "viewer_type": "",
+                        "is_attached_to_public_version": false, "id": id,
+                        "name": format!("{}.tsv", id), "size": id, "is_link_only": false,
+                        "download_url": "", "supplied_md5": "", "computed_md5": ""
+                    }]));
+            });
+            let yaml = format!("!FigShareAPI\narticle_id: {}\nname: Test remote\n", id);
+            let mut remote: Remote = serde_yaml::from_str(&yaml).unwrap();
+            remote.set_base_url(server.url("")).unwrap();
+            remote.set_token("secret".to_string()).unwrap();
+            dc.remotes.insert(format!("data/{}", id), remote);
+        }
+
+        let (fetched, failures) = dc.fetch_all().await.unwrap();
+        assert!(failures.is_empty());
+        assert_eq!(fetched.len() as u64, num_remotes);
+        for id in 1..=num_remotes {
+            let files = &fetched[&("FigShare".to_string(), format!("data/{}", id))];
+            let names: Vec<_> = files.keys().cloned().collect();
+            assert_eq!(names, vec![format!("{}.tsv", id)]);
+            assert_eq!(files[&names[0]].size, Some(id));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_changed() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::write(path_context.join("a.tsv"), "a\n").unwrap();
+        fs::write(path_context.join("b.tsv"), "b\n").unwrap();
+        let mut dc = DataCollection::new();
+        for name in ["a.tsv", "b.tsv"] {
+            let data_file = DataFile::new(name.to_string(), None, path_context)
+                .await
+                .unwrap();
+            dc.register(data_file).unwrap();
+        }
+        let a = "a.tsv".to_string();
+
+        // rehashing unchanged files is not a change
+        assert!(!dc.update(Some(&a), path_context).await.unwrap());
+        assert!(!dc.update(None, path_context).await.unwrap());
+
+        fs::write(path_context.join("a.tsv"), "changed\n").unwrap();
+        assert!(dc.update(Some(&a), path_context).await.unwrap());
+        assert_eq!(dc.files["a.tsv"].size, 8);
+        assert!(!dc.update(Some(&a), path_context).await.unwrap());
+
+        fs::write(path_context.join("b.tsv"), "changed\n").unwrap();
+        assert!(dc.update(None, path_context).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_push_unhashed() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/supplement")).unwrap();
+        fs::write(path_context.join("data/supplement/a.tsv"), "a\n").unwrap();
+        let mut dc = DataCollection::new();
+        let mut data_file =
+            DataFile::unhashed("data/supplement/a.tsv".to_string(), path_context).unwrap();
+        assert!(!data_file.is_hashed());
+        assert_eq!(
+            data_file.status(path_context).await.unwrap(),
+            LocalStatusCode::Unhashed
+        );
+        data_file.tracked = true;
+        dc.register(data_file).unwrap();
+
+        let result = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await;
+        check_error(result, "1 file has not been hashed yet");
+
+        // hashing an unhashed file counts as a change
+        let changed = dc
+            .update(Some(&"data/supplement/a.tsv".to_string()), path_context)
+            .await
+            .unwrap();
+        assert!(changed);
+        assert!(dc.files["data/supplement/a.tsv"].is_hashed());
+        assert!(dc.check_hashed(|_| true).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_pull_overwrite_backup() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200)
+                .json_body(json!([figshare_file_json(&server, 1, "a.tsv", "remote\n")]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/files/1");
+            then.status(200).body("remote\n");
+        });
+
+        // the same local file, which differs from the remote's, pulled
+        // without --overwrite, with --backup, and with --overwrite
+        for (overwrite, backup) in [(false, false), (false, true), (true, false)] {
+            let dir = tempdir().unwrap();
+            let path_context = dir.path();
+            let supplement = path_context.join("data/supplement");
+            fs::create_dir_all(&supplement).unwrap();
+            fs::write(supplement.join("a.tsv"), "a\n").unwrap();
+            let mut dc = mock_manifest(
+                &server,
+                &[("data/supplement/a.tsv", "a\n", true)],
+                &[("data/supplement", FIGSHARE_REMOTE)],
+                "",
+            );
+
+            dc.pull(
+                path_context,
+                &PathFilter::new(),
+                &PullOptions {
+                    overwrite,
+                    backup,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+            let contents = fs::read_to_string(supplement.join("a.tsv")).unwrap();
+            let backups: Vec<_> = fs::read_dir(&supplement)
+                .unwrap()
+                .map(|entry| entry.unwrap().path())
+                .filter(|path| path.to_string_lossy().contains("a.tsv.local."))
+                .collect();
+            match (overwrite, backup) {
+                (false, false) => {
+                    assert_eq!(contents, "a\n");
+                    assert!(backups.is_empty());
+                }
+                (false, true) => {
+                    assert_eq!(contents, "remote\n");
+                    assert_eq!(backups.len(), 1);
+                    assert_eq!(fs::read_to_string(&backups[0]).unwrap(), "a\n");
+                }
+                _ => {
+                    assert_eq!(contents, "remote\n");
+                    assert!(backups.is_empty());
+                }
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_missing_download_url() {
+        let server = MockServer::start();
+        let listing: Vec<_> = ["a.tsv", "b.tsv", "c.tsv"]
+            .iter()
+            .enumerate()
+            .map(|(id, name)| {
+                let mut file =
+                    figshare_file_json(&server, id as u64, name, &format!("{}\n", &name[..1]));
+                // b.tsv is embargoed, so it has no download URL
+                if *name == "b.tsv" {
+                    file["download_url"] = Value::Null;
+                }
+                file
+            })
+            .collect();
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200).json_body(json!(listing));
+        });
+        for (id, contents) in [(0, "a\n"), (2, "c\n")] {
+            server.mock(|when, then| {
+                when.method(GET).path(format!("/files/{}", id));
+                then.status(200).body(contents);
+            });
+        }
+
+        // none of the files are local yet
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let supplement = path_context.join("data/supplement");
+        fs::create_dir_all(&supplement).unwrap();
+        let mut dc = mock_manifest(
+            &server,
+            &[
+                ("data/supplement/a.tsv", "a\n", true),
+                ("data/supplement/b.tsv", "b\n", true),
+                ("data/supplement/c.tsv", "c\n", true),
+            ],
+            &[("data/supplement", FIGSHARE_REMOTE)],
+            "",
+        );
+
+        // the other files still download, but the pull fails
+        let report = dc
+            .pull(path_context, &PathFilter::new(), &PullOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].path, "data/supplement/b.tsv");
+        let mut downloaded = report.downloaded.clone();
+        downloaded.sort();
+        assert_eq!(
+            downloaded,
+            vec!["data/supplement/a.tsv", "data/supplement/c.tsv"]
+        );
+        check_error(
+            report.failures("downloaded"),
+            "1 file could not be downloaded",
+        );
+        assert_eq!(fs::read_to_string(supplement.join("a.tsv")).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(supplement.join("c.tsv")).unwrap(), "c\n");
+        assert!(!supplement.join("b.tsv").exists());
+    }
+
+    #[test]
+    fn test_push_exclude() {
+        let yaml = "files: []\n\
+                    remotes:\n  \
+                      data/supplement: !FigShareAPI\n    \
+                        article_id: 42\n    \
+                        name: Test remote\n\
+                    metadata:\n  \
+                      title: null\n";
+        let mut dc: DataCollection = serde_yaml::from_str(yaml).unwrap();
+        check_error(dc.add_push_exclude("data/other", "*.bam"), "not linked");
+        check_error(
+            dc.add_push_exclude("data/supplement", "[*.bam"),
+            "Invalid pattern",
+        );
+        assert!(dc.add_push_exclude("data/supplement/", "*.bam").unwrap());
+        assert!(dc.add_push_exclude("./data/supplement", "tmp/**").unwrap());
+        assert!(!dc.add_push_exclude("data/supplement", "*.bam").unwrap());
+        assert_eq!(dc.push_exclude["data/supplement"], vec!["*.bam", "tmp/**"]);
+
+        // patterns are relative to the linked directory, and match nested paths
+        assert!(dc.push_excluded("data/supplement/a.bam").unwrap());
+        assert!(dc.push_excluded("data/supplement/sample/a.bam").unwrap());
+        assert!(dc.push_excluded("data/supplement/tmp/run/a.tsv").unwrap());
+        assert!(!dc.push_excluded("data/supplement/a.tsv").unwrap());
+        assert!(!dc
+            .push_excluded("data/supplement/sample/tmp/a.tsv")
+            .unwrap());
+        assert!(!dc.push_excluded("data/a.bam").unwrap());
+
+        let saved = serde_yaml::to_string(&dc).unwrap();
+        assert!(saved.contains("push_exclude:"), "{}", saved);
+        let reloaded: DataCollection = serde_yaml::from_str(&saved).unwrap();
+        assert_eq!(reloaded.push_exclude, dc.push_exclude);
+        assert!(!serde_yaml::to_string(&DataCollection::new())
+            .unwrap()
+            .contains("push_exclude"));
+    }
+
+    #[tokio::test]
+    async fn test_push_exclude_overwrite() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200)
+                .json_body(json!([figshare_file_json(&server, 1, "a.bam", "remote\n")]));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/files/1");
+            then.status(200).body("remote\n");
+        });
+        let upload = server.mock(|when, then| {
+            when.method(POST).path("/account/articles/42/files");
+            then.status(500);
+        });
+
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let supplement = path_context.join("data/supplement");
+        fs::create_dir_all(&supplement).unwrap();
+        fs::write(supplement.join("a.bam"), "a\n").unwrap();
+        let mut dc = mock_manifest(
+            &server,
+            &[("data/supplement/a.bam", "a\n", true)],
+            &[("data/supplement", FIGSHARE_REMOTE)],
+            "push_exclude:\n  data/supplement:\n  - '*.bam'\n",
+        );
+
+        // the remote's copy differs, but --overwrite doesn't push excluded files
+        let summary = dc
+            .push(
+                path_context,
+                &PathFilter::new(),
+                &PushOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            summary.skipped_for(SkipReason::Excluded),
+            vec!["data/supplement/a.bam"]
+        );
+        assert_eq!(summary.uploaded.len(), 0);
+        upload.assert_hits(0);
+
+        // pulls ignore the patterns
+        dc.pull(
+            path_context,
+            &PathFilter::new(),
+            &PullOptions {
+                overwrite: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            fs::read_to_string(supplement.join("a.bam")).unwrap(),
+            "remote\n"
+        );
+    }
+
+    // One file, data/a.tsv, in a directory linked to a TestRemote: its
+    // manifest entry (None if not in the manifest), its contents on disk
+    // (None if deleted), and the remote's copy (None if not uploaded).
+    fn test_remote_collection(
+        path_context: &Path,
+        manifest: Option<&str>,
+        local: Option<&str>,
+        remote_copy: Option<&str>,
+    ) -> (DataCollection, TestRemote) {
+        fs::create_dir_all(path_context.join("data")).unwrap();
+        if let Some(contents) = local {
+            fs::write(path_context.join("data/a.tsv"), contents).unwrap();
+        }
+        let remote = TestRemote::new("test");
+        if let Some(contents) = remote_copy {
+            remote.put_file("a.tsv", contents.as_bytes());
+        }
+        let mut dc = DataCollection::new();
+        dc.remotes
+            .insert("data".to_string(), Remote::Test(remote.clone()));
+        if let Some(contents) = manifest {
+            let data_file = DataFile {
+                path: "data/a.tsv".to_string(),
+                tracked: true,
+                md5: format!("{:x}", md5::compute(contents)),
+                size: contents.len() as u64,
+                urls: Vec::new(),
+                source: None,
+                added_at: None,
+                added_by: None,
+                remote_md5: None,
+            };
+            dc.files.insert(data_file.path.clone(), data_file);
+        }
+        (dc, remote)
+    }
+
+    // A FigShare listing's entry for a file, which downloads from the mock
+    // server at /files/<id>.
+    fn figshare_file_json(server: &MockServer, id: u64, name: &str, contents: &str) -> Value {
+        json!({
+            "upload_token": "", "upload_url": "", "status": "available",
+            "preview_state": "", "viewer_type": "",
+            "is_attached_to_public_version": false, "id": id,
+            "name": name, "size": contents.len(), "is_link_only": false,
+            "download_url": server.url(format!("/files/{}", id)),
+            "supplied_md5": "", "computed_md5": format!("{:x}", md5::compute(contents))
+        })
+    }
+
+    // The remote YAML of the FigShare article listed at
+    // /account/articles/42/files, for mock_manifest().
+    const FIGSHARE_REMOTE: &str = "!FigShareAPI\n    article_id: 42\n    name: Test remote";
+
+    // The remote YAML of a Zenodo deposition, for mock_manifest().
+    fn zenodo_remote(server: &MockServer, deposition_id: u64) -> String {
+        format!(
+            "!ZenodoAPI\n    \
+               name: Test remote\n    \
+               deposition_id: {}\n    \
+               bucket_url: {}",
+            deposition_id,
+            server.url("/files/bucket")
+        )
+    }
+
+    // A collection loaded from a manifest with the given files, each (path,
+    // contents when added, tracked), remotes, each (directory, YAML), and
+    // other top-level sections. The remotes use the mock server.
+    fn mock_manifest(
+        server: &MockServer,
+        files: &[(&str, &str, bool)],
+        remotes: &[(&str, &str)],
+        sections: &str,
+    ) -> DataCollection {
+        let mut yaml = String::from(if files.is_empty() {
+            "files: []\n"
+        } else {
+            "files:\n"
+        });
+        for (path, contents, tracked) in files {
+            yaml.push_str(&format!(
+                "- path: {}\n  tracked: {}\n  md5: {:x}\n  size: {}\n  url: null\n",
+                path,
+                tracked,
+                md5::compute(contents),
+                contents.len()
+            ));
+        }
+        yaml.push_str("remotes:\n");
+        for (directory, remote) in remotes {
+            yaml.push_str(&format!("  {}: {}\n", directory, remote));
+        }
+        yaml.push_str(sections);
+        yaml.push_str("metadata:\n  title: null\n");
+        let mut dc: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        for remote in dc.remotes.values_mut() {
+            remote.set_base_url(server.url("")).unwrap();
+            remote.set_token("secret".to_string()).unwrap();
+        }
+        dc
+    }
+
+    // The states of data/a.tsv (see test_remote_collection()) for each
+    // RemoteStatusCode a push or pull can see, and whether the remote
+    // lists MD5s.
+    #[allow(clippy::type_complexity)]
+    fn remote_status_cases() -> Vec<(
+        RemoteStatusCode,
+        Option<&'static str>,
+        Option<&'static str>,
+        Option<&'static str>,
+        bool,
+    )> {
+        vec![
+            (
+                RemoteStatusCode::NotExists,
+                Some("a\n"),
+                Some("a\n"),
+                None,
+                true,
+            ),
+            (
+                RemoteStatusCode::Current,
+                Some("a\n"),
+                Some("a\n"),
+                Some("a\n"),
+                true,
+            ),
+            (
+                RemoteStatusCode::Different,
+                Some("a\n"),
+                Some("a\n"),
+                Some("remote\n"),
+                true,
+            ),
+            (
+                RemoteStatusCode::Exists,
+                Some("a\n"),
+                Some("a\n"),
+                Some("remote\n"),
+                false,
+            ),
+            (
+                RemoteStatusCode::MessyLocal,
+                Some("a\n"),
+                Some("changed\n"),
+                Some("a\n"),
+                true,
+            ),
+            (
+                RemoteStatusCode::DeletedLocal,
+                Some("a\n"),
+                None,
+                Some("remote\n"),
+                true,
+            ),
+            (
+                RemoteStatusCode::NoLocal,
+                None,
+                None,
+                Some("remote\n"),
+                true,
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_tracking_suggestions() {
+        let (mut dc, _remote) =
+            test_remote_collection(tempdir().unwrap().path(), Some("a\n"), None, None);
+        let data_file = |path: &str, tracked: bool, size: u64| DataFile {
+            path: path.to_string(),
+            tracked,
+            md5: String::new(),
+            size,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        for file in [
+            data_file("data/big.tsv", false, 2000),
+            data_file("data/big_tracked.tsv", true, 2000),
+            data_file("data/README.md", true, 20),
+            data_file("other/big.tsv", false, 2000),
+        ] {
+            dc.files.insert(file.path.clone(), file);
+        }
+        // data/a.tsv is tracked, and 2 bytes
+        let (to_track, to_untrack) = dc.tracking_suggestions(1000);
+        let paths = |files: Vec<&DataFile>| -> Vec<String> {
+            files.iter().map(|file| file.path.clone()).collect()
+        };
+        assert_eq!(paths(to_track), vec!["data/big.tsv"]);
+        assert_eq!(paths(to_untrack), vec!["data/README.md", "data/a.tsv"]);
+    }
+
+    #[tokio::test]
+    async fn test_clean_remote_status() {
+        for (code, manifest, local, remote_copy, md5s) in remote_status_cases() {
+            for force in [false, true] {
+                let dir = tempdir().unwrap();
+                let path_context = dir.path();
+                let (mut dc, remote) =
+                    test_remote_collection(path_context, manifest, local, remote_copy);
+                remote.set_no_md5s(!md5s);
+                let path = path_context.join("data/a.tsv");
+
+                // only files identical on the remote, and with --force ones
+                // whose remote has no MD5 but the same size, are deleted
+                let deletes = match code {
+                    RemoteStatusCode::Current => true,
+                    RemoteStatusCode::Exists => {
+                        force && remote_copy.map(str::len) == local.map(str::len)
+                    }
+                    _ => false,
+                };
+                let report = dc
+                    .clean(path_context, force, true, &PathFilter::new())
+                    .await
+                    .unwrap();
+                assert_eq!(report.deleted.len(), deletes as usize, "{:?}", code);
+                assert_eq!(path.exists(), local.is_some(), "{:?}", code);
+
+                let report = dc
+                    .clean(path_context, force, false, &PathFilter::new())
+                    .await
+                    .unwrap();
+                assert_eq!(report.deleted.len(), deletes as usize, "{:?}", code);
+                assert_eq!(path.exists(), local.is_some() && !deletes, "{:?}", code);
+                // the manifest keeps deleted files, so they can be pulled again
+                assert_eq!(dc.files.contains_key("data/a.tsv"), manifest.is_some());
+            }
+        }
+
+        // a remote without MD5s, with a copy of the same size
+        let dir = tempdir().unwrap();
+        let (mut dc, remote) =
+            test_remote_collection(dir.path(), Some("a\n"), Some("a\n"), Some("b\n"));
+        remote.set_no_md5s(true);
+        let report = dc
+            .clean(dir.path(), false, false, &PathFilter::new())
+            .await
+            .unwrap();
+        assert!(report.deleted.is_empty());
+        assert_eq!(
+            report.kept,
+            [(
+                "data/a.tsv".to_string(),
+                "the remote has no MD5 to verify it (use --force)".to_string()
+            )]
+        );
+        let report = dc
+            .clean(dir.path(), true, false, &PathFilter::new())
+            .await
+            .unwrap();
+        assert_eq!(report.deleted, ["data/a.tsv"]);
+        assert_eq!(report.bytes, 2);
+        assert!(!dir.path().join("data/a.tsv").exists());
+
+        // a file added with --no-hash, whose remote copy has an MD5 and the
+        // same size, is kept even with --force
+        let dir = tempdir().unwrap();
+        let (mut dc, _remote) =
+            test_remote_collection(dir.path(), Some("a\n"), Some("b\n"), Some("a\n"));
+        dc.files.get_mut("data/a.tsv").unwrap().md5 = String::new();
+        let report = dc
+            .clean(dir.path(), true, false, &PathFilter::new())
+            .await
+            .unwrap();
+        assert!(report.deleted.is_empty());
+        assert_eq!(
+            report.kept,
+            [(
+                "data/a.tsv".to_string(),
+                "not hashed yet (run sdf update)".to_string()
+            )]
+        );
+        assert!(dir.path().join("data/a.tsv").exists());
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_remote() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let (mut dc, remote) =
+            test_remote_collection(path_context, Some("local"), Some("local"), Some("remote"));
+        remote.duplicate_listing("a.tsv");
+        let all_files = dc.merge(true).await.unwrap();
+        let merged = &all_files["data"]["data/a.tsv"];
+        assert_eq!(
+            merged.status(path_context).await.unwrap(),
+            RemoteStatusCode::DuplicateRemote
+        );
+
+        // neither copy is pushed over or pulled, even with --overwrite
+        let report = dc
+            .push(
+                path_context,
+                &PathFilter::new(),
+                &PushOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.skipped_for(SkipReason::Duplicate), ["data/a.tsv"]);
+        assert_eq!(remote.num_uploads(), 0);
+        let report = dc
+            .pull(
+                path_context,
+                &PathFilter::new(),
+                &PullOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.skipped_for(SkipReason::Duplicate), ["data/a.tsv"]);
+        assert!(report.downloaded.is_empty());
+        assert_eq!(
+            fs::read_to_string(path_context.join("data/a.tsv")).unwrap(),
+            "local"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_remote_status() {
+        for (code, manifest, local, remote_copy, md5s) in remote_status_cases() {
+            for overwrite in [false, true] {
+                let dir = tempdir().unwrap();
+                let path_context = dir.path();
+                let (mut dc, remote) =
+                    test_remote_collection(path_context, manifest, local, remote_copy);
+                remote.set_no_md5s(!md5s);
+                let all_files = dc.merge(true).await.unwrap();
+                let merged = &all_files["data"]["data/a.tsv"];
+                assert_eq!(merged.status(path_context).await.unwrap(), code);
+
+                let summary = dc
+                    .push(
+                        path_context,
+                        &PathFilter::new(),
+                        &PushOptions {
+                            overwrite,
+                            ..Default::default()
+                        },
+                    )
+                    .await
+                    .unwrap();
+                // only new files, and with --overwrite different ones, are uploaded
+                let uploads = match code {
+                    RemoteStatusCode::NotExists => true,
+                    RemoteStatusCode::Different | RemoteStatusCode::Exists => overwrite,
+                    _ => false,
+                };
+                assert_eq!(summary.uploaded.len(), uploads as usize, "{:?}", code);
+                assert_eq!(remote.num_uploads(), uploads as usize, "{:?}", code);
+                let expected = if uploads { local } else { remote_copy };
+                assert_eq!(
+                    remote.file("a.tsv"),
+                    expected.map(|contents| contents.as_bytes().to_vec()),
+                    "{:?}",
+                    code
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_remote_status() {
+        for (code, manifest, local, remote_copy, md5s) in remote_status_cases() {
+            for overwrite in [false, true] {
+                let dir = tempdir().unwrap();
+                let path_context = dir.path();
+                let (mut dc, remote) =
+                    test_remote_collection(path_context, manifest, local, remote_copy);
+                remote.set_no_md5s(!md5s);
+
+                dc.pull(
+                    path_context,
+                    &PathFilter::new(),
+                    &PullOptions {
+                        overwrite,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+                // deleted files are restored, and with --overwrite different
+                // ones are replaced; files not in the manifest are not pulled
+                let downloads = match code {
+                    RemoteStatusCode::DeletedLocal => true,
+                    RemoteStatusCode::Different | RemoteStatusCode::Exists => overwrite,
+                    _ => false,
+                };
+                let expected = if downloads { remote_copy } else { local };
+                let contents = fs::read_to_string(path_context.join("data/a.tsv")).ok();
+                assert_eq!(contents.as_deref(), expected, "{:?}", code);
+                assert_eq!(remote.num_uploads(), 0);
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_contains_resolved_path() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/v2")).unwrap();
+        fs::write(path_context.join("data/v2/a.tsv"), "a\n").unwrap();
+        std::os::unix::fs::symlink("v2", path_context.join("data/latest")).unwrap();
+        let mut dc = DataCollection::new();
+        dc.register(DataFile {
+            path: "data/latest/a.tsv".to_string(),
+            tracked: false,
+            md5: format!("{:x}", md5::compute("a\n")),
+            size: 2,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        })
+        .unwrap();
+
+        // the same file, spelled differently
+        for filename in [
+            "./data/latest/a.tsv",
+            "data/v2/a.tsv",
+            "data/v2/../v2/a.tsv",
+        ] {
+            assert!(
+                dc.contains(filename, path_context).await.unwrap(),
+                "{}",
+                filename
+            );
+            assert_eq!(
+                dc.registered_key(filename, path_context).as_deref(),
+                Some("data/latest/a.tsv")
+            );
+        }
+        // a different (or missing) file with the same name
+        fs::write(path_context.join("data/a.tsv"), "a\n").unwrap();
+        assert!(!dc.contains("data/a.tsv", path_context).await.unwrap());
+        assert!(!dc.contains("data/v1/a.tsv", path_context).await.unwrap());
+    }
+
+    // The changed side in data/a.tsv's status (see test_remote_collection()),
+    // and how 'sdf verify --remote' compares it.
+    async fn changed_side(
+        dc: &mut DataCollection,
+        path_context: &Path,
+    ) -> (Option<ChangedSide>, Reconciliation) {
+        let all_files = dc.merge(true).await.unwrap();
+        let entry = all_files["data"]["data/a.tsv"]
+            .status_entry(path_context, true)
+            .await
+            .unwrap();
+        let verified = dc.verify_remote(path_context).await.unwrap();
+        (entry.changed_side(), verified.into_keys().next().unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_remote_md5_changed_side() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let (mut dc, remote) =
+            test_remote_collection(path_context, Some("a\n"), Some("a\n"), Some("a\n"));
+        let path = "data/a.tsv".to_string();
+        let md5 = |contents: &str| format!("{:x}", md5::compute(contents));
+
+        // nothing recorded: no direction, and files not in linked
+        // directories are left alone
+        assert_eq!(changed_side(&mut dc, path_context).await.0, None);
+        let recorded = dc
+            .record_remote_md5s(&[path.clone(), "other.tsv".to_string()])
+            .await
+            .unwrap();
+        assert_eq!(recorded, 1);
+        assert_eq!(dc.files[&path].remote_md5, Some(md5("a\n")));
+
+        // changed on the remote
+        remote.put_file("a.tsv", b"remote\n");
+        assert_eq!(
+            changed_side(&mut dc, path_context).await,
+            (Some(ChangedSide::Remote), Reconciliation::RemoteDiffers)
+        );
+
+        // changed locally (and updated), with the remote as last seen
+        remote.put_file("a.tsv", b"a\n");
+        fs::write(path_context.join(&path), "b\n").unwrap();
+        dc.update(Some(&path), path_context).await.unwrap();
+        assert_eq!(
+            changed_side(&mut dc, path_context).await,
+            (Some(ChangedSide::Local), Reconciliation::LocalDiffers)
+        );
+
+        // changed on both
+        remote.put_file("a.tsv", b"remote\n");
+        assert_eq!(
+            changed_side(&mut dc, path_context).await.0,
+            Some(ChangedSide::Both)
+        );
+
+        // a push brings the last-seen MD5 up to date
+        dc.push(
+            path_context,
+            &PathFilter::new(),
+            &PushOptions {
+                overwrite: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(dc.files[&path].remote_md5, Some(md5("b\n")));
+        assert!(dc.has_remote_md5s(std::slice::from_ref(&path)));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_pull_readonly() {
+        use crate::lib::utils::is_readonly;
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let local = path_context.join("data/a.tsv");
+        let (mut dc, remote) = test_remote_collection(path_context, Some("a\n"), None, Some("a\n"));
+        dc.pull(
+            path_context,
+            &PathFilter::new(),
+            &PullOptions {
+                readonly: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(is_readonly(&local).unwrap());
+
+        // read-only files can be hashed and pushed
+        let data_file = dc.files.get("data/a.tsv").unwrap().clone();
+        assert_eq!(
+            data_file.status(path_context).await.unwrap(),
+            LocalStatusCode::Current
+        );
+        remote.delete_file("a.tsv").unwrap();
+        dc.push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(remote.file("a.tsv").unwrap(), b"a\n");
+
+        // a read-only file replaced with --overwrite stays read-only
+        remote.put_file("a.tsv", b"changed\n");
+        dc.pull(
+            path_context,
+            &PathFilter::new(),
+            &PullOptions {
+                overwrite: true,
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(fs::read_to_string(&local).unwrap(), "changed\n");
+        assert!(is_readonly(&local).unwrap());
+
+        // the directory's readonly_on_pull applies without --readonly
+        check_error(dc.set_readonly_on_pull("other", true), "not linked");
+        dc.set_readonly_on_pull("data/", true).unwrap();
+        assert!(dc.readonly_on_pull("data/a.tsv"));
+        fs::remove_file(&local).unwrap();
+        dc.pull(path_context, &PathFilter::new(), &PullOptions::default())
+            .await
+            .unwrap();
+        assert!(is_readonly(&local).unwrap());
+        dc.set_readonly_on_pull("data", false).unwrap();
+        assert!(dc.readonly_on_pull.is_empty());
+    }
+
+    #[test]
+    fn test_messy_file_hint() {
+        let messy = |manifest: &str, local: &str, remote: Option<&str>| MessyFile {
+            path: "data/a.tsv".to_string(),
+            manifest_md5: Some(manifest.to_string()),
+            local_md5: Some(local.to_string()),
+            remote_md5: remote.map(|md5| md5.to_string()),
+        };
+
+        // the manifest is behind local and remote
+        let file = messy("m", "l", Some("l"));
+        assert!(!file.remote_matches_manifest());
+        assert_eq!(
+            file.hint(),
+            "local matches the remote, but the manifest is out of date; \
+             run 'sdf update data/a.tsv'"
+        );
+
+        // only local changed
+        let file = messy("m", "l", Some("m"));
+        assert!(file.remote_matches_manifest());
+        assert!(file
+            .hint()
+            .contains("'sdf update data/a.tsv' then 'sdf push --overwrite'"));
+        assert!(file
+            .hint()
+            .contains("delete it and run 'sdf pull' to discard them"));
+
+        // all three differ
+        let file = messy("m", "l", Some("r"));
+        assert!(file
+            .hint()
+            .starts_with("local, manifest, and remote all differ"));
+        assert!(!file.hint().contains("sdf pull"));
+
+        // the remote has no MD5
+        let file = messy("m", "l", None);
+        assert!(!file.remote_matches_manifest());
+        assert!(file.hint().contains("no MD5"));
+        assert!(!file.hint().contains("sdf pull"));
+    }
+
+    #[tokio::test]
+    async fn test_push_test_remote_failures() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let (mut dc, remote) = test_remote_collection(path_context, Some("a\n"), Some("a\n"), None);
+        fs::write(path_context.join("data/b.tsv"), "b\n").unwrap();
+        dc.register(DataFile {
+            path: "data/b.tsv".to_string(),
+            tracked: true,
+            md5: format!("{:x}", md5::compute("b\n")),
+            size: 2,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        })
+        .unwrap();
+
+        // the second upload fails, after the first file was uploaded
+        remote.fail_upload(2);
+        let result = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await;
+        check_error(result, "Test upload of 'data/");
+        assert_eq!(remote.file_names().len(), 1);
+
+        // a failed listing stops the push before any uploads
+        remote.set_fail_listing(true);
+        let result = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await;
+        assert!(result.is_err());
+        assert_eq!(remote.num_uploads(), 2);
+
+        // once the remote works again, the rest is uploaded
+        remote.set_fail_listing(false);
+        let summary = dc
+            .push(
+                path_context,
+                &PathFilter::new(),
+                &PushOptions {
+                    verify: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(summary.uploaded.len(), 1);
+        assert_eq!(remote.file_names(), vec!["a.tsv", "b.tsv"]);
+    }
+
+    #[tokio::test]
+    async fn test_push_resume() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let (mut dc, remote) = test_remote_collection(path_context, Some("a\n"), Some("a\n"), None);
+        for name in ["b", "c"] {
+            let contents = format!("{}\n", name);
+            let path = format!("data/{}.tsv", name);
+            fs::write(path_context.join(&path), &contents).unwrap();
+            dc.register(DataFile {
+                path,
+                tracked: true,
+                md5: format!("{:x}", md5::compute(&contents)),
+                size: 2,
+                urls: Vec::new(),
+                source: None,
+                added_at: None,
+                added_by: None,
+                remote_md5: None,
+            })
+            .unwrap();
+        }
+
+        // the second upload fails, so the batch state has the first
+        remote.fail_upload(2);
+        let result = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await;
+        assert!(result.is_err());
+        let state = SyncState::resume(path_context, "push");
+        assert_eq!(state.completed.len(), 1);
+        let first = state.completed.keys().next().unwrap().clone();
+
+        // resuming skips the first file without checking it again
+        let report = dc
+            .push(
+                path_context,
+                &PathFilter::new(),
+                &PushOptions {
+                    resume: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            report.skipped_for(SkipReason::Resumed),
+            vec![first.as_str()]
+        );
+        assert_eq!(report.uploaded.len(), 2);
+        assert!(!report.uploaded.contains(&first));
+        assert_eq!(remote.file_names(), vec!["a.tsv", "b.tsv", "c.tsv"]);
+        // and the state is deleted once the push succeeds
+        assert!(!SyncState::path(path_context).exists());
+    }
+
+    #[tokio::test]
+    async fn test_pull_resume() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let (mut dc, remote) = test_remote_collection(path_context, Some("a\n"), None, Some("a\n"));
+        dc.register(DataFile {
+            path: "data/b.tsv".to_string(),
+            tracked: true,
+            md5: format!("{:x}", md5::compute("b\n")),
+            size: 2,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        })
+        .unwrap();
+        remote.put_slow_file("b.tsv", b"b\n", std::time::Duration::from_secs(30));
+
+        // the pull is interrupted (here, dropped) while b.tsv is still
+        // downloading; a.tsv was recorded as soon as it finished
+        let filter = PathFilter::new();
+        let options = PullOptions::default();
+        let pull = dc.pull(path_context, &filter, &options);
+        assert!(
+            tokio::time::timeout(std::time::Duration::from_secs(3), pull)
+                .await
+                .is_err()
+        );
+        let state = SyncState::resume(path_context, "pull");
+        assert_eq!(
+            state.completed.keys().collect::<Vec<_>>(),
+            vec!["data/a.tsv"]
+        );
+
+        // resuming skips a.tsv without checking it again
+        let _ = fs::remove_file(path_context.join("data/b.tsv"));
+        remote.put_file("b.tsv", b"b\n");
+        let report = dc
+            .pull(
+                path_context,
+                &PathFilter::new(),
+                &PullOptions {
+                    resume: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(report.skipped_for(SkipReason::Resumed), vec!["data/a.tsv"]);
+        assert_eq!(report.downloaded, vec!["data/b.tsv"]);
+        assert_eq!(
+            fs::read_to_string(path_context.join("data/b.tsv")).unwrap(),
+            "b\n"
+        );
+        assert!(!SyncState::path(path_context).exists());
+    }
+
+    #[tokio::test]
+    async fn test_remote_conflicts() {
+        // data/sub is linked, and inside data, which is linked too, so its
+        // files are on both remotes
+        let mut dc = DataCollection::new();
+        for (dir, prefix, a) in [("data", "sub/", "a\n"), ("data/sub", "", "a, changed\n")] {
+            let remote = TestRemote::new("test");
+            remote.put_file(&format!("{}a.tsv", prefix), a.as_bytes());
+            remote.put_file(&format!("{}b.tsv", prefix), b"b\n");
+            remote.put_file("README.md", dir.as_bytes());
+            dc.remotes.insert(dir.to_string(), Remote::Test(remote));
+        }
+        dc.record_files.push("data/README.md".to_string());
+        dc.record_files.push("data/sub/README.md".to_string());
+        // files with the same name in unrelated directories are different
+        // files
+        let other = TestRemote::new("test");
+        other.put_file("a.tsv", b"other\n");
+        dc.remotes
+            .insert("results".to_string(), Remote::Test(other));
+
+        let (fetched, _) = dc.fetch_all().await.unwrap();
+        let conflicts = dc.remote_conflicts(&fetched);
+        // only a.tsv differs; b.tsv matches, and READMEs are record files
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].path, "data/sub/a.tsv");
+        let directories: Vec<&str> = conflicts[0]
+            .copies
+            .iter()
+            .map(|(directory, _, _)| directory.as_str())
+            .collect();
+        assert_eq!(directories, vec!["data", "data/sub"]);
+        assert!(conflicts[0]
+            .message()
+            .starts_with("'data/sub/a.tsv' differs between remotes: 'data' (Test, MD5"));
+
+        // status returns them, rather than warning as it merges
+        let (_, conflicts) = dc.status_with(Path::new("."), true, false).await.unwrap();
+        assert_eq!(conflicts.len(), 1);
+
+        // a remote without an MD5 for its copy can't conflict
+        if let Some(Remote::Test(remote)) = dc.remotes.get("data/sub") {
+            remote.set_no_md5s(true);
+        }
+        let (fetched, _) = dc.fetch_all().await.unwrap();
+        assert!(dc.remote_conflicts(&fetched).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remote_gone() {
+        // a deleted Zenodo deposition and FigShare article
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7/files");
+            then.status(404)
+                .json_body(json!({"message": "PID does not exist.", "status": 404}));
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(404)
+                .json_body(json!({"message": "Entity not found: article"}));
+        });
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let paths = [
+            "data/zenodo/a.tsv",
+            "data/figshare/b.tsv",
+            "data/test/c.tsv",
+        ];
+        for path in paths {
+            fs::create_dir_all(path_context.join(path).parent().unwrap()).unwrap();
+            fs::write(path_context.join(path), path).unwrap();
+        }
+        let files: Vec<_> = paths.iter().map(|path| (*path, *path, true)).collect();
+        let mut dc = mock_manifest(
+            &server,
+            &files,
+            &[
+                ("data/zenodo", &zenodo_remote(&server, 7)),
+                ("data/figshare", FIGSHARE_REMOTE),
+            ],
+            "",
+        );
+        let test = TestRemote::new("test");
+        dc.remotes
+            .insert("data/test".to_string(), Remote::Test(test.clone()));
+
+        // status shows why, per directory
+        let statuses = dc.status(path_context, true).await.unwrap();
+        assert_eq!(
+            statuses["data/zenodo"][0].remote_unavailable.as_deref(),
+            Some(
+                "the linked Zenodo deposition 7 no longer exists; link 'data/zenodo' to a new \
+                 one with 'sdf link --relink data/zenodo zenodo <TOKEN>'"
+            )
+        );
+        let figshare = statuses["data/figshare"][0].remote_unavailable.clone();
+        assert!(
+            figshare.as_ref().is_some_and(
+                |reason| reason.starts_with("the linked FigShare article 42 no longer exists")
+            ),
+            "{:?}",
+            figshare
+        );
+
+        // push and pull skip these directories, but not the others
+        let report = dc
+            .push(path_context, &PathFilter::new(), &PushOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.uploaded, ["data/test/c.tsv"]);
+        assert_eq!(
+            report.skipped_for(SkipReason::RemoteGone),
+            ["data/figshare/b.tsv", "data/zenodo/a.tsv"]
+        );
+        assert_eq!(test.file_names(), ["c.tsv"]);
+        let report = dc
+            .pull(path_context, &PathFilter::new(), &PullOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(report.skipped_for(SkipReason::RemoteGone).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_test_remotes() {
+        // two Zenodo depositions (the second published) on a server that
+        // accepts their token, and one on a server that rejects it
+        let server = MockServer::start();
+        let bad_server = MockServer::start();
+        let deposition = |id: u32, submitted: bool| {
+            json!({
+                "conceptrecid": "", "created": "", "id": id, "links": {},
+                "metadata": {"title": "Test remote", "upload_type": null,
+                             "description": null, "creators": null},
+                "modified": "", "owner": 1, "record_id": id,
+                "state": if submitted { "done" } else { "unsubmitted" },
+                "submitted": submitted, "title": "Test remote"
+            })
+        };
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(200).json_body(json!([]));
+        });
+        for (id, submitted) in [(1, false), (2, true)] {
+            server.mock(|when, then| {
+                when.method(GET)
+                    .path(format!("/deposit/depositions/{}", id));
+                then.status(200).json_body(deposition(id, submitted));
+            });
+        }
+        bad_server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions");
+            then.status(401).body("{\"message\": \"invalid token\"}");
+        });
+        let mut dc = mock_manifest(
+            &server,
+            &[],
+            &[
+                ("data/draft", &zenodo_remote(&server, 1)),
+                ("data/published", &zenodo_remote(&server, 2)),
+                ("data/bad", &zenodo_remote(&server, 3)),
+            ],
+            "",
+        );
+        dc.remotes
+            .get_mut("data/bad")
+            .unwrap()
+            .set_base_url(bad_server.url(""))
+            .unwrap();
+        let test = TestRemote::new("test");
+        dc.remotes
+            .insert("data/test".to_string(), Remote::Test(test.clone()));
+
+        let checks = dc.test_remotes(None, false).await.unwrap();
+        let lines: Vec<String> = checks.iter().map(|check| check.line()).collect();
+        assert_eq!(lines.len(), 4);
+        assert!(lines[0].starts_with("FAIL  data/bad (Zenodo): Zenodo rejected the token (401)"));
+        assert_eq!(
+            lines[1],
+            "PASS  data/draft (Zenodo): deposition 1 is writable"
+        );
+        assert!(lines[2].starts_with("FAIL  data/published (Zenodo): deposition 2 is published"));
+        assert_eq!(lines[3], "PASS  data/test (Test): writable");
+        assert_eq!(test.num_uploads(), 0);
+
+        // the write probe is uploaded, then deleted
+        let checks = dc.test_remotes(Some("data/test"), true).await.unwrap();
+        assert_eq!(checks.len(), 1);
+        assert_eq!(
+            checks[0].result.as_ref().unwrap(),
+            "writable; write probe passed"
+        );
+        assert_eq!(test.num_uploads(), 1);
+        assert!(test.file_names().is_empty());
+
+        // and deleted even if it isn't listed
+        test.set_fail_listing(true);
+        let checks = dc.test_remotes(Some("data/test"), true).await.unwrap();
+        assert!(!checks[0].passed());
+        assert!(
+            checks[0].line().contains("write probe failed"),
+            "{}",
+            checks[0].line()
+        );
+        assert!(test.file_names().is_empty());
+
+        check_error(
+            dc.test_remotes(Some("data/other"), false).await,
+            "Directory 'data/other' is not linked to a remote.",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_push_parallel_remotes() {
+        for parallel_remotes in [false, true] {
+            let dir = tempdir().unwrap();
+            let path_context = dir.path();
+            let mut dc = DataCollection::new();
+            let mut remotes = Vec::new();
+            for tracked_dir in ["data", "raw"] {
+                fs::create_dir_all(path_context.join(tracked_dir)).unwrap();
+                for name in ["a.tsv", "b.tsv"] {
+                    let path = format!("{}/{}", tracked_dir, name);
+                    fs::write(path_context.join(&path), &path).unwrap();
+                    let mut data_file = DataFile::new(path, None, path_context).await.unwrap();
+                    data_file.tracked = true;
+                    dc.register(data_file).unwrap();
+                }
+                let remote = TestRemote::new(tracked_dir);
+                dc.remotes
+                    .insert(tracked_dir.to_string(), Remote::Test(remote.clone()));
+                remotes.push(remote);
+            }
+
+            let report = dc
+                .push(
+                    path_context,
+                    &PathFilter::new(),
+                    &PushOptions {
+                        verify: true,
+                        parallel_remotes,
+                        ..Default::default()
+                    },
+                )
+                .await
+                .unwrap();
+            let mut uploaded = report.uploaded.clone();
+            uploaded.sort();
+            assert_eq!(
+                uploaded,
+                ["data/a.tsv", "data/b.tsv", "raw/a.tsv", "raw/b.tsv"]
+            );
+            for (remote, tracked_dir) in remotes.iter().zip(["data", "raw"]) {
+                assert_eq!(remote.file_names(), ["a.tsv", "b.tsv"]);
+                let contents = format!("{}/a.tsv", tracked_dir);
+                assert_eq!(remote.file("a.tsv"), Some(contents.into_bytes()));
+            }
+
+            // an upload error stops the push
+            remotes[0].fail_upload(3);
+            fs::write(path_context.join("data/a.tsv"), "changed").unwrap();
+            dc.update(None, path_context).await.unwrap();
+            let result = dc
+                .push(
+                    path_context,
+                    &PathFilter::new(),
+                    &PushOptions {
+                        overwrite: true,
+                        parallel_remotes,
+                        ..Default::default()
+                    },
+                )
+                .await;
+            check_error(result, "Test upload of 'data/a.tsv' failed");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_upload_error_context() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/1234/files");
+            then.status(500).body("internal error");
+        });
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/raw")).unwrap();
+        fs::write(path_context.join("data/raw/x.tsv"), "x\n").unwrap();
+        let yaml = format!(
+            "files: []\n\
+             remotes:\n  \
+               data/raw: !ZenodoAPI\n    \
+                 name: Test remote\n    \
+                 deposition_id: 1234\n    \
+                 bucket_url: {}\n\
+             metadata:\n  \
+               title: null\n",
+            server.url("/files/bucket")
+        );
+        let mut dc: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        let remote = dc.remotes.get_mut("data/raw").unwrap();
+        remote.set_base_url(server.url("")).unwrap();
+        remote.set_token("s3cret".to_string()).unwrap();
+        let data_file = DataFile::new("data/raw/x.tsv".to_string(), None, path_context)
+            .await
+            .unwrap();
+
+        let err = dc.remotes["data/raw"]
+            .upload(&data_file, "data/raw", path_context, false)
+            .await
+            .unwrap_err();
+        let message = format!("{:#}", err);
+        assert!(
+            message.starts_with("Zenodo upload of 'data/raw/x.tsv' to deposition 1234 failed: "),
+            "{}",
+            message
+        );
+        assert!(message.contains("HTTP Error: 500"), "{}", message);
+        assert!(!message.contains("s3cret"), "{}", message);
+    }
+
+    // A file in a subdirectory of a directory linked to Zenodo is stored
+    // under its encoded relative path, and merged back to its local path.
+    #[tokio::test]
+    async fn test_zenodo_nested_file_round_trip() {
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/raw/sub/dir")).unwrap();
+        fs::write(path_context.join("data/raw/sub/dir/file.txt"), "nested\n").unwrap();
+        // Zenodo's bucket URLs are requested relative to the base URL
+        let mut dc = mock_manifest(
+            &server,
+            &[("data/raw/sub/dir/file.txt", "nested\n", true)],
+            &[(
+                "data/raw",
+                "!ZenodoAPI\n    \
+                   name: Test remote\n    \
+                   deposition_id: 7\n    \
+                   bucket_url: https://zenodo.org/api/files/bucket",
+            )],
+            "",
+        );
+        let md5 = format!("{:x}", md5::compute("nested\n"));
+
+        let mut empty_listing = server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7/files");
+            then.status(200).json_body(json!([]));
+        });
+        let upload = server.mock(|when, then| {
+            when.method(PUT)
+                .path("/files/bucket/sub%252Fdir%252Ffile.txt");
+            then.status(201).json_body(json!({
+                "key": "sub%2Fdir%2Ffile.txt", "mimetype": "text/plain",
+                "checksum": format!("md5:{}", md5), "version_id": "1", "size": 7,
+                "created": "", "updated": "", "links": {},
+                "is_head": true, "delete_marker": false
+            }));
+        });
+        let data_file = dc.files["data/raw/sub/dir/file.txt"].clone();
+        let outcome = dc.remotes["data/raw"]
+            .upload(&data_file, "data/raw", path_context, false)
+            .await
+            .unwrap();
+        assert_eq!(outcome, UploadOutcome::Uploaded);
+        upload.assert();
+        empty_listing.delete();
+
+        server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/7/files");
+            then.status(200).json_body(json!([{
+                "checksum": md5, "filename": "sub%2Fdir%2Ffile.txt",
+                "filesize": 7, "id": "1", "links": {}
+            }]));
+        });
+        let merged = dc.merge(true).await.unwrap();
+        let merged_file = &merged["data/raw/sub/dir"]["data/raw/sub/dir/file.txt"];
+        assert!(merged_file.local.is_some());
+        assert_eq!(
+            merged_file.remote.as_ref().unwrap().name,
+            "sub/dir/file.txt"
+        );
+        assert_eq!(merged.values().map(|files| files.len()).sum::<usize>(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_status_deep_verify() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(POST)
+                .path("/files/ls")
+                .query_param("arg", "/scidataflow/test");
+            then.status(200).json_body(json!({
+                "Entries": [
+                    {"Name": "a.tsv", "Type": 0, "Size": 2, "Hash": "bafya"},
+                    {"Name": "b.tsv", "Type": 0, "Size": 2, "Hash": "bafyb"}
+                ]
+            }));
+        });
+        let download_a = server.mock(|when, then| {
+            when.method(GET).path("/ipfs/bafya");
+            then.status(200).body("a\n");
+        });
+        let download_b = server.mock(|when, then| {
+            when.method(GET).path("/ipfs/bafyb");
+            then.status(200).body("remote\n");
+        });
+
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let ipfs_dir = path_context.join("data/ipfs");
+        fs::create_dir_all(&ipfs_dir).unwrap();
+        fs::write(ipfs_dir.join("a.tsv"), "a\n").unwrap();
+        fs::write(ipfs_dir.join("b.tsv"), "b\n").unwrap();
+        let ipfs_remote = format!(
+            "!IpfsAPI\n    \
+               gateway_url: {}\n    \
+               name: Test remote\n    \
+               directory: /scidataflow/test",
+            server.url("")
+        );
+        let mut dc = mock_manifest(
+            &server,
+            &[
+                ("data/ipfs/a.tsv", "a\n", true),
+                ("data/ipfs/b.tsv", "b\n", true),
+            ],
+            &[("data/ipfs", &ipfs_remote)],
+            "",
+        );
+        let remote_status = |statuses: &BTreeMap<String, Vec<StatusEntry>>, name: &str| {
+            statuses["data/ipfs"]
+                .iter()
+                .find(|entry| entry.name == name)
+                .unwrap()
+                .remote_status
+                .clone()
+        };
+
+        // without --deep-verify, IPFS files can only be shown as existing
+        let (statuses, _) = dc.status_with(path_context, true, false).await.unwrap();
+        assert_eq!(
+            remote_status(&statuses, "a.tsv"),
+            Some(RemoteStatusCode::Exists)
+        );
+        download_a.assert_hits(0);
+
+        let (statuses, _) = dc.status_with(path_context, true, true).await.unwrap();
+        assert_eq!(
+            remote_status(&statuses, "a.tsv"),
+            Some(RemoteStatusCode::Current)
+        );
+        assert_eq!(
+            remote_status(&statuses, "b.tsv"),
+            Some(RemoteStatusCode::Different)
+        );
+        download_a.assert();
+        download_b.assert();
+    }
+
+    #[tokio::test]
+    async fn test_verify_remote() {
+        let server = MockServer::start();
+        let files_mock = server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200).json_body(json!([
+                figshare_file_json(&server, 1, "synced.tsv", "a\n"),
+                figshare_file_json(&server, 2, "remote.tsv", "remote\n"),
+                figshare_file_json(&server, 3, "local.tsv", "c\n"),
+                figshare_file_json(&server, 4, "remote_only.tsv", "e\n"),
+                figshare_file_json(&server, 5, "README.md", "readme\n"),
+            ]));
+        });
+
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        let supplement = path_context.join("data/supplement");
+        fs::create_dir_all(&supplement).unwrap();
+        // (path, manifest contents, tracked), and the contents on disk
+        let files = [
+            ("data/supplement/synced.tsv", "a\n", true),
+            ("data/supplement/remote.tsv", "b\n", true),
+            ("data/supplement/local.tsv", "c\n", true),
+            ("data/supplement/local_only.tsv", "d\n", true),
+            ("data/supplement/untracked.tsv", "f\n", false),
+        ];
+        for ((path, _, _), current) in files.iter().zip(["a\n", "b\n", "changed\n", "d\n", "f\n"]) {
+            fs::write(path_context.join(path), current).unwrap();
+        }
+        let mut dc = mock_manifest(&server, &files, &[("data/supplement", FIGSHARE_REMOTE)], "");
+        // the README is a record file, not missing locally
+        dc.record_files.push("README.md".to_string());
+
+        let report = dc.verify_remote(path_context).await.unwrap();
+        let names = |reconciliation| -> Vec<&str> {
+            report[&reconciliation]
+                .iter()
+                .map(|name| name.as_str())
+                .collect()
+        };
+        assert_eq!(
+            names(Reconciliation::Synced),
+            ["data/supplement/synced.tsv"]
+        );
+        assert_eq!(
+            names(Reconciliation::RemoteDiffers),
+            ["data/supplement/remote.tsv"]
+        );
+        assert_eq!(
+            names(Reconciliation::LocalDiffers),
+            ["data/supplement/local.tsv"]
+        );
+        assert_eq!(
+            names(Reconciliation::MissingRemote),
+            ["data/supplement/local_only.tsv"]
+        );
+        assert_eq!(
+            names(Reconciliation::MissingLocal),
+            ["data/supplement/remote_only.tsv"]
+        );
+        assert_eq!(report.len(), 5);
+        files_mock.assert();
+        // nothing was downloaded or changed
+        assert_eq!(
+            fs::read_to_string(supplement.join("local.tsv")).unwrap(),
+            "changed\n"
+        );
+        assert!(!supplement.join("remote_only.tsv").exists());
+    }
+
+    #[tokio::test]
+    async fn test_pull_archive() {
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/supplement/sub")).unwrap();
+        fs::write(path_context.join("data/supplement/a.tsv"), "a\n").unwrap();
+        fs::write(path_context.join("data/supplement/sub/b.tsv"), "b\n").unwrap();
+        let md5 = |contents: &str| format!("{:x}", md5::compute(contents));
+
+        let yaml = format!(
+            "files:\n\
+             - path: data/supplement/a.tsv\n  tracked: true\n  md5: {}\n  size: 2\n  url: null\n\
+             - path: data/supplement/sub/b.tsv\n  tracked: true\n  md5: {}\n  size: 2\n  url: null\n\
+             remotes:\n  \
+               data/supplement: !FigShareAPI\n    \
+                 article_id: 42\n    \
+                 name: Test remote\n\
+             metadata:\n  \
+               title: null\n",
+            md5("a\n"),
+            md5("b\n")
+        );
+        let mut dc: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        let remote = dc.remotes.get_mut("data/supplement").unwrap();
+        remote.set_base_url(server.url("")).unwrap();
+        remote.set_token("secret".to_string()).unwrap();
+
+        // the archive as it was pushed, recorded in the manifest
+        let members = BTreeMap::from([
+            ("a.tsv".to_string(), md5("a\n")),
+            ("sub/b.tsv".to_string(), md5("b\n")),
+        ]);
+        let archive_path = path_context.join("data_supplement.tar.gz");
+        build_archive(path_context, "data/supplement", &members, &archive_path).unwrap();
+        let archive_bytes = fs::read(&archive_path).unwrap();
+        fs::remove_file(&archive_path).unwrap();
+        dc.archives.insert(
+            "data/supplement".to_string(),
+            Archive {
+                name: "data_supplement.tar.gz".to_string(),
+                md5: format!("{:x}", md5::compute(&archive_bytes)),
+                size: archive_bytes.len() as u64,
+                members,
+            },
+        );
+        let loaded: DataCollection =
+            serde_yaml::from_str(&serde_yaml::to_string(&dc).unwrap()).unwrap();
+        assert_eq!(loaded.archives, dc.archives);
+
+        server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(200).json_body(json!([{
+                "upload_token": "", "upload_url": "", "status": "available",
+                "preview_state": "", "viewer_type": "",
+                "is_attached_to_public_version": false, "id": 9,
+                "name": "data_supplement.tar.gz", "size": archive_bytes.len(),
+                "is_link_only": false, "download_url": server.url("/files/9"),
+                "supplied_md5": "", "computed_md5": ""
+            }]));
+        });
+        let download = server.mock(|when, then| {
+            when.method(GET)
+                .path("/files/9")
+                .query_param("token", "secret");
+            then.status(200).body(&archive_bytes);
+        });
+
+        // a.tsv is current and is skipped; sub/b.tsv is restored
+        fs::remove_file(path_context.join("data/supplement/sub/b.tsv")).unwrap();
+        dc.pull_archive("data/supplement", path_context, false)
+            .await
+            .unwrap();
+        download.assert();
+        assert_eq!(
+            fs::read_to_string(path_context.join("data/supplement/sub/b.tsv")).unwrap(),
+            "b\n"
+        );
+
+        // members are checked against the manifest's MD5s
+        dc.files.get_mut("data/supplement/sub/b.tsv").unwrap().md5 = md5("changed\n");
+        fs::remove_file(path_context.join("data/supplement/sub/b.tsv")).unwrap();
+        let result = dc
+            .pull_archive("data/supplement", path_context, false)
+            .await;
+        check_error(result, "did not match");
+
+        // an archive that doesn't match the manifest's MD5 is not unpacked
+        dc.files.get_mut("data/supplement/sub/b.tsv").unwrap().md5 = md5("b\n");
+        dc.archives.get_mut("data/supplement").unwrap().md5 = md5("other");
+        let result = dc
+            .pull_archive("data/supplement", path_context, false)
+            .await;
+        check_error(result, "but the manifest's is");
+        assert!(!path_context.join("data/supplement/sub/b.tsv").exists());
+        let left: Vec<_> = fs::read_dir(path_context.join("data/supplement/sub"))
+            .unwrap()
+            .collect();
+        assert!(left.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_status_remote_unavailable() {
+        let figshare_server = MockServer::start();
+        let zenodo_server = MockServer::start();
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::create_dir_all(path_context.join("data/supplement")).unwrap();
+        fs::create_dir_all(path_context.join("data/raw")).unwrap();
+        fs::write(path_context.join("data/supplement/a.tsv"), "a\n").unwrap();
+        fs::write(path_context.join("data/raw/b.tsv"), "b\n").unwrap();
+        let md5 = |contents: &str| format!("{:x}", md5::compute(contents));
+
+        let yaml = format!(
+            "files:\n\
+             - path: data/supplement/a.tsv\n  tracked: true\n  md5: {}\n  size: 2\n  url: null\n\
+             - path: data/raw/b.tsv\n  tracked: true\n  md5: {}\n  size: 2\n  url: null\n\
+             remotes:\n  \
+               data/supplement: !FigShareAPI\n    \
+                 article_id: 42\n    \
+                 name: Test remote\n  \
+               data/raw: !ZenodoAPI\n    \
+                 name: Test remote\n    \
+                 deposition_id: 1234\n    \
+                 bucket_url: null\n\
+             metadata:\n  \
+               title: null\n",
+            md5("a\n"),
+            md5("b\n")
+        );
+        let mut dc: DataCollection = serde_yaml::from_str(&yaml).unwrap();
+        for (directory, base_url) in [
+            ("data/supplement", figshare_server.url("")),
+            ("data/raw", zenodo_server.url("")),
+        ] {
+            let remote = dc.remotes.get_mut(directory).unwrap();
+            remote.set_base_url(base_url).unwrap();
+            remote.set_token("expired-token".to_string()).unwrap();
+        }
+
+        figshare_server.mock(|when, then| {
+            when.method(GET).path("/account/articles/42/files");
+            then.status(403).body("{\"message\": \"Invalid token\"}");
+        });
+        zenodo_server.mock(|when, then| {
+            when.method(GET).path("/deposit/depositions/1234/files");
+            then.status(200).json_body(json!([{
+                "checksum": md5("b\n"),
+                "filename": "b.tsv",
+                "filesize": 2,
+                "id": "1",
+                "links": {}
+            }]));
+        });
+
+        let statuses = dc.status(path_context, true).await.unwrap();
+        let supplement = &statuses["data/supplement"][0];
+        assert!(supplement
+            .remote_unavailable
+            .as_ref()
+            .unwrap()
+            .contains("403"));
+        assert!(supplement.columns(None, true)[5].starts_with("remote unavailable: "));
+        let raw = &statuses["data/raw"][0];
+        assert!(raw.remote_unavailable.is_none());
+        assert_eq!(raw.remote_status, Some(RemoteStatusCode::Current));
+
+        // merge() (e.g. for push and pull) still fails
+        let result = dc.merge(true).await;
+        check_error(result, "Could not fetch files from the FigShare remote");
+    }
+
+    #[tokio::test]
+    async fn test_merge_flattened_remote_names() {
+        let mut dc = DataCollection::new();
+        for path in [
+            "data/a.tsv",
+            "data/sub/b.tsv",
+            "data/x/c.tsv",
+            "data/y/c.tsv",
+        ] {
+            dc.files.insert(
+                path.to_string(),
+                DataFile {
+                    path: path.to_string(),
+                    tracked: true,
+                    md5: "".to_string(),
+                    size: 4,
+                    urls: Vec::new(),
+                    source: None,
+                    added_at: None,
+                    added_by: None,
+                    remote_md5: None,
+                },
+            );
+        }
+        let remote_file = |name: &str| RemoteFile {
+            name: name.to_string(),
+            md5: None,
+            size: Some(4),
+            remote_service: "FigShare".to_string(),
+            url: None,
+            mod_time: None,
+            duplicate_ids: Vec::new(),
+        };
+        let remote_files: HashMap<String, RemoteFile> = ["a.tsv", "b.tsv", "c.tsv", "d.tsv"]
+            .into_iter()
+            .map(|name| (name.to_string(), remote_file(name)))
+            .collect();
+        let all_remote_files =
+            HashMap::from([(("FigShare".to_string(), "data".to_string()), remote_files)]);
+
+        let mut result = dc.merge(false).await.unwrap();
+        DataCollection::merge_remote_files(&mut result, &all_remote_files);
+
+        // exact path
+        assert!(result["data"]["data/a.tsv"].remote.is_some());
+        // flattened to its basename, matched within the linked directory
+        assert!(result["data/sub"]["data/sub/b.tsv"].remote.is_some());
+        assert!(!result["data"].contains_key("data/b.tsv"));
+        // ambiguous basenames are not matched
+        assert!(result["data/x"]["data/x/c.tsv"].remote.is_none());
+        assert!(result["data/y"]["data/y/c.tsv"].remote.is_none());
+        assert!(result["data"]["data/c.tsv"].local.is_none());
+        // remote only
+        assert!(result["data"]["data/d.tsv"].local.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_merged_file_status() {
+        let dir = tempdir().unwrap();
+        let path_context = dir.path();
+        fs::write(path_context.join("file.tsv"), "a\tb\n").unwrap();
+        let md5 = format!("{:x}", md5::compute("a\tb\n"));
+
+        let local = DataFile {
+            path: "file.tsv".to_string(),
+            tracked: true,
+            md5: md5.clone(),
+            size: 4,
+            urls: Vec::new(),
+            source: None,
+            added_at: None,
+            added_by: None,
+            remote_md5: None,
+        };
+        let remote = |md5: Option<&str>| RemoteFile {
+            name: "file.tsv".to_string(),
+            md5: md5.map(|m| m.to_string()),
+            size: Some(4),
+            remote_service: "Zenodo".to_string(),
+            url: None,
+            mod_time: None,
+            duplicate_ids: Vec::new(),
+        };
+        let merged = |local: Option<&DataFile>, remote: Option<RemoteFile>| MergedFile {
+            local: local.cloned(),
+            remote,
+            remote_service: Some("Zenodo".to_string()),
+        };
+
+        let cases = [
+            (merged(Some(&local), None), RemoteStatusCode::NotExists),
+            (
+                merged(None, Some(remote(Some(&md5)))),
+                RemoteStatusCode::NoLocal,
+            ),
+            (
+                merged(Some(&local), Some(remote(Some(&md5)))),
+                RemoteStatusCode::Current,
+            ),
+            (
+                merged(Some(&local), Some(remote(Some("different")))),
+                RemoteStatusCode::Different,
+            ),
+            // the remote does not provide an MD5
+            (
+                merged(Some(&local), Some(remote(None))),
+                RemoteStatusCode::Exists,
+            ),
+        ];
+        for (merged_file, expected) in cases {
+            assert_eq!(merged_file.status(path_context).await.unwrap(), expected);
+        }
+
+        // the local file differs from the manifest
+        let mut modified = local.clone();
+        modified.md5 = "out-of-date".to_string();
+        let merged_file = merged(Some(&modified), Some(remote(Some(&md5))));
+        assert_eq!(
+            merged_file.status(path_context).await.unwrap(),
+            RemoteStatusCode::MessyLocal
+        );
+
+        // the file is in the manifest, but not on disk
+        fs::remove_file(path_context.join("file.tsv")).unwrap();
+        let merged_file = merged(Some(&local), Some(remote(Some(&md5))));
+        assert_eq!(
+            merged_file.status(path_context).await.unwrap(),
+            RemoteStatusCode::DeletedLocal
+        );
+    }
+
+    #[test]
+    fn test_status_entry_newer_direction() {
+        let earlier = Utc.with_ymd_and_hms(2023, 8, 20, 0, 0, 0).unwrap();
+        let later = Utc.with_ymd_and_hms(2023, 9, 1, 0, 0, 0).unwrap();
+        let entry = |remote_status, local_mod_time, remote_mod_time| StatusEntry {
+            name: "file.tsv".to_string(),
+            local_status: Some(LocalStatusCode::Current),
+            remote_status: Some(remote_status),
+            tracked: Some(true),
+            remote_service: Some("Zenodo".to_string()),
+            local_md5: Some("aaa".to_string()),
+            remote_md5: Some("bbb".to_string()),
+            manifest_md5: Some("aaa".to_string()),
+            local_mod_time,
+            remote_mod_time,
+            size: Some(2048),
+            local_size: None,
+            remote_unavailable: None,
+            last_remote_md5: None,
+        };
+
+        let local_newer = entry(RemoteStatusCode::Different, Some(later), Some(earlier));
+        assert_eq!(local_newer.local_is_newer(), Some(true));
+        assert!(local_newer.columns(None, true)[5].ends_with("local newer (push)"));
+        assert_eq!(local_newer.columns(None, true)[3], "2.00 KB");
+        // --no-size drops the size column
+        assert!(local_newer.columns(None, false)[4].ends_with("local newer (push)"));
+
+        let remote_newer = entry(RemoteStatusCode::Different, Some(earlier), Some(later));
+        assert_eq!(remote_newer.local_is_newer(), Some(false));
+        assert!(remote_newer.columns(None, true)[5].ends_with("remote record modified later"));
+
+        // a last-seen remote MD5 tells which side changed, whatever the times
+        let changed_locally = StatusEntry {
+            last_remote_md5: Some("bbb".to_string()),
+            ..remote_newer.clone()
+        };
+        assert!(changed_locally.columns(None, true)[5].ends_with("changed locally (push)"));
+
+        // no remote modification time
+        let unknown = entry(RemoteStatusCode::Different, Some(later), None);
+        assert_eq!(unknown.local_is_newer(), None);
+        assert!(unknown.columns(None, true)[5].ends_with(")"));
+
+        // only reported for files that differ
+        let current = entry(RemoteStatusCode::Current, Some(later), Some(earlier));
+        assert_eq!(current.local_is_newer(), None);
+    }
 }
@@ -1,16 +1,107 @@
 use anyhow::{anyhow, Context, Result};
+use colored::*;
+use futures::stream::{self, StreamExt};
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use percent_encoding::percent_decode_str;
 use reqwest::Url;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
-use std::path::PathBuf;
+use std::io;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use suppaftp::native_tls::TlsConnector;
+use suppaftp::types::FileType;
+use suppaftp::{FtpStream, ImplFtpStream, NativeTlsConnector, NativeTlsFtpStream, TlsStream};
 
-use trauma::download::Download;
-use trauma::downloader::{DownloaderBuilder, ProgressBarOpts, StyleOptions};
+use trauma::download::{Download, Status, Summary};
+use trauma::downloader::{Downloader, DownloaderBuilder, ProgressBarOpts, StyleOptions};
 
-use crate::lib::progress::{DEFAULT_PROGRESS_INC, DEFAULT_PROGRESS_STYLE};
-use crate::lib::utils::pluralize;
+use crate::lib::http;
+use crate::lib::interrupt::{self, Staged};
+use crate::lib::output::quiet;
+use crate::lib::overwrite::OverwriteAction;
+use crate::lib::progress::{Progress, ProgressGroup, DEFAULT_PROGRESS_INC, DEFAULT_PROGRESS_STYLE};
+use crate::lib::utils::{case_collisions, confirm, format_bytes, pluralize};
+use crate::{print_info, print_warn};
+
+/// Default number of files downloaded simultaneously.
+pub const DEFAULT_CONCURRENT_DOWNLOADS: usize = 8;
+
+const FTP_PORT: u16 = 21;
+
+/// Pulls downloading more than this many bytes in total ask for
+/// confirmation first, unless set with `sdf config --pull-confirm-size`.
+pub const DEFAULT_CONFIRM_SIZE: u64 = 5 * 1024 * 1024 * 1024;
+
+// The pull size above which to ask for confirmation; 0 never asks.
+static CONFIRM_SIZE: AtomicU64 = AtomicU64::new(DEFAULT_CONFIRM_SIZE);
+
+/// Ask for confirmation of pulls larger than this many bytes, or never
+/// if 0 (e.g. with `sdf pull --yes`).
+pub fn set_confirm_size(size: u64) {
+    CONFIRM_SIZE.store(size, Ordering::Relaxed);
+}
+
+pub fn confirm_size() -> u64 {
+    CONFIRM_SIZE.load(Ordering::Relaxed)
+}
+
+// The confirmation prompt for downloading files of these (directory,
+// size) if their total is over the threshold, with the total size of
+// each directory's files.
+fn large_download_prompt(sizes: &[(String, u64)], threshold: u64) -> Option<String> {
+    let total: u64 = sizes.iter().map(|(_, size)| size).sum();
+    if threshold == 0 || total <= threshold {
+        return None;
+    }
+    let mut by_dir: BTreeMap<&str, (usize, u64)> = BTreeMap::new();
+    for (dir, size) in sizes {
+        let entry = by_dir.entry(dir).or_default();
+        entry.0 += 1;
+        entry.1 += size;
+    }
+    let mut prompt = format!(
+        "This will download {} ({}), more than {}:",
+        pluralize(sizes.len() as u64, "file"),
+        format_bytes(total),
+        format_bytes(threshold)
+    );
+    for (dir, (num_files, size)) in by_dir {
+        prompt.push_str(&format!(
+            "\n - {}: {} ({})",
+            dir,
+            pluralize(num_files as u64, "file"),
+            format_bytes(size)
+        ));
+    }
+    prompt.push_str("\nContinue?");
+    Some(prompt)
+}
+
+/// Before a pull downloads files of these (directory, size), ask for
+/// confirmation if they total more than [`confirm_size()`]; an error if
+/// the pull is cancelled.
+pub fn confirm_download(sizes: &[(String, u64)]) -> Result<()> {
+    if let Some(prompt) = large_download_prompt(sizes, confirm_size()) {
+        if !confirm(&prompt)? {
+            return Err(anyhow!(
+                "Pull cancelled; nothing was downloaded (pass --yes to skip this check)."
+            ));
+        }
+    }
+    Ok(())
+}
 
 pub struct Downloads {
     pub queue: Vec<Download>,
+    // Fallback URLs (e.g. mirrors) by download filename, tried in order if
+    // the download fails.
+    mirrors: HashMap<String, Vec<Url>>,
+    // Progress bar labels (e.g. the remote) by download filename; if set,
+    // each label gets its own bar.
+    labels: HashMap<String, String>,
 }
 
 pub trait Downloadable {
@@ -39,7 +130,31 @@ impl Default for Downloads {
 impl Downloads {
     pub fn new() -> Self {
         let queue = Vec::new();
-        Downloads { queue }
+        Downloads {
+            queue,
+            mirrors: HashMap::new(),
+            labels: HashMap::new(),
+        }
+    }
+
+    /// Show a download's progress on the bar with this label, rather than
+    /// the single bar of unlabeled downloads.
+    pub fn set_label(&mut self, filename: &str, label: &str) {
+        self.labels.insert(filename.to_string(), label.to_string());
+    }
+
+    /// Download the queued file `filename` to `path` instead, e.g. to
+    /// check it before it replaces an existing file.
+    pub fn download_to(&mut self, filename: &str, path: &str) {
+        for download in self.queue.iter_mut().filter(|d| d.filename == filename) {
+            download.filename = path.to_string();
+        }
+        if let Some(mirrors) = self.mirrors.remove(filename) {
+            self.mirrors.insert(path.to_string(), mirrors);
+        }
+        if let Some(label) = self.labels.remove(filename) {
+            self.labels.insert(path.to_string(), label);
+        }
     }
 
     pub fn add<T: Downloadable>(
@@ -52,16 +167,12 @@ impl Downloads {
 
         let resolved_filename = match filename {
             Some(name) => name.to_string(),
-            None => url
-                .path_segments()
-                .ok_or_else(|| anyhow::anyhow!("Error parsing URL."))?
-                .last()
-                .ok_or_else(|| anyhow::anyhow!("Error getting filename from download URL."))?
-                .to_string(),
+            None => url_filename(&url)?,
         };
 
+        // returns None if skipped; see overwrite.rs
         let file_path = PathBuf::from(&resolved_filename);
-        if file_path.exists() && !overwrite {
+        if !OverwriteAction::new(file_path.exists(), overwrite).transfers() {
             return Ok(None);
         }
 
@@ -77,6 +188,32 @@ impl Downloads {
         ))
     }
 
+    /// Add a download from the first of `urls`, falling back to the rest
+    /// (e.g. mirrors) in order if it fails. As with add(), returns None if
+    /// the download is skipped.
+    pub fn add_with_mirrors(
+        &mut self,
+        urls: &[String],
+        filename: Option<&str>,
+        overwrite: bool,
+    ) -> Result<Option<&Download>> {
+        let (url, mirrors) = urls
+            .split_first()
+            .ok_or_else(|| anyhow!("Internal Error: no download URLs given."))?;
+        let mirrors = mirrors
+            .iter()
+            .map(|mirror| mirror.clone().to_url())
+            .collect::<Result<Vec<_>>>()?;
+        let filename = match self.add(url.clone(), filename, overwrite)? {
+            Some(download) => download.filename.clone(),
+            None => return Ok(None),
+        };
+        if !mirrors.is_empty() {
+            self.mirrors.insert(filename, mirrors);
+        }
+        Ok(self.queue.last())
+    }
+
     pub fn default_style(&self) -> Result<StyleOptions> {
         let style = ProgressBarOpts::new(
             Some(DEFAULT_PROGRESS_STYLE.to_string()),
@@ -95,26 +232,100 @@ impl Downloads {
     // This is because overwrite-safety is checked at Downloads::add(), per-file.
     // The trauma crate does not overwrite files; delete must be done manually here
     // first if it exists.
+    //
+    // At most max_concurrent files (default DEFAULT_CONCURRENT_DOWNLOADS) are
+    // downloaded at once, and a single progress bar shows the number of files
+    // completed and the aggregate bytes downloaded.
+    // Refuse to download several files to paths that are the same on a
+    // case-insensitive filesystem, since each would clobber the last.
+    fn check_case_collisions(&self) -> Result<()> {
+        let collisions = case_collisions(self.queue.iter().map(|d| d.filename.as_str()));
+        if collisions.is_empty() {
+            return Ok(());
+        }
+        let groups: Vec<String> = collisions
+            .iter()
+            .map(|group| format!("  {}", group.join(", ")))
+            .collect();
+        Err(anyhow!(
+            "Refusing to download files whose paths differ only by case, since they \
+             would overwrite each other on case-insensitive filesystems:\n{}",
+            groups.join("\n")
+        ))
+    }
+
     pub async fn retrieve(
         &self,
         success_status: Option<&str>,
         no_downloads_message: Option<&str>,
         show_total: bool,
+        max_concurrent: Option<usize>,
     ) -> Result<()> {
+        self.retrieve_with_failures(
+            success_status,
+            no_downloads_message,
+            show_total,
+            max_concurrent,
+        )
+        .await
+        .map(|_| ())
+    }
+
+    // Like retrieve(), but also return the downloads that failed (after
+    // trying their mirrors), as (filename, error message) pairs.
+    pub async fn retrieve_with_failures(
+        &self,
+        success_status: Option<&str>,
+        no_downloads_message: Option<&str>,
+        show_total: bool,
+        max_concurrent: Option<usize>,
+    ) -> Result<Vec<(String, String)>> {
+        self.retrieve_each(
+            success_status,
+            no_downloads_message,
+            show_total,
+            max_concurrent,
+            &mut |_| {},
+        )
+        .await
+    }
+
+    // Like retrieve_with_failures(), but call downloaded with each file's
+    // name as soon as it has downloaded, e.g. to record progress that
+    // survives the process being killed partway through.
+    pub async fn retrieve_each(
+        &self,
+        success_status: Option<&str>,
+        no_downloads_message: Option<&str>,
+        show_total: bool,
+        max_concurrent: Option<usize>,
+        downloaded: &mut dyn FnMut(&str),
+    ) -> Result<Vec<(String, String)>> {
+        self.check_case_collisions()?;
         let downloads = &self.queue;
         let total_files = downloads.len();
         if !downloads.is_empty() {
             // Let's handle the file operations:
             // 1) Move all the files to temporary destinations
             // 2) Create the directory structure if it does not exist.
+            // Until the downloads finish, these are staged so that if they
+            // are interrupted, partial downloads are removed and the moved
+            // files are restored.
             let mut temp_files = Vec::new();
+            let mut staged = Vec::new();
             for file in downloads {
                 let path = PathBuf::from(&file.filename);
                 if path.exists() {
                     // rather than delete, we move the file
                     let temp_file_path = path.with_extension(".tmp");
                     fs::rename(&path, &temp_file_path)?;
-                    temp_files.push(temp_file_path);
+                    staged.push(interrupt::stage(Staged::Backup {
+                        backup: temp_file_path.clone(),
+                        original: path.clone(),
+                    }));
+                    temp_files.push((temp_file_path, path.clone()));
+                } else {
+                    staged.push(interrupt::stage(Staged::Partial(path.clone())));
                 }
 
                 // recreate the directory structure if not there
@@ -125,20 +336,99 @@ impl Downloads {
                 }
             }
 
-            let downloader = DownloaderBuilder::new()
-                .style_options(self.default_style()?)
-                .build();
+            // trauma's own per-file bars are hidden in favor of one
+            // aggregated bar.
+            let downloader = DownloaderBuilder::hidden().build();
+            let max_concurrent = max_concurrent
+                .unwrap_or(DEFAULT_CONCURRENT_DOWNLOADS)
+                .max(1);
 
             // download everything
-            downloader.download(downloads).await;
+            let mut bars = DownloadBars::new(downloads, &self.labels)?;
+            let mut total_bytes = 0;
+            let mut failed = download_all(
+                downloads,
+                &downloader,
+                max_concurrent,
+                &mut bars,
+                &mut total_bytes,
+                downloaded,
+            )
+            .await;
 
-            // now remove the temp files
-            for temp_file_path in temp_files {
-                if temp_file_path.exists() {
+            // retry failed downloads from their mirrors, in order
+            let mut mirrored = Vec::new();
+            for attempt in 0.. {
+                let retries: Vec<Download> = failed
+                    .iter()
+                    .filter_map(|(filename, _)| {
+                        let mirror = self.mirrors.get(filename)?.get(attempt)?;
+                        Some(Download::new(mirror, filename))
+                    })
+                    .collect();
+                if retries.is_empty() {
+                    break;
+                }
+                for retry in &retries {
+                    info!(
+                        "retrying download of '{}' from {}",
+                        retry.filename, retry.url
+                    );
+                    // remove any partial download, which trauma would resume
+                    let _ = fs::remove_file(&retry.filename);
+                }
+                for retry in &retries {
+                    bars.retrying(&retry.filename);
+                }
+                let retries_failed = download_all(
+                    &retries,
+                    &downloader,
+                    max_concurrent,
+                    &mut bars,
+                    &mut total_bytes,
+                    downloaded,
+                )
+                .await;
+                failed.retain(|(filename, _)| !retries.iter().any(|r| &r.filename == filename));
+                mirrored.extend(retries.into_iter().filter(|retry| {
+                    !retries_failed
+                        .iter()
+                        .any(|(filename, _)| filename == &retry.filename)
+                }));
+                failed.extend(retries_failed);
+            }
+            bars.finish();
+
+            for download in &mirrored {
+                info!("downloaded '{}' from {}", download.filename, download.url);
+                print_info!(
+                    "Downloaded '{}' from the mirror {}.",
+                    download.filename,
+                    download.url
+                );
+            }
+            for (filename, msg) in &failed {
+                print_warn!("Download of '{}' failed: {}", filename, msg);
+            }
+
+            // now restore the files whose replacements failed to
+            // download, and remove the rest of the temp files
+            for (temp_file_path, original) in temp_files {
+                if !temp_file_path.exists() {
+                    continue;
+                }
+                let original_name = original.to_string_lossy();
+                if failed
+                    .iter()
+                    .any(|(filename, _)| *filename == original_name)
+                {
+                    fs::rename(&temp_file_path, &original)?;
+                } else {
                     fs::remove_file(temp_file_path)?;
                 }
             }
-            if show_total {
+            drop(staged);
+            if show_total && !quiet() {
                 let punc = if total_files > 0 { "." } else { ":" };
                 println!(
                     "Downloaded {}{}",
@@ -146,8 +436,11 @@ impl Downloads {
                     punc
                 );
             }
-            for download in downloads {
-                if let Some(msg) = success_status {
+            let succeeded = downloads
+                .iter()
+                .filter(|download| !failed.iter().any(|(name, _)| name == &download.filename));
+            for download in succeeded {
+                if let Some(msg) = success_status.filter(|_| !quiet()) {
                     let filename = PathBuf::from(&download.filename);
                     let name_str = filename.file_name().ok_or(anyhow!(
                         "Internal Error: could not extract filename from download"
@@ -156,9 +449,471 @@ impl Downloads {
                     println!("{}", msg.replace("{}", &name_str.to_string_lossy()));
                 }
             }
-        } else if no_downloads_message.is_some() {
+            return Ok(failed);
+        } else if no_downloads_message.is_some() && !quiet() {
             println!("{}", no_downloads_message.unwrap_or(""));
         }
-        Ok(())
+        Ok(Vec::new())
+    }
+}
+
+/// The filename a URL is downloaded to by default: the last segment of
+/// its path.
+pub fn url_filename(url: &Url) -> Result<String> {
+    Ok(url
+        .path_segments()
+        .ok_or_else(|| anyhow::anyhow!("Error parsing URL."))?
+        .next_back()
+        .ok_or_else(|| anyhow::anyhow!("Error getting filename from download URL."))?
+        .to_string())
+}
+
+fn is_ftp(url: &Url) -> bool {
+    matches!(url.scheme(), "ftp" | "ftps")
+}
+
+fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().to_string()
+}
+
+// The progress bars of a retrieve: a single bar, or if the downloads are
+// labeled (see Downloads::set_label()), one for each label.
+struct DownloadBars {
+    // each bar and the bytes downloaded on it, by label ("" if unlabeled)
+    bars: BTreeMap<String, (Progress, u64)>,
+    labels: HashMap<String, String>,
+    // kept so the labeled bars are drawn together until finished
+    #[allow(dead_code)]
+    group: Option<ProgressGroup>,
+}
+
+impl DownloadBars {
+    fn new(downloads: &[Download], labels: &HashMap<String, String>) -> Result<Self> {
+        let mut bars = BTreeMap::new();
+        if labels.is_empty() {
+            bars.insert(String::new(), (Progress::new(downloads.len() as u64)?, 0));
+            return Ok(DownloadBars {
+                bars,
+                labels: HashMap::new(),
+                group: None,
+            });
+        }
+        let mut counts: BTreeMap<&str, u64> = BTreeMap::new();
+        for download in downloads {
+            let label = labels.get(&download.filename).map_or("", String::as_str);
+            *counts.entry(label).or_default() += 1;
+        }
+        let group = ProgressGroup::new();
+        for (label, count) in counts {
+            bars.insert(label.to_string(), (group.add(label, count)?, 0));
+        }
+        Ok(DownloadBars {
+            bars,
+            labels: labels.clone(),
+            group: Some(group),
+        })
+    }
+
+    fn bar(&mut self, filename: &str) -> Option<&mut (Progress, u64)> {
+        let label = self.labels.get(filename).map_or("", String::as_str);
+        self.bars.get_mut(label)
+    }
+
+    // Add a finished download (and its size, unless it failed).
+    fn finished(&mut self, filename: &str, size: Option<u64>) {
+        if let Some((pb, bytes)) = self.bar(filename) {
+            *bytes += size.unwrap_or(0);
+            pb.bar.inc(1);
+            pb.bar.set_message(format_bytes(*bytes));
+        }
+    }
+
+    // Add a download retried from a mirror.
+    fn retrying(&mut self, filename: &str) {
+        if let Some((pb, _)) = self.bar(filename) {
+            pb.bar.inc_length(1);
+        }
+    }
+
+    fn finish(&self) {
+        for (pb, bytes) in self.bars.values() {
+            pb.bar
+                .finish_with_message(format!("{} downloaded.", format_bytes(*bytes)));
+        }
+    }
+}
+
+// Download files (at most max_concurrent at once), adding each to its
+// progress bar as it finishes, and return those that failed with why.
+// trauma only speaks HTTP, so FTP URLs are downloaded with ftp_download().
+async fn download_all(
+    downloads: &[Download],
+    downloader: &Downloader,
+    max_concurrent: usize,
+    bars: &mut DownloadBars,
+    total_bytes: &mut u64,
+    downloaded: &mut dyn FnMut(&str),
+) -> Vec<(String, String)> {
+    let mut failed = Vec::new();
+    let mut results = stream::iter(downloads)
+        .map(|download| async move {
+            if is_ftp(&download.url) {
+                let result = ftp_download(download).await;
+                vec![(download.filename.clone(), result)]
+            } else {
+                let summaries = downloader.download(std::slice::from_ref(download)).await;
+                summaries.iter().map(summary_result).collect()
+            }
+        })
+        .buffer_unordered(max_concurrent);
+    while let Some(result) = results.next().await {
+        for (filename, result) in result {
+            match result {
+                Err(msg) => {
+                    bars.finished(&filename, None);
+                    failed.push((filename, msg));
+                }
+                Ok(size) => {
+                    let size = fs::metadata(&filename).map(|md| md.len()).unwrap_or(size);
+                    *total_bytes += size;
+                    bars.finished(&filename, Some(size));
+                    downloaded(&filename);
+                }
+            }
+        }
+    }
+    failed
+}
+
+// A download's file and its size, or why it failed.
+fn summary_result(summary: &Summary) -> (String, std::result::Result<u64, String>) {
+    let result = match summary.status() {
+        Status::Fail(msg) => Err(msg.clone()),
+        _ => Ok(summary.size()),
+    };
+    (summary.download().filename.clone(), result)
+}
+
+// Download an ftp:// or ftps:// (explicit TLS) URL, logging in as the
+// URL's user or anonymously. The FTP client blocks, so it is run on a
+// blocking thread.
+async fn ftp_download(download: &Download) -> std::result::Result<u64, String> {
+    let (url, filename) = (download.url.clone(), download.filename.clone());
+    tokio::task::spawn_blocking(move || ftp_retrieve(&url, Path::new(&filename)))
+        .await
+        .map_err(|err| anyhow!(err))
+        .and_then(|result| result)
+        .map_err(|err| format!("{:#}", err))
+}
+
+fn ftp_retrieve(url: &Url, path: &Path) -> Result<u64> {
+    let host = url
+        .host_str()
+        .ok_or_else(|| anyhow!("FTP URL '{}' has no host.", url))?;
+    let stream = ftp_connect(host, url.port().unwrap_or(FTP_PORT))?;
+    if url.scheme() == "ftps" {
+        let connector = NativeTlsConnector::from(TlsConnector::new()?);
+        let mut ftp =
+            NativeTlsFtpStream::connect_with_stream(stream)?.into_secure(connector, host)?;
+        ftp_copy(&mut ftp, url, path)
+    } else {
+        let mut ftp = FtpStream::connect_with_stream(stream)?;
+        ftp_copy(&mut ftp, url, path)
+    }
+}
+
+// Connect to an FTP server with the HTTP client's timeouts: connecting
+// within http::CONNECT_TIMEOUT, and each read and write within the
+// request timeout (see http::timeout()), if one is set.
+fn ftp_connect(host: &str, port: u16) -> Result<TcpStream> {
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| anyhow!("Could not resolve the FTP host '{}'.", host))?;
+    let stream = TcpStream::connect_timeout(&address, http::CONNECT_TIMEOUT)
+        .context(format!("Could not connect to the FTP server '{}'.", host))?;
+    stream.set_read_timeout(http::timeout())?;
+    stream.set_write_timeout(http::timeout())?;
+    Ok(stream)
+}
+
+// Save the file at the URL's path, removing it if the transfer fails
+// part way.
+fn ftp_copy<T: TlsStream>(ftp: &mut ImplFtpStream<T>, url: &Url, path: &Path) -> Result<u64> {
+    let (user, password) = match url.username() {
+        "" => ("anonymous".to_string(), "anonymous@".to_string()),
+        user => (decode(user), decode(url.password().unwrap_or(""))),
+    };
+    ftp.login(&user, &password)
+        .context(format!("FTP login as '{}' failed.", user))?;
+    ftp.transfer_type(FileType::Binary)?;
+    let mut transfer = ftp.retr_as_stream(decode(url.path()))?;
+    // the data connection is separate from the one ftp_connect() made
+    transfer
+        .get_ref()
+        .get_ref()
+        .set_read_timeout(http::timeout())?;
+    let mut file = fs::File::create(path).context(format!("Could not create {:?}", path))?;
+    let size = io::copy(&mut transfer, &mut file)
+        .map_err(anyhow::Error::from)
+        .and_then(|size| Ok(transfer.finish().map(|_| size)?));
+    if size.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    let _ = ftp.quit();
+    size
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use httpmock::prelude::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use tempfile::tempdir;
+
+    // A minimal FTP server for one session, serving `contents` for any
+    // RETR of `path` (in passive mode). Returns the server's port.
+    fn serve_ftp(path: &'static str, contents: &'static str) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (control, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(control.try_clone().unwrap());
+            let mut control = control;
+            let mut data = None;
+            control.write_all(b"220 ready\r\n").unwrap();
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 {
+                let (command, arg) = line
+                    .trim_end()
+                    .split_once(' ')
+                    .unwrap_or((line.trim_end(), ""));
+                let reply = match command {
+                    "USER" => "331 password please".to_string(),
+                    "PASS" => "230 logged in".to_string(),
+                    "TYPE" => "200 binary".to_string(),
+                    "PASV" => {
+                        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+                        let port = listener.local_addr().unwrap().port();
+                        data = Some(listener);
+                        format!(
+                            "227 Entering Passive Mode (127,0,0,1,{},{})",
+                            port / 256,
+                            port % 256
+                        )
+                    }
+                    "RETR" if arg == path => {
+                        control.write_all(b"150 sending\r\n").unwrap();
+                        let (mut stream, _) = data.take().unwrap().accept().unwrap();
+                        stream.write_all(contents.as_bytes()).unwrap();
+                        drop(stream);
+                        "226 done".to_string()
+                    }
+                    "RETR" => "550 no such file".to_string(),
+                    "QUIT" => {
+                        control.write_all(b"221 bye\r\n").unwrap();
+                        break;
+                    }
+                    _ => "502 not implemented".to_string(),
+                };
+                control
+                    .write_all(format!("{}\r\n", reply).as_bytes())
+                    .unwrap();
+                line.clear();
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_ftp() {
+        let dir = tempdir().unwrap();
+        let port = serve_ftp("/pub/data file.fa", ">seq\nACGT\n");
+        let filename = dir.path().join("data.fa");
+        let mut downloads = Downloads::new();
+        let url = format!("ftp://127.0.0.1:{}/pub/data%20file.fa", port);
+        downloads
+            .add(url, Some(&filename.to_string_lossy()), false)
+            .unwrap();
+        downloads.retrieve(None, None, false, None).await.unwrap();
+        assert_eq!(fs::read_to_string(&filename).unwrap(), ">seq\nACGT\n");
+
+        // a missing file fails the download (with a warning) rather than
+        // leaving a partial one
+        let port = serve_ftp("/pub/data.fa", "");
+        let url = Url::parse(&format!("ftp://127.0.0.1:{}/pub/missing.fa", port)).unwrap();
+        let missing = dir.path().join("missing.fa");
+        let msg = ftp_download(&Download::new(&url, &missing.to_string_lossy()))
+            .await
+            .unwrap_err();
+        assert!(msg.contains("550"), "{}", msg);
+        assert!(!missing.exists());
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_concurrent() {
+        let server = MockServer::start();
+        let dir = tempdir().unwrap();
+        let mut downloads = Downloads::new();
+        let mut mocks = Vec::new();
+        for i in 0..5 {
+            let path = format!("/file_{}.tsv", i);
+            mocks.push(server.mock(|when, then| {
+                when.method(GET).path(path.clone());
+                then.status(200).body(format!("contents of file {}", i));
+            }));
+            let filename = dir.path().join("nested").join(format!("file_{}.tsv", i));
+            downloads
+                .add(server.url(&path), Some(&filename.to_string_lossy()), false)
+                .unwrap();
+        }
+
+        downloads
+            .retrieve(None, None, false, Some(2))
+            .await
+            .unwrap();
+
+        for (i, mock) in mocks.iter().enumerate() {
+            mock.assert();
+            let filename = dir.path().join("nested").join(format!("file_{}.tsv", i));
+            let contents = fs::read_to_string(filename).unwrap();
+            assert_eq!(contents, format!("contents of file {}", i));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_mirrors() {
+        let server = MockServer::start();
+        let missing = server.mock(|when, then| {
+            when.method(GET).path("/primary/a.tsv");
+            then.status(404);
+        });
+        let mirror = server.mock(|when, then| {
+            when.method(GET).path("/mirror/a.tsv");
+            then.status(200).body("from the mirror\n");
+        });
+        let unused = server.mock(|when, then| {
+            when.method(GET).path("/other/a.tsv");
+            then.status(200).body("from another mirror\n");
+        });
+        let dir = tempdir().unwrap();
+        let filename = dir.path().join("a.tsv");
+        let urls = ["/primary/a.tsv", "/mirror/a.tsv", "/other/a.tsv"].map(|path| server.url(path));
+        let mut downloads = Downloads::new();
+        downloads
+            .add_with_mirrors(&urls, Some(&filename.to_string_lossy()), false)
+            .unwrap();
+        downloads.retrieve(None, None, false, None).await.unwrap();
+        missing.assert();
+        mirror.assert();
+        unused.assert_hits(0);
+        assert_eq!(fs::read_to_string(&filename).unwrap(), "from the mirror\n");
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_failed_overwrite() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/a.tsv");
+            then.status(200).body("new a\n");
+        });
+        server.mock(|when, then| {
+            when.method(GET).path("/b.tsv");
+            then.status(404);
+        });
+        let dir = tempdir().unwrap();
+        let a = dir.path().join("a.tsv");
+        let b = dir.path().join("b.tsv");
+        fs::write(&a, "old a\n").unwrap();
+        fs::write(&b, "modified b\n").unwrap();
+        let mut downloads = Downloads::new();
+        for (path, filename) in [("/a.tsv", &a), ("/b.tsv", &b)] {
+            downloads
+                .add(server.url(path), Some(&filename.to_string_lossy()), true)
+                .unwrap();
+        }
+        let failed = downloads
+            .retrieve_with_failures(None, None, false, None)
+            .await
+            .unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(fs::read_to_string(&a).unwrap(), "new a\n");
+        // the file whose download failed keeps its original contents
+        assert_eq!(fs::read_to_string(&b).unwrap(), "modified b\n");
+        assert!(!a.with_extension(".tmp").exists());
+        assert!(!b.with_extension(".tmp").exists());
+    }
+
+    #[test]
+    fn test_download_bars() {
+        let downloads: Vec<Download> = ["data/a.tsv", "data/b.tsv", "raw/c.tsv"]
+            .iter()
+            .map(|name| Download::new(&Url::parse("http://localhost/").unwrap(), name))
+            .collect();
+        let mut labels = HashMap::new();
+        for download in &downloads {
+            let dir = download.filename.split('/').next().unwrap();
+            labels.insert(download.filename.clone(), format!("{} (Test)", dir));
+        }
+        let mut bars = DownloadBars::new(&downloads, &labels).unwrap();
+        bars.finished("data/a.tsv", Some(10));
+        bars.finished("data/b.tsv", None);
+        bars.retrying("raw/c.tsv");
+        let (data, data_bytes) = &bars.bars["data (Test)"];
+        assert_eq!((data.bar.position(), data.bar.length()), (2, Some(2)));
+        assert_eq!(*data_bytes, 10);
+        let (raw, _) = &bars.bars["raw (Test)"];
+        assert_eq!((raw.bar.position(), raw.bar.length()), (0, Some(2)));
+
+        // without labels, there's one bar
+        let bars = DownloadBars::new(&downloads, &HashMap::new()).unwrap();
+        assert_eq!(bars.bars.len(), 1);
+        assert_eq!(bars.bars[""].0.bar.length(), Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_retrieve_case_collision() {
+        let dir = tempdir().unwrap();
+        let mut downloads = Downloads::new();
+        for name in ["results.tsv", "Results.tsv"] {
+            let filename = dir.path().join(name);
+            downloads
+                .add(
+                    format!("http://localhost/{}", name),
+                    Some(&filename.to_string_lossy()),
+                    false,
+                )
+                .unwrap();
+        }
+        let err = downloads
+            .retrieve(None, None, false, None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("differ only by case"));
+        assert!(!dir.path().join("results.tsv").exists());
+    }
+
+    #[test]
+    fn test_large_download_prompt() {
+        const GB: u64 = 1024 * 1024 * 1024;
+        let sizes = vec![
+            ("data/raw".to_string(), 3 * GB),
+            ("data/supplement".to_string(), GB),
+            ("data/raw".to_string(), GB),
+        ];
+        // at or below the threshold (or with none), nothing is asked
+        assert_eq!(large_download_prompt(&sizes, 5 * GB), None);
+        assert_eq!(large_download_prompt(&sizes, 0), None);
+        assert_eq!(large_download_prompt(&[], 5 * GB), None);
+
+        let prompt = large_download_prompt(&sizes, 4 * GB).unwrap();
+        assert_eq!(
+            prompt,
+            "This will download 3 files (5.00 GB), more than 4.00 GB:\n \
+             - data/raw: 2 files (4.00 GB)\n \
+             - data/supplement: 1 file (1.00 GB)\n\
+             Continue?"
+        );
     }
 }
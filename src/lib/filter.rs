@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use glob::Pattern;
+use std::path::Path;
+
+/// Restricts which manifest paths an operation (e.g. push or pull) applies to.
+///
+/// Paths are manifest keys, i.e. relative to the project root. Glob patterns
+/// are matched against the whole key, and `*` also matches `/`, so
+/// `--include '*.bam'` matches BAM files in any directory. An empty filter
+/// matches everything.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathFilter {
+    /// Only include paths within these directories.
+    pub directories: Vec<String>,
+    /// Only include paths matching at least one of these patterns.
+    pub include: Vec<Pattern>,
+    /// Exclude paths matching any of these patterns (takes precedence
+    /// over the directories and include patterns).
+    pub exclude: Vec<Pattern>,
+}
+
+fn parse_patterns(patterns: &[String]) -> Result<Vec<Pattern>> {
+    patterns
+        .iter()
+        .map(|pattern| {
+            Pattern::new(pattern)
+                .map_err(|err| anyhow!("Invalid glob pattern '{}': {}", pattern, err))
+        })
+        .collect()
+}
+
+impl PathFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_directories(directories: Vec<String>) -> Self {
+        PathFilter {
+            directories,
+            ..Default::default()
+        }
+    }
+
+    pub fn with_globs(include: &[String], exclude: &[String]) -> Result<Self> {
+        Ok(PathFilter {
+            directories: Vec::new(),
+            include: parse_patterns(include)?,
+            exclude: parse_patterns(exclude)?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.directories.is_empty() && self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether the manifest path passes the filter.
+    pub fn matches(&self, path: &str) -> bool {
+        let in_directories = self.directories.is_empty()
+            || self
+                .directories
+                .iter()
+                .any(|dir| Path::new(path).starts_with(dir));
+        let included =
+            self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches(path));
+        let excluded = self.exclude.iter().any(|pattern| pattern.matches(path));
+        in_directories && included && !excluded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn globs(include: &[&str], exclude: &[&str]) -> PathFilter {
+        let to_strings =
+            |patterns: &[&str]| -> Vec<String> { patterns.iter().map(|p| p.to_string()).collect() };
+        PathFilter::with_globs(&to_strings(include), &to_strings(exclude)).unwrap()
+    }
+
+    #[test]
+    fn test_empty_filter_matches_all() {
+        let filter = PathFilter::new();
+        assert!(filter.is_empty());
+        assert!(filter.matches("data/raw/file.tsv"));
+        assert!(filter.matches("file.tsv"));
+    }
+
+    #[test]
+    fn test_directory_filter() {
+        let filter = PathFilter::with_directories(vec!["data/raw".to_string()]);
+        assert!(filter.matches("data/raw/file.tsv"));
+        assert!(filter.matches("data/raw/nested/file.tsv"));
+        assert!(!filter.matches("data/supplement/file.tsv"));
+        // prefixes are matched by path component, not by string
+        assert!(!filter.matches("data/raw_v2/file.tsv"));
+    }
+
+    #[test]
+    fn test_include_globs() {
+        let filter = globs(&["*.bam", "*.bai"], &[]);
+        assert!(filter.matches("data/aligned/sample.bam"));
+        assert!(filter.matches("sample.bai"));
+        assert!(!filter.matches("data/raw/sample.fastq.gz"));
+    }
+
+    #[test]
+    fn test_exclude_takes_precedence() {
+        let filter = globs(&["*.bam"], &["data/tmp/*"]);
+        assert!(filter.matches("data/aligned/sample.bam"));
+        assert!(!filter.matches("data/tmp/sample.bam"));
+
+        // everything except the raw directory
+        let filter = globs(&[], &["data/raw/*"]);
+        assert!(filter.matches("data/supplement/big_1.tsv.gz"));
+        assert!(!filter.matches("data/raw/medium.tsv.gz"));
+
+        let mut filter = globs(&[], &["*.tmp"]);
+        filter.directories = vec!["data/raw".to_string()];
+        assert!(filter.matches("data/raw/medium.tsv.gz"));
+        assert!(!filter.matches("data/raw/medium.tsv.tmp"));
+        assert!(!filter.matches("data/supplement/big_1.tsv.gz"));
+    }
+
+    #[test]
+    fn test_invalid_glob() {
+        let result = PathFilter::with_globs(&["data/[".to_string()], &[]);
+        assert!(result.is_err());
+    }
+}
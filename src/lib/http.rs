@@ -0,0 +1,574 @@
+//! Shared HTTP client configuration.
+//!
+//! All requests to remotes go through clients built here, so they share
+//! the same timeouts and proxy settings. By default the standard
+//! `HTTP_PROXY`, `HTTPS_PROXY`, and `NO_PROXY` environment variables are
+//! used; a proxy set with `sdf config --proxy` takes precedence over them.
+//! Requests have no overall timeout unless one is set with
+//! `sdf config --remote-timeout` (downloads, whose clients the downloader
+//! builds itself, are not affected).
+//!
+//! Requests to the same service share one client (see [`client_for()`]),
+//! so a project with many remotes on a service reuses their connections
+//! (over HTTP/2 where the service supports it) rather than setting up TLS
+//! for each remote. Remotes' file listings are fetched at most
+//! [`remote_concurrency()`] at a time (`sdf config --remote-concurrency`).
+
+use anyhow::{anyhow, Context, Result};
+use futures::StreamExt;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use percent_encoding::{AsciiSet, NON_ALPHANUMERIC};
+use reqwest::{Client, ClientBuilder, NoProxy, Proxy, Response, StatusCode};
+use std::collections::HashMap;
+use std::env;
+use std::fmt;
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Characters escaped when a file name is put in a URL: all but the
+/// unreserved ones (RFC 3986).
+pub const FILENAME_ESCAPE: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// How often HTTP/2 connections are pinged, so they stay open between a
+// command's requests.
+const HTTP2_KEEPALIVE: Duration = Duration::from_secs(30);
+
+/// The number of remotes whose file listings are fetched at once, unless
+/// set with `sdf config --remote-concurrency`.
+pub const DEFAULT_REMOTE_CONCURRENCY: usize = 8;
+
+// The proxy from the user's config, if set.
+static PROXY: OnceLock<String> = OnceLock::new();
+
+// The overall request timeout from the user's config, if set.
+static TIMEOUT: OnceLock<Duration> = OnceLock::new();
+
+// The number of remotes fetched at once from the user's config, if set.
+static REMOTE_CONCURRENCY: OnceLock<usize> = OnceLock::new();
+
+// The clients shared by requests to each service, by base URL.
+static CLIENTS: OnceLock<Mutex<HashMap<String, Client>>> = OnceLock::new();
+
+/// Use this proxy for all requests, rather than any in the environment.
+/// This must be called at startup, before any other threads are started:
+/// the proxy is also exported as HTTP_PROXY and HTTPS_PROXY, since the
+/// downloader builds its own clients.
+pub fn set_proxy(proxy: &str) -> Result<()> {
+    Proxy::all(proxy).context(format!("Invalid proxy URL '{}'.", proxy))?;
+    PROXY
+        .set(proxy.to_string())
+        .map_err(|_| anyhow!("Internal error: proxy set twice."))?;
+    env::set_var("HTTP_PROXY", proxy);
+    env::set_var("HTTPS_PROXY", proxy);
+    Ok(())
+}
+
+/// The configured proxy, if any.
+pub fn proxy() -> Option<&'static str> {
+    PROXY.get().map(|proxy| proxy.as_str())
+}
+
+/// Time out requests to remotes that take longer than this. Like
+/// set_proxy(), this must be called at startup.
+pub fn set_timeout(timeout: Duration) -> Result<()> {
+    TIMEOUT
+        .set(timeout)
+        .map_err(|_| anyhow!("Internal error: timeout set twice."))
+}
+
+/// The configured request timeout, if any.
+pub fn timeout() -> Option<Duration> {
+    TIMEOUT.get().copied()
+}
+
+/// Fetch at most this many remotes' file listings at once. Like
+/// set_proxy(), this must be called at startup.
+pub fn set_remote_concurrency(concurrency: usize) -> Result<()> {
+    REMOTE_CONCURRENCY
+        .set(concurrency.max(1))
+        .map_err(|_| anyhow!("Internal error: remote concurrency set twice."))
+}
+
+/// The number of remotes whose file listings are fetched at once.
+pub fn remote_concurrency() -> usize {
+    REMOTE_CONCURRENCY
+        .get()
+        .copied()
+        .unwrap_or(DEFAULT_REMOTE_CONCURRENCY)
+}
+
+fn build_client(proxy: Option<&str>, timeout: Option<Duration>) -> Result<Client> {
+    let mut builder = ClientBuilder::new()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .http2_keep_alive_interval(HTTP2_KEEPALIVE)
+        .http2_keep_alive_while_idle(true);
+    if let Some(timeout) = timeout {
+        builder = builder.timeout(timeout);
+    }
+    // with no explicit proxy, reqwest uses the environment's
+    if let Some(proxy) = proxy {
+        let proxy = Proxy::all(proxy)
+            .context(format!("Invalid proxy URL '{}'.", proxy))?
+            .no_proxy(NoProxy::from_env());
+        builder = builder.proxy(proxy);
+    }
+    builder.build().context("Failed to build the HTTP client.")
+}
+
+/// A client with the shared timeouts and proxy settings.
+pub fn client() -> Result<Client> {
+    build_client(proxy(), timeout())
+}
+
+/// The client for requests to a service's API at `base_url`, built the
+/// first time it is needed and then shared (with its connection pool) by
+/// all remotes of the service.
+pub fn client_for(base_url: &str) -> Result<Client> {
+    let mut clients = CLIENTS
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .map_err(|_| anyhow!("Internal error: HTTP client registry poisoned."))?;
+    if let Some(client) = clients.get(base_url) {
+        return Ok(client.clone());
+    }
+    let client = client()?;
+    clients.insert(base_url.to_string(), client.clone());
+    Ok(client)
+}
+
+/// GET a URL with the shared client for its host.
+pub async fn get(url: &str) -> Result<Response> {
+    let origin = reqwest::Url::parse(url)
+        .map(|url| url.origin().ascii_serialization())
+        .unwrap_or_default();
+    client_for(&origin)?
+        .get(url)
+        .send()
+        .await
+        .map_err(send_error)
+}
+
+/// Download a URL with the shared client and return its MD5, without
+/// saving it.
+pub async fn get_md5(url: &str) -> Result<String> {
+    let response = get(url).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(HttpError::from(status).into());
+    }
+    let mut context = md5::Context::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        context.consume(&chunk?);
+    }
+    Ok(format!("{:x}", context.compute()))
+}
+
+/// Describe a request that could not be sent or answered, e.g. "timeout
+/// after 30s". The URL is left out, since it may hold a token; callers
+/// add it (masked) as context.
+pub fn send_error(err: reqwest::Error) -> anyhow::Error {
+    describe_send_error(err, timeout())
+}
+
+fn describe_send_error(err: reqwest::Error, timeout: Option<Duration>) -> anyhow::Error {
+    if err.is_timeout() {
+        // the reqwest::Error is kept as the cause for is_retryable()
+        let err = anyhow!(err.without_url());
+        match timeout {
+            Some(timeout) => err.context(format!("timeout after {}s", timeout.as_secs())),
+            // only connecting can time out
+            None => err.context(format!(
+                "connection timeout after {}s",
+                CONNECT_TIMEOUT.as_secs()
+            )),
+        }
+    } else {
+        anyhow!(err.without_url())
+    }
+}
+
+/// Hide a token in text that may be logged or shown to the user, such
+/// as a request URL or an error.
+pub fn mask_token(text: &str, token: &str) -> String {
+    if token.is_empty() {
+        text.to_string()
+    } else {
+        text.replace(token, "****")
+    }
+}
+
+/// An unsuccessful response to a remote's request, with the (masked)
+/// URL and the response's body if they are known.
+#[derive(Debug)]
+pub struct HttpError {
+    pub status: StatusCode,
+    pub url: Option<String>,
+    pub body: Option<String>,
+}
+
+impl HttpError {
+    pub fn new(status: StatusCode, url: &str, body: String) -> Self {
+        HttpError {
+            status,
+            url: Some(url.to_string()),
+            body: Some(body),
+        }
+    }
+}
+
+impl From<StatusCode> for HttpError {
+    fn from(status: StatusCode) -> Self {
+        HttpError {
+            status,
+            url: None,
+            body: None,
+        }
+    }
+}
+
+impl fmt::Display for HttpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP Error: {}", self.status)?;
+        if let Some(url) = &self.url {
+            write!(f, "\nurl: {:?}", url)?;
+        }
+        if let Some(body) = &self.body {
+            write!(f, "\n{:?}", body)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+/// The HTTP status code of an error from a remote's request, if it is
+/// one (see [`HttpError`]).
+pub fn error_status(err: &anyhow::Error) -> Option<u16> {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<HttpError>())
+        .map(|http_err| http_err.status.as_u16())
+}
+
+/// Whether a failed request may succeed if it is repeated: server errors
+/// (5xx), rate limiting (429), and failures to connect or timeouts. Other
+/// errors, such as a rejected token (401 or 403), are not retried.
+pub fn is_retryable(err: &anyhow::Error) -> bool {
+    if let Some(status) = error_status(err) {
+        return status == 429 || (500..600).contains(&status);
+    }
+    err.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|cause| cause.is_connect() || cause.is_timeout())
+}
+
+/// The most pages of a listing that are fetched (see [`fetch_pages()`]),
+/// in case a service ignores the page parameters and keeps returning
+/// full pages.
+pub const MAX_PAGES: u32 = 1000;
+
+/// Fetch every page of a paginated listing. The `fetch` closure gets one
+/// page (numbered from 1), returning its items and whether there is a
+/// next page.
+pub async fn fetch_pages<T, F, Fut>(mut fetch: F) -> Result<Vec<T>>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = Result<(Vec<T>, bool)>>,
+{
+    let mut items = Vec::new();
+    for page in 1..=MAX_PAGES {
+        let (page_items, has_next) = fetch(page).await?;
+        let empty = page_items.is_empty();
+        items.extend(page_items);
+        if !has_next || empty {
+            return Ok(items);
+        }
+    }
+    Err(anyhow!(
+        "The remote listing has more than {} pages; it may not support pagination.",
+        MAX_PAGES
+    ))
+}
+
+/// Whether a response's `Link` header has a `rel="next"` link, as
+/// paginated Zenodo listings do.
+pub fn has_next_link(response: &Response) -> bool {
+    response
+        .headers()
+        .get_all(reqwest::header::LINK)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|link| {
+            link.split(';')
+                .skip(1)
+                .any(|param| param.replace(['"', ' '], "") == "rel=next")
+        })
+}
+
+/// How failed requests are retried: up to `max_retries` times, waiting
+/// `initial_delay` before the first retry and doubling the wait after
+/// each. Only errors for which [`is_retryable()`] is true are retried, and
+/// only requests that are safe to repeat should be run with a policy.
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub initial_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 3,
+            initial_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run `request` until it succeeds or the retries run out, returning
+    /// its result and the number of retries used.
+    pub async fn run<T, F, Fut>(&self, mut request: F) -> Result<(T, u32)>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut delay = self.initial_delay;
+        let mut retries = 0;
+        loop {
+            match request().await {
+                Ok(result) => return Ok((result, retries)),
+                Err(err) if !is_retryable(&err) => return Err(err),
+                Err(err) if retries < self.max_retries => {
+                    debug!("request failed ({}); retrying in {:?}", err, delay);
+                    tokio::time::sleep(delay).await;
+                    delay *= 2;
+                    retries += 1;
+                }
+                Err(err) => {
+                    return Err(err.context(format!(
+                        "Request failed after {} retries.",
+                        self.max_retries
+                    )))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::test_utilities::check_error;
+    use httpmock::prelude::*;
+    use std::cell::Cell;
+
+    #[tokio::test]
+    async fn test_build_client_with_proxy() {
+        // the mock server stands in for the proxy, so the request for a
+        // host that does not exist is sent to it
+        let proxy = MockServer::start();
+        let proxied = proxy.mock(|when, then| {
+            when.method(GET).path("/files");
+            then.status(200).body("via proxy");
+        });
+        let client = build_client(Some(&proxy.base_url()), None).unwrap();
+        let response = client
+            .get("http://data.example.invalid/files")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.text().await.unwrap(), "via proxy");
+        proxied.assert();
+
+        assert!(build_client(Some("not a url"), None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_client_for() {
+        let server = MockServer::start();
+        let listing = server.mock(|when, then| {
+            when.method(GET).path("/files");
+            then.status(200).body("[]");
+        });
+        let client = client_for(&server.base_url()).unwrap();
+        let num_clients = CLIENTS.get().unwrap().lock().unwrap().len();
+        // the same service's client is reused
+        let shared = client_for(&server.base_url()).unwrap();
+        assert_eq!(CLIENTS.get().unwrap().lock().unwrap().len(), num_clients);
+        for client in [client, shared] {
+            let response = client.get(server.url("/files")).send().await.unwrap();
+            assert_eq!(response.text().await.unwrap(), "[]");
+        }
+        listing.assert_hits(2);
+    }
+
+    #[tokio::test]
+    async fn test_request_timeout() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/slow");
+            then.status(200).delay(Duration::from_secs(3));
+        });
+        let timeout = Some(Duration::from_secs(1));
+        let client = build_client(None, timeout).unwrap();
+        let err = client
+            .get(server.url("/slow?access_token=s3cret"))
+            .send()
+            .await
+            .unwrap_err();
+        let err = describe_send_error(err, timeout);
+        assert_eq!(err.to_string(), "timeout after 1s");
+    }
+
+    #[tokio::test]
+    async fn test_retry_policy() {
+        let policy = RetryPolicy {
+            max_retries: 2,
+            initial_delay: Duration::from_millis(1),
+        };
+        // fails twice, then succeeds
+        let attempts = Cell::new(0);
+        let request = || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                match attempt {
+                    1 => Err(HttpError::from(StatusCode::SERVICE_UNAVAILABLE).into()),
+                    2 => Err(HttpError::from(StatusCode::TOO_MANY_REQUESTS).into()),
+                    _ => Ok(attempt),
+                }
+            }
+        };
+        assert_eq!(policy.run(request).await.unwrap(), (3, 2));
+
+        let always_fails =
+            || async { Err::<(), _>(HttpError::from(StatusCode::BAD_GATEWAY).into()) };
+        let err = policy.run(always_fails).await.unwrap_err();
+        assert!(err.to_string().contains("after 2 retries"));
+
+        // a rejected token, or an error that isn't from a request, fails
+        // on the first attempt
+        for status in [StatusCode::UNAUTHORIZED, StatusCode::FORBIDDEN] {
+            let attempts = Cell::new(0);
+            let rejected = || {
+                attempts.set(attempts.get() + 1);
+                async { Err::<(), _>(HttpError::from(status).into()) }
+            };
+            let err = policy.run(rejected).await.unwrap_err();
+            assert_eq!(error_status(&err), Some(status.as_u16()));
+            assert_eq!(attempts.get(), 1);
+        }
+        let attempts = Cell::new(0);
+        let fails = || {
+            attempts.set(attempts.get() + 1);
+            async { Err::<(), _>(anyhow!("file not found")) }
+        };
+        check_error(policy.run(fails).await, "file not found");
+        assert_eq!(attempts.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_is_retryable() {
+        assert!(is_retryable(
+            &HttpError::from(StatusCode::INTERNAL_SERVER_ERROR).into()
+        ));
+        assert!(!is_retryable(
+            &HttpError::from(StatusCode::NOT_FOUND).into()
+        ));
+
+        // nothing listens on port 1, so connecting fails
+        let err = reqwest::Client::new()
+            .get("http://127.0.0.1:1/")
+            .send()
+            .await
+            .unwrap_err();
+        assert!(is_retryable(
+            &send_error(err).context("Could not list files.")
+        ));
+
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(GET).path("/slow");
+            then.status(200).delay(Duration::from_secs(2));
+        });
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(100))
+            .build()
+            .unwrap();
+        let err = client.get(server.url("/slow")).send().await.unwrap_err();
+        let err = describe_send_error(err, Some(Duration::from_secs(1)));
+        assert_eq!(err.to_string(), "timeout after 1s");
+        assert!(is_retryable(&err));
+    }
+
+    #[test]
+    fn test_mask_token_and_error_status() {
+        let url = "https://zenodo.org/api/deposit/depositions?access_token=s3cret";
+        assert_eq!(
+            mask_token(url, "s3cret"),
+            "https://zenodo.org/api/deposit/depositions?access_token=****"
+        );
+        assert_eq!(mask_token(url, ""), url);
+
+        let err = anyhow!(HttpError::new(
+            StatusCode::UNAUTHORIZED,
+            &mask_token(url, "s3cret"),
+            String::new()
+        ));
+        assert!(err.to_string().starts_with("HTTP Error: 401 Unauthorized"));
+        assert_eq!(error_status(&err), Some(401));
+        assert_eq!(error_status(&err.context("Could not link.")), Some(401));
+        assert_eq!(error_status(&anyhow!("connection refused")), None);
+        // only typed errors have a status, not ones that mention it
+        assert_eq!(error_status(&anyhow!("HTTP Error: 401 Unauthorized")), None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_pages() {
+        let items = fetch_pages(|page| async move { Ok((vec![page; 2], page < 3)) })
+            .await
+            .unwrap();
+        assert_eq!(items, vec![1, 1, 2, 2, 3, 3]);
+
+        // an empty page ends the listing, even if it claims there's more
+        let items: Vec<u32> = fetch_pages(|_| async { Ok((Vec::new(), true)) })
+            .await
+            .unwrap();
+        assert!(items.is_empty());
+
+        // a listing that never ends is an error
+        let result = fetch_pages(|page| async move { Ok((vec![page], true)) }).await;
+        check_error(result, "more than 1000 pages");
+    }
+
+    #[tokio::test]
+    async fn test_has_next_link() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.path("/next");
+            then.status(200).header(
+                "Link",
+                "<https://z/?page=1>; rel=\"self\", <https://z/?page=2>; rel=\"next\"",
+            );
+        });
+        server.mock(|when, then| {
+            when.path("/last");
+            then.status(200)
+                .header("Link", "<https://z/?page=1>; rel=\"prev\"");
+        });
+        let client = client().unwrap();
+        let response = client.get(server.url("/next")).send().await.unwrap();
+        assert!(has_next_link(&response));
+        let response = client.get(server.url("/last")).send().await.unwrap();
+        assert!(!has_next_link(&response));
+    }
+}
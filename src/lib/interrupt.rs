@@ -0,0 +1,191 @@
+//! Cleanup after an interrupted (Ctrl-C) transfer.
+//!
+//! While a transfer is in flight, it stages what an interruption would
+//! leave behind: partially written files (removed), files moved aside to
+//! be replaced by a download (restored), and pending FigShare uploads
+//! (deleted from the article). Each [`stage`] returns a guard that
+//! unstages it when dropped, i.e. when the transfer finishes or fails
+//! normally. On Ctrl-C, the handler in main.rs calls [`cleanup`] and exits.
+
+use anyhow::Result;
+use colored::*;
+use futures::future::BoxFuture;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
+
+use crate::print_warn;
+
+type CleanupTask = Box<dyn FnOnce() -> BoxFuture<'static, Result<()>> + Send>;
+
+/// Something an interrupted transfer would leave behind.
+pub enum Staged {
+    /// A partially written file or temporary directory, which is removed.
+    Partial(PathBuf),
+    /// A file moved to `backup` while it is replaced, which is moved back
+    /// to `original` (over any partial download).
+    Backup { backup: PathBuf, original: PathBuf },
+    /// An upload started on a remote, which is cancelled by running the
+    /// task (e.g. deleting the pending file).
+    Upload {
+        description: String,
+        cancel: CleanupTask,
+    },
+}
+
+impl Staged {
+    // Undo the staged item, returning a description of what was done.
+    async fn undo(self) -> Result<String> {
+        match self {
+            Staged::Partial(path) => {
+                if path.is_dir() {
+                    fs::remove_dir_all(&path)?;
+                } else if path.exists() {
+                    fs::remove_file(&path)?;
+                }
+                Ok(format!("removed {:?}", path))
+            }
+            Staged::Backup { backup, original } => {
+                if backup.exists() {
+                    fs::rename(&backup, &original)?;
+                }
+                Ok(format!("restored {:?}", original))
+            }
+            Staged::Upload {
+                description,
+                cancel,
+            } => {
+                cancel().await?;
+                Ok(format!("cancelled {}", description))
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct Registry {
+    next_id: u64,
+    staged: BTreeMap<u64, Staged>,
+}
+
+impl Registry {
+    fn add(&mut self, staged: Staged) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.staged.insert(id, staged);
+        id
+    }
+
+    fn remove(&mut self, id: u64) {
+        self.staged.remove(&id);
+    }
+
+    fn take_all(&mut self) -> Vec<Staged> {
+        std::mem::take(&mut self.staged).into_values().collect()
+    }
+}
+
+static REGISTRY: OnceLock<Mutex<Registry>> = OnceLock::new();
+
+fn registry() -> &'static Mutex<Registry> {
+    REGISTRY.get_or_init(|| Mutex::new(Registry::default()))
+}
+
+/// Unstages its item when dropped.
+#[must_use = "the item is unstaged when the guard is dropped"]
+pub struct StagedGuard {
+    id: u64,
+}
+
+impl Drop for StagedGuard {
+    fn drop(&mut self) {
+        if let Ok(mut registry) = registry().lock() {
+            registry.remove(self.id);
+        }
+    }
+}
+
+/// Stage something to be cleaned up if the transfer is interrupted.
+pub fn stage(staged: Staged) -> StagedGuard {
+    let mut registry = registry().lock().unwrap_or_else(|err| err.into_inner());
+    StagedGuard {
+        id: registry.add(staged),
+    }
+}
+
+// Undo staged items, warning about any that fail; returns the number undone.
+async fn undo_all(staged: Vec<Staged>) -> usize {
+    let mut num_undone = 0;
+    for item in staged {
+        match item.undo().await {
+            Ok(done) => {
+                debug!("interrupt cleanup: {}", done);
+                num_undone += 1;
+            }
+            Err(err) => {
+                print_warn!("Cleanup after interruption failed: {}", err);
+            }
+        }
+    }
+    num_undone
+}
+
+/// Clean up everything currently staged, returning the number of items
+/// cleaned up.
+pub async fn cleanup() -> usize {
+    let staged = registry()
+        .lock()
+        .unwrap_or_else(|err| err.into_inner())
+        .take_all();
+    undo_all(staged).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_undo_staged() {
+        let dir = tempdir().unwrap();
+        let partial = dir.path().join("new.tsv");
+        let original = dir.path().join("old.tsv");
+        let backup = dir.path().join("old.tmp");
+        fs::write(&partial, "par").unwrap();
+        fs::write(&original, "partial download").unwrap();
+        fs::write(&backup, "original contents").unwrap();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let flag = cancelled.clone();
+
+        // a local registry, as the global one is shared with other tests
+        let mut registry = Registry::default();
+        registry.add(Staged::Partial(partial.clone()));
+        let done = registry.add(Staged::Partial(dir.path().join("done.tsv")));
+        registry.add(Staged::Backup {
+            backup: backup.clone(),
+            original: original.clone(),
+        });
+        registry.add(Staged::Upload {
+            description: "upload of 'new.tsv'".to_string(),
+            cancel: Box::new(move || {
+                Box::pin(async move {
+                    flag.store(true, Ordering::SeqCst);
+                    Ok(())
+                })
+            }),
+        });
+        registry.remove(done);
+
+        assert_eq!(undo_all(registry.take_all()).await, 3);
+        assert!(!partial.exists());
+        assert!(!backup.exists());
+        assert_eq!(fs::read_to_string(&original).unwrap(), "original contents");
+        assert!(cancelled.load(Ordering::SeqCst));
+        assert!(registry.take_all().is_empty());
+    }
+}
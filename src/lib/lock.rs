@@ -0,0 +1,145 @@
+//! A lock file next to the manifest, held by commands that must not run
+//! concurrently with another sdf process in the same project (e.g.
+//! `sdf link`, which would otherwise let two processes both create a
+//! remote). Ctrl-C exits without dropping the lock, so the handler in
+//! main.rs releases it with [`release_held`].
+
+use anyhow::{anyhow, Context, Result};
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+// The lock files this process holds.
+static HELD: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Remove the lock files this process holds, for when it exits without
+/// dropping them (i.e. on Ctrl-C).
+pub fn release_held() {
+    release(&mut HELD.lock().unwrap_or_else(|err| err.into_inner()));
+}
+
+// Remove the lock files in paths, emptying it.
+fn release(paths: &mut Vec<PathBuf>) {
+    for path in paths.drain(..) {
+        if let Err(err) = fs::remove_file(&path) {
+            debug!("could not remove manifest lock {:?}: {}", path, err);
+        }
+    }
+}
+
+/// The lock on a manifest; released when dropped.
+#[derive(Debug)]
+#[must_use = "the manifest is unlocked when the lock is dropped"]
+pub struct ManifestLock {
+    path: PathBuf,
+}
+
+impl ManifestLock {
+    pub fn path_for(manifest: &Path) -> PathBuf {
+        let mut name = manifest.as_os_str().to_owned();
+        name.push(".lock");
+        PathBuf::from(name)
+    }
+
+    /// Lock the manifest, failing (rather than waiting) if another
+    /// process holds the lock.
+    pub fn acquire(manifest: &Path) -> Result<ManifestLock> {
+        let path = ManifestLock::path_for(manifest);
+        let mut file = match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                let holder = fs::read_to_string(&path).unwrap_or_default();
+                return Err(anyhow!(
+                    "The manifest is locked by another sdf process (PID {}). If no \
+                     other sdf process is running, remove the stale lock file {:?}.",
+                    holder.trim(),
+                    path
+                ));
+            }
+            Err(err) => {
+                return Err(err).context(format!("Could not create lock file {:?}", path));
+            }
+        };
+        write!(file, "{}", std::process::id())?;
+        trace!("acquired manifest lock {:?}", path);
+        HELD.lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(path.clone());
+        Ok(ManifestLock { path })
+    }
+}
+
+impl Drop for ManifestLock {
+    fn drop(&mut self) {
+        let mut held = HELD.lock().unwrap_or_else(|err| err.into_inner());
+        let Some(index) = held.iter().position(|path| path == &self.path) else {
+            // already released by release_held()
+            return;
+        };
+        held.remove(index);
+        if let Err(err) = fs::remove_file(&self.path) {
+            debug!("could not remove manifest lock {:?}: {}", self.path, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_manifest_lock() {
+        let dir = tempdir().unwrap();
+        let manifest = dir.path().join("data_manifest.yml");
+        let lock = ManifestLock::acquire(&manifest).unwrap();
+        assert!(dir.path().join("data_manifest.yml.lock").exists());
+
+        let err = ManifestLock::acquire(&manifest).unwrap_err();
+        assert!(err.to_string().contains("locked by another sdf process"));
+
+        drop(lock);
+        assert!(!dir.path().join("data_manifest.yml.lock").exists());
+        assert!(ManifestLock::acquire(&manifest).is_ok());
+
+        // an interrupted command's lock is released without dropping it,
+        // and dropping it later doesn't remove another process's lock
+        // (HELD is process-wide, so only this test's entry is taken out of
+        // it, rather than calling release_held())
+        let lock = ManifestLock::acquire(&manifest).unwrap();
+        let other = ManifestLock::acquire(&dir.path().join("other.yml")).unwrap();
+        HELD.lock().unwrap().retain(|path| path != &lock.path);
+        release(&mut vec![lock.path.clone()]);
+        assert!(!dir.path().join("data_manifest.yml.lock").exists());
+        assert!(dir.path().join("other.yml.lock").exists());
+        fs::write(dir.path().join("data_manifest.yml.lock"), "1").unwrap();
+        drop(lock);
+        assert!(dir.path().join("data_manifest.yml.lock").exists());
+        drop(other);
+        assert!(!dir.path().join("other.yml.lock").exists());
+    }
+
+    #[test]
+    fn test_release() {
+        let dir = tempdir().unwrap();
+        let first = dir.path().join("first.yml.lock");
+        let second = dir.path().join("second.yml.lock");
+        let unrelated = dir.path().join("unrelated.yml.lock");
+        for path in [&first, &second, &unrelated] {
+            fs::write(path, "1").unwrap();
+        }
+
+        // a lock file that's already gone doesn't stop the rest from
+        // being released
+        let missing = dir.path().join("missing.yml.lock");
+        let mut paths = vec![first.clone(), missing, second.clone()];
+        release(&mut paths);
+        assert!(paths.is_empty());
+        assert!(!first.exists());
+        assert!(!second.exists());
+        assert!(unrelated.exists());
+    }
+}
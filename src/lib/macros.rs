@@ -1,13 +1,19 @@
 #[macro_export]
 macro_rules! print_warn {
     ($($arg:tt)*) => {
-        println!("{}: {}", "Warning".to_string().red().bold(), format!($($arg)*));
+        if $crate::lib::output::json() {
+            eprintln!("{}: {}", "Warning".to_string().red().bold(), format!($($arg)*));
+        } else {
+            println!("{}: {}", "Warning".to_string().red().bold(), format!($($arg)*));
+        }
     };
 }
 
 #[macro_export]
 macro_rules! print_info {
     ($($arg:tt)*) => {
-        println!("{}: {}", "Info".to_string().green().bold(), format!($($arg)*));
+        if !$crate::lib::output::quiet() {
+            println!("{}: {}", "Info".to_string().green().bold(), format!($($arg)*));
+        }
     };
 }
@@ -0,0 +1,59 @@
+//! Output control for `-q/--quiet` (or `SCIDATAFLOW_QUIET`), e.g. for
+//! sdf run inside workflow rules, whose logs would otherwise fill with
+//! progress bar redraws. When quiet, progress bars are hidden,
+//! `print_info!` messages are dropped, and push and pull print a single
+//! summary line. Warnings and errors are still shown.
+//!
+//! With `--json` (push and pull), the summary is printed as JSON instead
+//! (see `TransferReport`). This implies quiet, and warnings are printed
+//! to stderr so that stdout is only the JSON.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub const QUIET_ENV: &str = "SCIDATAFLOW_QUIET";
+
+static QUIET: AtomicBool = AtomicBool::new(false);
+static JSON: AtomicBool = AtomicBool::new(false);
+
+pub fn set_json(json: bool) {
+    JSON.store(json, Ordering::Relaxed);
+    if json {
+        set_quiet(true);
+    }
+}
+
+pub fn json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+pub fn set_quiet(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+pub fn quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Whether a value of `SCIDATAFLOW_QUIET` turns quiet mode on: any value
+/// but an empty one, "0", or "false".
+pub fn quiet_from_env(value: Option<&str>) -> bool {
+    match value.map(|value| value.trim().to_lowercase()) {
+        None => false,
+        Some(value) => !(value.is_empty() || value == "0" || value == "false"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quiet_from_env() {
+        assert!(!quiet_from_env(None));
+        assert!(!quiet_from_env(Some("")));
+        assert!(!quiet_from_env(Some("0")));
+        assert!(!quiet_from_env(Some("False")));
+        assert!(quiet_from_env(Some("1")));
+        assert!(quiet_from_env(Some("yes")));
+    }
+}
@@ -0,0 +1,126 @@
+//! The overwrite semantics shared by `get`, `bulk`, `push`, and `pull`.
+//!
+//! A transfer whose destination (a local file for downloads, a remote
+//! file for uploads) does not exist is always done. If the destination
+//! exists, it is only replaced with `--overwrite` (or with
+//! `default_overwrite` set in the config, which `--no-overwrite`
+//! overrides); otherwise the transfer is skipped and reported, and the
+//! command still succeeds. Local files being replaced are kept as `.tmp`
+//! files until their download finishes (see `Downloads::retrieve()`).
+//! `sdf pull --backup` replaces files too, but first renames each to
+//! `<name>.local.<timestamp>` (see [`backup_local_file`]).
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::lib::utils::pluralize;
+
+/// What to do with a transfer, given whether its destination exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverwriteAction {
+    /// The destination does not exist.
+    Write,
+    /// The destination exists and will be replaced.
+    Replace,
+    /// The destination exists and will be left as is.
+    Skip,
+}
+
+impl OverwriteAction {
+    pub fn new(exists: bool, overwrite: bool) -> Self {
+        match (exists, overwrite) {
+            (false, _) => OverwriteAction::Write,
+            (true, true) => OverwriteAction::Replace,
+            (true, false) => OverwriteAction::Skip,
+        }
+    }
+
+    pub fn transfers(&self) -> bool {
+        *self != OverwriteAction::Skip
+    }
+}
+
+/// Whether to overwrite, given the --overwrite and --no-overwrite flags
+/// and the config's default.
+pub fn resolve_overwrite(overwrite: bool, no_overwrite: bool, default_overwrite: bool) -> bool {
+    if no_overwrite {
+        false
+    } else {
+        overwrite || default_overwrite
+    }
+}
+
+/// Report the files a command skipped because they would be overwritten.
+pub fn print_overwrite_skipped(command: &str, skipped: &[String]) {
+    if skipped.is_empty() {
+        return;
+    }
+    println!(
+        "  Would overwrite (use 'sdf {} --overwrite' to replace): {}",
+        command,
+        pluralize(skipped.len() as u64, "file")
+    );
+    for path in skipped {
+        println!("   - {:}", path);
+    }
+}
+
+/// Rename a local file that is about to be replaced to
+/// `<name>.local.<timestamp>` (adding a counter if that exists too),
+/// returning its new path.
+pub fn backup_local_file(path: &Path) -> Result<PathBuf> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".local.{}", Utc::now().format("%Y%m%dT%H%M%S")));
+    let mut backup = PathBuf::from(&name);
+    let mut counter = 1;
+    while backup.exists() {
+        let mut numbered = name.clone();
+        numbered.push(format!(".{}", counter));
+        backup = PathBuf::from(numbered);
+        counter += 1;
+    }
+    fs::rename(path, &backup)
+        .with_context(|| format!("Could not rename {:?} to {:?}", path, backup))?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_overwrite_action() {
+        assert_eq!(OverwriteAction::new(false, false), OverwriteAction::Write);
+        assert_eq!(OverwriteAction::new(false, true), OverwriteAction::Write);
+        assert_eq!(OverwriteAction::new(true, true), OverwriteAction::Replace);
+        assert_eq!(OverwriteAction::new(true, false), OverwriteAction::Skip);
+        assert!(!OverwriteAction::Skip.transfers());
+
+        assert!(resolve_overwrite(true, false, false));
+        assert!(resolve_overwrite(false, false, true));
+        assert!(!resolve_overwrite(false, true, true));
+        assert!(!resolve_overwrite(false, false, false));
+    }
+
+    #[test]
+    fn test_backup_local_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("a.tsv");
+        fs::write(&path, "first\n").unwrap();
+        let first = backup_local_file(&path).unwrap();
+        assert!(!path.exists());
+        let name = first.file_name().unwrap().to_string_lossy().to_string();
+        assert!(name.starts_with("a.tsv.local."), "{}", name);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first\n");
+
+        // a second backup in the same second doesn't replace the first
+        fs::write(&path, "second\n").unwrap();
+        let second = backup_local_file(&path).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(fs::read_to_string(&first).unwrap(), "first\n");
+        assert_eq!(fs::read_to_string(&second).unwrap(), "second\n");
+    }
+}
@@ -1,13 +1,18 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::thread;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
 use std::time::Duration;
 
+use crate::lib::output::quiet;
+
 // these are separated since some APIs don't overload
 // indicatif bars, but take the same primitives.
 pub const DEFAULT_PROGRESS_STYLE: &str = "{spinner:.green} [{bar:40.green/white}] {pos:>}/{len} ({percent}%) eta {eta_precise:.green} {msg}";
 pub const DEFAULT_PROGRESS_INC: &str = "=> ";
+// as DEFAULT_PROGRESS_STYLE, with the bar's label (e.g. its remote) first
+pub const LABELED_PROGRESS_STYLE: &str = "{spinner:.green} {prefix:.bold} [{bar:40.green/white}] {pos:>}/{len} ({percent}%) eta {eta_precise:.green} {msg}";
+
+// how often the spinner is redrawn while nothing else changes
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
 
 pub fn default_progress_style() -> Result<ProgressStyle, anyhow::Error> {
     let style = ProgressStyle::default_bar()
@@ -16,41 +21,63 @@ pub fn default_progress_style() -> Result<ProgressStyle, anyhow::Error> {
     Ok(style)
 }
 
+pub fn labeled_progress_style() -> Result<ProgressStyle, anyhow::Error> {
+    let style = ProgressStyle::default_bar()
+        .progress_chars(DEFAULT_PROGRESS_INC)
+        .template(LABELED_PROGRESS_STYLE)?;
+    Ok(style)
+}
+
 pub struct Progress {
     pub bar: ProgressBar,
-    stop_spinner: Sender<()>,
-    #[allow(dead_code)]
-    spinner: Option<thread::JoinHandle<()>>,
 }
 
 impl Progress {
     pub fn new(len: u64) -> Result<Progress> {
-        let bar = ProgressBar::new(len);
-        bar.set_style(default_progress_style()?);
-
-        let (tx, rx): (Sender<()>, Receiver<()>) = mpsc::channel();
-
-        let bar_clone = bar.clone();
-        let spinner = thread::spawn(move || loop {
-            if rx.try_recv().is_ok() {
-                break;
-            }
-            bar_clone.tick();
-            thread::sleep(Duration::from_millis(20));
-        });
-        Ok(Progress {
-            bar,
-            stop_spinner: tx,
-            spinner: Some(spinner),
-        })
+        // hidden bars are still updated, but never drawn
+        let bar = if quiet() {
+            ProgressBar::hidden()
+        } else {
+            ProgressBar::new(len)
+        };
+        bar.set_length(len);
+        Ok(Progress::ticking(bar, default_progress_style()?))
+    }
+
+    // indicatif ticks the spinner from its own thread until the bar is
+    // finished or dropped.
+    fn ticking(bar: ProgressBar, style: ProgressStyle) -> Progress {
+        bar.set_style(style);
+        bar.enable_steady_tick(TICK_INTERVAL);
+        Progress { bar }
+    }
+}
+
+// Labeled progress bars drawn together, e.g. one for each remote that a
+// push or pull transfers files to or from.
+pub struct ProgressGroup {
+    multi: MultiProgress,
+}
+
+impl Default for ProgressGroup {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
-impl Drop for Progress {
-    fn drop(&mut self) {
-        self.stop_spinner.send(()).unwrap();
-        if let Some(spinner) = self.spinner.take() {
-            spinner.join().expect("Failed to join spinner thread");
-        }
+impl ProgressGroup {
+    pub fn new() -> Self {
+        let multi = if quiet() {
+            MultiProgress::with_draw_target(ProgressDrawTarget::hidden())
+        } else {
+            MultiProgress::new()
+        };
+        ProgressGroup { multi }
+    }
+
+    pub fn add(&self, label: &str, len: u64) -> Result<Progress> {
+        let bar = self.multi.add(ProgressBar::new(len));
+        bar.set_prefix(label.to_string());
+        Ok(Progress::ticking(bar, labeled_progress_style()?))
     }
 }
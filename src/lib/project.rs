@@ -1,56 +1,282 @@
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
+use colored::*;
 use csv::{ReaderBuilder, StringRecord};
 use dirs;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use serde_derive::{Deserialize, Serialize};
 use serde_yaml;
+use std::cell::Cell;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs::{canonicalize, metadata, rename, File};
 use std::io::{Read, Write};
 use std::path::{Path, PathBuf};
+use std::time::Duration;
 
-use crate::lib::api::figshare::FigShareAPI;
+use crate::lib::api::figshare::{FigShareAPI, FigShareLayout};
+use crate::lib::api::ipfs::IpfsAPI;
 use crate::lib::api::zenodo::ZenodoAPI;
+use crate::lib::checksums::{
+    check_sha256, parse_sha256, unverified_path, verify_download_sha256, ChecksumAlgorithm,
+    ChecksumFile,
+};
+use crate::lib::client::RemoteClient;
 use crate::lib::data::LocalStatusCode;
-use crate::lib::data::{DataCollection, DataFile};
-use crate::lib::download::Downloads;
-use crate::lib::remote::Remote;
-use crate::lib::remote::{authenticate_remote, AuthKeys};
-use crate::lib::utils::{load_file, pluralize, print_status};
+use crate::lib::data::{
+    pull_decision, push_decision, Author, DataCollection, DataFile, MergedFile, MessyFile,
+    PullOptions, PushOptions, RemoteConflict, StatusEntry, TransferDecision, TransferReport,
+};
+use crate::lib::download::{url_filename, Downloadable, Downloads};
+use crate::lib::filter::PathFilter;
+use crate::lib::history::{History, HistoryEntry};
+use crate::lib::lock::ManifestLock;
+use crate::lib::output::{json, quiet};
+use crate::lib::overwrite::{print_overwrite_skipped, resolve_overwrite};
+use crate::lib::remote::{authenticate_remote, Remote, RemoteStatusCode};
+use crate::lib::remote::{AuthKeys, SERVICES};
+use crate::lib::report::{Report, ReportFile, ReportFormat, ReportRemote};
+use crate::lib::utils::{
+    budget_percent, compute_digests, compute_md5, confirm, format_bytes, format_mod_time,
+    hash_file, is_email, is_orcid, load_file, normalize_path, normalize_path_key, parse_bytes,
+    pluralize, print_provenance, sized_summary, spdx_license, strict_paths, without_userinfo,
+};
 #[allow(unused_imports)]
 use crate::{print_info, print_warn};
 
-use super::status::StatusDisplayOptions;
-use super::utils::is_directory;
-
-const MANIFEST: &str = "data_manifest.yml";
+use super::status::{status_report, StatusDisplayOptions, StatusReport};
+use super::utils::{ensure_directory, is_directory};
+
+pub const MANIFEST: &str = "data_manifest.yml";
+/// The manifest of the hidden layout (see `sdf init --hidden`), relative
+/// to the project root. It shares the directory with the history.
+pub const HIDDEN_MANIFEST: &str = ".scidataflow/manifest.yml";
+/// The comment starting a saved manifest, followed by the hash of its
+/// contents (see `DataCollection::manifest_hash()`). The root manifest of
+/// a project with includes has the hash of the merged contents, as printed
+/// by `sdf manifest-hash`.
+pub const MANIFEST_HASH_HEADER: &str = "# manifest-hash:";
+
+/// The manifest of a project rooted at dir, in either layout. It is an
+/// error for both to exist, since which one is used would be ambiguous.
+pub fn manifest_in(dir: &Path) -> Result<Option<PathBuf>> {
+    let standard = dir.join(MANIFEST);
+    let hidden = dir.join(HIDDEN_MANIFEST);
+    match (metadata(&standard).is_ok(), hidden.is_file()) {
+        (true, true) => Err(anyhow!(
+            "Both {:?} and {:?} exist; remove one of them.",
+            standard,
+            hidden
+        )),
+        (true, false) => Ok(Some(standard)),
+        (false, true) => Ok(Some(hidden)),
+        (false, false) => Ok(None),
+    }
+}
 
-pub fn find_manifest(start_dir: Option<&PathBuf>, filename: &str) -> Option<PathBuf> {
+/// Find the manifest of the project containing start_dir (default: the
+/// current directory).
+pub fn find_manifest(start_dir: Option<&PathBuf>) -> Result<Option<PathBuf>> {
     let mut current_dir = match start_dir {
         Some(dir) => dir.to_path_buf(),
         None => env::current_dir().expect("Failed to get current directory"),
     };
 
     loop {
-        let config_path = current_dir.join(filename);
-
-        if metadata(&config_path).is_ok() {
-            return Some(config_path);
+        if let Some(manifest) = manifest_in(&current_dir)? {
+            return Ok(Some(manifest));
         }
 
         match current_dir.parent() {
             Some(parent) => current_dir = parent.to_path_buf(),
-            None => return None,
+            None => return Ok(None),
+        }
+    }
+}
+
+/// The root directory of a manifest's project.
+// The summary printed by sdf track and untrack, e.g. "Tracked 2 files
+// (1 already tracked)."
+fn tracking_summary(verb: &str, changed: usize, unchanged: usize, state: &str) -> String {
+    let summary = format!("{} {}", verb, pluralize(changed as u64, "file"));
+    if unchanged > 0 {
+        format!("{} ({} already {}).", summary, unchanged, state)
+    } else {
+        format!("{}.", summary)
+    }
+}
+
+// Whether a directory name looks autogenerated rather than chosen, e.g.
+// a generic working directory, a temporary directory, or a hash or ID.
+fn looks_autogenerated(name: &str) -> bool {
+    const GENERIC: &[&str] = &[
+        "workdir",
+        "work",
+        "workspace",
+        "tmp",
+        "temp",
+        "build",
+        "checkout",
+        "repo",
+        "src",
+        "project",
+        "data",
+        "home",
+    ];
+    let lower = name.to_lowercase();
+    let is_hex = |s: &str| s.chars().all(|c| c.is_ascii_hexdigit());
+    GENERIC.contains(&lower.as_str())
+        || name.starts_with('.')
+        || lower.starts_with("tmp")
+        || name.chars().all(|c| c.is_ascii_digit())
+        || (name.len() >= 7 && is_hex(name))
+        || (name.len() >= 32 && is_hex(&name.replace('-', "")))
+}
+
+pub fn manifest_root(manifest: &Path) -> &Path {
+    let parent = manifest.parent().unwrap();
+    if manifest.ends_with(HIDDEN_MANIFEST) {
+        parent.parent().unwrap()
+    } else {
+        parent
+    }
+}
+
+// The canonical directory containing a path, without resolving the path
+// itself (which may be a symlink, or not exist).
+fn canonical_parent(path: &Path) -> Result<PathBuf> {
+    let parent = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    canonicalize(parent).context(format!(
+        "Failed to canonicalize path '{}'.",
+        parent.to_string_lossy()
+    ))
+}
+
+// A download URL as saved in the manifest, which is shared: without the
+// user name and password of an FTP (or HTTP) login, which 'sdf pull
+// --urls' then downloads without.
+fn manifest_url(url: &str) -> String {
+    let saved = without_userinfo(url);
+    if saved != url {
+        print_warn!(
+            "The login in {} is not saved in the manifest, so 'sdf pull --urls' \
+             downloads it anonymously.",
+            saved
+        );
+    }
+    saved
+}
+
+// The files (as manifest keys) in a directory of the project, and with
+// recursive, in its subdirectories. Hidden files and directories, and
+// manifests, are left out.
+fn directory_files(path_context: &Path, dir: &str, recursive: bool) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    let full_dir = path_context.join(dir);
+    if !full_dir.is_dir() {
+        return Ok(files);
+    }
+    for entry in std::fs::read_dir(&full_dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name.starts_with('.') || name == MANIFEST {
+            continue;
+        }
+        let key = normalize_path_key(&Path::new(dir).join(&name).to_string_lossy());
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                files.extend(directory_files(path_context, &key, true)?);
+            }
+        } else if file_type.is_file() {
+            files.push(key);
+        }
+    }
+    Ok(files)
+}
+
+const CONFIG_DIR: &str = "scidataflow";
+const CONFIG: &str = "config.yml";
+const AUTHKEYS: &str = "authkeys.yml";
+
+// Legacy (pre-XDG) locations in the home directory.
+const LEGACY_CONFIG: &str = ".scidataflow_config";
+const LEGACY_AUTHKEYS: &str = ".scidataflow_authkeys.yml";
+
+// Resolve the SciDataFlow configuration directory following XDG conventions:
+// $XDG_CONFIG_HOME/scidataflow if set (and absolute), else ~/.config/scidataflow.
+fn resolve_config_dir(xdg_config_home: Option<String>, home: Option<PathBuf>) -> Result<PathBuf> {
+    let base = match xdg_config_home.map(PathBuf::from) {
+        Some(xdg) if xdg.is_absolute() => xdg,
+        _ => home
+            .ok_or_else(|| anyhow!("Cannot load home directory!"))?
+            .join(".config"),
+    };
+    Ok(base.join(CONFIG_DIR))
+}
+
+pub fn config_dir() -> Result<PathBuf> {
+    resolve_config_dir(env::var("XDG_CONFIG_HOME").ok(), dirs::home_dir())
+}
+
+// Whether a legacy dotfile is still to be moved to its new location.
+fn needs_migration(legacy: &Path, new: &Path) -> bool {
+    !new.exists() && legacy.exists()
+}
+
+// Move a legacy dotfile to its new location, if the new file does not exist yet.
+// Returns true if the file was migrated.
+fn migrate_legacy_file(legacy: &Path, new: &Path) -> Result<bool> {
+    if !needs_migration(legacy, new) {
+        return Ok(false);
+    }
+    if let Some(parent) = new.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if rename(legacy, new).is_err() {
+        // e.g. the home and config directories are on different filesystems
+        std::fs::copy(legacy, new)?;
+        std::fs::remove_file(legacy)?;
+    }
+    Ok(true)
+}
+
+// Where a config file is, without migrating it (unlike config_file_path()):
+// the legacy dotfile, if it has not been moved yet.
+fn describe_config_file(legacy: Option<&Path>, new: &Path) -> String {
+    match legacy {
+        Some(legacy) if needs_migration(legacy, new) => format!(
+            "{} (moved to {} on next use)",
+            legacy.display(),
+            new.display()
+        ),
+        _ => new.display().to_string(),
+    }
+}
+
+fn config_file_path(filename: &str, legacy_filename: &str) -> Result<PathBuf> {
+    let path = config_dir()?.join(filename);
+    if let Some(home) = dirs::home_dir() {
+        let legacy = home.join(legacy_filename);
+        if migrate_legacy_file(&legacy, &path)? {
+            print_info!("moved {:?} to {:?}.", legacy, path);
         }
     }
+    Ok(path)
 }
 
+/// The path to the user's SciDataFlow config (e.g. name, email).
 pub fn config_path() -> Result<PathBuf> {
-    let mut config_path: PathBuf =
-        dirs::home_dir().ok_or_else(|| anyhow!("Cannot load home directory!"))?;
-    config_path.push(".scidataflow_config");
-    Ok(config_path)
+    config_file_path(CONFIG, LEGACY_CONFIG)
+}
+
+/// The path to the user's remote authentication keys.
+pub fn authkeys_path() -> Result<PathBuf> {
+    config_file_path(AUTHKEYS, LEGACY_AUTHKEYS)
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
@@ -58,11 +284,113 @@ pub struct User {
     pub name: String,
     pub email: Option<String>,
     pub affiliation: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orcid: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, PartialEq)]
 pub struct Config {
     user: User,
+    // HTTP(S) proxy for all requests (see http.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    proxy: Option<String>,
+    // Overwrite by default in get, bulk, push, and pull (see overwrite.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default_overwrite: Option<bool>,
+    // Timeout in seconds for requests to remotes (see http.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote_timeout: Option<u64>,
+    // The number of remotes fetched at once (see http.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    remote_concurrency: Option<usize>,
+    // If set, 'sdf add' tracks files in linked directories of at least
+    // this many bytes, and 'sdf status' suggests tracking changes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    auto_track_min_size: Option<u64>,
+    // Pulls of more than this many bytes ask for confirmation first (0
+    // never asks; see download.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pull_confirm_size: Option<u64>,
+    // Batch state older than this many hours isn't resumed (see
+    // sync_state.rs).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    resume_max_age: Option<u64>,
+}
+
+impl Config {
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+    pub fn default_overwrite(&self) -> bool {
+        self.default_overwrite.unwrap_or(false)
+    }
+    pub fn remote_timeout(&self) -> Option<Duration> {
+        self.remote_timeout.map(Duration::from_secs)
+    }
+    pub fn remote_concurrency(&self) -> Option<usize> {
+        self.remote_concurrency
+    }
+    pub fn auto_track_min_size(&self) -> Option<u64> {
+        self.auto_track_min_size
+    }
+    pub fn pull_confirm_size(&self) -> Option<u64> {
+        self.pull_confirm_size
+    }
+    pub fn resume_max_age(&self) -> Option<u64> {
+        self.resume_max_age
+    }
+}
+
+// The options of sdf link (see Project::link()).
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct LinkOptions {
+    // the remote's name, rather than the project's title
+    pub name: Option<String>,
+    pub link_only: bool,
+    pub relink: bool,
+    pub figshare_layout: Option<String>,
+    pub record_type: Option<String>,
+}
+
+// The options of sdf bulk (see Project::bulk()). Columns are numbered
+// from 1.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct BulkOptions {
+    // the URL column (the first, by default)
+    pub column: Option<u64>,
+    pub mirror_columns: Vec<u64>,
+    pub sha256_column: Option<u64>,
+    pub header: bool,
+    pub overwrite: bool,
+    pub max_concurrent: Option<usize>,
+}
+
+// Changes to the user's config (see Project::set_config()); fields that
+// are None are left unchanged.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ConfigUpdate {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub affiliation: Option<String>,
+    pub orcid: Option<String>,
+    pub proxy: Option<String>,
+    pub default_overwrite: Option<bool>,
+    pub remote_timeout: Option<u64>,
+    pub remote_concurrency: Option<usize>,
+    pub auto_track_min_size: Option<String>,
+    pub pull_confirm_size: Option<String>,
+    pub resume_max_age: Option<u64>,
+}
+
+// Changes to the project metadata (see Project::set_metadata()); fields
+// that are None are left unchanged.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct MetadataUpdate {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub license: Option<String>,
+    pub size_budget: Option<String>,
+    pub require_metadata: Option<bool>,
 }
 
 // Metadata about *local* project
@@ -76,8 +404,11 @@ pub struct LocalMetadata {
     pub author_name: Option<String>,
     pub email: Option<String>,
     pub affiliation: Option<String>,
+    pub orcid: Option<String>,
     pub title: Option<String>,
     pub description: Option<String>,
+    pub license: Option<String>,
+    pub co_authors: Vec<Author>,
 }
 
 impl LocalMetadata {
@@ -86,26 +417,57 @@ impl LocalMetadata {
             author_name: Some(project.config.user.name.clone()),
             email: project.config.user.email.clone(),
             affiliation: project.config.user.affiliation.clone(),
+            orcid: project.config.user.orcid.clone(),
             title: project.data.metadata.title.clone(),
             description: project.data.metadata.description.clone(),
+            license: project.data.metadata.license.clone(),
+            co_authors: project.data.metadata.authors.clone(),
         }
     }
+
+    // All authors in order: the configured user (if named), then the
+    // co-authors.
+    pub fn authors(&self) -> Vec<Author> {
+        let user = self.author_name.as_ref().map(|name| Author {
+            name: name.clone(),
+            email: self.email.clone(),
+            affiliation: self.affiliation.clone(),
+            orcid: self.orcid.clone(),
+        });
+        user.into_iter().chain(self.co_authors.clone()).collect()
+    }
 }
 
 pub struct Project {
     pub manifest: PathBuf,
     pub data: DataCollection,
     pub config: Config,
+    // whether this command's manifest change is in the history yet
+    recorded: Cell<bool>,
 }
 
 impl Project {
     fn get_manifest() -> Result<PathBuf> {
-        find_manifest(None, MANIFEST).ok_or(anyhow!("SciDataFlow not initialized."))
+        find_manifest(None)?.ok_or(anyhow!("SciDataFlow not initialized."))
     }
 
     pub fn load_config() -> Result<Config> {
-        let config_path = config_path()?;
-        let mut file = File::open(&config_path).map_err(|_| {
+        Project::read_config(&config_path()?)
+    }
+
+    // Load the config from wherever it is, without moving a legacy
+    // dotfile (see config_file_path()), e.g. at startup, before knowing
+    // whether the command only reads.
+    pub fn peek_config() -> Result<Config> {
+        let path = config_dir()?.join(CONFIG);
+        match dirs::home_dir().map(|home| home.join(LEGACY_CONFIG)) {
+            Some(legacy) if needs_migration(&legacy, &path) => Project::read_config(&legacy),
+            _ => Project::read_config(&path),
+        }
+    }
+
+    fn read_config(config_path: &Path) -> Result<Config> {
+        let mut file = File::open(config_path).map_err(|_| {
             anyhow!(
                 "No SciDataFlow config found at \
                                  {:?}. Please set with sdf config --name <NAME> \
@@ -122,6 +484,9 @@ impl Project {
 
     pub fn save_config(config: Config) -> Result<()> {
         let config_path = config_path()?;
+        if let Some(parent) = config_path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
         let serialized_config = serde_yaml::to_string(&config)?;
         std::fs::write(config_path, serialized_config)
             .with_context(|| "Failed to write the configuration to file")?;
@@ -137,90 +502,488 @@ impl Project {
             manifest,
             data,
             config,
+            recorded: Cell::new(false),
         };
         Ok(proj)
     }
 
-    fn get_parent_dir(file: &Path) -> String {
-        file.parent()
-            .and_then(|path| path.file_name())
+    fn get_dir_name(dir: &Path) -> Result<String> {
+        dir.file_name()
             .map(|os_str| os_str.to_string_lossy().into_owned())
-            .unwrap_or_else(|| panic!("invalid project location: is it in root?"))
+            .ok_or_else(|| {
+                anyhow!(
+                    "The project's manifest is in the filesystem root ({:?}); \
+                     move it into a project directory.",
+                    dir
+                )
+            })
     }
 
     // This tries to figure out a good default name to use, e.g. for
     // remote titles or names.
     //
     // The precedence is local metadata in manifest > project directory
-    pub fn name(&self) -> String {
+    pub fn name(&self) -> Result<String> {
         if let Some(t) = &self.data.metadata.title {
-            return t.to_string();
+            return Ok(t.to_string());
         }
-        Project::get_parent_dir(&self.manifest)
+        Project::get_dir_name(&self.path_context())
     }
 
-    pub fn init(name: Option<String>) -> Result<()> {
-        // the new manifest should be in the present directory
-        let manifest: PathBuf = PathBuf::from(MANIFEST);
-        if manifest.exists() {
+    // The name for a new remote, when none is given. Directory names that
+    // look autogenerated (e.g. 'workdir', or a CI checkout named by a hash)
+    // would make poor titles for a public record, so these require a name.
+    fn remote_name(&self) -> Result<String> {
+        let name = self.name()?;
+        if self.data.metadata.title.is_none() && looks_autogenerated(&name) {
             return Err(anyhow!(
-                "Project already initialized. Manifest file already exists."
+                "No project title is set, and the directory name '{}' doesn't look \
+                 like a project name.\nUse 'sdf link --name <NAME>', or set a title \
+                 with 'sdf metadata --title <TITLE>'.",
+                name
             ));
-        } else {
-            // TODO could pass metadata parameters here
-            let mut data = DataCollection::new();
-            if let Some(name) = name {
-                data.metadata.title = Some(name);
+        }
+        Ok(name)
+    }
+
+    // Create a manifest in the present directory. Projects nested inside
+    // another project are refused unless force is set, since commands run
+    // in the nested project would silently use its manifest, not the
+    // parent's. With hidden, the manifest is .scidataflow/manifest.yml
+    // rather than data_manifest.yml.
+    pub fn init(name: Option<String>, force: bool, hidden: bool) -> Result<()> {
+        let manifest = PathBuf::from(if hidden { HIDDEN_MANIFEST } else { MANIFEST });
+        // the new manifest should be in the present directory
+        if let Some(existing) = manifest_in(Path::new("."))? {
+            if !force {
+                return Err(anyhow!(
+                    "Project already initialized. Manifest file already exists.\n\
+                     Use 'sdf init --force' to rebuild it, keeping the entries that \
+                     can be read (the manifest is backed up first)."
+                ));
             }
-            let config = Project::load_config()?;
-            let proj = Project {
-                manifest,
-                data,
-                config,
-            };
-            // save to create the manifest
-            proj.save()?;
+            if !existing.ends_with(&manifest) {
+                return Err(anyhow!(
+                    "The project's manifest is {:?}; rebuild it with{} --hidden.",
+                    existing,
+                    if hidden { "out" } else { "" }
+                ));
+            }
+            return Project::reinit(&manifest, name);
+        }
+        if let Some(parent) = find_manifest(None)? {
+            let message = format!(
+                "This directory is inside an existing project (manifest {:?}); \
+                 a nested project's manifest would shadow it here.",
+                parent
+            );
+            if !force {
+                return Err(anyhow!(
+                    "{}\nUse 'sdf init --force' to create a nested project anyway.",
+                    message
+                ));
+            }
+            print_warn!("{}", message);
+        }
+        if let Some(dir) = manifest.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        // TODO could pass metadata parameters here
+        let mut data = DataCollection::new();
+        if let Some(name) = name {
+            data.metadata.title = Some(name);
+        }
+        let config = Project::load_config()?;
+        let proj = Project {
+            manifest,
+            data,
+            config,
+            recorded: Cell::new(false),
+        };
+        // save to create the manifest
+        proj.save()
+    }
+
+    // Rebuild a manifest (sdf init --force): back it up to
+    // '<manifest>.backup.<timestamp>', then write a new one with the file
+    // entries, remotes, and settings that can be read from it (see
+    // DataCollection::salvage()), and the files in its directories that
+    // are missing from it, hashed afresh (see rescan()). This is a way to
+    // recover a manifest that no longer loads.
+    fn reinit(manifest: &Path, name: Option<String>) -> Result<()> {
+        let contents = std::fs::read_to_string(manifest)
+            .context(format!("Failed to read the manifest {:?}.", manifest))?;
+        // a second rebuild in the same second mustn't replace the backup
+        let stamp = Utc::now().format("%Y%m%dT%H%M%S").to_string();
+        let mut backup = PathBuf::from(format!("{}.backup.{}", manifest.display(), stamp));
+        let mut num_backups = 1;
+        while backup.exists() {
+            backup = PathBuf::from(format!(
+                "{}.backup.{}.{}",
+                manifest.display(),
+                stamp,
+                num_backups
+            ));
+            num_backups += 1;
+        }
+        std::fs::copy(manifest, &backup)
+            .context(format!("Failed to back up the manifest to {:?}.", backup))?;
+        print_info!("Backed up {:?} to {:?}.", manifest, backup);
+
+        let (mut data, num_dropped, dropped_paths) = match DataCollection::salvage(&contents) {
+            Ok(salvaged) => salvaged,
+            Err(err) => {
+                print_warn!("No entries could be salvaged: {:#}", err);
+                (DataCollection::new(), 0, Vec::new())
+            }
+        };
+        if !data.includes.is_empty() {
+            // the sub-manifests are saved along with the root, so they
+            // must be loaded so as not to lose their entries
+            Project::load_includes(manifest, &mut data)?;
+        }
+        if let Some(name) = name {
+            data.metadata.title = Some(name);
+        }
+        let mut proj = Project {
+            manifest: manifest.to_path_buf(),
+            data,
+            config: Project::load_config()?,
+            recorded: Cell::new(false),
+        };
+        let rescanned = proj.rescan(&dropped_paths)?;
+        proj.save()?;
+
+        let path_context = proj.path_context();
+        let num_missing = proj
+            .data
+            .files
+            .values()
+            .filter(|data_file| !data_file.is_alive(&path_context))
+            .count();
+        println!(
+            "Rebuilt {:?} with {} and {}.",
+            manifest,
+            pluralize(proj.data.files.len() as u64, "file"),
+            pluralize(proj.data.remotes.len() as u64, "remote")
+        );
+        if num_dropped > 0 {
+            print_warn!(
+                "Left out unreadable file entries and remotes: {} (see {:?}).",
+                num_dropped,
+                backup
+            );
+        }
+        if !rescanned.is_empty() {
+            println!(
+                "Added {} found in the project's directories, with fresh MD5s \
+                 (track them with 'sdf track' if needed):",
+                pluralize(rescanned.len() as u64, "file")
+            );
+            for path in &rescanned {
+                println!(" - {}", path);
+            }
+        }
+        if num_missing > 0 {
+            print_warn!(
+                "{} in the manifest no longer {}; see 'sdf status'.",
+                pluralize(num_missing as u64, "file"),
+                if num_missing == 1 { "exists" } else { "exist" }
+            );
         }
         Ok(())
     }
 
+    // Add the files in the project's tracked directories that are not in
+    // the manifest, computing their MD5s afresh, for a rebuilt manifest
+    // (see reinit()). These are the files of the entries that could not
+    // be read (dropped_paths), and the other files in linked directories
+    // (recursively) and in the directories of files in the manifest.
+    // Returns the added files.
+    fn rescan(&mut self, dropped_paths: &[String]) -> Result<Vec<String>> {
+        let path_context = self.path_context();
+        let mut candidates: BTreeSet<String> = dropped_paths.iter().cloned().collect();
+        for dir in self.data.remotes.keys() {
+            candidates.extend(directory_files(&path_context, dir, true)?);
+        }
+        let dirs: BTreeSet<String> = self
+            .data
+            .files
+            .keys()
+            .chain(dropped_paths)
+            .filter_map(|path| Path::new(path).parent())
+            .map(|dir| dir.to_string_lossy().to_string())
+            // the project root holds more than data
+            .filter(|dir| !dir.is_empty())
+            .collect();
+        for dir in &dirs {
+            candidates.extend(directory_files(&path_context, dir, false)?);
+        }
+
+        let mut added = Vec::new();
+        for path in candidates {
+            let full_path = path_context.join(&path);
+            let known = self.data.files.contains_key(&path)
+                || self.data.record_files.contains(&path)
+                || self.data.includes.contains(&path);
+            if known || !full_path.is_file() {
+                continue;
+            }
+            let digests = hash_file(&full_path, false)?
+                .ok_or_else(|| anyhow!("Could not compute the MD5 of '{}'.", path))?;
+            let data_file = DataFile::with_md5(path.clone(), digests.md5, None, &path_context)?;
+            self.register(data_file)?;
+            added.push(path);
+        }
+        Ok(added)
+    }
+
     // TODO could add support for other metadata here
-    pub fn set_metadata(
-        &mut self,
-        title: &Option<String>,
-        description: &Option<String>,
-    ) -> Result<()> {
+    // Set the project metadata. A license is also set on the records of
+    // the linked remotes that have them (Zenodo and FigShare); remotes
+    // that can't be updated are warned about. A size budget is a size
+    // like '100GB' (see parse_bytes()).
+    pub async fn set_metadata(&mut self, update: &MetadataUpdate) -> Result<()> {
+        let MetadataUpdate {
+            title,
+            description,
+            license,
+            size_budget,
+            require_metadata,
+        } = update;
         if let Some(new_title) = title {
             self.data.metadata.title = Some(new_title.to_string());
         }
         if let Some(new_description) = description {
             self.data.metadata.description = Some(new_description.to_string());
         }
-        self.save()
+        if let Some(size_budget) = size_budget {
+            // a budget of 0 removes it
+            self.data.metadata.size_budget =
+                Some(parse_bytes(size_budget)?).filter(|size| *size > 0);
+        }
+        if let Some(require_metadata) = require_metadata {
+            self.data.metadata.require_metadata = *require_metadata;
+        }
+        let license = license.as_deref().map(spdx_license).transpose()?;
+        if let Some(license) = &license {
+            self.data.metadata.license = Some(license.clone());
+        }
+        self.save()?;
+        if let Some(license) = license {
+            self.propagate_license(&license).await;
+        }
+        Ok(())
     }
 
-    pub fn set_config(
-        name: &Option<String>,
-        email: &Option<String>,
-        affiliation: &Option<String>,
-    ) -> Result<()> {
+    // With the require_metadata policy (see 'sdf metadata
+    // --require-metadata'), check that the title, author, and license are
+    // set, so link and push fail before anything is created on a remote.
+    pub fn check_required_metadata(&self) -> Result<()> {
+        if !self.data.metadata.require_metadata {
+            return Ok(());
+        }
+        let metadata = &self.data.metadata;
+        let mut missing = Vec::new();
+        if metadata.title.as_deref().unwrap_or("").trim().is_empty() {
+            missing.push("title: set it with 'sdf metadata --title <TITLE>'");
+        }
+        if self.config.user.name.trim().is_empty() {
+            missing.push("author: set it with 'sdf config --name <NAME>'");
+        }
+        if metadata.license.is_none() {
+            missing.push("license: set it with 'sdf metadata --license <SPDX-ID>'");
+        }
+        if missing.is_empty() {
+            return Ok(());
+        }
+        Err(anyhow!(
+            "The project requires metadata (require_metadata) that is not set:\n  - {}",
+            missing.join("\n  - ")
+        ))
+    }
+
+    async fn propagate_license(&mut self, license: &str) {
+        let mut dirs: Vec<String> = self.data.remotes.keys().cloned().collect();
+        dirs.sort();
+        for dir in dirs {
+            let remote = self
+                .data
+                .remotes
+                .get_mut(&dir)
+                .expect("remote of a listed directory");
+            // only FigShare and Zenodo remotes have records with a license
+            if remote.record_type().is_none() {
+                continue;
+            }
+            let result = match authenticate_remote(remote) {
+                Ok(()) => remote.update_license(license).await,
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(()) => println!(
+                    "Set the license of '{}' on {} to '{}'.",
+                    dir,
+                    remote.name(),
+                    license
+                ),
+                Err(err) => print_warn!(
+                    "Could not set the license of '{}' on {}: {:#}",
+                    dir,
+                    remote.name(),
+                    err
+                ),
+            }
+        }
+    }
+
+    // Show where the config and authentication keys are, without moving
+    // legacy dotfiles (this only reads).
+    pub fn show_config_paths() -> Result<()> {
+        let dir = config_dir()?;
+        let home = dirs::home_dir();
+        for (label, filename, legacy_filename) in [
+            ("config", CONFIG, LEGACY_CONFIG),
+            ("authentication keys", AUTHKEYS, LEGACY_AUTHKEYS),
+        ] {
+            let legacy = home.as_ref().map(|home| home.join(legacy_filename));
+            println!(
+                "{}: {}",
+                label,
+                describe_config_file(legacy.as_deref(), &dir.join(filename))
+            );
+        }
+        Ok(())
+    }
+
+    // List the services directories can be linked to, how fully each is
+    // supported, and whether a token is stored for it.
+    pub fn list_services() -> Result<()> {
+        let auth_keys = AuthKeys::new();
+        println!("{:<10} {:<10} token", "service", "support");
+        for service in &SERVICES {
+            let token = match (auth_keys.contains(service.key), service.token_required) {
+                (true, _) => "stored",
+                (false, true) => "not stored",
+                (false, false) => "not stored (optional)",
+            };
+            println!("{:<10} {:<10} {}", service.key, service.support, token);
+        }
+        println!(
+            "\nLink a directory with 'sdf link <DIR> <SERVICE> <KEY>'; tokens are \
+             stored in {}.",
+            authkeys_path()?.display()
+        );
+        Ok(())
+    }
+
+    // Make a minimal, read-only authenticated request to a service with
+    // the stored token, to check the token and network before linking.
+    pub async fn test_remote(service: &str) -> Result<()> {
+        let name = "connection test";
+        let remote = match service.to_lowercase().as_str() {
+            "figshare" => Remote::FigShareAPI(FigShareAPI::new(name, None)?),
+            "zenodo" => Remote::ZenodoAPI(ZenodoAPI::new(name, None)?),
+            "ipfs" => Remote::IpfsAPI(IpfsAPI::new(name, None)?),
+            _ => return Err(anyhow!("Service '{}' is not supported!", service)),
+        };
+        let summary = remote
+            .check_connection()
+            .await
+            .context(format!("Connection to {} failed.", remote.name()))?;
+        print_info!("Connection to {} succeeded; {}.", remote.name(), summary);
+        Ok(())
+    }
+
+    pub fn set_config(update: &ConfigUpdate) -> Result<()> {
+        let ConfigUpdate {
+            name,
+            email,
+            affiliation,
+            orcid,
+            proxy,
+            default_overwrite,
+            remote_timeout,
+            remote_concurrency,
+            auto_track_min_size,
+            pull_confirm_size,
+            resume_max_age,
+        } = update;
         let mut config = Project::load_config().unwrap_or_else(|_| Config {
             user: User {
                 name: "".to_string(),
                 email: None,
                 affiliation: None,
+                orcid: None,
             },
+            proxy: None,
+            default_overwrite: None,
+            remote_timeout: None,
+            remote_concurrency: None,
+            auto_track_min_size: None,
+            pull_confirm_size: None,
+            resume_max_age: None,
         });
         info!("read config: {:?}", config);
         if let Some(new_name) = name {
             config.user.name = new_name.to_string();
         }
         if let Some(new_email) = email {
-            config.user.email = Some(new_email.to_string());
+            // an empty email removes it
+            if !new_email.is_empty() && !is_email(new_email) {
+                return Err(anyhow!(
+                    "Invalid email '{}'; use the form 'name@example.edu'.",
+                    new_email
+                ));
+            }
+            config.user.email = Some(new_email.to_string()).filter(|email| !email.is_empty());
         }
         if let Some(new_affiliation) = affiliation {
             config.user.affiliation = Some(new_affiliation.to_string());
         }
+        if let Some(new_orcid) = orcid {
+            // an empty ORCID removes it
+            if !new_orcid.is_empty() && !is_orcid(new_orcid) {
+                return Err(anyhow!(
+                    "Invalid ORCID iD '{}'; use the form '0000-0002-1825-0097'.",
+                    new_orcid
+                ));
+            }
+            config.user.orcid = Some(new_orcid.to_string()).filter(|orcid| !orcid.is_empty());
+        }
+        if let Some(new_proxy) = proxy {
+            // an empty proxy removes it
+            config.proxy = if new_proxy.is_empty() {
+                None
+            } else {
+                reqwest::Proxy::all(new_proxy.as_str())
+                    .context(format!("Invalid proxy URL '{}'.", new_proxy))?;
+                Some(new_proxy.to_string())
+            };
+        }
+        if let Some(default_overwrite) = default_overwrite {
+            config.default_overwrite = default_overwrite.then_some(true);
+        }
+        if let Some(remote_timeout) = remote_timeout {
+            // 0 removes the timeout
+            config.remote_timeout = Some(*remote_timeout).filter(|secs| *secs > 0);
+        }
+        if let Some(remote_concurrency) = remote_concurrency {
+            // 0 restores the default
+            config.remote_concurrency = Some(*remote_concurrency).filter(|n| *n > 0);
+        }
+        if let Some(min_size) = auto_track_min_size {
+            // 0 turns auto-tracking off
+            config.auto_track_min_size = Some(parse_bytes(min_size)?).filter(|size| *size > 0);
+        }
+        if let Some(confirm_size) = pull_confirm_size {
+            // 0 never asks
+            config.pull_confirm_size = Some(parse_bytes(confirm_size)?);
+        }
+        if let Some(max_age) = resume_max_age {
+            // 0 restores the default
+            config.resume_max_age = Some(*max_age).filter(|hours| *hours > 0);
+        }
         if config.user.name.is_empty() {
             return Err(anyhow!("Config 'name' not set, and cannot be empty."));
         }
@@ -228,22 +991,49 @@ impl Project {
         Ok(())
     }
 
-    pub fn save(&self) -> Result<()> {
+    fn write_manifest(manifest: &Path, data: &DataCollection, hash: &str) -> Result<()> {
         // Serialize the data
-        let serialized_data = serde_yaml::to_string(&self.data)
+        let serialized_data = serde_yaml::to_string(data)
             .map_err(|err| anyhow::anyhow!("Failed to serialize data manifest: {}", err))?;
 
         // Create the file
-        let mut file = File::create(&self.manifest)
-            .map_err(|err| anyhow::anyhow!("Failed to open file '{:?}': {}", self.manifest, err))?;
+        let mut file = File::create(manifest)
+            .map_err(|err| anyhow::anyhow!("Failed to open file '{:?}': {}", manifest, err))?;
 
-        // Write the serialized data to the file
-        write!(file, "{}", serialized_data)
+        // Write the serialized data to the file, after the hash header
+        let header = format!("{} {}", MANIFEST_HASH_HEADER, hash);
+        write!(file, "{}\n{}", header, serialized_data)
             .map_err(|err| anyhow::anyhow!("Failed to write data manifest: {}", err))?;
 
         Ok(())
     }
 
+    // Save the manifest. If it includes sub-manifests, each file and
+    // remote is saved to the sub-manifest that owns its path. The first
+    // save of a command snapshots the manifests for 'sdf undo'.
+    pub fn save(&self) -> Result<()> {
+        if !self.recorded.replace(true) {
+            let root_manifest = self
+                .manifest
+                .strip_prefix(manifest_root(&self.manifest))
+                .unwrap();
+            let mut manifests = vec![root_manifest.to_string_lossy().to_string()];
+            manifests.extend(self.data.includes.iter().cloned());
+            self.history().record(&manifests)?;
+        }
+        let hash = self.data.manifest_hash()?;
+        if self.data.includes.is_empty() {
+            return Project::write_manifest(&self.manifest, &self.data, &hash);
+        }
+        let path_context = self.path_context();
+        let (root, subs) = self.data.split_includes()?;
+        for (include, sub) in subs {
+            Project::write_manifest(&path_context.join(include), &sub, &sub.manifest_hash()?)?;
+        }
+        // the root manifest's header covers the sub-manifests too
+        Project::write_manifest(&self.manifest, &root, &hash)
+    }
+
     fn load(manifest: &PathBuf) -> Result<DataCollection> {
         let contents = load_file(manifest);
 
@@ -254,13 +1044,189 @@ impl Project {
             ));
         }
 
-        let data = serde_yaml::from_str(&contents)?;
+        let mut data: DataCollection = serde_yaml::from_str(&contents)?;
+        if !data.includes.is_empty() {
+            Project::load_includes(manifest, &mut data)?;
+        }
+        data.check_case_collisions(strict_paths())?;
         Ok(data)
     }
 
+    // Load the sub-manifests listed in the root manifest's include section
+    // and merge them into its DataCollection, re-rooting their paths
+    // relative to the project root.
+    fn load_includes(manifest: &Path, data: &mut DataCollection) -> Result<()> {
+        let path_context = manifest_root(manifest);
+        let root_name = manifest.to_string_lossy().to_string();
+
+        // where each file and remote was declared, for reporting conflicts
+        let mut file_origins: HashMap<String, String> = data
+            .files
+            .keys()
+            .map(|key| (key.clone(), root_name.clone()))
+            .collect();
+        let mut remote_origins: HashMap<String, String> = data
+            .remotes
+            .keys()
+            .map(|key| (key.clone(), root_name.clone()))
+            .collect();
+
+        for include in data.includes.clone() {
+            let sub_manifest = path_context.join(&include);
+            let sub_name = sub_manifest.to_string_lossy().to_string();
+            if !sub_manifest.is_file() {
+                return Err(anyhow!(
+                    "Included manifest '{}' does not exist (listed in '{}').",
+                    sub_name,
+                    root_name
+                ));
+            }
+            let sub: DataCollection = serde_yaml::from_str(&load_file(&sub_manifest))
+                .context(format!("Failed to load included manifest '{}'.", sub_name))?;
+            if !sub.includes.is_empty() {
+                return Err(anyhow!(
+                    "Included manifest '{}' has its own include section; \
+                     nested includes are not supported.",
+                    sub_name
+                ));
+            }
+
+            let dir = PathBuf::from(DataCollection::include_directory(&include));
+            let rerooted = |path: &str| normalize_path_key(&dir.join(path).to_string_lossy());
+            for (_, mut data_file) in sub.files {
+                let key = rerooted(&data_file.path);
+                if let Some(origin) = file_origins.insert(key.clone(), sub_name.clone()) {
+                    return Err(anyhow!(
+                        "File '{}' is declared in both '{}' and '{}'.",
+                        key,
+                        origin,
+                        sub_name
+                    ));
+                }
+                data_file.path = key.clone();
+                data.files.insert(key, data_file);
+            }
+            for (remote_dir, remote) in sub.remotes {
+                let key = rerooted(&remote_dir);
+                if let Some(origin) = remote_origins.insert(key.clone(), sub_name.clone()) {
+                    return Err(anyhow!(
+                        "Remote for directory '{}' is declared in both '{}' and '{}'.",
+                        key,
+                        origin,
+                        sub_name
+                    ));
+                }
+                data.remotes.insert(key, remote);
+            }
+        }
+        Ok(())
+    }
+
     /// Get the absolute path context of the current project.
+    fn history(&self) -> History {
+        History::new(&self.path_context())
+    }
+
+    // Print the hash of the manifest's contents (including any
+    // sub-manifests); see DataCollection::manifest_hash().
+    pub fn manifest_hash(&self) -> Result<()> {
+        println!("{}", self.data.manifest_hash()?);
+        Ok(())
+    }
+
+    // Print the manifest changes that 'sdf undo' can undo, newest first.
+    pub fn log(oneline: bool, json: bool) -> Result<()> {
+        let manifest = Project::get_manifest()?;
+        let mut entries = History::new(manifest_root(&manifest)).entries()?;
+        entries.reverse();
+        if json {
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+            return Ok(());
+        }
+        if entries.is_empty() {
+            println!("No manifest changes recorded.");
+        }
+        for entry in &entries {
+            let time = entry.time.format("%Y-%m-%d %H:%M:%S");
+            if oneline {
+                println!(
+                    "{} {} {}",
+                    entry.id.to_string().yellow(),
+                    time,
+                    entry.command
+                );
+                continue;
+            }
+            println!("{}", format!("change {}", entry.id).yellow());
+            println!("Date:    {} UTC", time);
+            println!("Command: {}", entry.command);
+            for moved in &entry.moves {
+                println!("Moved:   {} -> {}", moved.source, moved.destination);
+            }
+            println!();
+        }
+        Ok(())
+    }
+
+    // Undo the last manifest change. The manifest doesn't need to load, in
+    // case the change broke it.
+    pub fn undo() -> Result<HistoryEntry> {
+        let manifest = Project::get_manifest()?;
+        let _lock = ManifestLock::acquire(&manifest)?;
+        let entry = History::new(manifest_root(&manifest)).undo()?;
+        println!(
+            "Undid '{}' ({} UTC).",
+            entry.command,
+            entry.time.format("%Y-%m-%d %H:%M:%S")
+        );
+        Ok(entry)
+    }
+
+    // Move the manifest to the hidden layout, .scidataflow/manifest.yml,
+    // checking that it loads the same from there (and moving it back if
+    // not). The history's snapshots of the manifest are moved too, so
+    // 'sdf undo' restores them to the new location, and the move is
+    // recorded, so 'sdf undo' can move the manifest back.
+    pub fn migrate_to_hidden() -> Result<()> {
+        let manifest = Project::get_manifest()?;
+        let root = manifest_root(&manifest).to_path_buf();
+        if manifest.ends_with(HIDDEN_MANIFEST) {
+            return Err(anyhow!("The manifest is already at {:?}.", manifest));
+        }
+        let _lock = ManifestLock::acquire(&manifest)?;
+        let data = Project::load(&manifest)?;
+        let hidden = root.join(HIDDEN_MANIFEST);
+        std::fs::create_dir_all(hidden.parent().unwrap())?;
+        rename(&manifest, &hidden)
+            .with_context(|| format!("Could not move {:?} to {:?}", manifest, hidden))?;
+
+        let check = || -> Result<()> {
+            if find_manifest(Some(&root))?.as_ref() != Some(&hidden) {
+                return Err(anyhow!("the moved manifest is not found."));
+            }
+            if Project::load(&hidden)? != data {
+                return Err(anyhow!("the moved manifest loads differently."));
+            }
+            Ok(())
+        };
+        if let Err(err) = check() {
+            rename(&hidden, &manifest)?;
+            return Err(anyhow!(
+                "Migration failed, so {:?} was left in place: {:#}",
+                manifest,
+                err
+            ));
+        }
+        let history = History::new(&root);
+        history.rename_manifest(MANIFEST, HIDDEN_MANIFEST)?;
+        history.record(&[HIDDEN_MANIFEST.to_string()])?;
+        history.record_move(MANIFEST, HIDDEN_MANIFEST)?;
+        println!("Moved {} to {}.", MANIFEST, HIDDEN_MANIFEST);
+        Ok(())
+    }
+
     pub fn path_context(&self) -> PathBuf {
-        let path = self.manifest.parent().unwrap().to_path_buf();
+        let path = manifest_root(&self.manifest).to_path_buf();
         debug!("path_context = {:?}", path);
         path
     }
@@ -272,37 +1238,29 @@ impl Project {
         resolved_path
     }
 
-    pub fn relative_path(&self, path: &Path) -> Result<PathBuf> {
-        let absolute_path = canonicalize(path).context(format!(
-            "Failed to canonicalize path '{}'.",
-            path.to_string_lossy()
-        ))?;
-        //ensure_directory(&absolute_path)?;
-        let path_context = canonicalize(self.path_context()).context(format!(
-            "Failed to canonicalize path '{}'.",
-            path.to_string_lossy()
-        ))?;
-
-        // Compute relative path directly using strip_prefix
-        match absolute_path.strip_prefix(&path_context) {
-            Ok(rel_path) => Ok(rel_path.to_path_buf()),
-            Err(_) => Err(anyhow::anyhow!("Failed to compute relative path")),
-        }
-    }
-
-    pub fn relative_path_string(&self, path: &Path) -> Result<String> {
-        if !path.exists() {
-            Err(anyhow!("Path '{}' does not exist.", path.to_string_lossy()))
-        } else {
-            Ok(self.relative_path(path)?.to_string_lossy().to_string())
-        }
+    // The error for a path outside the project's directory tree, which
+    // can't be in the manifest.
+    fn outside_project_error(&self, path: &Path, path_context: &Path) -> anyhow::Error {
+        let manifest = self
+            .manifest
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(MANIFEST.to_string());
+        anyhow!(
+            "'{}' is not within the project directory '{}' (where {} is); \
+             files must be in the project's directory tree. Move the file \
+             into the project, or run 'sdf init' in a directory that contains it.",
+            path.to_string_lossy(),
+            path_context.to_string_lossy(),
+            manifest
+        )
     }
 
     pub async fn remove(&mut self, files: &Vec<String>) -> Result<()> {
         let mut num_removed = 0;
         for filename in files {
             info!("Removing file '{}'.", filename);
-            let filepath = self.relative_path_string(Path::new(filename))?;
+            let filepath = self.path_key(Path::new(filename))?;
             let removed = self.data.remove(&filepath).await;
             num_removed += removed as i32;
         }
@@ -310,135 +1268,898 @@ impl Project {
         self.save()
     }
 
-    pub async fn status(&mut self, display_options: &StatusDisplayOptions) -> Result<()> {
+    // Get the status entries of each directory, with the remote-only
+    // entries of record files (e.g. a README) split out, and the files
+    // whose copies differ between remotes.
+    async fn status_rows(
+        &mut self,
+        display_options: &StatusDisplayOptions,
+    ) -> Result<(
+        BTreeMap<String, Vec<StatusEntry>>,
+        HashMap<(String, String), Option<String>>,
+        Vec<RemoteConflict>,
+    )> {
         // if include_remotes (e.g. --remotes) is set, we need to merge
         // in the remotes, so we authenticate first and then get them.
         let path_context = &canonicalize(self.path_context())?;
-        let status_rows = self
+        let (mut status_rows, conflicts) = self
             .data
-            .status(path_context, display_options.remotes)
+            .status_with(
+                path_context,
+                display_options.remotes,
+                display_options.deep_verify,
+            )
             .await?;
+        let records = self.data.take_record_statuses(&mut status_rows);
+        Ok((status_rows, records, conflicts))
+    }
 
-        print_status(status_rows, Some(&self.data.remotes), display_options);
-        Ok(())
+    /// The project's status, grouped and counted for display (see
+    /// [`StatusReport`]), without printing anything.
+    pub async fn status_report(
+        &mut self,
+        display_options: &StatusDisplayOptions,
+    ) -> Result<StatusReport> {
+        let (status_rows, _, conflicts) = self.status_rows(display_options).await?;
+        let mut report = status_report(status_rows, Some(&self.data.remotes), display_options)?;
+        report.conflicts = conflicts.iter().map(RemoteConflict::message).collect();
+        Ok(report)
     }
 
-    // TODO
-    pub async fn is_clean(&self) -> Result<bool> {
-        for data_file in self.data.files.values() {
-            let status = data_file.status(&self.path_context()).await?;
-            if status != LocalStatusCode::Current {
-                return Ok(false);
+    /// Print the project's status. Returns the exit code for the counts
+    /// if --exit-code is set (see `FileCounts::exit_code()`), or else 0.
+    pub async fn status(&mut self, display_options: &StatusDisplayOptions) -> Result<i32> {
+        let path_context = &canonicalize(self.path_context())?;
+        let (status_rows, records, conflicts) = self.status_rows(display_options).await?;
+        let num_unavailable = status_rows
+            .values()
+            .flatten()
+            .filter(|entry| entry.remote_unavailable.is_some())
+            .count();
+        let mut report = status_report(status_rows, Some(&self.data.remotes), display_options)?;
+        report.conflicts = conflicts.iter().map(RemoteConflict::message).collect();
+        if display_options.json {
+            println!("{}", report.to_json()?);
+        } else {
+            print!("{}", report.render(display_options));
+            if display_options.remotes {
+                self.print_record_files(&records, path_context).await?;
             }
+            if display_options.provenance {
+                print_provenance(&self.data.files);
+            }
+            self.print_tracking_suggestions();
         }
-        Ok(true)
+        if display_options.strict && num_unavailable > 0 {
+            return Err(anyhow!(
+                "The remote status of {} could not be determined, since a remote is unavailable.",
+                pluralize(num_unavailable as u64, "file")
+            ));
+        }
+        if display_options.exit_code {
+            return Ok(report.counts.exit_code());
+        }
+        Ok(0)
     }
-    /*
-       pub fn stats(&self) -> Result<()> {
-       let mut rows: Vec<StatusEntry> = Vec::new();
-       for (key, data_file) in self.data.files.iter() {
-       let size = format_bytes(data_file.get_size(&self.path_context())?);
-       let cols = vec![key.clone(), size];
-    // TODO use different more general struct?
-    // Or print_fixed_width should be a trait?
-    let entry = StatusEntry {
-    local_status: LocalStatusCode::Invalid,
-    remote_status: RemoteStatusCode::NotExists,
-    tracked: Some(false),
-    remote_service: None,
-    cols: Some(cols) };
-    rows.push(entry);
-    }
-    print_status(rows, None);
-    Ok(())
-    } */
 
-    pub async fn add(&mut self, files: &Vec<String>) -> Result<()> {
-        let mut num_added = 0;
+    // Suggest tracking large untracked files, and untracking small tracked
+    // ones, if the config's auto_track_min_size is set.
+    fn print_tracking_suggestions(&self) {
+        let min_size = match self.config.auto_track_min_size() {
+            Some(min_size) => min_size,
+            None => return,
+        };
+        let (to_track, to_untrack) = self.data.tracking_suggestions(min_size);
+        if to_track.is_empty() && to_untrack.is_empty() {
+            return;
+        }
+        println!(
+            "\nTracking suggestions (auto_track_min_size: {}):",
+            format_bytes(min_size)
+        );
+        if !to_track.is_empty() {
+            println!("  Large untracked files; track with 'sdf track <FILE>':");
+            for data_file in to_track {
+                println!("   - {} ({})", data_file.path, format_bytes(data_file.size));
+            }
+        }
+        if !to_untrack.is_empty() {
+            println!("  Small tracked files that may not need a remote; 'sdf untrack <FILE>':");
+            for data_file in to_untrack {
+                println!("   - {} ({})", data_file.path, format_bytes(data_file.size));
+            }
+        }
+    }
+
+    // Print the status of record files (e.g. a README), which are uploaded
+    // to remotes but are not manifest entries, given their remote MD5s
+    // from DataCollection::take_record_statuses().
+    async fn print_record_files(
+        &self,
+        records: &HashMap<(String, String), Option<String>>,
+        path_context: &Path,
+    ) -> Result<()> {
+        let mut lines = Vec::new();
+        let mut dirs: Vec<&String> = self.data.remotes.keys().collect();
+        dirs.sort();
+        for dir in dirs {
+            let remote = &self.data.remotes[dir];
+            for record in self.data.record_files_for(dir) {
+                let name = Path::new(record)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let local_md5 = compute_md5(&path_context.join(record)).await?;
+                let status = match (local_md5, records.get(&(dir.clone(), name))) {
+                    (None, _) => "no local file",
+                    (Some(_), None) => "not on remote (uploaded on push)",
+                    (Some(local), Some(Some(remote))) if &local == remote => "current",
+                    (Some(_), Some(_)) => "changed (refreshed on push)",
+                };
+                lines.push(format!(
+                    "  {} -> {} ({}): {}",
+                    record,
+                    dir,
+                    remote.name(),
+                    status
+                ));
+            }
+        }
+        if !lines.is_empty() {
+            println!("\n{}", "Record files:".bold());
+            println!("{}", lines.join("\n"));
+        }
+        Ok(())
+    }
+
+    // TODO
+    pub async fn is_clean(&self) -> Result<bool> {
+        for data_file in self.data.files.values() {
+            let status = data_file.status(&self.path_context()).await?;
+            if status != LocalStatusCode::Current {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+    // Print the number and total size of files in the manifest and,
+    // if remote is set, on each linked remote (with its quota, if the
+    // service reports one).
+    pub async fn stats(&mut self, remote: bool) -> Result<()> {
+        let files = self.data.files.values();
+        let total: u64 = files.clone().map(|data_file| data_file.size).sum();
+        let tracked: Vec<_> = files.filter(|data_file| data_file.tracked).collect();
+        let tracked_total: u64 = tracked.iter().map(|data_file| data_file.size).sum();
+        println!(
+            "Manifest: {}, {} ({} tracked, {}).",
+            pluralize(self.data.files.len() as u64, "file"),
+            format_bytes(total),
+            pluralize(tracked.len() as u64, "file"),
+            format_bytes(tracked_total)
+        );
+        if let Some(budget) = self.data.metadata.size_budget {
+            println!(
+                "Size budget: {} of {} used ({}).",
+                format_bytes(total),
+                format_bytes(budget),
+                budget_percent(total, budget)
+            );
+        }
+        if !remote {
+            return Ok(());
+        }
+
+        let (usages, failures) = self.data.remote_usage().await?;
+        println!("Remote storage:");
+        for usage in &usages {
+            let mut line = format!(
+                " {} ({}): {}, {}",
+                usage.directory,
+                usage.service,
+                pluralize(usage.num_files as u64, "file"),
+                format_bytes(usage.total_bytes)
+            );
+            if usage.num_unknown_size > 0 {
+                line.push_str(&format!(" ({} of unknown size)", usage.num_unknown_size));
+            }
+            if let Some(doi) = self.data.remotes[&usage.directory].doi() {
+                line.push_str(&format!("; DOI (once published): {}", doi));
+            }
+            if let Some(quota) = &usage.quota {
+                line.push_str(&format!(
+                    "; account quota: {} used of {}, {} remaining",
+                    format_bytes(quota.used),
+                    format_bytes(quota.total),
+                    format_bytes(quota.remaining())
+                ));
+            }
+            println!("{}", line);
+        }
+        for failure in failures {
+            print_warn!(
+                "Could not fetch files from the {} remote for '{}': {}",
+                failure.service,
+                failure.directory,
+                failure.reason()
+            );
+        }
+        Ok(())
+    }
+
+    // Compare every file in a linked directory with its remote's MD5,
+    // without changing anything. Any discrepancy is an error, so this can
+    // be used in CI (e.g. before publishing).
+    pub async fn verify_remote(&mut self) -> Result<()> {
+        let path_context = self.path_context();
+        let report = self.data.verify_remote(&path_context).await?;
+        let total: usize = report.values().map(|names| names.len()).sum();
+        println!(
+            "Verified {} against {}:",
+            pluralize(total as u64, "file"),
+            pluralize(self.data.remotes.len() as u64, "remote")
+        );
+        let mut discrepancies = 0;
+        for (reconciliation, names) in &report {
+            println!(" {}: {}", reconciliation.label(), names.len());
+            if reconciliation.is_discrepancy() {
+                discrepancies += names.len();
+                for name in names {
+                    println!("   - {}", name);
+                }
+            }
+        }
+        if discrepancies > 0 {
+            return Err(anyhow!(
+                "{} {} not match {} remote.",
+                pluralize(discrepancies as u64, "file"),
+                if discrepancies == 1 { "does" } else { "do" },
+                if discrepancies == 1 { "its" } else { "their" }
+            ));
+        }
+        Ok(())
+    }
+
+    /// Explain what a push and a pull would do with a file in the manifest,
+    /// and why: its MD5s, sizes, and modification times, its statuses, and
+    /// the decisions push and pull make from them (see `sdf why`).
+    pub async fn explain(
+        &mut self,
+        filename: &str,
+        overwrite: bool,
+        backup: bool,
+    ) -> Result<String> {
+        let path_context = self.path_context();
+        let path = self.path_key(Path::new(filename))?;
+        let local = self
+            .data
+            .files
+            .get(&path)
+            .cloned()
+            .ok_or_else(|| anyhow!("'{}' is not in the manifest.", path))?;
+        let linked = self.data.linked_directory(&path);
+        let unmerged = MergedFile {
+            local: Some(local.clone()),
+            remote: None,
+            remote_service: None,
+        };
+        // merged as push and pull merge it, so the remote file is the one
+        // they would see
+        let merged_file = match &linked {
+            Some(dir) => {
+                let (merged, failures) = self.data.merge_available(true).await?;
+                if let Some(failure) = failures.into_iter().find(|f| &f.directory == dir) {
+                    return Err(failure.into_error());
+                }
+                merged
+                    .get(&local.directory()?)
+                    .and_then(|files| files.get(&path))
+                    .cloned()
+                    .unwrap_or(unmerged)
+            }
+            None => unmerged,
+        };
+
+        let md5_and_size = |md5: Option<String>, size: Option<u64>| {
+            format!(
+                "MD5 {}, {}",
+                md5.unwrap_or_else(|| "unknown".to_string()),
+                size.map(format_bytes)
+                    .unwrap_or_else(|| "size unknown".to_string())
+            )
+        };
+        let mut lines = vec![format!(
+            "{} ({})",
+            path,
+            if local.tracked {
+                "tracked"
+            } else {
+                "untracked"
+            }
+        )];
+        lines.push(format!(
+            "  Manifest:      {}",
+            md5_and_size(Some(local.md5.clone()), Some(local.size))
+        ));
+        lines.push(match local.get_mod_time(&path_context) {
+            Ok(mod_time) => format!(
+                "  Local:         {}, modified {}",
+                md5_and_size(
+                    merged_file.local_md5(&path_context).await,
+                    local.get_size(&path_context).ok()
+                ),
+                format_mod_time(mod_time)
+            ),
+            Err(_) => "  Local:         no file".to_string(),
+        });
+        lines.push(match (&linked, &merged_file.remote_service) {
+            (Some(dir), Some(service)) => {
+                format!("  Remote:        {}, linked to '{}'", service, dir)
+            }
+            _ => "  Remote:        none (not in a linked directory)".to_string(),
+        });
+        if linked.is_some() {
+            lines.push(match &merged_file.remote {
+                Some(remote) => format!(
+                    "  Remote file:   {}{}",
+                    md5_and_size(merged_file.remote_md5(), remote.size),
+                    remote
+                        .mod_time
+                        .map(|mod_time| format!(", modified {}", format_mod_time(mod_time)))
+                        .unwrap_or_default()
+                ),
+                None => "  Remote file:   none".to_string(),
+            });
+        }
+        let local_status = local.status(&path_context).await?;
+        lines.push(format!("  Local status:  {:?}", local_status));
+
+        let Some(dir) = linked else {
+            lines.push("  Push would:    nothing (not in a linked directory)".to_string());
+            lines.push("  Pull would:    nothing (not in a linked directory)".to_string());
+            return Ok(lines.join("\n"));
+        };
+        let status = merged_file.status(&path_context).await?;
+        lines.push(format!("  Remote status: {:?}", status));
+        if status == RemoteStatusCode::MessyLocal {
+            let messy = MessyFile::new(&merged_file, &path_context).await?;
+            lines.push(format!("  Messy:         {}", messy.hint()));
+        }
+        let push = if self.data.archives.contains_key(&dir) {
+            format!("nothing ('{}' is pushed as an archive)", dir)
+        } else {
+            match self.data.push_skipped(Some(&local), &path)? {
+                Some(reason) => TransferDecision::Skip(reason).describe("upload", &status),
+                None => push_decision(&status, overwrite).describe("upload", &status),
+            }
+        };
+        let pull = if merged_file.can_download() {
+            pull_decision(&status, overwrite || backup).describe("download", &status)
+        } else {
+            "nothing (not on the remote)".to_string()
+        };
+        lines.push(format!("  Push would:    {}", push));
+        lines.push(format!("  Pull would:    {}", pull));
+        Ok(lines.join("\n"))
+    }
+
+    pub async fn why(&mut self, filename: &str, overwrite: bool, backup: bool) -> Result<()> {
+        println!("{}", self.explain(filename, overwrite, backup).await?);
+        Ok(())
+    }
+
+    // Build a report of the manifest and its remotes (see sdf report).
+    // With check, every manifest file must be current locally, and every
+    // tracked file current on its remote.
+    pub async fn report(&mut self, format: &str, check: bool, output: Option<&Path>) -> Result<()> {
+        let format = ReportFormat::parse(format)?;
+        let verified_at = if check {
+            self.check_report().await?;
+            Some(Utc::now())
+        } else {
+            None
+        };
+
+        let mut files: Vec<&DataFile> = self.data.files.values().collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let report_file = |data_file: &DataFile, source: Option<String>| ReportFile {
+            path: data_file.path.clone(),
+            size: data_file.size,
+            md5: data_file.md5.clone(),
+            source,
+        };
+        let tracked: Vec<ReportFile> = files
+            .iter()
+            .filter(|data_file| data_file.tracked)
+            .map(|data_file| {
+                let service = self
+                    .data
+                    .linked_directory(&data_file.path)
+                    .map(|dir| self.data.remotes[&dir].name().to_string());
+                report_file(data_file, service)
+            })
+            .collect();
+        let url_files = files
+            .iter()
+            .filter(|data_file| data_file.url().is_some())
+            .map(|data_file| report_file(data_file, data_file.url().map(str::to_string)))
+            .collect();
+
+        let mut remotes: Vec<ReportRemote> = self
+            .data
+            .remotes
+            .iter()
+            .map(|(dir, remote)| ReportRemote {
+                directory: dir.clone(),
+                service: remote.name().to_string(),
+                title: remote.title().to_string(),
+                url: remote.landing_url(),
+                doi: remote.doi().map(|doi| doi.to_string()),
+                // including files in subdirectories, as files_in_directory()
+                num_files: tracked
+                    .iter()
+                    .filter(|file| Path::new(&file.path).starts_with(dir))
+                    .count(),
+            })
+            .collect();
+        remotes.sort_by(|a, b| a.directory.cmp(&b.directory));
+
+        let report = Report {
+            title: self.data.metadata.title.clone(),
+            description: self.data.metadata.description.clone(),
+            remotes,
+            tracked,
+            url_files,
+            num_files: files.len(),
+            total_bytes: files.iter().map(|data_file| data_file.size).sum(),
+            verified_at,
+        };
+        let rendered = report.render(format);
+        match output {
+            Some(path) => {
+                std::fs::write(path, rendered)
+                    .context(format!("Could not write report to {:?}.", path))?;
+                println!("Wrote report to {:?}.", path);
+            }
+            None => print!("{}", rendered),
+        }
+        Ok(())
+    }
+
+    // Check that all manifest files are current locally, and that tracked
+    // files are current on their remotes, for a verified report.
+    async fn check_report(&mut self) -> Result<()> {
+        let path_context = &canonicalize(self.path_context())?;
+        let mut statuses = self.data.status(path_context, true).await?;
+        self.data.take_record_statuses(&mut statuses);
+        let mut problems = Vec::new();
+        for (dir, entries) in &statuses {
+            for entry in entries {
+                // skip remote files not in the manifest
+                if entry.manifest_md5.is_none() {
+                    continue;
+                }
+                let path = Path::new(dir).join(&entry.name);
+                if entry.local_status != Some(LocalStatusCode::Current) {
+                    problems.push(format!("  {} is not current locally", path.display()));
+                } else if entry.tracked == Some(true)
+                    && entry.remote_status != Some(RemoteStatusCode::Current)
+                {
+                    problems.push(format!(
+                        "  {} is not pushed or not current on its remote",
+                        path.display()
+                    ));
+                }
+            }
+        }
+        if !problems.is_empty() {
+            problems.sort();
+            return Err(anyhow!(
+                "Cannot verify the report; {} not current (see 'sdf status --remotes'):\n{}",
+                pluralize(problems.len() as u64, "file"),
+                problems.join("\n")
+            ));
+        }
+        Ok(())
+    }
+
+    // Register a file in the manifest, recording who added it (the
+    // rest of its provenance is set by DataCollection::register()).
+    fn register(&mut self, mut data_file: DataFile) -> Result<()> {
+        if data_file.added_by.is_none() && !self.config.user.name.is_empty() {
+            data_file.added_by = Some(self.config.user.name.clone());
+        }
+        self.data.register(data_file)
+    }
+
+    pub async fn add(&mut self, files: &Vec<String>) -> Result<()> {
+        let total_before = self.data.total_size();
+        let mut added = Vec::new();
+        let mut sizes = Vec::new();
         for filepath in files {
-            let filename = self.relative_path_string(Path::new(&filepath.clone()))?;
+            let filename = self.path_key(Path::new(filepath))?;
             let data_file = DataFile::new(filename.clone(), None, &self.path_context()).await?;
             info!("Adding file '{}'.", filename);
-            self.data.register(data_file)?;
-            num_added += 1;
+            sizes.push((filename.clone(), data_file.size));
+            self.register(data_file)?;
+            added.push(filename);
+        }
+        if quiet() {
+            println!("Added {}.", pluralize(added.len() as u64, "file"));
+        } else {
+            println!("Added {}.", sized_summary(&sizes));
         }
-        println!("Added {}.", pluralize(num_added as u64, "file"));
+        self.auto_track(&added);
+        self.warn_size_budget(total_before);
         self.save()
     }
 
-    pub async fn update(&mut self, files: Option<&Vec<String>>) -> Result<()> {
+    // Warn if the manifest's files grew to more than the size budget
+    // (see 'sdf metadata --size-budget').
+    fn warn_size_budget(&self, total_before: u64) {
+        let budget = match self.data.metadata.size_budget {
+            Some(budget) => budget,
+            None => return,
+        };
+        let total = self.data.total_size();
+        if total > budget && total > total_before {
+            print_warn!(
+                "The manifest's files now total {}, over the project's size budget of {} ({} used).",
+                format_bytes(total),
+                format_bytes(budget),
+                budget_percent(total, budget)
+            );
+        }
+    }
+
+    // Track the added files in linked directories that are at least the
+    // config's auto_track_min_size, if set.
+    fn auto_track(&mut self, added: &[String]) {
+        let min_size = match self.config.auto_track_min_size() {
+            Some(min_size) => min_size,
+            None => return,
+        };
+        let mut tracked = Vec::new();
+        for path in added {
+            if self.data.linked_directory(path).is_none() {
+                continue;
+            }
+            if let Some(data_file) = self.data.files.get_mut(path) {
+                if !data_file.tracked && data_file.size >= min_size {
+                    data_file.tracked = true;
+                    tracked.push(path);
+                }
+            }
+        }
+        if !tracked.is_empty() {
+            print_info!(
+                "Tracking {} of at least {} (auto_track_min_size):",
+                pluralize(tracked.len() as u64, "file"),
+                format_bytes(min_size)
+            );
+            for path in tracked {
+                println!(" - {}", path);
+            }
+        }
+    }
+
+    // Add files with only their sizes (sdf add --no-hash), for quickly
+    // registering many large files; 'sdf update' computes their MD5s later.
+    pub fn add_unhashed(&mut self, files: &[String]) -> Result<()> {
         let path_context = self.path_context();
-        let mut num_updated = 0;
+        let total_before = self.data.total_size();
+        let mut added = Vec::new();
+        for filepath in files {
+            let filename = self.path_key(Path::new(filepath))?;
+            let data_file = DataFile::unhashed(filename.clone(), &path_context)?;
+            info!("Adding file '{}' without hashing.", filename);
+            self.register(data_file)?;
+            added.push(filename);
+        }
+        println!(
+            "Added {} without hashing; run 'sdf update' to hash them.",
+            pluralize(files.len() as u64, "file")
+        );
+        self.auto_track(&added);
+        self.warn_size_budget(total_before);
+        self.save()
+    }
 
-        let filepaths: Result<Vec<String>> = match files {
-            None => Ok(self.data.files.keys().cloned().collect::<Vec<String>>()),
-            Some(file_list) => file_list
+    // Add files using the digests in a sidecar checksum file (e.g.
+    // CHECKSUMS.md5), verifying each file against its listed digest. If no
+    // files are given, all files listed in the checksum file are added.
+    // Nothing is added if any file does not match.
+    pub async fn add_with_checksums(&mut self, files: &[String], checksums: &Path) -> Result<()> {
+        let mut checksum_file = ChecksumFile::load(checksums)?;
+        checksum_file.directory = canonical_parent(checksums)?;
+        let paths = if files.is_empty() {
+            checksum_file.paths()
+        } else {
+            files
                 .iter()
-                .map(|f| {
-                    Ok(self
-                        .relative_path(Path::new(&f))?
-                        .to_string_lossy()
-                        .to_string())
+                .map(|file| {
+                    let path = Path::new(file);
+                    Ok(canonical_parent(path)?.join(path.file_name().unwrap_or_default()))
                 })
-                .collect(),
+                .collect::<Result<Vec<PathBuf>>>()?
         };
 
-        let filepaths = filepaths?; // Use ? here to propagate any errors
+        let path_context = self.path_context();
+        let sha256 = checksum_file.algorithm == ChecksumAlgorithm::Sha256;
+        let mut data_files = Vec::new();
+        let mut mismatches = Vec::new();
+        for path in paths {
+            let filename = self.path_key(&path)?;
+            let digests = compute_digests(&path, sha256)
+                .await?
+                .ok_or_else(|| anyhow!("File '{}' does not exist.", filename))?;
+            match checksum_file.expected(&path) {
+                Some(expected) if checksum_file.actual(&digests) != Some(expected) => {
+                    mismatches.push(filename);
+                    continue;
+                }
+                Some(_) => {}
+                None => {
+                    print_warn!(
+                        "File '{}' is not listed in '{}', so it could not be verified.",
+                        filename,
+                        checksums.display()
+                    );
+                }
+            }
+            data_files.push(DataFile::with_md5(
+                filename,
+                digests.md5,
+                None,
+                &path_context,
+            )?);
+        }
 
-        for filepath in filepaths {
-            match self.data.update(Some(&filepath), &path_context).await {
-                Ok(_) => {
-                    info!("Updated file '{}'.", filepath);
-                    num_updated += 1;
+        if !mismatches.is_empty() {
+            return Err(anyhow!(
+                "{} did not match the {} in '{}', so no files were added:\n  {}",
+                pluralize(mismatches.len() as u64, "file"),
+                checksum_file.algorithm.name(),
+                checksums.display(),
+                mismatches.join("\n  ")
+            ));
+        }
+
+        let total_before = self.data.total_size();
+        let num_added = data_files.len();
+        let added: Vec<String> = data_files.iter().map(|file| file.path.clone()).collect();
+        for data_file in data_files {
+            info!("Adding file '{}'.", data_file.path);
+            self.register(data_file)?;
+        }
+        println!(
+            "Added {} (verified against '{}').",
+            pluralize(num_added as u64, "file"),
+            checksums.display()
+        );
+        self.auto_track(&added);
+        self.warn_size_budget(total_before);
+        self.save()
+    }
+
+    // Resolve the arguments to update into manifest keys, grouped by the
+    // directory argument they came from (None for file arguments). A
+    // directory expands to all the manifest entries under it.
+    fn update_targets(&self, files: &[String]) -> Result<Vec<(Option<String>, Vec<String>)>> {
+        let mut targets = Vec::new();
+        for file in files {
+            let path = Path::new(file);
+            if !path.is_dir() {
+                let filepath = self.path_key(path)?;
+                targets.push((None, vec![filepath]));
+                continue;
+            }
+            let dir = self.directory_key(path)?;
+            let mut filepaths: Vec<String> = self
+                .data
+                .files_in_directory(&dir)
+                .iter()
+                .map(|data_file| data_file.path.clone())
+                .collect();
+            if filepaths.is_empty() {
+                return Err(anyhow!(
+                    "No files in the data manifest are in directory '{}'.",
+                    file
+                ));
+            }
+            filepaths.sort();
+            targets.push((Some(file.trim_end_matches('/').to_string()), filepaths));
+        }
+        Ok(targets)
+    }
+
+    pub async fn update(&mut self, files: Option<&Vec<String>>, remote: bool) -> Result<()> {
+        let path_context = self.path_context();
+        let total_before = self.data.total_size();
+        let mut num_updated = 0;
+        let mut updated = Vec::new();
+        // the (path, size) of the files whose MD5 changed
+        let mut changed = Vec::new();
+
+        let targets = match files {
+            None => vec![(None, self.data.files.keys().cloned().collect())],
+            Some(file_list) => self.update_targets(file_list)?,
+        };
+
+        for (dir, filepaths) in targets {
+            let mut num_dir_updated = 0;
+            for filepath in filepaths {
+                // a file deleted from a directory shouldn't stop the others
+                // from being updated; it's shown as deleted by 'sdf status'
+                if dir.is_some()
+                    && !self
+                        .data
+                        .files
+                        .get(&filepath)
+                        .is_some_and(|data_file| data_file.is_alive(&path_context))
+                {
+                    print_warn!("Skipping '{}', which no longer exists.", filepath);
+                    continue;
                 }
-                Err(e) => {
-                    return Err(anyhow!("Failed to update file '{}': {}", filepath, e));
+                match self.data.update(Some(&filepath), &path_context).await {
+                    Ok(md5_changed) => {
+                        info!("Updated file '{}'.", filepath);
+                        num_dir_updated += 1;
+                        if md5_changed {
+                            let data_file = &self.data.files[&normalize_path_key(&filepath)];
+                            changed.push((filepath.clone(), data_file.size));
+                        }
+                        updated.push(filepath);
+                    }
+                    Err(e) => {
+                        return Err(anyhow!("Failed to update file '{}': {}", filepath, e));
+                    }
                 }
             }
+            if let Some(dir) = dir {
+                println!(
+                    "Updated {} in '{}'.",
+                    pluralize(num_dir_updated as u64, "file"),
+                    dir
+                );
+            }
+            num_updated += num_dir_updated;
         }
-        println!("Updated {}.", pluralize(num_updated as u64, "file"));
+        if quiet() {
+            println!("Updated {}.", pluralize(num_updated as u64, "file"));
+        } else {
+            println!(
+                "Updated {}: {} changed, {} already current.",
+                pluralize(num_updated as u64, "file"),
+                changed.len(),
+                num_updated - changed.len()
+            );
+            if !changed.is_empty() {
+                println!("Changed {}.", sized_summary(&changed));
+            }
+        }
+        if remote {
+            let num_recorded = self.data.record_remote_md5s(&updated).await?;
+            println!(
+                "Recorded the remote MD5 of {}.",
+                pluralize(num_recorded as u64, "file")
+            );
+        }
+        self.warn_size_budget(total_before);
         self.save()
     }
 
+    // Check that a directory to be linked exists, returning its relative
+    // path. This warns if the directory has no registered files, since
+    // the remote would be created with nothing to push.
+    pub fn validate_link_directory(&self, dir: &str) -> Result<String> {
+        let path = Path::new(dir);
+        if !path.exists() {
+            return Err(anyhow!(
+                "Directory '{}' does not exist; cannot link it to a remote.",
+                dir
+            ));
+        }
+        ensure_directory(path)?;
+        let rel_dir = self.directory_key(path)?;
+        if self.data.files_in_directory(&rel_dir).is_empty() {
+            print_warn!(
+                "Directory '{}' contains no files registered in the data manifest,\n\
+                 so there is nothing to push to the remote yet. Add files first with:\n  \
+                 $ sdf add {}/<FILE>",
+                dir,
+                dir.trim_end_matches('/')
+            );
+        }
+        Ok(rel_dir)
+    }
+
     pub async fn link(
         &mut self,
         dir: &str,
         service: &str,
         key: &str,
-        name: &Option<String>,
-        link_only: &bool,
+        options: &LinkOptions,
     ) -> Result<()> {
-        // (0) get the relative directory path
-        let dir = self.relative_path_string(Path::new(dir))?;
-
-        // (1) save the auth key to home dir
-        let mut auth_keys = AuthKeys::new();
-        auth_keys.add(service, key);
+        let LinkOptions {
+            name,
+            link_only,
+            relink,
+            figshare_layout,
+            record_type,
+        } = options;
+        // hold the manifest lock for the whole link, so two processes can't
+        // both initialize a remote; reload the manifest in case another
+        // process changed it before we took the lock
+        let _lock = ManifestLock::acquire(&self.manifest)?;
+        self.data = Project::load(&self.manifest)?;
+
+        // (0) check the directory and get its relative path, and the options
+        let dir = self.validate_link_directory(dir)?;
+        let layout = figshare_layout
+            .as_deref()
+            .map(FigShareLayout::parse)
+            .transpose()?;
+        if layout.is_some() && !service.eq_ignore_ascii_case("figshare") {
+            return Err(anyhow!(
+                "--figshare-layout can only be used with FigShare remotes."
+            ));
+        }
+        self.check_required_metadata()?;
+
+        // if relinking, confirm before anything is created on the new remote;
+        // the manifest is only changed once the new remote is set up
+        let relinking = *relink && self.data.remotes.contains_key(&dir);
+        if relinking {
+            let existing = &self.data.remotes[&dir];
+            let prompt = format!(
+                "Directory '{}' is linked to {}; replace it with a {} remote?",
+                dir,
+                existing.name(),
+                service
+            );
+            if !confirm(&prompt)? {
+                return Err(anyhow!("Relink of '{}' cancelled.", dir));
+            }
+        }
 
-        // (2) create a new remote, with a name
+        // (1) create a new remote, with a name
         // Associate a project (either by creating it, or finding it on FigShare)
         let name = if let Some(n) = name {
             n.to_string()
         } else {
-            self.name()
+            self.remote_name()?
         };
 
         let service = service.to_lowercase();
         let mut remote = match service.as_str() {
-            "figshare" => Ok(Remote::FigShareAPI(FigShareAPI::new(&name, None)?)),
+            "figshare" => {
+                let mut figshare = FigShareAPI::new(&name, None)?;
+                figshare.set_layout(layout.unwrap_or_default());
+                Ok(Remote::FigShareAPI(figshare))
+            }
             "zenodo" => Ok(Remote::ZenodoAPI(ZenodoAPI::new(&name, None)?)),
+            "ipfs" => Ok(Remote::IpfsAPI(IpfsAPI::new(&name, None)?)),
             _ => Err(anyhow!("Service '{}' is not supported!", service)),
         }?;
+        if let Some(record_type) = record_type {
+            remote.set_record_type(record_type)?;
+        }
 
-        // (3) authenticate remote
-        authenticate_remote(&mut remote)?;
+        // (2) check the service accepts the token before anything is
+        // created, and only then save it to the home dir
+        remote.set_token(key.to_string())?;
+        remote.validate_token().await?;
+        let mut auth_keys = AuthKeys::new();
+        auth_keys.add(&service, key);
 
         // (4) validate this a proper remote directory (this is
         // also done in register_remote() for caution,
         // but we also want do it here to prevent the situation
         // where self.data.register_remote() fails, but remote_init()
         // is already done.
-        self.data.validate_remote_directory(&dir)?;
+        if !relinking {
+            self.data.validate_remote_directory(&dir)?;
+        }
 
         // (5) initialize the remote (e.g. for FigShare, this
         // checks that the article doesn't exist (error if it
@@ -449,8 +2170,29 @@ impl Project {
         remote.remote_init(local_metadata, *link_only).await?;
 
         // (6) register the remote in the manifest
-        self.data.register_remote(&dir, remote)?;
-        self.save()
+        if relinking {
+            let old = self.data.replace_remote(&dir, remote)?;
+            print_info!(
+                "Relinked '{}' from {} to {}; files on the old remote were not changed.",
+                dir,
+                old.name(),
+                self.data.remotes[&dir].name()
+            );
+        } else {
+            self.data.register_remote(&dir, remote)?;
+        }
+        self.save()?;
+        if let Some(doi) = self.data.remotes[&dir].doi() {
+            print_info!("Your dataset's DOI will be {} once published.", doi);
+        }
+        if layout == Some(FigShareLayout::PerFile) {
+            print_info!(
+                "Each file in '{}' will be its own FigShare article, with its own \
+                 DOI once published.",
+                dir
+            );
+        }
+        Ok(())
     }
 
     pub async fn ls(&mut self) -> Result<()> {
@@ -467,13 +2209,24 @@ impl Project {
     // Move a file within the project.
     //
     // Note: file moving is done within relatively higher project-level API.
-    // The reason why is that we need to access Project::relative_path_string() for
+    // The reason why is that we need to access Project::path_key() for
     // both the source *and* destination; the latter does not exist until after the file
     // has been successfully moved. So the updating is all done on the DataFile
     // directly, since lower interfaces cannot access the relative path.
-    pub async fn mv(&mut self, source: &str, destination: &str) -> Result<()> {
+    //
+    // A tracked file moved into a directory linked to a different remote
+    // stays tracked (to be uploaded there on the next push), unless
+    // `untrack` is set; with `check_remote`, the destination's remote is
+    // checked for an existing copy, as with 'sdf track --check-remote'.
+    pub async fn mv(
+        &mut self,
+        source: &str,
+        destination: &str,
+        untrack: bool,
+        check_remote: bool,
+    ) -> Result<()> {
         let source_path = Path::new(source);
-        let source_path_str = self.relative_path_string(source_path)?;
+        let source_path_str = self.path_key(source_path)?;
         if let Some(file) = self.data.files.remove(&source_path_str) {
             let mut destination_path = PathBuf::from(destination);
 
@@ -489,16 +2242,69 @@ impl Project {
             rename(source, &destination_path).context("Error encountered when moving file.")?;
 
             // update the relative path
-            let relative_destination = self.relative_path_string(destination_path.as_path())?;
+            let relative_destination = self.path_key(destination_path.as_path())?;
 
             // modify the DataFile
             let mut new_file = file.clone();
-            new_file.path = relative_destination;
+            new_file.path = relative_destination.clone();
+
+            // check whether the file has moved between linked directories
+            let mut moved_remotes = false;
+            if new_file.tracked {
+                let source_dir = self.data.linked_directory(&source_path_str);
+                let destination_dir = self.data.linked_directory(&relative_destination);
+                match (source_dir, destination_dir) {
+                    (_, None) => {
+                        new_file.set_untracked()?;
+                        print_info!(
+                            "'{}' is no longer tracked, since its new directory is not \
+                             linked to a remote.",
+                            relative_destination
+                        );
+                    }
+                    (Some(source_dir), Some(destination_dir))
+                        if source_dir != destination_dir && untrack =>
+                    {
+                        new_file.set_untracked()?;
+                        print_info!(
+                            "'{}' is no longer tracked, since it was moved to a directory \
+                             linked to a different remote. The {} remote for '{}' still has \
+                             the file under its old name.",
+                            relative_destination,
+                            self.data.remotes[&source_dir].name(),
+                            source_dir
+                        );
+                    }
+                    (Some(source_dir), Some(destination_dir)) if source_dir != destination_dir => {
+                        moved_remotes = true;
+                        print_warn!(
+                            "'{}' was moved to a directory linked to a different remote.\n\
+                             The {} remote for '{}' still has the file under its old name; \
+                             delete it there if it is no longer needed. The file will be \
+                             uploaded to the {} remote for '{}' on the next 'sdf push'.",
+                            relative_destination,
+                            self.data.remotes[&source_dir].name(),
+                            source_dir,
+                            self.data.remotes[&destination_dir].name(),
+                            destination_dir
+                        );
+                    }
+                    _ => {}
+                }
+            }
 
             // insert it back into the map with the new key
-            self.data.files.insert(destination.to_string(), new_file);
-
-            self.save()
+            self.data
+                .files
+                .insert(relative_destination.clone(), new_file);
+
+            self.save()?;
+            self.history()
+                .record_move(&source_path_str, &relative_destination)?;
+            if moved_remotes && check_remote {
+                self.check_remote_file(&relative_destination).await?;
+            }
+            Ok(())
         } else {
             Err(anyhow!(
                 "Cannot move file '{}' with 'sdf mv' since it is not in the manifest.",
@@ -507,53 +2313,165 @@ impl Project {
         }
     }
 
-    pub async fn get(&mut self, url: &str, filename: Option<&str>, overwrite: bool) -> Result<()> {
+    // Whether get, bulk, push, and pull should overwrite, given their
+    // --overwrite and --no-overwrite flags and the config's default.
+    pub fn overwrite(&self, overwrite: bool, no_overwrite: bool) -> bool {
+        resolve_overwrite(overwrite, no_overwrite, self.config.default_overwrite())
+    }
+
+    /// Download a file and register it with its URL. With `no_download`,
+    /// the file must already exist locally, and is registered without
+    /// downloading it (after checking it against `md5`, if given).
+    pub async fn get(
+        &mut self,
+        url: &str,
+        filename: Option<&str>,
+        overwrite: bool,
+        no_download: bool,
+        md5: Option<&str>,
+        sha256: Option<&str>,
+    ) -> Result<()> {
+        let sha256 = sha256.map(parse_sha256).transpose()?;
+        if no_download {
+            return self
+                .get_existing(url, filename, md5, sha256.as_deref())
+                .await;
+        }
         let mut downloads = Downloads::new();
         let download = downloads.add(url.to_string(), filename, overwrite)?;
         if let Some(dl) = download {
             let filepath = dl.filename.clone();
 
-            // get the file
-            downloads
-                .retrieve(Some("Downloaded '{}'."), None, false)
-                .await?;
+            // get the file; one to be checked is downloaded beside any
+            // existing file, which it only replaces if it matches
+            match &sha256 {
+                None => {
+                    downloads
+                        .retrieve(Some("Downloaded '{}'."), None, false, None)
+                        .await?
+                }
+                Some(expected) => {
+                    let unverified = unverified_path(&filepath);
+                    downloads.download_to(&filepath, &unverified);
+                    downloads.retrieve(None, None, false, None).await?;
+                    verify_download_sha256(Path::new(&unverified), Path::new(&filepath), expected)
+                        .await?;
+                    if !quiet() {
+                        println!("Downloaded and verified '{}'.", filepath);
+                    }
+                }
+            }
 
             // convert to relative path (based on where we are)
-            let filepath = self.relative_path_string(Path::new(&filepath))?;
+            let filepath = self.path_key(Path::new(&filepath))?;
 
             // TODO: should compare MD5s!
-            if !self.data.contains(&filepath).await? {
+            let registered = self.data.registered_key(&filepath, &self.path_context());
+            if let Some(key) = registered {
+                println!(
+                    "File '{}' already existed in \
+                    the manifest, so it was not added.",
+                    &key
+                );
+            } else {
+                let url = manifest_url(url);
                 let data_file =
-                    DataFile::new(filepath.clone(), Some(url), &self.path_context()).await?;
+                    DataFile::new(filepath.clone(), Some(&url), &self.path_context()).await?;
 
                 // Note: we do not use Project::add() since this works off strings.
                 // and we need to pass the URL, etc.
-                self.data.register(data_file)?;
+                self.register(data_file)?;
                 self.save()?;
-            } else {
-                println!(
-                    "File '{}' already existed in \
-                    the manifest, so it was not added.",
-                    &filepath
-                );
             }
-            Ok(())
         } else {
-            Err(anyhow!(
-                "The file at '{}' was not downloaded because it would overwrite a file.\n\
-                    Use 'sdf get <URL> --ovewrite' to overwrite it.",
-                url
-            ))
+            print_overwrite_skipped("get", &[url.to_string()]);
         }
+        Ok(())
     }
 
-    pub async fn bulk(
+    // Register a local file as downloaded from the URL (see get()).
+    async fn get_existing(
         &mut self,
-        filename: &str,
-        column: Option<u64>,
-        header: bool,
-        overwrite: bool,
+        url: &str,
+        filename: Option<&str>,
+        md5: Option<&str>,
+        sha256: Option<&str>,
     ) -> Result<()> {
+        let filename = match filename {
+            Some(filename) => filename.to_string(),
+            None => url_filename(&url.to_string().to_url()?)?,
+        };
+        if !Path::new(&filename).is_file() {
+            return Err(anyhow!(
+                "'{}' does not exist, so it cannot be registered without downloading it.",
+                filename
+            ));
+        }
+        let filepath = self.path_key(Path::new(&filename))?;
+        if let Some(key) = self.data.registered_key(&filepath, &self.path_context()) {
+            return Err(anyhow!(
+                "File '{}' is already in the manifest, so it was not added.",
+                key
+            ));
+        }
+        let url = manifest_url(url);
+        let data_file = DataFile::new(filepath.clone(), Some(&url), &self.path_context()).await?;
+        if let Some(expected) = md5 {
+            if !data_file.md5.eq_ignore_ascii_case(expected) {
+                return Err(anyhow!(
+                    "The MD5 of '{}' ({}) does not match the expected MD5 ({}).",
+                    filepath,
+                    data_file.md5,
+                    expected
+                ));
+            }
+        }
+        if let Some(expected) = sha256 {
+            check_sha256(Path::new(&filename), expected).await?;
+        }
+        self.register(data_file)?;
+        self.save()?;
+        print_info!("Registered '{}' from {}.", filepath, url);
+        Ok(())
+    }
+
+    /// Set a file's download URL, or with `append`, add it as a mirror.
+    pub fn set_url(&mut self, filename: &str, url: &str, append: bool) -> Result<()> {
+        url.to_string().to_url()?;
+        let url = &manifest_url(url);
+        let filepath = self.path_key(Path::new(filename))?;
+        let data_file = self
+            .data
+            .files
+            .get_mut(&filepath)
+            .ok_or_else(|| anyhow!("File '{}' is not in the manifest.", filepath))?;
+        if !append {
+            data_file.urls = vec![url.to_string()];
+            print_info!("Set the URL of '{}' to {}.", filepath, url);
+        } else if data_file.urls.iter().any(|existing| existing == url) {
+            print_info!("'{}' already has the URL {}.", filepath, url);
+            return Ok(());
+        } else {
+            data_file.urls.push(url.to_string());
+            print_info!(
+                "Added {} to the URLs of '{}' ({} in total).",
+                url,
+                filepath,
+                data_file.urls.len()
+            );
+        }
+        self.save()
+    }
+
+    pub async fn bulk(&mut self, filename: &str, options: &BulkOptions) -> Result<()> {
+        let &BulkOptions {
+            column,
+            ref mirror_columns,
+            sha256_column,
+            header,
+            overwrite,
+            max_concurrent,
+        } = options;
         let extension = std::path::Path::new(filename)
             .extension()
             .and_then(std::ffi::OsStr::to_str);
@@ -570,87 +2488,765 @@ impl Project {
             .has_headers(header)
             .from_reader(file);
 
-        // convert 0-indexed to 1; first column is default
-        let column = column.unwrap_or(0) as usize - 1;
+        // convert 1-indexed to 0; first column is default
+        let column_index = |column: u64| match column {
+            0 => Err(anyhow!(
+                "Columns are numbered from 1, so column 0 is invalid."
+            )),
+            column => Ok(column as usize - 1),
+        };
+        let columns: Vec<usize> = std::iter::once(column.unwrap_or(1))
+            .chain(mirror_columns.iter().copied())
+            .map(column_index)
+            .collect::<Result<_>>()?;
+        let sha256_column = sha256_column.map(column_index).transpose()?;
 
         let mut downloads = Downloads::new();
         let mut filepaths = Vec::new();
         let mut urls = Vec::new();
+        // the expected SHA-256 of each download, if any
+        let mut sha256s = Vec::new();
         let mut skipped = Vec::new();
         let mut num_lines = 0;
         for result in reader.records() {
             let record: StringRecord = result?;
-            if let Some(url) = record.get(column) {
+            if let Some(url) = record.get(columns[0]) {
                 num_lines += 1;
-                let url = url.to_string();
-                let download = downloads.add(url.clone(), None, overwrite)?;
+                // an empty SHA-256 cell is not checked
+                let sha256 = sha256_column
+                    .and_then(|column| record.get(column))
+                    .filter(|digest| !digest.is_empty())
+                    .map(parse_sha256)
+                    .transpose()
+                    .context(format!("Invalid SHA-256 for '{}'.", url))?;
+                // the link and any (non-empty) mirrors
+                let file_urls: Vec<String> = columns
+                    .iter()
+                    .filter_map(|column| record.get(*column))
+                    .filter(|url| !url.is_empty())
+                    .map(|url| url.to_string())
+                    .collect();
+                let download = downloads.add_with_mirrors(&file_urls, None, overwrite)?;
                 if let Some(dl) = download {
                     let filepath = dl.filename.clone();
+                    // as with get(), downloads to check only replace
+                    // existing files once they are verified
+                    if sha256.is_some() {
+                        downloads.download_to(&filepath, &unverified_path(&filepath));
+                    }
                     filepaths.push(filepath);
-                    urls.push(url.clone());
+                    urls.push(file_urls);
+                    sha256s.push(sha256);
                 } else {
-                    skipped.push(url.clone());
+                    skipped.push(url.to_string());
                 }
             }
         }
 
         // grab all the files
-        downloads.retrieve(None, None, false).await?;
+        downloads
+            .retrieve(None, None, false, max_concurrent)
+            .await?;
 
         let mut num_added = 0;
         let mut num_already_registered = 0;
-        for (filepath, url) in filepaths.iter().zip(urls.iter()) {
-            let rel_file_path = self.relative_path_string(Path::new(&filepath))?;
-            if !self.data.contains(&rel_file_path).await? {
-                let data_file =
-                    DataFile::new(rel_file_path.clone(), Some(url), &self.path_context()).await?;
-                self.data.register(data_file)?;
+        // downloads that didn't match their SHA-256, which are discarded
+        let mut mismatched = Vec::new();
+        for ((filepath, file_urls), sha256) in filepaths.iter().zip(urls.iter()).zip(&sha256s) {
+            if let Some(expected) = sha256 {
+                let unverified = unverified_path(filepath);
+                if let Err(err) =
+                    verify_download_sha256(Path::new(&unverified), Path::new(filepath), expected)
+                        .await
+                {
+                    mismatched.push(format!("{:#}", err));
+                    continue;
+                }
+            }
+            let rel_file_path = self.path_key(Path::new(&filepath))?;
+            if !self
+                .data
+                .contains(&rel_file_path, &self.path_context())
+                .await?
+            {
+                let file_urls: Vec<String> =
+                    file_urls.iter().map(|url| manifest_url(url)).collect();
+                let mut data_file = DataFile::new(
+                    rel_file_path.clone(),
+                    Some(&file_urls[0]),
+                    &self.path_context(),
+                )
+                .await?;
+                data_file.urls = file_urls;
+                self.register(data_file)?;
                 num_added += 1;
             } else {
                 num_already_registered += 1;
             }
         }
-        let num_skipped = skipped.len();
         println!(
             "{} URLs found in '{}.'\n\
-            {} files were downloaded, {} added to manifest ({} were already registered).\n\
-            {} files were skipped because they existed (and --overwrite was no specified).",
+            {} files were downloaded, {} added to manifest ({} were already registered).",
             num_lines,
             filename,
             urls.len(),
             num_added,
             num_already_registered,
-            num_skipped
         );
+        print_overwrite_skipped("bulk", &skipped);
         self.save()?;
+        if !mismatched.is_empty() {
+            return Err(anyhow!(
+                "{} did not match the expected SHA-256, and {} discarded:\n{}",
+                pluralize(mismatched.len() as u64, "download"),
+                if mismatched.len() == 1 { "was" } else { "were" },
+                mismatched.join("\n")
+            ));
+        }
         Ok(())
     }
 
-    pub fn untrack(&mut self, filepath: &String) -> Result<()> {
-        let filepath = self.relative_path_string(Path::new(filepath))?;
-        self.data.untrack_file(&filepath)?;
+    pub fn remote_exclude(&mut self, dir: &str, pattern: &str) -> Result<()> {
+        let dir = self.directory_key(Path::new(dir))?;
+        if self.data.add_push_exclude(&dir, pattern)? {
+            println!(
+                "Files matching '{}' in '{}' will not be pushed.",
+                pattern, dir
+            );
+        } else {
+            println!("'{}' already excludes '{}' from pushes.", dir, pattern);
+        }
+        self.save()
+    }
+
+    // Set whether files pulled to a linked directory are made read-only.
+    pub fn remote_readonly(&mut self, dir: &str, readonly: bool) -> Result<()> {
+        let dir = self.directory_key(Path::new(dir))?;
+        self.data.set_readonly_on_pull(&dir, readonly)?;
+        if readonly {
+            println!("Files pulled to '{}' will be made read-only.", dir);
+        } else {
+            println!(
+                "Files pulled to '{}' will no longer be made read-only.",
+                dir
+            );
+        }
         self.save()
     }
 
-    pub fn track(&mut self, filepath: &String) -> Result<()> {
-        let filepath = self.relative_path_string(Path::new(filepath))?;
-        self.data.track_file(&filepath, &self.path_context())?;
+    // Change the type of a linked directory's records, both in the
+    // manifest (for records created later) and on the remote.
+    pub async fn remote_type(&mut self, dir: &str, record_type: &str) -> Result<()> {
+        let dir = self.directory_key(Path::new(dir))?;
+        let remote = self
+            .data
+            .remotes
+            .get_mut(&dir)
+            .ok_or_else(|| anyhow!("Directory '{}' is not linked to a remote.", dir))?;
+        remote.set_record_type(record_type)?;
+        authenticate_remote(remote)?;
+        remote.update_record_type().await?;
+        println!(
+            "Set the type of '{}' on {} to '{}'.",
+            dir,
+            remote.name(),
+            remote.record_type().unwrap_or_default()
+        );
+        self.save()
+    }
+
+    // Check each linked remote (or that of dir) before a push, printing a
+    // line for each (see DataCollection::test_remotes()).
+    pub async fn remote_test(&mut self, dir: Option<&str>, write_probe: bool) -> Result<()> {
+        let dir = dir
+            .map(|dir| self.directory_key(Path::new(dir)))
+            .transpose()?;
+        let checks = self.data.test_remotes(dir.as_deref(), write_probe).await?;
+        for check in &checks {
+            println!("{}", check.line());
+        }
+        let num_failed = checks.iter().filter(|check| !check.passed()).count();
+        if num_failed > 0 {
+            return Err(anyhow!(
+                "{} of {} failed.",
+                num_failed,
+                pluralize(checks.len() as u64, "remote")
+            ));
+        }
+        Ok(())
+    }
+
+    // Untrack files, whether or not their directories are still linked
+    // (e.g. files left tracked after their remote was removed). With
+    // if_needed, untracking a file that isn't tracked is a no-op rather
+    // than an error (as for tracking, in track()).
+    pub fn untrack(&mut self, filepaths: &[String], if_needed: bool) -> Result<()> {
+        let mut num_untracked = 0;
+        let mut num_unchanged = 0;
+        for filepath in filepaths {
+            let filepath = self.path_key(Path::new(filepath))?;
+            let untracked = self.data.files.get(&filepath).is_some_and(|f| !f.tracked);
+            if if_needed && untracked {
+                num_unchanged += 1;
+                continue;
+            }
+            self.data.untrack_file(&filepath)?;
+            num_untracked += 1;
+        }
+        println!(
+            "{}",
+            tracking_summary("Untracked", num_untracked, num_unchanged, "not tracked")
+        );
         self.save()
     }
 
-    pub async fn pull(&mut self, overwrite: bool, url: bool, all: bool) -> Result<()> {
+    pub async fn track(
+        &mut self,
+        filepaths: &[String],
+        check_remote: bool,
+        if_needed: bool,
+    ) -> Result<()> {
+        let mut num_tracked = 0;
+        let mut num_unchanged = 0;
+        let mut keys = Vec::new();
+        for filepath in filepaths {
+            let filepath = self.path_key(Path::new(filepath))?;
+            let tracked = self.data.files.get(&filepath).is_some_and(|f| f.tracked);
+            if if_needed && tracked {
+                num_unchanged += 1;
+            } else {
+                self.data.track_file(&filepath, &self.path_context())?;
+                num_tracked += 1;
+            }
+            keys.push(filepath);
+        }
+        println!(
+            "{}",
+            tracking_summary("Tracked", num_tracked, num_unchanged, "tracked")
+        );
+        self.save()?;
+        if check_remote {
+            for filepath in &keys {
+                self.check_remote_file(filepath).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Report whether a tracked file exists on its remote, and whether
+    // the remote MD5 matches the manifest, so that conflicts can be
+    // resolved before the next push.
+    async fn check_remote_file(&mut self, filepath: &str) -> Result<()> {
+        let merged_file = self.data.merge_file(filepath).await?;
+        let service = merged_file.remote_service.clone().unwrap_or_default();
+        let remote_md5 = merged_file.remote_md5();
+        let manifest_md5 = merged_file.manifest_md5();
+        match (&merged_file.remote, remote_md5) {
+            (None, _) => {
+                print_info!(
+                    "'{}' does not exist on the {} remote; it will be uploaded on the next 'sdf push'.",
+                    filepath,
+                    service
+                );
+            }
+            (Some(_), None) => {
+                print_warn!(
+                    "'{}' exists on the {} remote, but the remote does not provide an MD5 \
+                     to compare against the manifest.\n\
+                     The next 'sdf push' will only replace it with --overwrite.",
+                    filepath,
+                    service
+                );
+            }
+            (Some(_), Some(md5)) if Some(&md5) == manifest_md5.as_ref() => {
+                print_info!(
+                    "'{}' exists on the {} remote and its MD5 matches the manifest.",
+                    filepath,
+                    service
+                );
+            }
+            (Some(_), Some(_)) => {
+                print_warn!(
+                    "'{}' exists on the {} remote, but its MD5 differs from the manifest.\n\
+                     Use 'sdf pull --overwrite' to replace the local file with the remote version,\n\
+                     or 'sdf push --overwrite' to replace the remote file.",
+                    filepath,
+                    service
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Resolve a path given on the command line to its key in the manifest
+    // (relative to the project root). Every subcommand resolves its path
+    // arguments through this, so they work the same from the project root,
+    // from a subdirectory (e.g. '../data/x.tsv'), or given absolute paths.
+    // The path need not exist (e.g. a deleted file to remove, or a
+    // directory a pull will create): its deepest existing ancestor is
+    // canonicalized (resolving symlinks) and the rest appended.
+    pub fn path_key(&self, path: &Path) -> Result<String> {
+        let path_context = canonicalize(self.path_context()).context(format!(
+            "Failed to canonicalize the project directory '{}'.",
+            self.path_context().to_string_lossy()
+        ))?;
+        let absolute_path = normalize_path(&env::current_dir()?.join(path));
+        let mut existing = absolute_path.as_path();
+        while !existing.exists() {
+            match existing.parent() {
+                Some(parent) => existing = parent,
+                None => break,
+            }
+        }
+        let resolved = match absolute_path.strip_prefix(existing) {
+            Ok(rest) if existing.exists() => canonicalize(existing)
+                .context(format!(
+                    "Failed to canonicalize path '{}'.",
+                    path.to_string_lossy()
+                ))?
+                .join(rest),
+            _ => absolute_path.clone(),
+        };
+        match resolved.strip_prefix(&path_context) {
+            Ok(rel_path) => Ok(normalize_path_key(&rel_path.to_string_lossy())),
+            Err(_) => Err(self.outside_project_error(path, &path_context)),
+        }
+    }
+
+    // Resolve a directory given on the command line to its key in the
+    // manifest; see path_key().
+    pub fn directory_key(&self, dir: &Path) -> Result<String> {
+        self.path_key(dir)
+    }
+
+    // The absolute path of a manifest key; the inverse of path_key().
+    pub fn absolute_path(&self, key: &str) -> Result<PathBuf> {
+        if Path::new(key).is_absolute() {
+            return Err(anyhow!(
+                "'{}' is an absolute path, not a key relative to the project root.",
+                key
+            ));
+        }
+        let path_context = canonicalize(self.path_context())?;
+        let path = normalize_path(&path_context.join(key));
+        if !path.starts_with(&path_context) {
+            return Err(anyhow!(
+                "'{}' is not within the project directory '{}'.",
+                key,
+                path_context.to_string_lossy()
+            ));
+        }
+        Ok(path)
+    }
+
+    // Resolve the directories a pull is limited to, checking that each is
+    // in the manifest (for URL pulls) or linked to a remote (for remote pulls).
+    fn pull_directories(
+        &self,
+        directories: &[PathBuf],
+        url: bool,
+        all: bool,
+    ) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        for dir in directories {
+            let key = self.directory_key(dir)?;
+            let in_manifest = !self.data.files_in_directory(&key).is_empty();
+            let is_linked = self.data.remotes.keys().any(|tracked_dir| {
+                Path::new(&key).starts_with(tracked_dir) || Path::new(tracked_dir).starts_with(&key)
+            });
+            let valid = if all {
+                in_manifest || is_linked
+            } else if url {
+                in_manifest
+            } else {
+                is_linked
+            };
+            if !valid {
+                let reason = if url && !all {
+                    "has no files in the manifest"
+                } else {
+                    "is not linked to a remote (see 'sdf link --help')"
+                };
+                return Err(anyhow!(
+                    "Cannot pull directory '{}': it {}.",
+                    dir.to_string_lossy(),
+                    reason
+                ));
+            }
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+
+    pub async fn pull(
+        &mut self,
+        directories: &[PathBuf],
+        filter: &PathFilter,
+        options: &PullOptions,
+    ) -> Result<()> {
+        let PullOptions {
+            overwrite,
+            readonly,
+            urls: url,
+            all,
+            ..
+        } = *options;
+        let path_context = self.path_context();
+        let limit = PathFilter {
+            directories: self.pull_directories(directories, url, all)?,
+            ..filter.clone()
+        };
+        let mut report = TransferReport::default();
+        if url || all {
+            report.extend(
+                self.data
+                    .pull_urls(&path_context, overwrite, readonly, &limit)
+                    .await?,
+            );
+        }
+        if !url || all {
+            report.extend(self.data.pull(&path_context, &limit, options).await?);
+        }
+        if self.data.has_remote_md5s(&report.downloaded) {
+            self.save()?;
+        }
+        if json() {
+            println!("{}", report.to_json()?);
+        }
+        report.failures("downloaded")
+    }
+
+    // Delete the local copies of files that are safely on their remotes,
+    // keeping them in the manifest (see DataCollection::clean()).
+    pub async fn clean(
+        &mut self,
+        force: bool,
+        dry_run: bool,
+        directories: &[PathBuf],
+        filter: &PathFilter,
+    ) -> Result<()> {
         let path_context = self.path_context();
-        if all {
-            self.data.pull_urls(&path_context, overwrite).await?;
-            return self.data.pull(&path_context, overwrite).await;
+        let limit = PathFilter {
+            directories: directories
+                .iter()
+                .map(|dir| self.directory_key(dir))
+                .collect::<Result<_>>()?,
+            ..filter.clone()
+        };
+        let report = self
+            .data
+            .clean(&path_context, force, dry_run, &limit)
+            .await?;
+        let verb = if dry_run { "Would delete" } else { "Deleted" };
+        for path in &report.deleted {
+            println!(" - {}", path);
+        }
+        println!(
+            "{} {} ({}); 'sdf pull' restores them.",
+            verb,
+            pluralize(report.deleted.len() as u64, "local file"),
+            format_bytes(report.bytes)
+        );
+        if !report.kept.is_empty() {
+            println!(
+                "Kept {} not verified on a remote:",
+                pluralize(report.kept.len() as u64, "file")
+            );
+            for (path, reason) in &report.kept {
+                println!(" - {}: {}", path, reason);
+            }
+        }
+        Ok(())
+    }
+
+    // Pull the files of another project's manifest (e.g. a published
+    // dataset's) into the current directory, without linking its remotes
+    // or saving its manifest. The remotes' public files are used, so no
+    // tokens are needed.
+    pub async fn pull_manifest_from(
+        url: &str,
+        overwrite: bool,
+        readonly: bool,
+        urls: bool,
+        all: bool,
+        directories: &[PathBuf],
+        filter: &PathFilter,
+    ) -> Result<()> {
+        let dest = env::current_dir()?;
+        let limit = PathFilter {
+            directories: directories
+                .iter()
+                .map(|dir| dir.to_string_lossy().trim_end_matches('/').to_string())
+                .collect(),
+            ..filter.clone()
+        };
+        let client = RemoteClient::from_manifest_url(url).await?;
+        if !urls || all {
+            let summary = client.pull(&dest, overwrite, readonly, &limit).await?;
+            if !summary.downloaded.is_empty() {
+                println!("Downloaded:");
+                for path in &summary.downloaded {
+                    println!(" - {}", path);
+                }
+            }
+            println!(
+                "Downloaded {}; skipped {}.",
+                pluralize(summary.downloaded.len() as u64, "file"),
+                pluralize(
+                    (summary.current.len() + summary.overwrite_skipped.len()) as u64,
+                    "file"
+                )
+            );
+            if !summary.current.is_empty() {
+                println!(
+                    "  Remote file is indentical to local file: {}",
+                    pluralize(summary.current.len() as u64, "file")
+                );
+            }
+            print_overwrite_skipped("pull", &summary.overwrite_skipped);
+        }
+        if urls || all {
+            client
+                .into_manifest()
+                .pull_urls(&dest, overwrite, readonly, &limit)
+                .await?
+                .failures("downloaded")?;
+        }
+        Ok(())
+    }
+
+    pub async fn push(&mut self, filter: &PathFilter, options: &PushOptions) -> Result<()> {
+        self.check_required_metadata()?;
+        if let (Some(budget), false) = (self.data.metadata.size_budget, options.force) {
+            let size = self.data.remote_size(filter);
+            if size > budget {
+                return Err(anyhow!(
+                    "The tracked files to push total {}, over the project's size budget of {} ({} used); use --force to push anyway.",
+                    format_bytes(size),
+                    format_bytes(budget),
+                    budget_percent(size, budget)
+                ));
+            }
+        }
+        if options.include_readme {
+            let added = self.data.add_readme_record_files(&self.path_context())?;
+            for readme in &added {
+                print_info!("'{}' will be uploaded to remotes as a record file.", readme);
+            }
+            if !added.is_empty() {
+                self.save()?;
+            }
+        }
+        let report = self
+            .data
+            .push(&self.path_context(), filter, options)
+            .await?;
+        if self.data.has_remote_md5s(&report.uploaded) {
+            self.save()?;
+        }
+        if json() {
+            println!("{}", report.to_json()?);
+        }
+        report.failures("pushed; push them again (with --overwrite if they are on the remote)")
+    }
+
+    pub async fn push_archive(&mut self, dir: &str) -> Result<()> {
+        let dir = self.directory_key(Path::new(dir))?;
+        self.data.push_archive(&dir, &self.path_context()).await?;
+        self.save()
+    }
+
+    pub async fn pull_archive(&mut self, dir: &str, overwrite: bool) -> Result<()> {
+        let dir = self.directory_key(Path::new(dir))?;
+        self.data
+            .pull_archive(&dir, &self.path_context(), overwrite)
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tracking_summary() {
+        assert_eq!(
+            tracking_summary("Tracked", 1, 0, "tracked"),
+            "Tracked 1 file."
+        );
+        assert_eq!(
+            tracking_summary("Untracked", 2, 1, "not tracked"),
+            "Untracked 2 files (1 already not tracked)."
+        );
+    }
+
+    #[test]
+    fn test_dir_name() {
+        assert_eq!(
+            Project::get_dir_name(Path::new("/home/joan/maize")).unwrap(),
+            "maize"
+        );
+        let result = Project::get_dir_name(Path::new("/"));
+        assert!(result.unwrap_err().to_string().contains("filesystem root"));
+    }
+
+    #[test]
+    fn test_looks_autogenerated() {
+        for name in [
+            "workdir",
+            "Build",
+            ".tmpX1b2C3",
+            "tmp_8f2k",
+            "20231014",
+            "3f9a2c1d7e",
+            "123e4567-e89b-12d3-a456-426614174000",
+        ] {
+            assert!(looks_autogenerated(name), "{}", name);
         }
-        if url {
-            return self.data.pull_urls(&path_context, overwrite).await;
+        for name in ["maize-diversity", "beef", "hg38_variants", "workflows"] {
+            assert!(!looks_autogenerated(name), "{}", name);
         }
-        self.data.pull(&path_context, overwrite).await
     }
 
-    pub async fn push(&mut self, overwrite: bool) -> Result<()> {
-        self.data.push(&self.path_context(), overwrite).await
+    #[test]
+    fn test_resolve_config_dir() {
+        let home = Some(PathBuf::from("/home/joan"));
+        let dir = resolve_config_dir(Some("/xdg/config".to_string()), home.clone()).unwrap();
+        assert_eq!(dir, PathBuf::from("/xdg/config/scidataflow"));
+
+        // unset or relative $XDG_CONFIG_HOME falls back to ~/.config
+        let dir = resolve_config_dir(None, home.clone()).unwrap();
+        assert_eq!(dir, PathBuf::from("/home/joan/.config/scidataflow"));
+        let dir = resolve_config_dir(Some("relative".to_string()), home).unwrap();
+        assert_eq!(dir, PathBuf::from("/home/joan/.config/scidataflow"));
+
+        assert!(resolve_config_dir(None, None).is_err());
+    }
+
+    #[test]
+    fn test_config_remote_timeout() {
+        let config: Config = serde_yaml::from_str("user:\n  name: Joan\n").unwrap();
+        assert_eq!(config.remote_timeout(), None);
+        let config: Config =
+            serde_yaml::from_str("user:\n  name: Joan\nremote_timeout: 30\n").unwrap();
+        assert_eq!(config.remote_timeout(), Some(Duration::from_secs(30)));
+        assert!(serde_yaml::to_string(&config)
+            .unwrap()
+            .contains("remote_timeout: 30"));
+    }
+
+    #[test]
+    fn test_config_remote_concurrency() {
+        let config: Config = serde_yaml::from_str("user:\n  name: Joan\n").unwrap();
+        assert_eq!(config.remote_concurrency(), None);
+        let config: Config =
+            serde_yaml::from_str("user:\n  name: Joan\nremote_concurrency: 16\n").unwrap();
+        assert_eq!(config.remote_concurrency(), Some(16));
+    }
+
+    #[test]
+    fn test_migrate_legacy_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join(".scidataflow_config");
+        let new = dir.path().join(".config/scidataflow/config.yml");
+
+        // nothing to migrate
+        assert!(!migrate_legacy_file(&legacy, &new).unwrap());
+
+        std::fs::write(&legacy, "user:\n  name: Joan\n").unwrap();
+        assert!(migrate_legacy_file(&legacy, &new).unwrap());
+        assert!(!legacy.exists());
+        assert_eq!(
+            std::fs::read_to_string(&new).unwrap(),
+            "user:\n  name: Joan\n"
+        );
+
+        // an existing new file is never clobbered
+        std::fs::write(&legacy, "stale").unwrap();
+        assert!(!migrate_legacy_file(&legacy, &new).unwrap());
+        assert!(legacy.exists());
+    }
+
+    #[test]
+    fn test_describe_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let legacy = dir.path().join(".scidataflow_config");
+        let new = dir.path().join(".config/scidataflow/config.yml");
+        assert_eq!(
+            describe_config_file(Some(&legacy), &new),
+            new.display().to_string()
+        );
+
+        // a legacy dotfile is shown where it is, and not moved
+        std::fs::write(&legacy, "user:\n  name: Joan\n").unwrap();
+        assert_eq!(
+            describe_config_file(Some(&legacy), &new),
+            format!(
+                "{} (moved to {} on next use)",
+                legacy.display(),
+                new.display()
+            )
+        );
+        assert!(legacy.exists());
+        assert!(!new.exists());
+        assert_eq!(describe_config_file(None, &new), new.display().to_string());
+    }
+
+    // sdf why for a file in a linked directory (the integration tests
+    // can't link a remote)
+    #[tokio::test]
+    async fn test_why_linked() {
+        use crate::lib::api::test_remote::TestRemote;
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("data")).unwrap();
+        std::fs::write(dir.path().join("data/a.tsv"), "local\n").unwrap();
+        let remote = TestRemote::new("test");
+        remote.put_file("a.tsv", b"remote\n");
+        let mut data = DataCollection::new();
+        data.remotes
+            .insert("data".to_string(), Remote::Test(remote.clone()));
+        let mut data_file = DataFile::with_md5(
+            "data/a.tsv".to_string(),
+            format!("{:x}", md5::compute("local\n")),
+            None,
+            dir.path(),
+        )
+        .unwrap();
+        data_file.tracked = true;
+        data.files.insert(data_file.path.clone(), data_file);
+        let mut project = Project {
+            manifest: dir.path().join(MANIFEST),
+            data,
+            config: serde_yaml::from_str("user:\n  name: Joan\n").unwrap(),
+            recorded: Cell::new(false),
+        };
+        let path = dir.path().join("data/a.tsv");
+        let filename = path.to_str().unwrap();
+
+        let explanation = project.explain(filename, false, false).await.unwrap();
+        assert!(explanation.starts_with("data/a.tsv (tracked)"));
+        assert!(explanation.contains("Remote:        Test, linked to 'data'"));
+        assert!(explanation.contains(&format!(
+            "Remote file:   MD5 {:x}",
+            md5::compute("remote\n")
+        )));
+        assert!(explanation.contains("Remote status: Different"));
+        assert!(explanation.contains("Push would:    skip (remote differs, requires --overwrite)"));
+        assert!(explanation
+            .contains("Pull would:    skip (remote differs, requires --overwrite or --backup)"));
+
+        // --backup replaces the local file on pull, but doesn't push
+        let explanation = project.explain(filename, false, true).await.unwrap();
+        assert!(explanation.contains("Push would:    skip (remote differs, requires --overwrite)"));
+        assert!(explanation
+            .contains("Pull would:    download (remote differs, with --overwrite or --backup)"));
+
+        // a messy file is explained with how to resolve it
+        std::fs::write(&path, "changed\n").unwrap();
+        remote.put_file("a.tsv", b"local\n");
+        let explanation = project.explain(filename, true, false).await.unwrap();
+        assert!(explanation.contains("Remote status: MessyLocal"));
+        assert!(explanation.contains("Messy:         local changed since the manifest"));
+        assert!(explanation.contains("Pull would:    skip (local file differs from the manifest)"));
     }
 }
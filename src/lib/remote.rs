@@ -1,11 +1,13 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use reqwest::Url;
-use serde_derive::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 use serde_yaml;
-use std::collections::HashMap;
-use std::env;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 use std::fs;
 use std::fs::File;
 use std::io::Read;
@@ -14,11 +16,15 @@ use trauma::download::Download;
 
 use crate::lib::api::dryad::DataDryadAPI;
 use crate::lib::api::figshare::FigShareAPI;
+use crate::lib::api::ipfs::IpfsAPI;
+#[cfg(test)]
+use crate::lib::api::test_remote::TestRemote;
 use crate::lib::api::zenodo::ZenodoAPI;
 use crate::lib::data::{DataFile, MergedFile};
-use crate::lib::project::LocalMetadata;
-
-const AUTHKEYS: &str = ".scidataflow_authkeys.yml";
+use crate::lib::http;
+use crate::lib::overwrite::OverwriteAction;
+use crate::lib::project::{authkeys_path, LocalMetadata};
+use crate::print_warn;
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RemoteFile {
@@ -27,6 +33,87 @@ pub struct RemoteFile {
     pub size: Option<u64>,
     pub remote_service: String,
     pub url: Option<String>,
+    // Last modification time, if the remote provides one. Zenodo and
+    // FigShare only provide this for the whole deposition/article, so it
+    // is an upper bound on when the file itself was modified.
+    #[serde(default)]
+    pub mod_time: Option<DateTime<Utc>>,
+    // The remote's IDs of all the files listed with this name, if there is
+    // more than one (see duplicate_ids()).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_ids: Vec<String>,
+}
+
+// The IDs of the files in a remote's listing that share their name with
+// another file (e.g. after a failed upload was retried), by name. Maps
+// of a listing by name would otherwise keep only one of them.
+pub fn duplicate_ids<'a, I>(files: I) -> BTreeMap<String, Vec<String>>
+where
+    I: IntoIterator<Item = (&'a str, String)>,
+{
+    let mut ids: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for (name, id) in files {
+        ids.entry(name.to_string()).or_default().push(id);
+    }
+    ids.retain(|_, ids| ids.len() > 1);
+    ids
+}
+
+// Warn about each duplicated name in a remote's listing (see
+// duplicate_ids()), and set the IDs on its remote files, so that they
+// are not pushed or pulled (see RemoteStatusCode::DuplicateRemote).
+pub fn mark_duplicates(
+    service: &str,
+    duplicates: &BTreeMap<String, Vec<String>>,
+    remote_files: &mut [RemoteFile],
+) {
+    for (name, ids) in duplicates {
+        print_warn!(
+            "{} lists {} files named '{}' (file IDs {}), so it will not be pushed or \
+             pulled; delete the extra copies on {} first.",
+            service,
+            ids.len(),
+            name,
+            ids.join(", "),
+            service
+        );
+    }
+    for remote_file in remote_files.iter_mut() {
+        if let Some(ids) = duplicates.get(&remote_file.name) {
+            remote_file.duplicate_ids = ids.clone();
+        }
+    }
+}
+
+// The result of uploading a single file.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UploadOutcome {
+    Uploaded,
+    // The file already exists on the remote, and overwrite was not set.
+    SkippedExists,
+    // The remote's MD5 differed from the local one after upload (the
+    // remote file is deleted).
+    FailedChecksum,
+}
+
+// Storage used and allowed on a remote account, in bytes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quota {
+    pub used: u64,
+    pub total: u64,
+}
+
+impl Quota {
+    pub fn remaining(&self) -> u64 {
+        self.total.saturating_sub(self.used)
+    }
+}
+
+// Parse an RFC 3339 timestamp from a remote API.
+pub fn parse_remote_timestamp(timestamp: &str) -> Result<DateTime<Utc>> {
+    let parsed = DateTime::parse_from_rfc3339(timestamp)
+        .context(format!("Could not parse remote timestamp '{}'.", timestamp))?;
+    Ok(parsed.with_timezone(&Utc))
 }
 
 // This is the status of the local state with the remote state.
@@ -46,15 +133,16 @@ pub struct RemoteFile {
 //
 // Clean state: everything on the manifest tracked by the remote is
 // local, with nothing else.
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Serialize)]
 pub enum RemoteStatusCode {
-    Current,      // local and remote files are identical
-    MessyLocal,   // local file is different than remote and manifest, which agree
-    Different,    // the local file is current, but different than the remote
-    NotExists,    // no remote file
-    Exists,       // remote file exists, but remote does not support MD5s
-    NoLocal,      // a file on the remote, but not in manifest or found locally
-    DeletedLocal, // a file on the remote and in manifest, but not found locally
+    Current,         // local and remote files are identical
+    MessyLocal,      // local file is different than remote and manifest, which agree
+    Different,       // the local file is current, but different than the remote
+    NotExists,       // no remote file
+    Exists,          // remote file exists, but remote does not support MD5s
+    NoLocal,         // a file on the remote, but not in manifest or found locally
+    DeletedLocal,    // a file on the remote and in manifest, but not found locally
+    DuplicateRemote, // the remote lists more than one file with this name
     //OutsideSource,        // a file on the remote, but not in manifest but *is* found locally
     Invalid,
 }
@@ -79,17 +167,16 @@ pub struct AuthKeys {
 
 impl AuthKeys {
     pub fn new() -> Self {
-        let home_dir = env::var("HOME").expect("Could not infer home directory");
-        let path = Path::new(&home_dir).join(AUTHKEYS);
+        let path = authkeys_path().expect("Could not infer home directory");
         let keys = match path.exists() {
             true => {
                 let mut contents = String::new();
-                File::open(path)
+                File::open(&path)
                     .unwrap()
                     .read_to_string(&mut contents)
                     .unwrap();
                 serde_yaml::from_str(&contents)
-                    .unwrap_or_else(|_| panic!("Cannot load {}!", AUTHKEYS))
+                    .unwrap_or_else(|_| panic!("Cannot load {:?}!", path))
             }
             false => {
                 let keys: HashMap<String, String> = HashMap::new();
@@ -113,6 +200,10 @@ impl AuthKeys {
         self.keys.insert(service, key.to_owned());
     }
 
+    pub fn contains(&self, service: &str) -> bool {
+        self.keys.contains_key(&service.to_lowercase())
+    }
+
     pub fn get(&self, service: String) -> Result<String> {
         match self.keys.get(&service) {
             None => Err(anyhow!("no key found for service '{}'", service)),
@@ -123,18 +214,173 @@ impl AuthKeys {
     pub fn save(&self) {
         let serialized_keys =
             serde_yaml::to_string(&self.keys).expect("Cannot serialize authentication keys!");
-        let home_dir = env::var("HOME").expect("Could not infer home directory");
-        let path = Path::new(&home_dir).join(AUTHKEYS);
-        fs::write(path, serialized_keys)
-            .unwrap_or_else(|_| panic!("Cound not write {}!", AUTHKEYS));
+        let path = authkeys_path().expect("Could not infer home directory");
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .unwrap_or_else(|_| panic!("Could not create directory {:?}!", parent));
+        }
+        fs::write(&path, serialized_keys).unwrap_or_else(|_| panic!("Cound not write {:?}!", path));
     }
 }
 
-#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Remote {
     FigShareAPI(FigShareAPI),
     DataDryadAPI(DataDryadAPI),
     ZenodoAPI(ZenodoAPI),
+    IpfsAPI(IpfsAPI),
+    // An in-memory remote for tests (never in manifests outside tests).
+    #[cfg(test)]
+    Test(TestRemote),
+    // A remote from a service this version does not know (e.g. one added
+    // by a newer sdf). Its manifest entry is kept as is, and it is skipped
+    // by push, pull, and fetches.
+    Unknown {
+        service: String,
+        raw: serde_yaml::Value,
+    },
+}
+
+// The remotes this version can deserialize; see Remote's Deserialize.
+#[derive(Deserialize)]
+enum KnownRemote {
+    FigShareAPI(FigShareAPI),
+    DataDryadAPI(DataDryadAPI),
+    ZenodoAPI(ZenodoAPI),
+    IpfsAPI(IpfsAPI),
+}
+
+const KNOWN_SERVICES: [&str; 4] = ["FigShareAPI", "DataDryadAPI", "ZenodoAPI", "IpfsAPI"];
+
+/// How much of a service this version of sdf implements.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ServiceSupport {
+    /// Linking, pushing, and pulling.
+    Full,
+    /// Pulling existing records only.
+    ReadOnly,
+    /// Recognized in manifests, but not implemented.
+    Stub,
+}
+
+impl fmt::Display for ServiceSupport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let support = match self {
+            ServiceSupport::Full => "full",
+            ServiceSupport::ReadOnly => "read-only",
+            ServiceSupport::Stub => "stub",
+        };
+        f.pad(support)
+    }
+}
+
+/// A service directories can be linked to, i.e. a Remote variant.
+#[derive(Debug)]
+pub struct Service {
+    pub name: &'static str,
+    /// The service's name in 'sdf link' and the authentication keys.
+    pub key: &'static str,
+    /// The Remote variant's tag in manifests.
+    pub tag: &'static str,
+    pub support: ServiceSupport,
+    /// Whether a token is needed (e.g. local IPFS nodes need none).
+    pub token_required: bool,
+}
+
+/// The services, one for each Remote variant (see KNOWN_SERVICES).
+pub const SERVICES: [Service; 4] = [
+    Service {
+        name: "FigShare",
+        key: "figshare",
+        tag: "FigShareAPI",
+        support: ServiceSupport::Full,
+        token_required: true,
+    },
+    Service {
+        name: "Zenodo",
+        key: "zenodo",
+        tag: "ZenodoAPI",
+        support: ServiceSupport::Full,
+        token_required: true,
+    },
+    Service {
+        name: "IPFS",
+        key: "ipfs",
+        tag: "IpfsAPI",
+        support: ServiceSupport::Full,
+        token_required: false,
+    },
+    Service {
+        name: "Dryad",
+        key: "dryad",
+        tag: "DataDryadAPI",
+        support: ServiceSupport::Stub,
+        token_required: true,
+    },
+];
+
+impl Serialize for Remote {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            Remote::FigShareAPI(api) => {
+                serializer.serialize_newtype_variant("Remote", 0, "FigShareAPI", api)
+            }
+            Remote::DataDryadAPI(api) => {
+                serializer.serialize_newtype_variant("Remote", 1, "DataDryadAPI", api)
+            }
+            Remote::ZenodoAPI(api) => {
+                serializer.serialize_newtype_variant("Remote", 2, "ZenodoAPI", api)
+            }
+            Remote::IpfsAPI(api) => {
+                serializer.serialize_newtype_variant("Remote", 3, "IpfsAPI", api)
+            }
+            #[cfg(test)]
+            Remote::Test(api) => {
+                serializer.serialize_newtype_variant("Remote", 4, "TestRemote", api.get_name())
+            }
+            Remote::Unknown { raw, .. } => raw.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Remote {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        use serde::de::Error;
+        let raw = serde_yaml::Value::deserialize(deserializer)?;
+        // remotes are tagged with their service, e.g. '!FigShareAPI'
+        let service = match &raw {
+            serde_yaml::Value::Tagged(tagged) => tagged.tag.to_string(),
+            serde_yaml::Value::Mapping(map) if map.len() == 1 => map
+                .keys()
+                .next()
+                .and_then(|key| key.as_str())
+                .unwrap_or_default()
+                .to_string(),
+            _ => String::new(),
+        };
+        let service = service.trim_start_matches('!').to_string();
+        if service.is_empty() {
+            return Err(D::Error::custom(
+                "a remote must be tagged with its service (e.g. '!FigShareAPI')",
+            ));
+        }
+        if !KNOWN_SERVICES.contains(&service.as_str()) {
+            return Ok(Remote::Unknown { service, raw });
+        }
+        let known: KnownRemote = serde_yaml::from_value(raw).map_err(D::Error::custom)?;
+        Ok(match known {
+            KnownRemote::FigShareAPI(api) => Remote::FigShareAPI(api),
+            KnownRemote::DataDryadAPI(api) => Remote::DataDryadAPI(api),
+            KnownRemote::ZenodoAPI(api) => Remote::ZenodoAPI(api),
+            KnownRemote::IpfsAPI(api) => Remote::IpfsAPI(api),
+        })
+    }
 }
 
 macro_rules! service_not_implemented {
@@ -143,6 +389,15 @@ macro_rules! service_not_implemented {
     };
 }
 
+macro_rules! service_not_supported {
+    ($service:expr) => {
+        Err(anyhow!(
+            "Remote service '{}' is not supported by this version of sdf.",
+            $service
+        ))
+    };
+}
+
 // NOTE: these are not implemented as traits because many are async, and
 // it looked like this wasn't implemented yet.
 impl Remote {
@@ -151,25 +406,354 @@ impl Remote {
             Remote::FigShareAPI(_) => "FigShare",
             Remote::DataDryadAPI(_) => "Dryad",
             Remote::ZenodoAPI(_) => "Zenodo",
+            Remote::IpfsAPI(_) => "IPFS",
+            #[cfg(test)]
+            Remote::Test(_) => "Test",
+            Remote::Unknown { service, .. } => service,
+        }
+    }
+    // Whether this version of sdf supports the remote's service.
+    pub fn is_supported(&self) -> bool {
+        !matches!(self, Remote::Unknown { .. })
+    }
+    // The remote as shown in status headers.
+    pub fn label(&self) -> String {
+        match self {
+            Remote::Unknown { service, .. } => format!("(unsupported remote: {})", service),
+            _ => self.name().to_string(),
+        }
+    }
+    // The title of the remote data set, as given when linking.
+    pub fn title(&self) -> &str {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.get_name(),
+            Remote::ZenodoAPI(znd_api) => znd_api.get_name(),
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.get_name(),
+            Remote::DataDryadAPI(_) => "",
+            #[cfg(test)]
+            Remote::Test(test) => test.get_name(),
+            Remote::Unknown { .. } => "",
         }
     }
+    // The public URL of the remote data set, if known.
+    pub fn landing_url(&self) -> Option<String> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.landing_url(),
+            Remote::ZenodoAPI(znd_api) => znd_api.landing_url(),
+            Remote::IpfsAPI(_) => None,
+            Remote::DataDryadAPI(_) => None,
+            #[cfg(test)]
+            Remote::Test(_) => None,
+            Remote::Unknown { .. } => None,
+        }
+    }
+    // The DOI reserved for the remote data set, if the service reserves
+    // one before publishing.
+    pub fn doi(&self) -> Option<&str> {
+        match self {
+            Remote::ZenodoAPI(znd_api) => znd_api.doi(),
+            _ => None,
+        }
+    }
+    // The record on the service the remote's files are in, for error
+    // messages, e.g. "deposition 12345".
+    pub fn record_label(&self) -> Option<String> {
+        match self {
+            // unset for per-file FigShare layouts
+            Remote::FigShareAPI(fgsh_api) => fgsh_api
+                .get_article_id()
+                .ok()
+                .map(|id| format!("article {}", id)),
+            Remote::ZenodoAPI(znd_api) => znd_api
+                .get_deposition_id()
+                .ok()
+                .map(|id| format!("deposition {}", id)),
+            _ => None,
+        }
+    }
+    // Context for a failed operation, e.g. "Zenodo upload of 'data/x.tsv'
+    // to deposition 12345 failed".
+    fn failed(&self, operation: &str) -> String {
+        match self.record_label() {
+            Some(record) => format!("{} {} to {} failed", self.name(), operation, record),
+            None => format!("{} {} failed", self.name(), operation),
+        }
+    }
+    // Set the type of the remote's records (a FigShare article's item
+    // type, or a Zenodo deposition's upload type), which is 'dataset' if
+    // not set.
+    pub fn set_record_type(&mut self, record_type: &str) -> Result<()> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.set_record_type(record_type),
+            Remote::ZenodoAPI(znd_api) => znd_api.set_record_type(record_type),
+            Remote::IpfsAPI(_) => Err(anyhow!("IPFS remotes do not have a record type.")),
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Err(anyhow!("Test remotes do not have a record type.")),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    pub fn record_type(&self) -> Option<&str> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => Some(fgsh_api.record_type()),
+            Remote::ZenodoAPI(znd_api) => Some(znd_api.record_type()),
+            _ => None,
+        }
+    }
+    // Update the type of the remote's existing records to its record type.
+    pub async fn update_record_type(&self) -> Result<()> {
+        let context = match self.record_label() {
+            Some(record) => format!("Could not update the type of {} {}", self.name(), record),
+            None => format!("Could not update the type of the {} records", self.name()),
+        };
+        let result = match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.update_record_type().await,
+            Remote::ZenodoAPI(znd_api) => znd_api.update_record_type().await,
+            Remote::IpfsAPI(_) => Err(anyhow!("IPFS remotes do not have a record type.")),
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Err(anyhow!("Test remotes do not have a record type.")),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        };
+        result.context(context)
+    }
+    // Set the license (an SPDX identifier) of the remote's existing records.
+    pub async fn update_license(&self, license: &str) -> Result<()> {
+        let context = match self.record_label() {
+            Some(record) => format!("Could not set the license of {} {}", self.name(), record),
+            None => format!("Could not set the license of the {} records", self.name()),
+        };
+        let result = match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.update_license(license).await,
+            Remote::ZenodoAPI(znd_api) => znd_api.update_license(license).await,
+            Remote::IpfsAPI(_) => Err(anyhow!("IPFS remotes do not have a license.")),
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Err(anyhow!("Test remotes do not have a license.")),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        };
+        result.context(context)
+    }
     // initialize the remote (i.e. tell it we have a new empty data set)
     pub async fn remote_init(
         &mut self,
         local_metadata: LocalMetadata,
         link_only: bool,
     ) -> Result<()> {
-        match self {
+        let context = format!("Could not set up the {} remote", self.name());
+        let result = match self {
             Remote::FigShareAPI(fgsh_api) => fgsh_api.remote_init(local_metadata, link_only).await,
             Remote::ZenodoAPI(znd_api) => znd_api.remote_init(local_metadata, link_only).await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.remote_init(local_metadata, link_only).await,
             Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
-        }
+            #[cfg(test)]
+            Remote::Test(_) => Ok(()),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        };
+        result.context(context)
     }
     pub async fn get_files(&self) -> Result<Vec<RemoteFile>> {
         match self {
             Remote::FigShareAPI(fgsh_api) => fgsh_api.get_remote_files().await,
             Remote::ZenodoAPI(znd_api) => znd_api.get_remote_files().await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.get_remote_files().await,
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(test) => test.get_remote_files().await,
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    // Check that the remote's token and the network work, without
+    // modifying anything.
+    pub async fn check_connection(&self) -> Result<String> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.check_connection().await,
+            Remote::ZenodoAPI(znd_api) => znd_api.check_connection().await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.check_connection().await,
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(test) => Ok(format!("test remote '{}'", test.get_name())),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    // Check that the remote's record exists and its files can still be
+    // changed, e.g. that a Zenodo deposition is not published.
+    pub async fn check_writable(&self) -> Result<String> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.check_writable().await,
+            Remote::ZenodoAPI(znd_api) => znd_api.check_writable().await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.check_writable().await,
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Ok("writable".to_string()),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    // Delete a file from the remote by its name in the remote's listing.
+    pub async fn delete_file(&self, name: &str) -> Result<()> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.delete_file(name).await,
+            Remote::ZenodoAPI(znd_api) => znd_api.delete_file(name).await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.delete_file(name).await,
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(test) => test.delete_file(name),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    // Upload a 1-byte probe file under a scratch name, check that the
+    // remote lists it (with its MD5, if the remote lists MD5s), and delete
+    // it again (in FigShare's per-file layout, with the article created
+    // for it).
+    pub async fn write_probe(&self) -> Result<()> {
+        let name = format!("sdf-remote-test-{}.txt", Utc::now().format("%Y%m%dT%H%M%S"));
+        self.write_probe_as(&name).await
+    }
+    // The write probe, under the given name.
+    pub(crate) async fn write_probe_as(&self, name: &str) -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        fs::write(dir.path().join(name), "\n")?;
+        let data_file = DataFile::new(name.to_string(), None, dir.path()).await?;
+        self.upload(&data_file, "", dir.path(), true).await?;
+        // delete the probe even if it can't be verified
+        let listed = self
+            .get_files_hashmap()
+            .await
+            .map(|mut files| files.remove(name));
+        let deleted = match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.delete_probe(name).await,
+            _ => self.delete_file(name).await,
+        };
+        let listed = listed?
+            .ok_or_else(|| anyhow!("the probe file '{}' was not listed after its upload", name))?;
+        // an empty MD5 is one the remote has not computed yet
+        let remote_md5 = listed.md5.filter(|md5| !md5.is_empty());
+        if remote_md5.is_some_and(|md5| md5 != data_file.md5) {
+            return Err(anyhow!(
+                "the probe file '{}' was listed with the wrong MD5",
+                name
+            ));
+        }
+        deleted.with_context(|| format!("the probe file '{}' could not be deleted", name))
+    }
+    // Check the remote before a push (see 'sdf remote test'): that the
+    // service accepts the token, that the record can be changed, and with
+    // write_probe, that a file can be uploaded, listed, and deleted.
+    pub async fn test(&self, write_probe: bool) -> Result<String> {
+        self.validate_token().await?;
+        let summary = self.check_writable().await?;
+        if !write_probe {
+            return Ok(summary);
+        }
+        self.write_probe().await.context("write probe failed")?;
+        Ok(format!("{}; write probe passed", summary))
+    }
+    // Check that the service accepts the remote's token with a cheap
+    // read-only request, so 'sdf link' fails before creating anything.
+    pub async fn validate_token(&self) -> Result<()> {
+        let result = match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.validate_token().await,
+            Remote::ZenodoAPI(znd_api) => znd_api.validate_token().await,
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.check_connection().await.map(|_| ()),
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Ok(()),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        };
+        let err = match result {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+        match http::error_status(&err) {
+            Some(status @ (401 | 403)) => {
+                let hint = match self {
+                    Remote::FigShareAPI(_) => "Check that the token has 'article' scope.",
+                    Remote::ZenodoAPI(_) => {
+                        "Check that the token has the 'deposit:write' and 'deposit:actions' scopes."
+                    }
+                    _ => "Check that the token is correct.",
+                };
+                Err(anyhow!(
+                    "{} rejected the token ({}). {}",
+                    self.name(),
+                    status,
+                    hint
+                ))
+            }
+            _ => Err(err.context(format!("Could not check the {} token.", self.name()))),
+        }
+    }
+    // Get the account's storage quota, if the service has an API for it
+    // (Zenodo does not; its limits are per record).
+    pub async fn get_quota(&self) -> Result<Option<Quota>> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => Ok(Some(fgsh_api.get_quota().await?)),
+            Remote::ZenodoAPI(_) => Ok(None),
+            Remote::IpfsAPI(_) => Ok(None),
             Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Ok(None),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    // Get the files of a published remote without authenticating.
+    pub async fn get_public_files(&self) -> Result<Vec<RemoteFile>> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.get_public_files().await,
+            Remote::ZenodoAPI(znd_api) => znd_api.get_public_files().await,
+            // the listing comes from the node, but gateways serve files to anyone
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.get_remote_files().await,
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(test) => test.get_remote_files().await,
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        }
+    }
+    pub fn has_token(&self) -> bool {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.has_token(),
+            Remote::ZenodoAPI(znd_api) => znd_api.has_token(),
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.has_token(),
+            Remote::DataDryadAPI(_) => false,
+            #[cfg(test)]
+            Remote::Test(_) => true,
+            Remote::Unknown { .. } => false,
+        }
+    }
+    pub fn set_token(&mut self, token: String) -> Result<()> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.set_token(token),
+            Remote::ZenodoAPI(znd_api) => znd_api.set_token(token),
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.set_token(token),
+            Remote::DataDryadAPI(_) => return service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => {}
+            Remote::Unknown { service, .. } => return service_not_supported!(service),
+        }
+        Ok(())
+    }
+    pub fn set_base_url(&mut self, base_url: String) -> Result<()> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.set_base_url(base_url),
+            Remote::ZenodoAPI(znd_api) => znd_api.set_base_url(base_url),
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.set_base_url(base_url),
+            Remote::DataDryadAPI(_) => return service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => {}
+            Remote::Unknown { service, .. } => return service_not_supported!(service),
+        }
+        Ok(())
+    }
+    // Add the access token to a download URL.
+    pub fn authenticate_url(&self, url: &str) -> Result<String> {
+        match self {
+            Remote::FigShareAPI(fgsh_api) => fgsh_api.authenticate_url(url),
+            Remote::ZenodoAPI(znd_api) => znd_api.authenticate_url(url),
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.authenticate_url(url),
+            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
+            #[cfg(test)]
+            Remote::Test(_) => Ok(url.to_string()),
+            Remote::Unknown { service, .. } => service_not_supported!(service),
         }
     }
     pub async fn get_files_hashmap(&self) -> Result<HashMap<String, RemoteFile>> {
@@ -181,19 +765,31 @@ impl Remote {
         }
         Ok(file_map)
     }
+    // Upload a file of the linked directory tracked_dir (only Zenodo keeps
+    // the file's path relative to it; other remotes store basenames).
     pub async fn upload(
         &self,
         data_file: &DataFile,
+        tracked_dir: &str,
         path_context: &Path,
         overwrite: bool,
-    ) -> Result<bool> {
-        match self {
+    ) -> Result<UploadOutcome> {
+        let result = match self {
             Remote::FigShareAPI(fgsh_api) => {
                 fgsh_api.upload(data_file, path_context, overwrite).await
             }
-            Remote::ZenodoAPI(znd_api) => znd_api.upload(data_file, path_context, overwrite).await,
+            Remote::ZenodoAPI(znd_api) => {
+                znd_api
+                    .upload(data_file, tracked_dir, path_context, overwrite)
+                    .await
+            }
+            Remote::IpfsAPI(ipfs_api) => ipfs_api.upload(data_file, path_context, overwrite).await,
             Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
-        }
+            #[cfg(test)]
+            Remote::Test(test) => test.upload(data_file, path_context, overwrite).await,
+            Remote::Unknown { service, .. } => service_not_supported!(service),
+        };
+        result.with_context(|| self.failed(&format!("upload of '{}'", data_file.path)))
     }
     // Get Download info: the URL (with token) and destination
     // TODO: could be struct, if some APIs require more authentication
@@ -211,10 +807,9 @@ impl Remote {
             Some(file) => file,
         };
         // check to make sure we won't overwrite
-        if data_file.is_alive(path_context) && !overwrite {
+        if !OverwriteAction::new(data_file.is_alive(path_context), overwrite).transfers() {
             return Err(anyhow!(
-                "Data file '{}' exists locally, and would be \
-                               overwritten by download. Use --overwrite to download.",
+                "Data file '{}' exists locally; use 'sdf pull --overwrite' to replace it.",
                 data_file.path
             ));
         }
@@ -229,11 +824,7 @@ impl Remote {
             .as_ref()
             .ok_or(anyhow!("Cannot download; download URL not set."))?;
 
-        let authenticated_url = match self {
-            Remote::FigShareAPI(fgsh_api) => fgsh_api.authenticate_url(url),
-            Remote::ZenodoAPI(znd_api) => znd_api.authenticate_url(url),
-            Remote::DataDryadAPI(_) => service_not_implemented!("DataDryad"),
-        }?;
+        let authenticated_url = self.authenticate_url(url)?;
         let save_path = &data_file.full_path(path_context)?;
         let url = Url::parse(&authenticated_url)?;
         let filename = save_path.to_string_lossy().to_string();
@@ -244,11 +835,17 @@ impl Remote {
 pub fn authenticate_remote(remote: &mut Remote) -> Result<()> {
     // Get the keys off disk
     let auth_keys = AuthKeys::new();
+    let path = authkeys_path()?;
     let error_message = |service_name: &str, token_name: &str| {
-        format!("Expected {} access token not found.\n\n\
-                If you used 'sdf link', it should have saved this token in ~/.scidataflow_authkeys.yml.\n\
+        format!(
+            "Expected {} access token not found.\n\n\
+                If you used 'sdf link', it should have saved this token in {}.\n\
                 You will need to re-add this key manually, by adding a line to this file like:\n\
-                {}: <TOKEN>", service_name, token_name)
+                {}: <TOKEN>",
+            service_name,
+            path.display(),
+            token_name
+        )
     };
 
     match remote {
@@ -268,6 +865,16 @@ pub fn authenticate_remote(remote: &mut Remote) -> Result<()> {
                 .ok_or_else(|| anyhow::anyhow!(error_message("Zenodo", "zenodo")))?;
             znd_api.set_token(token);
         }
+        // the token is optional, e.g. for a local node
+        Remote::IpfsAPI(ref mut ipfs_api) => {
+            if let Some(token) = auth_keys.keys.get("ipfs") {
+                ipfs_api.set_token(token.clone());
+            }
+        }
+        // there is nothing to authenticate; these are skipped
+        #[cfg(test)]
+        Remote::Test(_) => {}
+        Remote::Unknown { .. } => {}
         // handle other Remote variants as necessary
         _ => Err(anyhow!(
             "Could not find correct API in authenticate_remote()"
@@ -279,16 +886,33 @@ pub fn authenticate_remote(remote: &mut Remote) -> Result<()> {
 // Common enum for issue_request() methods of APIs
 //
 // Notes: Binary() should be used only for small amounts of data,
-// that can be read into memory, e.g. FigShare's upload_parts().
+// that can be read into memory. Uploads should use Stream(), with a
+// window of the file from file_window().
 #[derive(Debug)]
 pub enum RequestData<T: serde::Serialize> {
     Json(T),
     Binary(Vec<u8>),
     File(tokio::fs::File),
-    Stream(tokio::fs::File),
+    Stream(tokio::io::Take<tokio::fs::File>),
     Empty,
 }
 
+/// Open `length` bytes of a file, from `start`, to stream as a request
+/// body (e.g. a whole file, or one part of a multipart upload) without
+/// reading them into memory.
+pub async fn file_window(
+    path: &Path,
+    start: u64,
+    length: u64,
+) -> Result<tokio::io::Take<tokio::fs::File>> {
+    use tokio::io::{AsyncReadExt, AsyncSeekExt};
+    let mut file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Could not open {:?}", path))?;
+    file.seek(std::io::SeekFrom::Start(start)).await?;
+    Ok(file.take(length))
+}
+
 /* impl DataDryadAPI {
 fn upload(&self) {
 }
@@ -301,3 +925,62 @@ Ok("ID".to_string())
 }
 }
 */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::test_utilities::check_error;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_services_match_remotes() {
+        let mut tags: Vec<&str> = SERVICES.iter().map(|service| service.tag).collect();
+        let mut known = KNOWN_SERVICES.to_vec();
+        tags.sort();
+        known.sort();
+        assert_eq!(tags, known);
+        for service in &SERVICES {
+            assert_eq!(service.key, service.name.to_lowercase());
+        }
+    }
+
+    #[test]
+    fn test_duplicate_ids() {
+        let listing = [("a.tsv", "1"), ("b.tsv", "2"), ("a.tsv", "3")];
+        let duplicates = duplicate_ids(listing.iter().map(|(name, id)| (*name, id.to_string())));
+        assert_eq!(duplicates.len(), 1);
+        assert_eq!(duplicates["a.tsv"], ["1", "3"]);
+    }
+
+    #[test]
+    fn test_download_info_overwrite() {
+        let dir = tempdir().unwrap();
+        fs::write(dir.path().join("a.tsv"), "a\n").unwrap();
+        let merged_file = MergedFile {
+            local: Some(DataFile {
+                path: "a.tsv".to_string(),
+                tracked: true,
+                md5: format!("{:x}", md5::compute("a\n")),
+                size: 2,
+                urls: Vec::new(),
+                source: None,
+                added_at: None,
+                added_by: None,
+                remote_md5: None,
+            }),
+            remote: None,
+            remote_service: None,
+        };
+        let remote = Remote::Test(TestRemote::new("test"));
+
+        // an existing file is only replaced with --overwrite
+        check_error(
+            remote.get_download_info(&merged_file, dir.path(), false),
+            "Data file 'a.tsv' exists locally; use 'sdf pull --overwrite' to replace it.",
+        );
+        check_error(
+            remote.get_download_info(&merged_file, dir.path(), true),
+            "Remote is None",
+        );
+    }
+}
@@ -0,0 +1,257 @@
+//! Data availability reports (`sdf report`).
+//!
+//! A report is a human-readable rendering of the manifest, with the
+//! linked remotes, the tracked files and files downloaded from URLs,
+//! suitable as the basis of a paper's data availability statement.
+
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+
+use crate::lib::utils::{format_bytes, pluralize};
+
+/// A linked remote, as listed in a report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportRemote {
+    pub directory: String,
+    pub service: String,
+    pub title: String,
+    pub url: Option<String>,
+    /// The DOI reserved for the data set, active once it is published.
+    pub doi: Option<String>,
+    pub num_files: usize,
+}
+
+/// A manifest file, as listed in a report. `source` is the remote's
+/// service for tracked files, and the URL for downloaded files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportFile {
+    pub path: String,
+    pub size: u64,
+    pub md5: String,
+    pub source: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Report {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub remotes: Vec<ReportRemote>,
+    pub tracked: Vec<ReportFile>,
+    pub url_files: Vec<ReportFile>,
+    pub num_files: usize,
+    pub total_bytes: u64,
+    /// Set when the report was generated with --check and everything
+    /// was pushed and current.
+    pub verified_at: Option<DateTime<Utc>>,
+}
+
+/// The supported report formats.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+}
+
+impl ReportFormat {
+    pub fn parse(format: &str) -> Result<Self> {
+        match format.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(ReportFormat::Markdown),
+            _ => Err(anyhow!(
+                "Unsupported report format '{}'; the only format is 'markdown'.",
+                format
+            )),
+        }
+    }
+}
+
+impl Report {
+    pub fn render(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Markdown => self.render_markdown(),
+        }
+    }
+
+    fn render_markdown(&self) -> String {
+        let mut out = String::new();
+        let title = self.title.as_deref().unwrap_or("Data availability");
+        out.push_str(&format!("# {}\n\n", escape(title)));
+        if let Some(description) = &self.description {
+            out.push_str(&format!("{}\n\n", description));
+        }
+
+        out.push_str("## Remotes\n\n");
+        if self.remotes.is_empty() {
+            out.push_str("No directories are linked to remotes.\n\n");
+        } else {
+            let rows: Vec<Vec<String>> = self
+                .remotes
+                .iter()
+                .map(|remote| {
+                    vec![
+                        code(&remote.directory),
+                        remote.service.clone(),
+                        escape(&remote.title),
+                        remote.url.as_deref().map_or("unknown".to_string(), link),
+                        remote.num_files.to_string(),
+                    ]
+                })
+                .collect();
+            out.push_str(&table(
+                &["Directory", "Service", "Title", "URL", "Files"],
+                &rows,
+            ));
+            let dois: Vec<String> = self
+                .remotes
+                .iter()
+                .filter_map(|remote| {
+                    let doi = remote.doi.as_ref()?;
+                    Some(format!(
+                        "- {}: {}\n",
+                        code(&remote.directory),
+                        link(&format!("https://doi.org/{}", doi))
+                    ))
+                })
+                .collect();
+            if !dois.is_empty() {
+                out.push_str("Reserved DOIs (active once published):\n\n");
+                out.push_str(&dois.concat());
+                out.push('\n');
+            }
+        }
+
+        out.push_str("## Tracked files\n\n");
+        if self.tracked.is_empty() {
+            out.push_str("No files are tracked on remotes.\n\n");
+        } else {
+            out.push_str(&file_table(&self.tracked, "Remote", |s| s.to_string()));
+        }
+
+        out.push_str("## Files available by URL\n\n");
+        if self.url_files.is_empty() {
+            out.push_str("No files were downloaded from URLs.\n\n");
+        } else {
+            out.push_str(&file_table(&self.url_files, "Source", link));
+        }
+
+        out.push_str("## Totals\n\n");
+        let tracked_bytes: u64 = self.tracked.iter().map(|file| file.size).sum();
+        let url_bytes: u64 = self.url_files.iter().map(|file| file.size).sum();
+        out.push_str(&format!(
+            "- {} in the manifest ({})\n",
+            pluralize(self.num_files as u64, "file"),
+            format_bytes(self.total_bytes)
+        ));
+        out.push_str(&format!(
+            "- {} tracked on {} ({})\n",
+            pluralize(self.tracked.len() as u64, "file"),
+            pluralize(self.remotes.len() as u64, "remote"),
+            format_bytes(tracked_bytes)
+        ));
+        out.push_str(&format!(
+            "- {} available by URL ({})\n",
+            pluralize(self.url_files.len() as u64, "file"),
+            format_bytes(url_bytes)
+        ));
+        if let Some(verified_at) = self.verified_at {
+            out.push_str(&format!(
+                "\nAll files were verified as pushed and current on {}.\n",
+                verified_at.format("%Y-%m-%d")
+            ));
+        }
+        out
+    }
+}
+
+// Escape the characters that would break a table cell.
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn code(text: &str) -> String {
+    format!("`{}`", text.replace('`', "'"))
+}
+
+fn link(url: &str) -> String {
+    format!("<{}>", url)
+}
+
+fn table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let mut out = format!("| {} |\n", header.join(" | "));
+    out.push_str(&format!("|{}\n", " --- |".repeat(header.len())));
+    for row in rows {
+        out.push_str(&format!("| {} |\n", row.join(" | ")));
+    }
+    out.push('\n');
+    out
+}
+
+fn file_table<F>(files: &[ReportFile], source_header: &str, source: F) -> String
+where
+    F: Fn(&str) -> String,
+{
+    let rows: Vec<Vec<String>> = files
+        .iter()
+        .map(|file| {
+            vec![
+                code(&file.path),
+                format_bytes(file.size),
+                code(&file.md5),
+                file.source.as_deref().map_or(String::new(), &source),
+            ]
+        })
+        .collect();
+    table(&["File", "Size", "MD5", source_header], &rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_render_markdown() {
+        let report = Report {
+            title: Some("Maize | teosinte".to_string()),
+            description: None,
+            remotes: vec![ReportRemote {
+                directory: "data/supplement".to_string(),
+                service: "FigShare".to_string(),
+                title: "Supplement".to_string(),
+                url: Some("https://figshare.com/articles/dataset/_/42".to_string()),
+                doi: None,
+                num_files: 1,
+            }],
+            tracked: vec![ReportFile {
+                path: "data/supplement/a.tsv".to_string(),
+                size: 2048,
+                md5: "abc".to_string(),
+                source: Some("FigShare".to_string()),
+            }],
+            url_files: vec![],
+            num_files: 2,
+            total_bytes: 3072,
+            verified_at: Some(Utc.with_ymd_and_hms(2024, 3, 1, 12, 0, 0).unwrap()),
+        };
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.starts_with("# Maize \\| teosinte\n"));
+        assert!(markdown.contains(
+            "| `data/supplement` | FigShare | Supplement | \
+             <https://figshare.com/articles/dataset/_/42> | 1 |"
+        ));
+        assert!(markdown.contains("| `data/supplement/a.tsv` | 2.00 KB | `abc` | FigShare |"));
+        assert!(markdown.contains("No files were downloaded from URLs."));
+        assert!(markdown.contains("- 2 files in the manifest (3.00 KB)"));
+        assert!(markdown.contains("verified as pushed and current on 2024-03-01"));
+
+        assert!(!markdown.contains("Reserved DOIs"));
+
+        let mut report = report;
+        report.remotes[0].doi = Some("10.5281/zenodo.1234".to_string());
+        let markdown = report.render(ReportFormat::Markdown);
+        assert!(markdown.contains(
+            "Reserved DOIs (active once published):\n\n\
+             - `data/supplement`: <https://doi.org/10.5281/zenodo.1234>\n"
+        ));
+
+        assert!(ReportFormat::parse("html").is_err());
+    }
+}
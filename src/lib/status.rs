@@ -1,4 +1,22 @@
+//! Project status summaries.
+//!
+//! [`status_report()`] groups the status entries of the manifest's files by
+//! directory (or by directory at a given depth) and counts them, without
+//! printing anything; [`StatusReport::render()`] and
+//! [`StatusReport::to_json()`] turn the report into text for `sdf status`.
+
+use anyhow::{anyhow, Result};
 use clap::Parser;
+use colored::*;
+use serde_derive::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::ops::Add;
+use std::path::{Path, PathBuf};
+
+use crate::lib::data::{LocalStatusCode, StatusEntry};
+use crate::lib::remote::{Remote, RemoteStatusCode};
+use crate::lib::utils::pluralize;
 
 /// Status display options
 #[derive(Parser, Debug)]
@@ -12,6 +30,25 @@ pub struct StatusDisplayOptions {
     #[arg(short, long)]
     pub all: bool,
 
+    /// Download and hash the remote copies of files whose remote has no
+    /// MD5 (shown as 'exists'), to tell whether they match (with
+    /// --remotes; this uses bandwidth).
+    #[arg(long, requires = "remotes")]
+    pub deep_verify: bool,
+
+    /// Exit with an error if any remote is unavailable (with --remotes),
+    /// rather than showing the status of the remaining remotes.
+    #[arg(long)]
+    pub strict: bool,
+
+    /// Also show where each file came from, and who added it when.
+    #[arg(long)]
+    pub provenance: bool,
+
+    /// Don't show the file size column.
+    #[arg(long)]
+    pub no_size: bool,
+
     /// Don't print status with terminal colors.
     #[arg(long)]
     pub no_color: bool,
@@ -34,6 +71,16 @@ pub struct StatusDisplayOptions {
     /// it will list files in reverse lexicographic order).
     #[arg(short, long)]
     pub reverse: bool,
+
+    /// Print the status as JSON.
+    #[arg(long)]
+    pub json: bool,
+
+    /// Exit with 1 if any files are modified, deleted or messy, or else
+    /// with 2 if any tracked files have changes not pushed to their remote
+    /// (which requires --remotes to tell), and 0 otherwise.
+    #[arg(long)]
+    pub exit_code: bool,
 }
 
 impl StatusDisplayOptions {
@@ -45,3 +92,759 @@ impl StatusDisplayOptions {
         self.depth
     }
 }
+
+/// A directory the status is grouped by, and the name of its remote (if
+/// it is linked to one).
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Serialize)]
+pub struct DirectoryEntry {
+    pub path: String,
+    pub remote_name: Option<String>,
+}
+
+impl DirectoryEntry {
+    fn display(&self) -> String {
+        if let Some(remote) = &self.remote_name {
+            format!("{} > {}", self.path, remote)
+        } else {
+            self.path.clone()
+        }
+    }
+}
+
+/// Counts of files by status.
+#[derive(Debug, Default, Clone, PartialEq, Serialize)]
+pub struct FileCounts {
+    pub local: u64,            // Total local files
+    pub local_current: u64,    // Files that match their manifest MD5
+    pub local_modified: u64,   // Files that differ from manifest MD5
+    pub local_deleted: u64,    // Files in manifest but not on disk
+    pub local_unhashed: u64,   // Files added without an MD5 (sdf add --no-hash)
+    pub remote: u64,           // Files only on remote
+    pub both: u64,             // Files synced between local and remote
+    pub remote_different: u64, // Files where local matches manifest but differs from remote
+    pub local_messy: u64, // Files where local differs from both manifest and remote (MessyLocal)
+    pub unpushed: u64,    // Tracked files that match manifest but aren't on remote
+    pub total: u64,       // Total number of files
+}
+
+/// `sdf status --exit-code` exit codes.
+pub const EXIT_CURRENT: i32 = 0;
+pub const EXIT_MODIFIED: i32 = 1;
+pub const EXIT_UNPUSHED: i32 = 2;
+
+impl FileCounts {
+    pub fn from_entries(files: &[StatusEntry], has_remote_info: bool) -> Result<FileCounts> {
+        let mut counts = FileCounts::default();
+
+        for file in files {
+            counts.total += 1;
+            if !has_remote_info {
+                // When we don't have remote info, only track local status
+                if let Some(status) = &file.local_status {
+                    match status {
+                        LocalStatusCode::Current => {
+                            counts.local += 1;
+                            counts.local_current += 1;
+                        }
+                        LocalStatusCode::Modified => {
+                            counts.local += 1;
+                            counts.local_modified += 1;
+                        }
+                        LocalStatusCode::Deleted => {
+                            counts.local_deleted += 1;
+                        }
+                        LocalStatusCode::Unhashed => {
+                            counts.local += 1;
+                            counts.local_unhashed += 1;
+                        }
+                        LocalStatusCode::Invalid => {
+                            counts.local_messy += 1;
+                        }
+                    }
+                }
+                continue;
+            }
+
+            match (&file.local_status, &file.remote_status, &file.tracked) {
+                (None, None, _) => {
+                    return Err(anyhow!(
+                        "Internal Error: FileCounts found a file with both local/remote set to None."
+                    ));
+                }
+                // Tracked local files that match manifest but haven't been pushed
+                (Some(LocalStatusCode::Current), Some(RemoteStatusCode::NotExists), Some(true)) => {
+                    counts.local += 1;
+                    counts.local_current += 1;
+                    counts.unpushed += 1;
+                }
+                // Local files that match manifest but have no remote or aren't tracked
+                (Some(LocalStatusCode::Current), Some(RemoteStatusCode::NotExists), _)
+                | (Some(LocalStatusCode::Current), None, Some(false))
+                | (Some(LocalStatusCode::Current), None, None) => {
+                    counts.local += 1;
+                    counts.local_current += 1;
+                }
+                // Modified local files that have no remote or aren't tracked
+                (Some(LocalStatusCode::Modified), Some(RemoteStatusCode::NotExists), _)
+                | (Some(LocalStatusCode::Modified), None, Some(false))
+                | (Some(LocalStatusCode::Modified), None, None) => {
+                    counts.local += 1;
+                    counts.local_modified += 1;
+                }
+                // Deleted local files
+                (Some(LocalStatusCode::Deleted), _, _) => {
+                    counts.local_deleted += 1;
+                }
+                // Local files not hashed yet, which can't be compared
+                (Some(LocalStatusCode::Unhashed), _, _) => {
+                    counts.local += 1;
+                    counts.local_unhashed += 1;
+                }
+                // Files that are perfectly synced (local matches manifest matches remote)
+                (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Current), Some(true)) => {
+                    counts.both += 1;
+                }
+                // Local file matches manifest but differs from remote
+                (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Different), Some(true)) => {
+                    counts.remote_different += 1;
+                }
+                // Local file exists but doesn't match manifest or remote
+                (Some(_), Some(RemoteStatusCode::MessyLocal), _) => {
+                    counts.local_messy += 1;
+                }
+                // Files that only exist on remote
+                (None, Some(RemoteStatusCode::Current), _)
+                | (None, Some(RemoteStatusCode::Exists), _)
+                | (None, Some(RemoteStatusCode::NoLocal), _) => {
+                    counts.remote += 1;
+                }
+                // Remote file exists but we can't compare MD5s
+                (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Exists), Some(true)) => {
+                    counts.remote_different += 1;
+                }
+                // Everything else is counted as messy
+                _ => {
+                    counts.local_messy += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    /// The exit code for `sdf status --exit-code`: local changes take
+    /// precedence over unpushed ones, since they need to be resolved first.
+    pub fn exit_code(&self) -> i32 {
+        if self.local_modified + self.local_deleted + self.local_messy > 0 {
+            EXIT_MODIFIED
+        } else if self.remote_different + self.unpushed > 0 {
+            EXIT_UNPUSHED
+        } else {
+            EXIT_CURRENT
+        }
+    }
+
+    fn render(&self, short: bool, color: bool, out: &mut String) {
+        // Helper closure to conditionally apply color
+        let colorize = |text: String, color_fn: fn(String) -> ColoredString| -> String {
+            if color {
+                color_fn(text).to_string()
+            } else {
+                text
+            }
+        };
+
+        if short {
+            let mut parts = Vec::new();
+            if self.local > 0 {
+                let mut local_str = format!("{} local", self.local);
+                local_str = colorize(local_str, |s| s.green());
+
+                let mut issues = Vec::new();
+                if self.local_modified > 0 {
+                    issues.push(format!(
+                        "{} modified",
+                        colorize(self.local_modified.to_string(), |s| s.red())
+                    ));
+                }
+                if self.local_deleted > 0 {
+                    issues.push(format!(
+                        "{} deleted",
+                        colorize(self.local_deleted.to_string(), |s| s.yellow())
+                    ));
+                }
+                if self.local_unhashed > 0 {
+                    issues.push(format!(
+                        "{} not yet hashed",
+                        colorize(self.local_unhashed.to_string(), |s| s.yellow())
+                    ));
+                }
+                if !issues.is_empty() {
+                    local_str = format!("{} ({})", local_str, issues.join(", "));
+                }
+                parts.push(local_str);
+            }
+            if self.remote > 0 {
+                parts.push(format!(
+                    "{} remote-only",
+                    colorize(self.remote.to_string(), |s| s.yellow())
+                ));
+            }
+            if self.both > 0 {
+                parts.push(format!(
+                    "{} synced",
+                    colorize(self.both.to_string(), |s| s.cyan())
+                ));
+            }
+            if self.remote_different > 0 {
+                parts.push(format!(
+                    "{} differ from remote",
+                    colorize(self.remote_different.to_string(), |s| s.yellow())
+                ));
+            }
+            if self.local_messy > 0 {
+                parts.push(format!(
+                    "{} needs update",
+                    colorize(self.local_messy.to_string(), |s| s.red())
+                ));
+            }
+            if parts.is_empty() {
+                let _ = writeln!(out, "no files");
+            } else {
+                let _ = writeln!(
+                    out,
+                    "{} ({})",
+                    parts.join(", "),
+                    colorize(format!("total: {}", self.total), |s| s.bold())
+                );
+            }
+        } else {
+            let _ = writeln!(
+                out,
+                "{}",
+                colorize(format!("  {} files total", self.total), |s| s.bold())
+            );
+            if self.both > 0 {
+                let _ = writeln!(
+                    out,
+                    "  ✓ {} synced with remote",
+                    colorize(self.both.to_string(), |s| s.cyan())
+                );
+            }
+            if self.local > 0 {
+                let mut status_parts = Vec::new();
+                if self.local_current > 0 {
+                    status_parts.push(format!(
+                        "{} current",
+                        colorize(self.local_current.to_string(), |s| s.green())
+                    ));
+                }
+                if self.local_modified > 0 {
+                    status_parts.push(format!(
+                        "{} modified",
+                        colorize(self.local_modified.to_string(), |s| s.red())
+                    ));
+                }
+                if self.local_deleted > 0 {
+                    status_parts.push(format!(
+                        "{} deleted",
+                        colorize(self.local_deleted.to_string(), |s| s.yellow())
+                    ));
+                }
+                if self.local_unhashed > 0 {
+                    status_parts.push(format!(
+                        "{} not yet hashed",
+                        colorize(self.local_unhashed.to_string(), |s| s.yellow())
+                    ));
+                }
+                let status = if !status_parts.is_empty() {
+                    format!(" ({})", status_parts.join(", "))
+                } else {
+                    String::from(" (all current)")
+                };
+                let _ = writeln!(
+                    out,
+                    "  + {} local only{}",
+                    colorize(self.local.to_string(), |s| s.green()),
+                    status
+                );
+            }
+            if self.remote > 0 {
+                let _ = writeln!(
+                    out,
+                    "  - {} remote only",
+                    colorize(self.remote.to_string(), |s| s.yellow())
+                );
+            }
+            if self.remote_different > 0 {
+                let _ = writeln!(
+                    out,
+                    "  ! {} differ from remote",
+                    colorize(self.remote_different.to_string(), |s| s.yellow())
+                );
+            }
+            if self.local_messy > 0 {
+                let _ = writeln!(
+                    out,
+                    "  ! {} need update",
+                    colorize(self.local_messy.to_string(), |s| s.red())
+                );
+            }
+        }
+    }
+}
+
+impl Add for FileCounts {
+    type Output = FileCounts;
+
+    fn add(self, other: FileCounts) -> FileCounts {
+        FileCounts {
+            local: self.local + other.local,
+            local_current: self.local_current + other.local_current,
+            local_modified: self.local_modified + other.local_modified,
+            local_deleted: self.local_deleted + other.local_deleted,
+            local_unhashed: self.local_unhashed + other.local_unhashed,
+            remote: self.remote + other.remote,
+            both: self.both + other.both,
+            remote_different: self.remote_different + other.remote_different,
+            local_messy: self.local_messy + other.local_messy,
+            unpushed: self.unpushed + other.unpushed,
+            total: self.total + other.total,
+        }
+    }
+}
+
+/// The status entries of one directory, in display order, and their counts.
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusGroup {
+    pub directory: DirectoryEntry,
+    pub entries: Vec<StatusEntry>,
+    pub counts: FileCounts,
+}
+
+/// A project's status, grouped by directory (see [`status_report()`]).
+#[derive(Debug, Clone, Serialize)]
+pub struct StatusReport {
+    pub groups: Vec<StatusGroup>,
+    /// Counts over all files, including those on remotes but not in the
+    /// manifest.
+    pub counts: FileCounts,
+    /// Whether remote statuses were fetched.
+    pub remotes: bool,
+    /// The depth the groups summarize over, if any.
+    pub depth: Option<usize>,
+    /// Files whose copies differ between remotes, described (see
+    /// `DataCollection::remote_conflicts()`).
+    pub conflicts: Vec<String>,
+}
+
+/// Get the directory at the specified depth from a path string
+fn get_dir_at_depth(dir: &str, filename: &str, depth: usize) -> String {
+    // Combine directory and filename into a full path
+    let full_path = if dir.is_empty() {
+        Path::new(filename).to_path_buf()
+    } else {
+        Path::new(dir).join(filename).to_path_buf()
+    };
+
+    // Get the parent directory of the full path
+    let parent_path = full_path.parent().unwrap_or(Path::new("."));
+
+    // Split the parent path into components
+    let components: Vec<_> = parent_path.components().collect();
+
+    if depth == 0 || components.is_empty() {
+        return ".".to_string();
+    }
+
+    // Take components up to the specified depth
+    let depth_path: PathBuf = components
+        .iter()
+        .take(depth.min(components.len()))
+        .collect();
+
+    if depth_path.as_os_str().is_empty() {
+        ".".to_string()
+    } else {
+        depth_path.to_string_lossy().to_string()
+    }
+}
+
+/// Group and count the status entries of each directory (as from
+/// DataCollection::status()) for display. Files not in the manifest are
+/// only included with --all; with a depth (e.g. --short), entries are
+/// regrouped by their directory at that depth and empty groups dropped.
+pub fn status_report(
+    rows: BTreeMap<String, Vec<StatusEntry>>,
+    remotes: Option<&HashMap<String, Remote>>,
+    options: &StatusDisplayOptions,
+) -> Result<StatusReport> {
+    let mut counts = FileCounts::default();
+    for entries in rows.values() {
+        counts = counts + FileCounts::from_entries(entries, options.remotes)?;
+    }
+
+    let mut rows_by_dir: BTreeMap<DirectoryEntry, Vec<StatusEntry>> = rows
+        .into_iter()
+        .map(|(dir, entries)| {
+            let remote_name = remotes
+                .and_then(|remote_map| remote_map.get(&dir))
+                .map(|remote| remote.label());
+            let entry = DirectoryEntry {
+                path: dir,
+                remote_name,
+            };
+            (entry, entries)
+        })
+        .collect();
+
+    let depth = options.get_depth();
+    if let Some(depth) = depth {
+        let mut depth_grouped: BTreeMap<DirectoryEntry, Vec<StatusEntry>> = BTreeMap::new();
+        for (dir_entry, entries) in rows_by_dir {
+            for entry in entries {
+                let base_dir = get_dir_at_depth(&dir_entry.path, &entry.name, depth);
+                depth_grouped
+                    .entry(DirectoryEntry {
+                        path: base_dir,
+                        remote_name: dir_entry.remote_name.clone(),
+                    })
+                    .or_default()
+                    .push(entry);
+            }
+        }
+        rows_by_dir = depth_grouped;
+    }
+
+    let mut groups = Vec::new();
+    for (directory, mut entries) in rows_by_dir {
+        // ignore things that aren't in the manifest, unless --all
+        entries.retain(|entry| entry.local_status.is_some() || options.all);
+        if depth.is_some() && entries.is_empty() {
+            continue;
+        }
+        if !options.time {
+            // Sort the statuses by filename
+            entries.sort_by(|a, b| a.name.cmp(&b.name));
+        } else {
+            // Sort the statuses by timestamp
+            entries.sort_by_key(|b| std::cmp::Reverse(b.local_mod_time));
+        }
+        if options.reverse {
+            entries.reverse();
+        }
+        let counts = FileCounts::from_entries(&entries, options.remotes)?;
+        groups.push(StatusGroup {
+            directory,
+            entries,
+            counts,
+        });
+    }
+
+    Ok(StatusReport {
+        groups,
+        counts,
+        remotes: options.remotes,
+        depth,
+        conflicts: Vec::new(),
+    })
+}
+
+impl StatusReport {
+    /// Render the report as `sdf status` shows it: a summary, then
+    /// either a table of files or (with a depth) counts per directory.
+    pub fn render(&self, options: &StatusDisplayOptions) -> String {
+        let mut out = self.render_summary();
+        if self.depth.is_some() {
+            out.push_str(&self.render_short(options));
+        } else {
+            out.push_str(&self.render_table(options));
+        }
+        if !self.conflicts.is_empty() {
+            let heading = if options.no_color {
+                "Remote conflicts:".to_string()
+            } else {
+                "Remote conflicts:".bold().to_string()
+            };
+            let _ = writeln!(out, "\n{}", heading);
+            for conflict in &self.conflicts {
+                let _ = writeln!(out, "  {}", conflict);
+            }
+        }
+        out
+    }
+
+    /// The report as JSON.
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    fn render_summary(&self) -> String {
+        let counts = &self.counts;
+        let mut out = format!("{}\n", "Project data status:".bold());
+        if self.remotes {
+            let _ = writeln!(
+                out,
+                "{} local and tracked by a remote ({} only local, {} only remote), {} total.\n",
+                pluralize(counts.both, "file"),
+                pluralize(counts.local, "file"),
+                pluralize(counts.remote, "file"),
+                pluralize(counts.total, "file")
+            );
+        } else {
+            let _ = writeln!(
+                out,
+                "{} local files total.\n",
+                pluralize(counts.total, "file")
+            );
+        }
+        out
+    }
+
+    fn group_header(directory: &DirectoryEntry, color: bool) -> String {
+        let display_key = if directory.path.is_empty() {
+            ".".to_string()
+        } else {
+            directory.display()
+        };
+        let prettier_key = if color {
+            display_key.bold().to_string()
+        } else {
+            display_key
+        };
+        format!("[{}]\n", prettier_key)
+    }
+
+    /// Counts of each directory's files.
+    pub fn render_short(&self, options: &StatusDisplayOptions) -> String {
+        let mut out = String::new();
+        for group in &self.groups {
+            out.push_str(&Self::group_header(&group.directory, !options.no_color));
+            group
+                .counts
+                .render(options.short, !options.no_color, &mut out);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A fixed-width table of each directory's files.
+    pub fn render_table(&self, options: &StatusDisplayOptions) -> String {
+        let nspaces = 6;
+        let abbrev = Some(8);
+        let entries = || self.groups.iter().flat_map(|group| group.entries.iter());
+
+        // get the max number of columns (in case ragged)
+        let max_cols = entries()
+            .map(|entry| entry.columns(abbrev, !options.no_size).len())
+            .max()
+            .unwrap_or(0);
+
+        // compute max lengths across all rows
+        let mut max_lengths = vec![0; max_cols];
+        for status in entries() {
+            let cols = status.columns(abbrev, !options.no_size);
+            for (i, col) in cols.iter().enumerate() {
+                max_lengths[i] = max_lengths[i].max(col.len());
+            }
+        }
+
+        let mut out = String::new();
+        for group in &self.groups {
+            out.push_str(&Self::group_header(&group.directory, !options.no_color));
+
+            // the rows, with the correct widths
+            for status in &group.entries {
+                let cols = status.columns(abbrev, !options.no_size);
+                let mut fixed_row = Vec::new();
+                for (i, col) in cols.iter().enumerate() {
+                    // push a fixed-width column to vector
+                    let spacer = if i == 0 { " " } else { "" };
+                    let fixed_col = format!("{}{:width$}", spacer, col, width = max_lengths[i]);
+                    fixed_row.push(fixed_col);
+                }
+                let spacer = " ".repeat(nspaces);
+                let line = fixed_row.join(&spacer);
+                let status_line = if !options.no_color {
+                    status.color(line)
+                } else {
+                    line
+                };
+                let _ = writeln!(out, "{}", status_line);
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, local_status: LocalStatusCode, md5: &str, size: u64) -> StatusEntry {
+        StatusEntry {
+            name: name.to_string(),
+            local_status: Some(local_status),
+            remote_status: None,
+            tracked: Some(false),
+            remote_service: None,
+            local_md5: Some(md5.to_string()),
+            remote_md5: None,
+            manifest_md5: Some(md5.to_string()),
+            local_mod_time: None,
+            remote_mod_time: None,
+            size: Some(size),
+            local_size: None,
+            remote_unavailable: None,
+            last_remote_md5: None,
+        }
+    }
+
+    fn rows() -> BTreeMap<String, Vec<StatusEntry>> {
+        BTreeMap::from([
+            (
+                "data/raw".to_string(),
+                vec![
+                    entry("b.tsv", LocalStatusCode::Current, "1111222233334444", 2048),
+                    entry("a.tsv", LocalStatusCode::Modified, "5555666677778888", 10),
+                ],
+            ),
+            (
+                "data/raw/sub".to_string(),
+                vec![entry(
+                    "c.tsv",
+                    LocalStatusCode::Deleted,
+                    "9999aaaabbbbcccc",
+                    1,
+                )],
+            ),
+            (
+                "".to_string(),
+                vec![entry(
+                    "top.txt",
+                    LocalStatusCode::Current,
+                    "ddddeeeeffff0000",
+                    4,
+                )],
+            ),
+        ])
+    }
+
+    fn options(args: &[&str]) -> StatusDisplayOptions {
+        StatusDisplayOptions::parse_from(std::iter::once("status").chain(args.iter().copied()))
+    }
+
+    #[test]
+    fn test_status_report_counts() {
+        let report = status_report(rows(), None, &options(&[])).unwrap();
+        assert_eq!(report.counts.total, 4);
+        assert_eq!(report.counts.local_current, 2);
+        assert_eq!(report.counts.local_modified, 1);
+        assert_eq!(report.counts.local_deleted, 1);
+        let dirs: Vec<&str> = report
+            .groups
+            .iter()
+            .map(|group| group.directory.path.as_str())
+            .collect();
+        assert_eq!(dirs, vec!["", "data/raw", "data/raw/sub"]);
+        // sorted by name within a directory
+        assert_eq!(report.groups[1].entries[0].name, "a.tsv");
+        assert_eq!(report.groups[1].counts.local, 2);
+
+        // grouped at depth 1, everything under data/ is one group
+        let report = status_report(rows(), None, &options(&["--depth", "1"])).unwrap();
+        assert_eq!(report.groups.len(), 2);
+        assert_eq!(report.groups[1].directory.path, "data");
+        assert_eq!(report.groups[1].counts.total, 3);
+    }
+
+    #[test]
+    fn test_exit_code() {
+        let report = status_report(rows(), None, &options(&[])).unwrap();
+        assert_eq!(report.counts.exit_code(), EXIT_MODIFIED);
+
+        let mut current = entry("a.tsv", LocalStatusCode::Current, "1111222233334444", 1);
+        current.tracked = Some(true);
+        current.remote_status = Some(RemoteStatusCode::Current);
+        let counts = |entries: &[StatusEntry]| FileCounts::from_entries(entries, true).unwrap();
+        assert_eq!(counts(&[current.clone()]).exit_code(), EXIT_CURRENT);
+
+        let mut not_pushed = current.clone();
+        not_pushed.remote_status = Some(RemoteStatusCode::NotExists);
+        assert_eq!(
+            counts(&[current.clone(), not_pushed.clone()]).exit_code(),
+            EXIT_UNPUSHED
+        );
+        let mut different = current.clone();
+        different.remote_status = Some(RemoteStatusCode::Different);
+        assert_eq!(counts(&[different.clone()]).exit_code(), EXIT_UNPUSHED);
+
+        // untracked files needn't be pushed
+        not_pushed.tracked = Some(false);
+        assert_eq!(counts(&[not_pushed]).exit_code(), EXIT_CURRENT);
+
+        let mut messy = current;
+        messy.local_status = Some(LocalStatusCode::Modified);
+        messy.remote_status = Some(RemoteStatusCode::MessyLocal);
+        assert_eq!(counts(&[different, messy]).exit_code(), EXIT_MODIFIED);
+    }
+
+    #[test]
+    fn test_status_report_unsupported_remote() {
+        let remotes = HashMap::from([(
+            "data/raw".to_string(),
+            Remote::Unknown {
+                service: "FutureAPI".to_string(),
+                raw: serde_yaml::Value::Null,
+            },
+        )]);
+        let report = status_report(rows(), Some(&remotes), &options(&[])).unwrap();
+        assert_eq!(
+            report.groups[1].directory.display(),
+            "data/raw > (unsupported remote: FutureAPI)"
+        );
+    }
+
+    #[test]
+    fn test_render_golden() {
+        let options = options(&["--no-color"]);
+        let report = status_report(rows(), None, &options).unwrap();
+        let expected = "\
+Project data status:
+4 files local files total.
+
+[.]
+ top.txt      current      ddddeeee      4 B          \n\
+\n\
+[data/raw]
+ a.tsv        changed      55556666      10 B         \n \
+b.tsv        current      11112222      2.00 KB      \n\
+\n\
+[data/raw/sub]
+ c.tsv        deleted      9999aaaa      1 B          \n\
+\n";
+        assert_eq!(report.render(&options), expected);
+
+        let options = self::options(&["--no-color", "--short"]);
+        let report = status_report(rows(), None, &options).unwrap();
+        let expected = "\
+Project data status:
+4 files local files total.
+
+[.]
+1 local (total: 1)
+
+[data/raw]
+2 local (1 modified, 1 deleted) (total: 3)
+
+";
+        assert_eq!(report.render(&options), expected);
+
+        // files that differ between remotes follow the files
+        let mut report = status_report(rows(), None, &options).unwrap();
+        report.conflicts = vec!["'data/raw/a.tsv' differs between remotes".to_string()];
+        assert!(report
+            .render(&options)
+            .ends_with("\n\nRemote conflicts:\n  'data/raw/a.tsv' differs between remotes\n"));
+    }
+}
@@ -0,0 +1,198 @@
+//! Batch state for resuming interrupted pushes and pulls (`--resume`).
+//!
+//! While a push or pull runs, the files it has transferred are recorded
+//! (with their MD5s and sizes) in [`SYNC_STATE_FILE`] in the project
+//! root. If the command dies partway through a large batch, running it
+//! again with `--resume` skips checking the files already transferred
+//! (as long as the manifest's MD5 and the local file's size still match
+//! what was recorded), rather than re-hashing and comparing every file.
+//! The state file is deleted once a push or pull succeeds, and ignored
+//! if it is older than [`max_age()`] (`sdf config --resume-max-age`).
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Duration, Utc};
+use colored::Colorize;
+#[allow(unused_imports)]
+use log::{debug, info, trace};
+use serde_derive::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::lib::data::DataFile;
+use crate::print_warn;
+
+pub const SYNC_STATE_FILE: &str = ".scidataflow_sync_state.json";
+
+/// Batch state older than this many hours is ignored, unless set with
+/// `sdf config --resume-max-age`.
+pub const DEFAULT_MAX_AGE_HOURS: u64 = 72;
+
+static MAX_AGE_HOURS: AtomicU64 = AtomicU64::new(DEFAULT_MAX_AGE_HOURS);
+
+pub fn set_max_age_hours(hours: u64) {
+    MAX_AGE_HOURS.store(hours, Ordering::Relaxed);
+}
+
+/// How old batch state can be and still be resumed.
+pub fn max_age() -> Duration {
+    Duration::hours(MAX_AGE_HOURS.load(Ordering::Relaxed) as i64)
+}
+
+// A file transferred in the batch.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CompletedFile {
+    pub md5: String,
+    pub size: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    // "push" or "pull"
+    pub operation: String,
+    pub updated: DateTime<Utc>,
+    // the transferred files, by manifest path
+    pub completed: BTreeMap<String, CompletedFile>,
+}
+
+impl SyncState {
+    pub fn new(operation: &str) -> Self {
+        SyncState {
+            operation: operation.to_string(),
+            updated: Utc::now(),
+            completed: BTreeMap::new(),
+        }
+    }
+
+    pub fn path(path_context: &Path) -> PathBuf {
+        path_context.join(SYNC_STATE_FILE)
+    }
+
+    // The state to resume an operation from: that of the last batch, if
+    // it was the same operation and is recent enough, or else a new one.
+    pub fn resume(path_context: &Path, operation: &str) -> Self {
+        let path = SyncState::path(path_context);
+        if !path.exists() {
+            return SyncState::new(operation);
+        }
+        let state = match SyncState::load(&path) {
+            Ok(state) => state,
+            Err(err) => {
+                print_warn!("Ignoring {:?}, which could not be read: {:#}", path, err);
+                return SyncState::new(operation);
+            }
+        };
+        if state.operation != operation {
+            print_warn!(
+                "Ignoring {:?}, which is from a {} rather than a {}.",
+                path,
+                state.operation,
+                operation
+            );
+            return SyncState::new(operation);
+        }
+        if Utc::now() - state.updated > max_age() {
+            print_warn!(
+                "Ignoring {:?}, which is older than {} hours (see 'sdf config --resume-max-age').",
+                path,
+                max_age().num_hours()
+            );
+            return SyncState::new(operation);
+        }
+        state
+    }
+
+    fn load(path: &Path) -> Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents).map_err(|err| anyhow!(err))
+    }
+
+    // Write the state, via a temporary file so an interrupted write
+    // doesn't leave a truncated one.
+    pub fn save(&mut self, path_context: &Path) -> Result<()> {
+        self.updated = Utc::now();
+        let path = SyncState::path(path_context);
+        let temp_path = path.with_extension("json.tmp");
+        fs::write(&temp_path, serde_json::to_string_pretty(self)?)
+            .and_then(|_| fs::rename(&temp_path, &path))
+            .context(format!("Failed to write the batch state {:?}.", path))
+    }
+
+    // Delete the state, once the batch succeeded.
+    pub fn remove(path_context: &Path) -> Result<()> {
+        let path = SyncState::path(path_context);
+        if path.exists() {
+            fs::remove_file(&path)
+                .context(format!("Failed to delete the batch state {:?}.", path))?;
+        }
+        Ok(())
+    }
+
+    pub fn complete(&mut self, data_file: &DataFile) {
+        self.completed.insert(
+            data_file.path.clone(),
+            CompletedFile {
+                md5: data_file.md5.clone(),
+                size: data_file.size,
+            },
+        );
+    }
+
+    // Whether a file was transferred in this batch and hasn't changed
+    // since: its manifest MD5 is the one recorded, and the local file
+    // still has the recorded size.
+    pub fn is_completed(&self, data_file: &DataFile, path_context: &Path) -> bool {
+        let Some(completed) = self.completed.get(&data_file.path) else {
+            return false;
+        };
+        let local_size = fs::metadata(path_context.join(&data_file.path)).map(|md| md.len());
+        completed.md5 == data_file.md5
+            && completed.size == data_file.size
+            && local_size.ok() == Some(completed.size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.tsv"), "abc").unwrap();
+        let a = DataFile::new("a.tsv".to_string(), None, dir.path())
+            .await
+            .unwrap();
+
+        let mut state = SyncState::resume(dir.path(), "push");
+        assert!(!state.is_completed(&a, dir.path()));
+        state.complete(&a);
+        state.save(dir.path()).unwrap();
+
+        let state = SyncState::resume(dir.path(), "push");
+        assert!(state.is_completed(&a, dir.path()));
+        // a changed file is checked again
+        let updated = DataFile {
+            md5: "another MD5".to_string(),
+            ..a.clone()
+        };
+        assert!(!state.is_completed(&updated, dir.path()));
+        fs::write(dir.path().join("a.tsv"), "abcd").unwrap();
+        assert!(!state.is_completed(&a, dir.path()));
+
+        // state from another operation or too long ago is ignored
+        assert!(SyncState::resume(dir.path(), "pull").completed.is_empty());
+        let mut old = SyncState::resume(dir.path(), "push");
+        old.updated = Utc::now() - max_age() - Duration::hours(1);
+        fs::write(
+            SyncState::path(dir.path()),
+            serde_json::to_string(&old).unwrap(),
+        )
+        .unwrap();
+        assert!(SyncState::resume(dir.path(), "push").completed.is_empty());
+
+        SyncState::remove(dir.path()).unwrap();
+        assert!(!SyncState::path(dir.path()).exists());
+    }
+}
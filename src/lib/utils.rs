@@ -4,21 +4,19 @@ use colored::*;
 #[allow(unused_imports)]
 use log::{debug, info, trace};
 use md5::Context;
+use sha2::{Digest, Sha256};
 use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::fs;
 use std::fs::File;
-use std::io::Read;
-use std::ops::Add;
-use std::path::{Path, PathBuf};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
 use timeago::Formatter;
+use tokio::sync::Semaphore;
 
-use crate::lib::data::StatusEntry;
-use crate::lib::remote::Remote;
-
-use super::data::LocalStatusCode;
-use super::remote::RemoteStatusCode;
-use super::status::StatusDisplayOptions;
+use crate::lib::data::DataFile;
 
 pub const ISSUE_URL: &str = "https://github.com/vsbuffalo/scidataflow/issues";
 
@@ -30,6 +28,16 @@ pub fn load_file(path: &PathBuf) -> String {
     contents
 }
 
+// Ask the user a yes/no question on the terminal; anything but "y" or
+// "yes" (including no input) is a no.
+pub fn confirm(prompt: &str) -> Result<bool> {
+    print!("{} [y/N] ", prompt);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
 pub fn ensure_directory(dir: &Path) -> Result<()> {
     let path = Path::new(dir);
     if path.is_dir() {
@@ -56,220 +64,179 @@ pub fn ensure_exists(path: &Path) -> Result<()> {
     }
 }
 
-/// Compute the MD5 of a file returning None if the file is empty.
-pub async fn compute_md5(file_path: &Path) -> Result<Option<String>> {
-    const BUFFER_SIZE: usize = 1024;
-
-    let mut file = match File::open(file_path) {
-        Ok(file) => file,
-        Err(_) => return Ok(None),
-    };
-
-    let mut buffer = [0; BUFFER_SIZE];
-    let mut md5 = Context::new();
+/// Lexically normalize a path, dropping `.` components and resolving `..`
+/// against the preceding component. Unlike canonicalize(), this does not
+/// touch the file system, so it works on paths that do not exist yet.
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => match normalized.components().next_back() {
+                Some(Component::Normal(_)) => {
+                    normalized.pop();
+                }
+                Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+                _ => normalized.push(".."),
+            },
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
 
-    loop {
-        let bytes_read = match file.read(&mut buffer) {
-            Ok(0) => break, // EOF
-            Ok(bytes_read) => bytes_read,
-            Err(e) => return Err(anyhow!("I/O reading file: {:?}", e)),
-        };
+/// Normalize a manifest key (a file or directory path relative to the
+/// project root), so that e.g. "./data/supplement/" and "data/supplement"
+/// map to the same key.
+pub fn normalize_path_key(path: &str) -> String {
+    normalize_path(Path::new(path))
+        .to_string_lossy()
+        .to_string()
+}
 
-        md5.consume(&buffer[..bytes_read]);
+/// The text with any user name and password removed, if it's a URL, so
+/// credentials are not saved in the manifest or the history log.
+pub fn without_userinfo(text: &str) -> String {
+    match url::Url::parse(text) {
+        Ok(mut url) if !url.username().is_empty() || url.password().is_some() => {
+            let _ = url.set_username("");
+            let _ = url.set_password(None);
+            url.to_string()
+        }
+        _ => text.to_string(),
     }
+}
 
-    let result = md5.compute();
-    Ok(Some(format!("{:x}", result)))
+/// Check that a path from an untrusted source (e.g. another project's
+/// manifest, or a remote's file listing) stays inside the directory it
+/// is joined to: it must be relative, with no `..` components.
+pub fn check_contained_path(path: &str) -> Result<()> {
+    let escapes = Path::new(path).components().any(|component| {
+        matches!(
+            component,
+            Component::ParentDir | Component::RootDir | Component::Prefix(_)
+        )
+    });
+    if escapes {
+        return Err(anyhow!(
+            "Refusing to use the path '{}', which is not within the project directory.",
+            path
+        ));
+    }
+    Ok(())
 }
 
-/// Get the directory at the specified depth from a path string
-fn get_dir_at_depth(dir: &str, filename: &str, depth: usize) -> String {
-    // Combine directory and filename into a full path
-    let full_path = if dir.is_empty() {
-        Path::new(filename).to_path_buf()
-    } else {
-        Path::new(dir).join(filename).to_path_buf()
-    };
+static STRICT_PATHS: AtomicBool = AtomicBool::new(false);
 
-    // Get the parent directory of the full path
-    let parent_path = full_path.parent().unwrap_or(Path::new("."));
+/// Treat manifest paths that differ only by case as an error rather than
+/// a warning (e.g. from --strict-paths).
+pub fn set_strict_paths(strict: bool) {
+    STRICT_PATHS.store(strict, Ordering::Relaxed);
+}
 
-    // Split the parent path into components
-    let components: Vec<_> = parent_path.components().collect();
+pub fn strict_paths() -> bool {
+    STRICT_PATHS.load(Ordering::Relaxed)
+}
 
-    if depth == 0 || components.is_empty() {
-        return ".".to_string();
+/// Group paths that are distinct but would be the same file on a
+/// case-insensitive filesystem (e.g. macOS or Windows defaults). Only
+/// groups with more than one path are returned, sorted.
+pub fn case_collisions<'a, I>(paths: I) -> Vec<Vec<String>>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for path in paths {
+        groups
+            .entry(path.to_lowercase())
+            .or_default()
+            .push(path.to_string());
     }
+    groups
+        .into_values()
+        .filter_map(|mut group| {
+            group.sort();
+            group.dedup();
+            (group.len() > 1).then_some(group)
+        })
+        .collect()
+}
 
-    // Take components up to the specified depth
-    let depth_path: PathBuf = components
-        .iter()
-        .take(depth.min(components.len()))
-        .collect();
+// Read buffer size for hashing (1 MB).
+pub const HASH_BUFFER_SIZE: usize = 1024 * 1024;
 
-    if depth_path.as_os_str().is_empty() {
-        ".".to_string()
-    } else {
-        depth_path.to_string_lossy().to_string()
-    }
-}
-
-pub fn print_fixed_width_status_short(
-    rows: BTreeMap<DirectoryEntry, Vec<StatusEntry>>,
-    options: &StatusDisplayOptions,
-) {
-    let depth = options.get_depth();
-    // If depth is provided, reorganize the data based on the specified depth
-    let grouped_rows: BTreeMap<DirectoryEntry, Vec<StatusEntry>> = if let Some(depth) = depth {
-        let mut depth_grouped: BTreeMap<DirectoryEntry, Vec<StatusEntry>> = BTreeMap::new();
-        for (dir_entry, entries) in rows {
-            for entry in entries {
-                let base_dir = get_dir_at_depth(&dir_entry.path, &entry.name, depth);
-                depth_grouped
-                    .entry(DirectoryEntry {
-                        path: base_dir,
-                        remote_name: dir_entry.remote_name.clone(),
-                    })
-                    .or_default()
-                    .push(entry);
-            }
-        }
-        depth_grouped
-    } else {
-        rows
-    };
-    // dbg!(&grouped_rows);
+/// The digests computed in a single pass over a file by hash_file().
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileDigests {
+    pub md5: String,
+    pub sha256: Option<String>,
+}
 
-    // Print status table
-    let mut dir_keys: Vec<&DirectoryEntry> = grouped_rows.keys().collect();
-    dir_keys.sort();
+/// Hash a reader, computing the MD5 and (optionally) the SHA-256 from
+/// the same buffer, so large files are only read once.
+pub fn hash_reader<R: Read>(reader: &mut R, sha256: bool) -> Result<FileDigests> {
+    let mut buffer = vec![0; HASH_BUFFER_SIZE];
+    let mut md5 = Context::new();
+    let mut sha = if sha256 { Some(Sha256::new()) } else { None };
 
-    for key in dir_keys {
-        let mut statuses = grouped_rows[key]
-            .iter()
-            .filter(|status| status.local_status.is_some() || options.all)
-            .cloned()
-            .collect::<Vec<_>>();
+    loop {
+        let bytes_read = match reader.read(&mut buffer) {
+            Ok(0) => break, // EOF
+            Ok(bytes_read) => bytes_read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(anyhow!("I/O reading file: {:?}", e)),
+        };
 
-        if statuses.is_empty() {
-            continue;
+        md5.consume(&buffer[..bytes_read]);
+        if let Some(sha) = sha.as_mut() {
+            sha.update(&buffer[..bytes_read]);
         }
+    }
 
-        // TODO: we should consolidate code between this and
-        // print_fixed_width_status_short.
-        if !options.time {
-            // Sort the statuses by filename
-            statuses.sort_by(|a, b| a.name.cmp(&b.name));
-        } else {
-            // Sort the statuses by timestamp
-            statuses.sort_by(|a, b| b.local_mod_time.cmp(&a.local_mod_time));
-        }
+    Ok(FileDigests {
+        md5: format!("{:x}", md5.compute()),
+        sha256: sha.map(|sha| format!("{:x}", sha.finalize())),
+    })
+}
 
-        if options.reverse {
-            statuses.reverse();
-        }
+/// Hash a file, returning None if the file cannot be opened.
+pub fn hash_file(file_path: &Path, sha256: bool) -> Result<Option<FileDigests>> {
+    let mut file = match File::open(file_path) {
+        Ok(file) => file,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(hash_reader(&mut file, sha256)?))
+}
 
-        let display_key = if key.path.is_empty() {
-            ".".to_string()
-        } else {
-            key.display().to_string()
-        };
-        let prettier_key = if !options.no_color {
-            display_key.bold().to_string()
-        } else {
-            display_key.to_string()
-        };
-        println!("[{}]", prettier_key);
-        let file_counts =
-            get_counts(&statuses, options.remotes).expect("Internal error: get_counts().");
-        file_counts.pretty_print(options.short, !options.no_color);
-        println!();
-    }
-}
-
-pub fn print_fixed_width_status(
-    rows: BTreeMap<DirectoryEntry, Vec<StatusEntry>>,
-    nspaces: Option<usize>,
-    indent: Option<usize>,
-    options: &StatusDisplayOptions,
-) {
-    let indent = indent.unwrap_or(0);
-    let nspaces = nspaces.unwrap_or(6);
-    let abbrev = Some(8);
-
-    // get the max number of columns (in case ragged)
-    let max_cols = rows
-        .values()
-        .flat_map(|v| v.iter())
-        .map(|entry| entry.columns(abbrev).len())
-        .max()
-        .unwrap_or(0);
-
-    let mut max_lengths = vec![0; max_cols];
-
-    // compute max lengths across all rows
-    for status in rows.values().flat_map(|v| v.iter()) {
-        let cols = status.columns(abbrev);
-        for (i, col) in cols.iter().enumerate() {
-            max_lengths[i] = max_lengths[i].max(col.len());
-        }
-    }
+// The default number of files hashed concurrently.
+pub const DEFAULT_HASH_TASKS: usize = 4;
 
-    // print status table
-    let mut dir_keys: Vec<&DirectoryEntry> = rows.keys().collect();
-    dir_keys.sort();
+static HASH_SEMAPHORE: OnceLock<Semaphore> = OnceLock::new();
 
-    for key in dir_keys {
-        let mut statuses = rows[key].clone();
-        if !options.time {
-            // Sort the statuses by filename
-            statuses.sort_by(|a, b| a.name.cmp(&b.name));
-        } else {
-            // Sort the statuses by timestamp
-            statuses.sort_by(|a, b| b.local_mod_time.cmp(&a.local_mod_time));
-        }
+/// Set the maximum number of files hashed concurrently (e.g. from --jobs).
+/// This must be called before any hashing is done; later calls have no effect.
+pub fn set_max_hash_tasks(max_tasks: usize) {
+    let _ = HASH_SEMAPHORE.set(Semaphore::new(max_tasks.max(1)));
+}
 
-        if options.reverse {
-            statuses.reverse();
-        }
+fn hash_semaphore() -> &'static Semaphore {
+    HASH_SEMAPHORE.get_or_init(|| Semaphore::new(DEFAULT_HASH_TASKS))
+}
 
-        let display_key = if key.path.is_empty() {
-            ".".to_string()
-        } else {
-            key.display().to_string()
-        };
-        let prettier_key = if !options.no_color {
-            display_key.bold().to_string()
-        } else {
-            display_key.to_string()
-        };
-        println!("[{}]", prettier_key);
+/// Hash a file on tokio's blocking thread pool, so that large files (or slow
+/// network filesystems) do not stall the async runtime.
+pub async fn compute_digests(file_path: &Path, sha256: bool) -> Result<Option<FileDigests>> {
+    let _permit = hash_semaphore().acquire().await?;
+    let file_path = file_path.to_path_buf();
+    tokio::task::spawn_blocking(move || hash_file(&file_path, sha256)).await?
+}
 
-        // Print the rows with the correct widths
-        for status in statuses {
-            if status.local_status.is_none() && !options.all {
-                // ignore things that aren't in the manifest, unless --all
-                continue;
-            }
-            let cols = status.columns(abbrev);
-            let mut fixed_row = Vec::new();
-            for (i, col) in cols.iter().enumerate() {
-                // push a fixed-width column to vector
-                let spacer = if i == 0 { " " } else { "" };
-                let fixed_col = format!("{}{:width$}", spacer, col, width = max_lengths[i]);
-                fixed_row.push(fixed_col);
-            }
-            let spacer = " ".repeat(nspaces);
-            let line = fixed_row.join(&spacer);
-            let status_line = if !options.no_color {
-                status.color(line)
-            } else {
-                line.to_string()
-            };
-            println!("{}{}", " ".repeat(indent), status_line);
-        }
-        println!();
-    }
+/// Compute the MD5 of a file, returning None if the file does not exist or
+/// cannot be opened.
+pub async fn compute_md5(file_path: &Path) -> Result<Option<String>> {
+    Ok(compute_digests(file_path, false)
+        .await?
+        .map(|digests| digests.md5))
 }
 
 /* fn organize_by_dir(rows: Vec<StatusEntry>) -> BTreeMap<String, Vec<StatusEntry>> {
@@ -299,340 +266,46 @@ pub fn pluralize<T: Into<u64>>(count: T, noun: &str) -> String {
     }
 }
 
-#[derive(Debug, Default)]
-struct FileCounts {
-    local: u64,            // Total local files
-    local_current: u64,    // Files that match their manifest MD5
-    local_modified: u64,   // Files that differ from manifest MD5
-    local_deleted: u64,    // Files in manifest but not on disk
-    remote: u64,           // Files only on remote
-    both: u64,             // Files synced between local and remote
-    remote_different: u64, // Files where local matches manifest but differs from remote
-    local_messy: u64,      // Files where local differs from both manifest and remote (MessyLocal)
-    total: u64,            // Total number of files
-}
-
-impl FileCounts {
-    pub fn pretty_print(&self, short: bool, color: bool) {
-        // Helper closure to conditionally apply color
-        let colorize = |text: String, color_fn: fn(String) -> ColoredString| -> String {
-            if color {
-                color_fn(text).to_string()
-            } else {
-                text
-            }
-        };
-
-        if short {
-            let mut parts = Vec::new();
-            if self.local > 0 {
-                let mut local_str = format!("{} local", self.local);
-                local_str = colorize(local_str, |s| s.green());
-
-                let mut issues = Vec::new();
-                if self.local_modified > 0 {
-                    issues.push(format!(
-                        "{} modified",
-                        colorize(self.local_modified.to_string(), |s| s.red())
-                    ));
-                }
-                if self.local_deleted > 0 {
-                    issues.push(format!(
-                        "{} deleted",
-                        colorize(self.local_deleted.to_string(), |s| s.yellow())
-                    ));
-                }
-                if !issues.is_empty() {
-                    local_str = format!("{} ({})", local_str, issues.join(", "));
-                }
-                parts.push(local_str);
-            }
-            if self.remote > 0 {
-                parts.push(format!(
-                    "{} remote-only",
-                    colorize(self.remote.to_string(), |s| s.yellow())
-                ));
-            }
-            if self.both > 0 {
-                parts.push(format!(
-                    "{} synced",
-                    colorize(self.both.to_string(), |s| s.cyan())
-                ));
-            }
-            if self.remote_different > 0 {
-                parts.push(format!(
-                    "{} differ from remote",
-                    colorize(self.remote_different.to_string(), |s| s.yellow())
-                ));
-            }
-            if self.local_messy > 0 {
-                parts.push(format!(
-                    "{} needs update",
-                    colorize(self.local_messy.to_string(), |s| s.red())
-                ));
-            }
-            if parts.is_empty() {
-                println!("no files");
-            } else {
-                println!(
-                    "{} ({})",
-                    parts.join(", "),
-                    colorize(format!("total: {}", self.total), |s| s.bold())
-                );
-            }
-        } else {
-            println!(
-                "{}",
-                colorize(format!("  {} files total", self.total), |s| s.bold())
-            );
-            if self.both > 0 {
-                println!(
-                    "  ✓ {} synced with remote",
-                    colorize(self.both.to_string(), |s| s.cyan())
-                );
-            }
-            if self.local > 0 {
-                let mut status_parts = Vec::new();
-                if self.local_current > 0 {
-                    status_parts.push(format!(
-                        "{} current",
-                        colorize(self.local_current.to_string(), |s| s.green())
-                    ));
-                }
-                if self.local_modified > 0 {
-                    status_parts.push(format!(
-                        "{} modified",
-                        colorize(self.local_modified.to_string(), |s| s.red())
-                    ));
-                }
-                if self.local_deleted > 0 {
-                    status_parts.push(format!(
-                        "{} deleted",
-                        colorize(self.local_deleted.to_string(), |s| s.yellow())
-                    ));
-                }
-                let status = if !status_parts.is_empty() {
-                    format!(" ({})", status_parts.join(", "))
-                } else {
-                    String::from(" (all current)")
-                };
-                println!(
-                    "  + {} local only{}",
-                    colorize(self.local.to_string(), |s| s.green()),
-                    status
-                );
-            }
-            if self.remote > 0 {
-                println!(
-                    "  - {} remote only",
-                    colorize(self.remote.to_string(), |s| s.yellow())
-                );
-            }
-            if self.remote_different > 0 {
-                println!(
-                    "  ! {} differ from remote",
-                    colorize(self.remote_different.to_string(), |s| s.yellow())
-                );
-            }
-            if self.local_messy > 0 {
-                println!(
-                    "  ! {} need update",
-                    colorize(self.local_messy.to_string(), |s| s.red())
-                );
-            }
-        }
-    }
-}
-
-fn get_counts(files: &Vec<StatusEntry>, has_remote_info: bool) -> Result<FileCounts> {
-    let mut counts = FileCounts::default();
-
-    for file in files {
-        counts.total += 1;
-        if !has_remote_info {
-            // When we don't have remote info, only track local status
-            if let Some(status) = &file.local_status {
-                match status {
-                    LocalStatusCode::Current => {
-                        counts.local += 1;
-                        counts.local_current += 1;
-                    }
-                    LocalStatusCode::Modified => {
-                        counts.local += 1;
-                        counts.local_modified += 1;
-                    }
-                    LocalStatusCode::Deleted => {
-                        counts.local_deleted += 1;
-                    }
-                    LocalStatusCode::Invalid => {
-                        counts.local_messy += 1;
-                    }
-                }
-            }
-            continue;
-        }
-
-        match (&file.local_status, &file.remote_status, &file.tracked) {
-            (None, None, _) => {
-                return Err(anyhow!(
-                    "Internal Error: get_counts found a file with both local/remote set to None."
-                ));
-            }
-            // Local files that match manifest but have no remote or aren't tracked
-            (Some(LocalStatusCode::Current), Some(RemoteStatusCode::NotExists), _)
-            | (Some(LocalStatusCode::Current), None, Some(false))
-            | (Some(LocalStatusCode::Current), None, None) => {
-                counts.local += 1;
-                counts.local_current += 1;
-            }
-            // Modified local files that have no remote or aren't tracked
-            (Some(LocalStatusCode::Modified), Some(RemoteStatusCode::NotExists), _)
-            | (Some(LocalStatusCode::Modified), None, Some(false))
-            | (Some(LocalStatusCode::Modified), None, None) => {
-                counts.local += 1;
-                counts.local_modified += 1;
-            }
-            // Deleted local files
-            (Some(LocalStatusCode::Deleted), _, _) => {
-                counts.local_deleted += 1;
-            }
-            // Files that are perfectly synced (local matches manifest matches remote)
-            (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Current), Some(true)) => {
-                counts.both += 1;
-            }
-            // Local file matches manifest but differs from remote
-            (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Different), Some(true)) => {
-                counts.remote_different += 1;
-            }
-            // Local file exists but doesn't match manifest or remote
-            (Some(_), Some(RemoteStatusCode::MessyLocal), _) => {
-                counts.local_messy += 1;
-            }
-            // Files that only exist on remote
-            (None, Some(RemoteStatusCode::Current), _)
-            | (None, Some(RemoteStatusCode::Exists), _)
-            | (None, Some(RemoteStatusCode::NoLocal), _) => {
-                counts.remote += 1;
-            }
-            // Remote file exists but we can't compare MD5s
-            (Some(LocalStatusCode::Current), Some(RemoteStatusCode::Exists), Some(true)) => {
-                counts.remote_different += 1;
-            }
-            // Everything else is counted as messy
-            _ => {
-                counts.local_messy += 1;
-            }
-        }
-    }
-    Ok(counts)
-}
-
-impl Add for FileCounts {
-    type Output = FileCounts;
-
-    fn add(self, other: FileCounts) -> FileCounts {
-        FileCounts {
-            local: self.local + other.local,
-            local_current: self.local_current + other.local_current,
-            local_modified: self.local_modified + other.local_modified,
-            local_deleted: self.local_deleted + other.local_deleted,
-            remote: self.remote + other.remote,
-            both: self.both + other.both,
-            remote_different: self.remote_different + other.remote_different,
-            local_messy: self.local_messy + other.local_messy,
-            total: self.total + other.total,
-        }
-    }
-}
-
-fn get_counts_tree(
-    rows: &BTreeMap<String, Vec<StatusEntry>>,
-    has_remote_info: bool,
-) -> Result<FileCounts> {
-    let mut counts = FileCounts::default();
-    for files in rows.values() {
-        counts = counts + get_counts(files, has_remote_info)?;
-    }
-    Ok(counts)
-}
-
-#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
-pub struct DirectoryEntry {
-    path: String,
-    remote_name: Option<String>,
-}
+// Print the provenance of the manifest's files (where each came from,
+// and who added it when), sorted by path.
+pub fn print_provenance(files: &HashMap<String, DataFile>) {
+    let unknown = || "unknown".to_string();
+    let mut rows: Vec<[String; 4]> = files
+        .values()
+        .map(|data_file| {
+            [
+                data_file.path.clone(),
+                data_file.source.clone().unwrap_or_else(unknown),
+                data_file.added_by.clone().unwrap_or_else(unknown),
+                data_file
+                    .added_at
+                    .map(|added_at| added_at.format("%Y-%m-%d %H:%M:%S UTC").to_string())
+                    .unwrap_or_else(unknown),
+            ]
+        })
+        .collect();
+    rows.sort();
 
-impl DirectoryEntry {
-    fn display(&self) -> String {
-        if let Some(remote) = &self.remote_name {
-            format!("{} > {}", self.path, remote)
-        } else {
-            self.path.clone()
+    let header = ["file", "source", "added by", "added at"].map(|col| col.to_string());
+    let mut widths = [0; 4];
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        for (width, col) in widths.iter_mut().zip(row) {
+            *width = (*width).max(col.len());
         }
     }
-}
-
-pub fn print_status(
-    rows: BTreeMap<String, Vec<StatusEntry>>,
-    remote: Option<&HashMap<String, Remote>>,
-    options: &StatusDisplayOptions,
-) {
-    println!("{}", "Project data status:".bold());
-
-    // Pass the remote info state to get_counts
-    let counts =
-        get_counts_tree(&rows, options.remotes).expect("Internal Error: get_counts() panicked.");
-
-    // Adjust the status message based on whether we have remote info
-    if options.remotes {
-        println!(
-            "{} local and tracked by a remote ({} only local, {} only remote), {} total.\n",
-            pluralize(counts.both, "file"),
-            pluralize(counts.local, "file"),
-            pluralize(counts.remote, "file"),
-            pluralize(counts.total, "file")
-        );
-    } else {
-        println!("{} local files total.\n", pluralize(counts.total, "file"));
-    }
-
-    let rows_by_dir: BTreeMap<DirectoryEntry, Vec<StatusEntry>> = match remote {
-        Some(remote_map) => {
-            let mut new_map = BTreeMap::new();
-            for (directory, statuses) in rows {
-                let entry = if let Some(remote) = remote_map.get(&directory) {
-                    DirectoryEntry {
-                        path: directory,
-                        remote_name: Some(remote.name().to_string()),
-                    }
-                } else {
-                    DirectoryEntry {
-                        path: directory,
-                        remote_name: None,
-                    }
-                };
-                new_map.insert(entry, statuses);
-            }
-            new_map
-        }
-        None => rows
-            .into_iter()
-            .map(|(dir, statuses)| {
-                (
-                    DirectoryEntry {
-                        path: dir,
-                        remote_name: None,
-                    },
-                    statuses,
-                )
-            })
-            .collect(),
+    let format_row = |row: &[String; 4]| {
+        row.iter()
+            .zip(widths)
+            .map(|(col, width)| format!("{:width$}", col, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
     };
-
-    if options.get_depth().is_some() {
-        print_fixed_width_status_short(rows_by_dir, options)
-    } else {
-        print_fixed_width_status(rows_by_dir, None, None, options);
+    println!("\n{}", "Provenance:".bold());
+    println!(" {}", format_row(&header).bold());
+    for row in &rows {
+        println!(" {}", format_row(row));
     }
 }
 
@@ -642,10 +315,13 @@ pub fn format_bytes(size: u64) -> String {
     const BYTES_IN_GB: f64 = BYTES_IN_MB * 1024.0;
     const BYTES_IN_TB: f64 = BYTES_IN_GB * 1024.0;
     const BYTES_IN_PB: f64 = BYTES_IN_TB * 1024.0;
+    if size < BYTES_IN_KB as u64 {
+        return format!("{} B", size);
+    }
     let size = size as f64;
 
     if size < BYTES_IN_MB {
-        format!("{:.2} MB", size / BYTES_IN_KB)
+        format!("{:.2} KB", size / BYTES_IN_KB)
     } else if size < BYTES_IN_GB {
         format!("{:.2} MB", size / BYTES_IN_MB)
     } else if size < BYTES_IN_TB {
@@ -657,6 +333,140 @@ pub fn format_bytes(size: u64) -> String {
     }
 }
 
+// Whether this looks like an email address: a name, an '@', and a
+// domain with a dot, without spaces.
+pub fn is_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((name, domain)) => {
+            !name.is_empty()
+                && !email.contains(char::is_whitespace)
+                && !domain.contains('@')
+                && domain
+                    .split_once('.')
+                    .is_some_and(|(host, tld)| !host.is_empty() && !tld.is_empty())
+                && !domain.ends_with('.')
+        }
+        None => false,
+    }
+}
+
+// Whether this is an ORCID iD, i.e. four groups of four digits (the last
+// of which may be an 'X' check digit), e.g. '0000-0002-1825-0097'.
+pub fn is_orcid(orcid: &str) -> bool {
+    let groups: Vec<&str> = orcid.split('-').collect();
+    groups.len() == 4
+        && groups.iter().enumerate().all(|(i, group)| {
+            group.len() == 4
+                && group
+                    .chars()
+                    .enumerate()
+                    .all(|(j, c)| c.is_ascii_digit() || (c == 'X' && i == 3 && j == 3))
+        })
+}
+
+// The percentage of a budget used, e.g. '42.5%'.
+pub fn budget_percent(used: u64, budget: u64) -> String {
+    format!("{:.1}%", used as f64 / budget as f64 * 100.0)
+}
+
+// The SPDX identifier of a license (e.g. 'CC-BY-4.0'), which is matched
+// ignoring case.
+pub fn spdx_license(license: &str) -> Result<String> {
+    let license = license.trim();
+    let found = spdx::identifiers::LICENSES
+        .iter()
+        .find(|(id, _, _)| id.eq_ignore_ascii_case(license));
+    if let Some((id, _, _)) = found {
+        return Ok(id.to_string());
+    }
+    let hint = spdx::imprecise_license_id(license)
+        .map(|(id, _)| format!(" (did you mean '{}'?)", id.name))
+        .unwrap_or_default();
+    Err(anyhow!(
+        "'{}' is not an SPDX license identifier{}; see https://spdx.org/licenses/.",
+        license,
+        hint
+    ))
+}
+
+// The number and total size of some files, then each file's size, e.g.
+// "2 files (2.40 GB): data/a.bam (2.10 GB), data/b.tsv (300.00 MB)".
+pub fn sized_summary(files: &[(String, u64)]) -> String {
+    let count = pluralize(files.len() as u64, "file");
+    if files.is_empty() {
+        return count;
+    }
+    let total = files.iter().map(|(_, size)| size).sum();
+    let sizes: Vec<String> = files
+        .iter()
+        .map(|(path, size)| format!("{} ({})", path, format_bytes(*size)))
+        .collect();
+    format!("{} ({}): {}", count, format_bytes(total), sizes.join(", "))
+}
+
+// Whether a file has no write permission (for anyone).
+pub fn is_readonly(path: &Path) -> Result<bool> {
+    Ok(fs::metadata(path)?.permissions().readonly())
+}
+
+// Make a file read-only (i.e. chmod a-w), or writable again. On unix only
+// the owner's write bit is restored, and the other bits (as set by the
+// umask when the file was created) are kept.
+pub fn set_readonly(path: &Path, readonly: bool) -> Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = permissions.mode();
+        permissions.set_mode(if readonly {
+            mode & !0o222
+        } else {
+            mode | 0o200
+        });
+    }
+    #[cfg(not(unix))]
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(readonly);
+    fs::set_permissions(path, permissions)
+        .map_err(|err| anyhow!("Could not change the permissions of {:?}: {}", path, err))
+}
+
+// Parse a size like '500', '10KB', '1.5 MB', or '2G' into bytes, with the
+// same 1024-based units as format_bytes().
+pub fn parse_bytes(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let split = size
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(split);
+    let number: f64 = number.parse().map_err(|_| {
+        anyhow!(
+            "Invalid size '{}'; use e.g. '500', '10KB', or '1.5GB'.",
+            size
+        )
+    })?;
+    let exponent = match unit
+        .trim()
+        .to_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 0,
+        "K" => 1,
+        "M" => 2,
+        "G" => 3,
+        "T" => 4,
+        "P" => 5,
+        _ => {
+            return Err(anyhow!(
+                "Invalid size unit in '{}'; use B, KB, MB, GB, TB, or PB.",
+                size
+            ))
+        }
+    };
+    Ok((number * 1024f64.powi(exponent)).round() as u64)
+}
+
 pub fn format_mod_time(mod_time: chrono::DateTime<Utc>) -> String {
     let now = Utc::now();
     let duration_since_mod = now.signed_duration_since(mod_time);
@@ -692,3 +502,219 @@ pub fn md5_status(
         _ => "".to_string(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lib::test_utilities::check_error;
+    use rand::{Rng, SeedableRng};
+    use std::io::Write;
+
+    fn random_bytes(len: usize) -> Vec<u8> {
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        (0..len).map(|_| rng.gen()).collect()
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let cases = [
+            ("data/raw", "data/raw"),
+            ("./data/raw/", "data/raw"),
+            ("data/./raw", "data/raw"),
+            ("data/supplement/../raw", "data/raw"),
+            ("../data", "../data"),
+            ("/project/./data/../raw", "/project/raw"),
+            ("/..", "/"),
+            (".", ""),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(normalize_path(Path::new(path)), PathBuf::from(expected));
+        }
+    }
+
+    #[test]
+    fn test_format_bytes() {
+        assert_eq!(format_bytes(0), "0 B");
+        assert_eq!(format_bytes(512), "512 B");
+        assert_eq!(format_bytes(2048), "2.00 KB");
+        assert_eq!(format_bytes(5 * 1024 * 1024), "5.00 MB");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.00 GB");
+    }
+
+    #[test]
+    fn test_spdx_license() {
+        assert_eq!(spdx_license("CC-BY-4.0").unwrap(), "CC-BY-4.0");
+        assert_eq!(spdx_license("cc0-1.0").unwrap(), "CC0-1.0");
+        assert_eq!(spdx_license(" mit ").unwrap(), "MIT");
+        check_error(
+            spdx_license("CC-BY-5.0"),
+            "'CC-BY-5.0' is not an SPDX license identifier",
+        );
+        check_error(spdx_license("apache 2"), "did you mean 'Apache-2.0'?");
+    }
+
+    #[test]
+    fn test_sized_summary() {
+        assert_eq!(sized_summary(&[]), "0 files");
+        let files = [
+            ("data/a.bam".to_string(), 3 * 1024 * 1024),
+            ("data/b.txt".to_string(), 512),
+        ];
+        assert_eq!(
+            sized_summary(&files),
+            "2 files (3.00 MB): data/a.bam (3.00 MB), data/b.txt (512 B)"
+        );
+    }
+
+    #[test]
+    fn test_is_email() {
+        assert!(is_email("joan@berkeley.edu"));
+        assert!(is_email("j.b.scientist+sdf@mail.example.org"));
+        assert!(!is_email("joan"));
+        assert!(!is_email("joan@berkeley"));
+        assert!(!is_email("@berkeley.edu"));
+        assert!(!is_email("joan@@berkeley.edu"));
+        assert!(!is_email("joan @berkeley.edu"));
+        assert!(!is_email("joan@berkeley."));
+    }
+
+    #[test]
+    fn test_is_orcid() {
+        assert!(is_orcid("0000-0002-1825-0097"));
+        assert!(is_orcid("0000-0002-9079-593X"));
+        assert!(!is_orcid("0000-0002-1825"));
+        assert!(!is_orcid("0000-000X-1825-0097"));
+        assert!(!is_orcid("https://orcid.org/0000-0002-1825-0097"));
+    }
+
+    #[test]
+    fn test_budget_percent() {
+        assert_eq!(budget_percent(0, 100), "0.0%");
+        assert_eq!(budget_percent(425, 1000), "42.5%");
+        assert_eq!(budget_percent(3, 2), "150.0%");
+    }
+
+    #[test]
+    fn test_parse_bytes() {
+        assert_eq!(parse_bytes("500").unwrap(), 500);
+        assert_eq!(parse_bytes("2KB").unwrap(), 2048);
+        assert_eq!(parse_bytes("1.5 mb").unwrap(), 1024 * 1024 * 3 / 2);
+        assert_eq!(parse_bytes("3G").unwrap(), 3 * 1024 * 1024 * 1024);
+        assert_eq!(parse_bytes("1GiB").unwrap(), 1024 * 1024 * 1024);
+        // GB and GiB are the same, as in format_bytes()
+        assert_eq!(
+            parse_bytes("100GB").unwrap(),
+            parse_bytes("100 GiB").unwrap()
+        );
+        assert_eq!(format_bytes(parse_bytes("100GB").unwrap()), "100.00 GB");
+        assert!(parse_bytes("10 furlongs").is_err());
+        assert!(parse_bytes("MB").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_readonly() {
+        use std::os::unix::fs::PermissionsExt;
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.tsv");
+        fs::write(&path, "a\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o664)).unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        set_readonly(&path, true).unwrap();
+        assert!(is_readonly(&path).unwrap());
+        assert_eq!(mode(&path), 0o444);
+        // only the owner can write again
+        set_readonly(&path, false).unwrap();
+        assert!(!is_readonly(&path).unwrap());
+        assert_eq!(mode(&path), 0o644);
+    }
+
+    #[test]
+    fn test_normalize_path_key() {
+        assert_eq!(normalize_path_key("data/supplement"), "data/supplement");
+        assert_eq!(normalize_path_key("data/supplement/"), "data/supplement");
+        assert_eq!(normalize_path_key("./data/supplement/"), "data/supplement");
+        assert_eq!(normalize_path_key("./data//file.tsv"), "data/file.tsv");
+    }
+
+    #[test]
+    fn test_check_contained_path() {
+        for path in ["data/a.tsv", "./data/a.tsv", "a.tsv", "data/..a.tsv"] {
+            assert!(check_contained_path(path).is_ok(), "{}", path);
+        }
+        for path in ["../a.tsv", "data/../../.bashrc", "/etc/x", "data/../.."] {
+            assert!(check_contained_path(path).is_err(), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let paths = [
+            "data/Results.tsv",
+            "data/results.tsv",
+            "data/other.tsv",
+            "DATA/results.tsv",
+        ];
+        assert_eq!(
+            case_collisions(paths),
+            vec![vec![
+                "DATA/results.tsv".to_string(),
+                "data/Results.tsv".to_string(),
+                "data/results.tsv".to_string(),
+            ]]
+        );
+        assert!(case_collisions(["data/a.tsv", "data/b.tsv"]).is_empty());
+    }
+
+    #[test]
+    fn test_hash_reader_known_digests() {
+        let digests = hash_reader(&mut "abc".as_bytes(), true).unwrap();
+        assert_eq!(digests.md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(
+            digests.sha256.unwrap(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_hash_reader_no_sha256() {
+        let digests = hash_reader(&mut "abc".as_bytes(), false).unwrap();
+        assert!(digests.sha256.is_none());
+    }
+
+    #[test]
+    fn test_hash_file_spans_buffers() {
+        // not a multiple of the buffer size, so the last read is partial
+        let data = random_bytes(3 * HASH_BUFFER_SIZE + 17);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        let digests = hash_file(file.path(), true).unwrap().unwrap();
+        assert_eq!(digests.md5, format!("{:x}", md5::compute(&data)));
+        assert_eq!(
+            digests.sha256.unwrap(),
+            format!("{:x}", Sha256::digest(&data))
+        );
+    }
+
+    #[test]
+    fn test_hash_file_missing() {
+        let result = hash_file(Path::new("some/nonexistent/file"), true).unwrap();
+        assert!(result.is_none());
+    }
+
+    // A ~100 MB file, to check that large files hash identically to an
+    // in-memory digest. This is slow in debug builds, so run it with:
+    // cargo test --release -- --ignored
+    #[test]
+    #[ignore]
+    fn test_hash_file_large() {
+        let data = random_bytes(100 * 1024 * 1024 + 1);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&data).unwrap();
+
+        let digests = hash_file(file.path(), false).unwrap().unwrap();
+        assert_eq!(digests.md5, format!("{:x}", md5::compute(&data)));
+    }
+}
@@ -1,4 +1,4 @@
-///! Testing Utility Functions
+//! Testing Utility Functions
 
 #[allow(unused_imports)]
 use anyhow::{anyhow, Result};
@@ -20,8 +20,11 @@ use std::path::{Path, PathBuf};
 use std::sync::Once;
 use tempfile::TempDir;
 
+use scidataflow::lib::api::figshare::FigShareAPI;
+use scidataflow::lib::api::zenodo::ZenodoAPI;
 use scidataflow::lib::data::StatusEntry;
-use scidataflow::lib::project::Project;
+use scidataflow::lib::project::{ConfigUpdate, Project};
+use scidataflow::lib::remote::Remote;
 
 pub fn make_mock_fixtures() -> Vec<DataFileFixture> {
     let files = vec![
@@ -102,7 +105,7 @@ fn generate_directory_structure(
         if cached_file_path.exists() {
             std::fs::copy(&cached_file_path, &file_path)?;
         } else {
-            let is_gzip = file_path.extension().map_or(false, |ext| ext == "gz");
+            let is_gzip = file_path.extension().is_some_and(|ext| ext == "gz");
             let size_in_bytes = data_file_fixture.size * 1_000_000;
             generate_random_tsv(&file_path, size_in_bytes, is_gzip, rng)?;
             std::fs::copy(&file_path, &cached_file_path)?; // Now this should work
@@ -112,6 +115,7 @@ fn generate_directory_structure(
 }
 
 pub struct TestEnvironment {
+    #[allow(dead_code)]
     pub name: String,
     pub temp_dir: TempDir,
     pub main_dir: PathBuf,
@@ -160,7 +164,7 @@ impl TestEnvironment {
     pub fn build_project_directories(&mut self, data_fixtures: Vec<DataFileFixture>) -> Result<()> {
         generate_directory_structure(
             &data_fixtures,
-            &self.temp_dir.path(),
+            self.temp_dir.path(),
             &self.cache_dir,
             &mut self.rng,
         )?;
@@ -175,7 +179,7 @@ impl TestEnvironment {
 
 #[allow(dead_code)] // will implement later
 pub fn read_keep_temp() -> bool {
-    return env::var("KEEP_TEMP_DIR").is_ok();
+    env::var("KEEP_TEMP_DIR").is_ok()
 }
 
 impl Drop for TestEnvironment {
@@ -206,12 +210,13 @@ pub async fn setup(do_add: bool) -> TestFixture {
         "temp_dir: {:?}, current directory: {:?}",
         test_env.temp_dir, current_dir
     );
-    let _ = Project::set_config(
-        &Some("Joan B. Scientist".to_string()),
-        &Some("joan@ucberkely.edu".to_string()),
-        &Some("UC Berkeley".to_string()),
-    );
-    let _ = Project::init(Some(project_name));
+    let _ = Project::set_config(&ConfigUpdate {
+        name: Some("Joan B. Scientist".to_string()),
+        email: Some("joan@ucberkely.edu".to_string()),
+        affiliation: Some("UC Berkeley".to_string()),
+        ..Default::default()
+    });
+    let _ = Project::init(Some(project_name), false, false);
     let mut project = Project::new().expect("setting up TestFixture failed");
 
     if do_add {
@@ -220,7 +225,7 @@ pub async fn setup(do_add: bool) -> TestFixture {
         // get the files to add
         let files = &test_env.files.as_ref().unwrap();
         let add_files: Vec<String> = files
-            .into_iter()
+            .iter()
             .filter(|f| f.add)
             .map(|f| f.path.clone())
             .collect();
@@ -235,6 +240,64 @@ pub async fn setup(do_add: bool) -> TestFixture {
     }
 }
 
+// Base URL for mock remotes; setting a base URL also makes the
+// APIs use a test token rather than the user's authentication keys.
+pub const MOCK_BASE_URL: &str = "http://localhost/mock/";
+
+// Link mock remotes (FigShare for data/supplement, Zenodo for data/raw)
+// without contacting the APIs, and track the fixture files marked track.
+#[allow(dead_code)]
+pub fn link_mock_remotes(fixture: &mut TestFixture) {
+    let project = &mut fixture.project;
+    let figshare = FigShareAPI::new("test_project", Some(MOCK_BASE_URL.to_string())).unwrap();
+    let zenodo = ZenodoAPI::new("test_project", Some(MOCK_BASE_URL.to_string())).unwrap();
+    project
+        .data
+        .register_remote("data/supplement", Remote::FigShareAPI(figshare))
+        .unwrap();
+    project
+        .data
+        .register_remote("data/raw", Remote::ZenodoAPI(zenodo))
+        .unwrap();
+
+    let path_context = project.path_context();
+    for file in fixture.env.files.as_ref().unwrap() {
+        if file.add && file.track {
+            project.data.track_file(&file.path, &path_context).unwrap();
+        }
+    }
+    project.save().unwrap();
+}
+
+// Link remotes served by a mock server at base_url: FigShare article 42
+// for data/supplement and Zenodo deposition 7 (with the bucket at
+// /files/bucket) for data/raw. Unlike
+// link_mock_remotes(), these can list their files.
+#[allow(dead_code)]
+pub fn link_server_remotes(fixture: &mut TestFixture, base_url: &str) {
+    let remotes = [
+        (
+            "data/supplement",
+            "!FigShareAPI\narticle_id: 42\nname: test_project".to_string(),
+        ),
+        (
+            "data/raw",
+            // Zenodo's bucket URLs are requested relative to the base URL
+            "!ZenodoAPI\nname: test_project\ndeposition_id: 7\n\
+             bucket_url: https://zenodo.org/api/files/bucket"
+                .to_string(),
+        ),
+    ];
+    let project = &mut fixture.project;
+    for (directory, yaml) in remotes {
+        let mut remote: Remote = serde_yaml::from_str(&yaml).unwrap();
+        remote.set_base_url(base_url.to_string()).unwrap();
+        remote.set_token("test-token".to_string()).unwrap();
+        project.data.register_remote(directory, remote).unwrap();
+    }
+    project.save().unwrap();
+}
+
 pub fn iter_status_entries<'a>(
     statuses: &'a BTreeMap<String, Vec<StatusEntry>>,
 ) -> impl Iterator<Item = (PathBuf, &'a StatusEntry)> + 'a {
@@ -254,7 +317,7 @@ pub async fn get_statuses(
     let statuses = fixture
         .project
         .data
-        .status(&path_context, false)
+        .status(path_context, false)
         .await
         .expect("Error in getting statuses.");
     iter_status_entries(&statuses)
@@ -269,7 +332,7 @@ pub async fn get_statuses_map(
     let statuses = fixture
         .project
         .data
-        .status(&path_context, false)
+        .status(path_context, false)
         .await
         .expect("Error in getting statuses.");
     iter_status_entries(&statuses)
@@ -2,7 +2,7 @@
 use log::{debug, info, trace};
 
 mod common;
-use common::{generate_random_tsv, get_statuses, setup};
+use common::{generate_random_tsv, get_statuses, link_mock_remotes, link_server_remotes, setup};
 
 #[cfg(test)]
 mod tests {
@@ -11,10 +11,19 @@ mod tests {
 
     use super::generate_random_tsv;
     use super::get_statuses;
+    use super::link_mock_remotes;
+    use super::link_server_remotes;
     use super::setup;
-    use scidataflow::lib::data::LocalStatusCode;
+    use scidataflow::lib::api::figshare::FigShareAPI;
+    use scidataflow::lib::data::{LocalStatusCode, PullOptions, PushOptions};
+    use scidataflow::lib::filter::PathFilter;
+    use scidataflow::lib::project::{
+        BulkOptions, LinkOptions, MetadataUpdate, Project, MANIFEST_HASH_HEADER,
+    };
+    use scidataflow::lib::remote::Remote;
+    use scidataflow::lib::test_utilities::check_error;
     use std::fs;
-    use std::path::PathBuf;
+    use std::path::{Path, PathBuf};
 
     #[tokio::test]
     async fn test_fixture() {
@@ -40,6 +49,81 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn test_init_force() {
+        let _fixture = setup(true).await;
+        // a bad manual edit breaks one file entry, so the manifest won't load
+        let manifest = fs::read_to_string("data_manifest.yml").unwrap();
+        fs::write(
+            "data_manifest.yml",
+            manifest.replacen("tracked: false", "tracked: maybe", 1),
+        )
+        .unwrap();
+        assert!(Project::new().is_err());
+
+        check_error(
+            Project::init(None, false, false),
+            "Use 'sdf init --force' to rebuild it",
+        );
+        check_error(
+            Project::init(None, true, true),
+            "rebuild it without --hidden",
+        );
+        Project::init(None, true, false).unwrap();
+
+        // the other entries are kept, the broken one is added again from
+        // the file on disk, and the original is backed up
+        let project = Project::new().unwrap();
+        assert_eq!(project.data.files.len(), 4);
+        let readded = project.data.files.get("data/data.tsv").unwrap();
+        assert_eq!(
+            readded.md5,
+            format!("{:x}", md5::compute(fs::read("data/data.tsv").unwrap()))
+        );
+        assert!(!readded.tracked);
+        let backups = || -> Vec<String> {
+            fs::read_dir(".")
+                .unwrap()
+                .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+                .filter(|name| name.starts_with("data_manifest.yml.backup."))
+                .collect()
+        };
+        assert_eq!(backups().len(), 1);
+        let first_backup = backups().remove(0);
+        assert!(fs::read_to_string(&first_backup)
+            .unwrap()
+            .contains("tracked: maybe"));
+
+        // rebuilding again right away keeps the first backup
+        Project::init(None, true, false).unwrap();
+        assert_eq!(backups().len(), 2);
+        assert!(fs::read_to_string(&first_backup)
+            .unwrap()
+            .contains("tracked: maybe"));
+    }
+
+    #[tokio::test]
+    async fn test_init_nested() {
+        let fixture = setup(false).await;
+        let root = fixture.env.temp_dir.path().to_path_buf();
+        let nested = root.join("analysis/nested");
+        fs::create_dir_all(&nested).unwrap();
+        std::env::set_current_dir(&nested).unwrap();
+
+        // a project inside another is refused without --force
+        let result = Project::init(None, false, false);
+        std::env::set_current_dir(&root).unwrap();
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("inside an existing project"), "{}", err);
+        assert!(!nested.join("data_manifest.yml").exists());
+
+        std::env::set_current_dir(&nested).unwrap();
+        let result = Project::init(None, true, false);
+        std::env::set_current_dir(&root).unwrap();
+        result.unwrap();
+        assert!(nested.join("data_manifest.yml").exists());
+    }
+
     #[tokio::test]
     async fn test_add_status_current() {
         let mut fixture = setup(false).await;
@@ -52,13 +136,13 @@ mod tests {
         // get the files to add
         let files = &fixture.env.files.as_ref().unwrap();
         let add_files: Vec<String> = files
-            .into_iter()
+            .iter()
             .filter(|f| f.add)
             .map(|f| f.path.clone())
             .collect();
 
         // add those files
-        let _ = fixture.project.add(&add_files);
+        let _ = fixture.project.add(&add_files).await;
 
         // get statuses again
         let statuses = get_statuses(&mut fixture, &path_context).await;
@@ -122,7 +206,7 @@ mod tests {
 
         for file in &re_add_files {
             let files = vec![file.clone()];
-            let result = fixture.project.update(Some(&files)).await;
+            let result = fixture.project.update(Some(&files), false).await;
             assert!(result.is_ok(), "re-adding raised Error!");
         }
 
@@ -137,19 +221,117 @@ mod tests {
         assert_eq!(readd_status, LocalStatusCode::Current);
     }
 
+    #[tokio::test]
+    async fn test_update_directory() {
+        let mut fixture = setup(true).await;
+        let path_context = fixture.project.path_context();
+
+        // modify one file under data/supplement and delete the other
+        let modified = PathBuf::from("data/supplement/big_1.tsv.gz");
+        let _ = generate_random_tsv(&modified, 5, true, &mut fixture.env.rng);
+        std::fs::remove_file("data/supplement/big_2.tsv.gz").unwrap();
+
+        let dirs = vec!["data/supplement/".to_string()];
+        let result = fixture.project.update(Some(&dirs), false).await;
+        assert!(result.is_ok(), "updating a directory raised Error!");
+        let statuses = get_statuses_map(&mut fixture, &path_context).await;
+        let status = statuses.get(&modified).unwrap().local_status.clone();
+        assert_eq!(status.unwrap(), LocalStatusCode::Current);
+
+        // a directory with no manifest entries is an error
+        std::fs::create_dir_all("data/empty").unwrap();
+        let dirs = vec!["data/empty".to_string()];
+        let result = fixture.project.update(Some(&dirs), false).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("No files in the data manifest"));
+    }
+
+    #[tokio::test]
+    async fn test_add_no_hash() {
+        let mut fixture = setup(false).await;
+        let path_context = fixture.project.path_context();
+        let add_files: Vec<String> = fixture
+            .env
+            .files
+            .as_ref()
+            .unwrap()
+            .iter()
+            .filter(|f| f.add)
+            .map(|f| f.path.clone())
+            .collect();
+
+        fixture.project.add_unhashed(&add_files).unwrap();
+        let statuses = get_statuses_map(&mut fixture, &path_context).await;
+        for file in &add_files {
+            let status = &statuses[&PathBuf::from(file)];
+            assert_eq!(status.local_status, Some(LocalStatusCode::Unhashed));
+            assert_eq!(status.manifest_md5.as_deref(), Some(""));
+        }
+
+        // sdf update hashes them
+        fixture.project.update(None, false).await.unwrap();
+        let statuses = get_statuses_map(&mut fixture, &path_context).await;
+        for file in &add_files {
+            let status = &statuses[&PathBuf::from(file)];
+            assert_eq!(status.local_status, Some(LocalStatusCode::Current));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_add_outside_project() {
+        let mut fixture = setup(false).await;
+        let outside = tempfile::tempdir().unwrap();
+        let file = outside.path().join("other.tsv");
+        fs::write(&file, "a\tb\n").unwrap();
+        let root = fs::canonicalize(fixture.project.path_context()).unwrap();
+        let result = fixture
+            .project
+            .add(&vec![file.to_string_lossy().to_string()])
+            .await;
+        check_error(
+            result,
+            &format!(
+                "is not within the project directory '{}' (where data_manifest.yml is)",
+                root.display()
+            ),
+        );
+        assert!(fixture.project.data.files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_why() {
+        let mut fixture = setup(true).await;
+        let explanation = fixture
+            .project
+            .explain("data/data.tsv", false, false)
+            .await
+            .unwrap();
+        assert!(explanation.starts_with("data/data.tsv (untracked)"));
+        assert!(explanation.contains("Local status:  Current"));
+        assert!(explanation.contains("Remote:        none (not in a linked directory)"));
+        assert!(explanation.contains("Push would:    nothing (not in a linked directory)"));
+
+        let result = fixture
+            .project
+            .explain("data/other.tsv", false, false)
+            .await;
+        check_error(result, "'data/other.tsv' is not in the manifest.");
+    }
+
     #[tokio::test]
     async fn test_add_already_added_error() {
         let mut fixture = setup(true).await;
 
         if let Some(files) = &fixture.env.files {
             for file in files {
-                let mut file_list = Vec::new();
-                file_list.push(file.path.clone());
+                let file_list = vec![file.path.clone()];
                 let result = fixture.project.add(&file_list).await;
 
                 // check that we get
                 match result {
-                    Ok(_) => assert!(false, "Expected an error, but got Ok"),
+                    Ok(_) => panic!("Expected an error, but got Ok"),
                     Err(err) => {
                         assert!(
                             err.to_string().contains("already registered"),
@@ -174,7 +356,7 @@ mod tests {
         // get the files to add
         let files = &fixture.env.files.as_ref().unwrap();
         let add_files: Vec<String> = files
-            .into_iter()
+            .iter()
             .filter(|f| f.add)
             .map(|f| f.path.clone())
             .collect();
@@ -190,7 +372,11 @@ mod tests {
         assert!(!exists); // not there before move
 
         // try moving a file (renaming)
-        fixture.project.mv("data/data.tsv", new_name).await.unwrap();
+        fixture
+            .project
+            .mv("data/data.tsv", new_name, false, false)
+            .await
+            .unwrap();
 
         let exists = statuses.iter().any(|(path, _status)| path == &target_path);
         assert!(!exists); // now it should be there
@@ -199,7 +385,7 @@ mod tests {
         fs::create_dir_all("new_data/").unwrap();
         fixture
             .project
-            .mv("data/supplement/big_1.tsv.gz", "new_data/")
+            .mv("data/supplement/big_1.tsv.gz", "new_data/", false, false)
             .await
             .unwrap();
 
@@ -208,4 +394,1517 @@ mod tests {
         let exists = statuses.iter().any(|(path, _status)| path == &target_path);
         assert!(!exists); // now it should be there
     }
+
+    #[tokio::test]
+    async fn test_link_missing_directory() {
+        let mut fixture = setup(true).await;
+        let result = fixture
+            .project
+            .link(
+                "data/nonexistent",
+                "zenodo",
+                "fake-token",
+                &LinkOptions::default(),
+            )
+            .await;
+        check_error(result, "does not exist");
+    }
+
+    #[tokio::test]
+    async fn test_metadata_license() {
+        let mut fixture = setup(false).await;
+        let result = fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                license: Some("not-a-license".to_string()),
+                ..Default::default()
+            })
+            .await;
+        check_error(result, "is not an SPDX license identifier");
+        assert_eq!(fixture.project.data.metadata.license, None);
+
+        fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                license: Some("cc-by-4.0".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(
+            fixture.project.data.metadata.license.as_deref(),
+            Some("CC-BY-4.0")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_link_figshare_layout_other_service() {
+        let mut fixture = setup(true).await;
+        let result = fixture
+            .project
+            .link(
+                "data/supplement",
+                "zenodo",
+                "fake-token",
+                &LinkOptions {
+                    figshare_layout: Some("per-file".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "can only be used with FigShare remotes");
+    }
+
+    #[tokio::test]
+    async fn test_link_unknown_record_type() {
+        let mut fixture = setup(true).await;
+        // 'figure' is a FigShare item type, but not a Zenodo upload type
+        let result = fixture
+            .project
+            .link(
+                "data/supplement",
+                "zenodo",
+                "fake-token",
+                &LinkOptions {
+                    record_type: Some("figure".to_string()),
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "Unknown Zenodo upload type 'figure'");
+    }
+
+    #[tokio::test]
+    async fn test_link_without_title() {
+        let mut fixture = setup(true).await;
+        assert_eq!(fixture.project.name().unwrap(), "test_project");
+
+        // without a title, the temporary directory's name (e.g.
+        // '.tmpXXXXXX') isn't used for the remote
+        fixture.project.data.metadata.title = None;
+        fixture.project.save().unwrap();
+        let result = fixture
+            .project
+            .link(
+                "data/supplement",
+                "zenodo",
+                "fake-token",
+                &LinkOptions::default(),
+            )
+            .await;
+        check_error(result, "sdf link --name <NAME>");
+        assert!(fixture.project.data.remotes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_link_empty_directory() {
+        let fixture = setup(true).await;
+        fs::create_dir_all("data/empty").unwrap();
+
+        // an empty directory only warns
+        let dir = fixture
+            .project
+            .validate_link_directory("data/empty/")
+            .unwrap();
+        assert_eq!(dir, "data/empty");
+        assert!(fixture.project.data.files_in_directory(&dir).is_empty());
+
+        // while a directory with registered files has something to push
+        let dir = fixture
+            .project
+            .validate_link_directory("data/supplement")
+            .unwrap();
+        assert_eq!(fixture.project.data.files_in_directory(&dir).len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_directory_key() {
+        let fixture = setup(true).await;
+        let key = |dir: &str| fixture.project.directory_key(&PathBuf::from(dir)).unwrap();
+        assert_eq!(key("data/supplement/"), "data/supplement");
+        assert_eq!(key("./data/raw"), "data/raw");
+        // directories that don't exist yet (e.g. before a pull) also resolve
+        assert_eq!(key("data/not_yet/../new"), "data/new");
+        check_error(
+            fixture
+                .project
+                .directory_key(&PathBuf::from("../elsewhere")),
+            "not within the project directory",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_path_key_absolute_path() {
+        let fixture = setup(true).await;
+        let key = fixture
+            .project
+            .path_key(&PathBuf::from("./data/data.tsv"))
+            .unwrap();
+        assert_eq!(key, "data/data.tsv");
+        let path = fixture.project.absolute_path(&key).unwrap();
+        assert!(path.is_absolute());
+        assert_eq!(fixture.project.path_key(&path).unwrap(), key);
+        check_error(
+            fixture.project.absolute_path("../elsewhere"),
+            "not within the project directory",
+        );
+        check_error(
+            fixture.project.absolute_path("/data/data.tsv"),
+            "is an absolute path",
+        );
+    }
+
+    // Each path argument resolves to the same manifest key whether given
+    // from the project root, from a subdirectory with '../', or absolute.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_path_arguments_by_invocation() {
+        for invocation in ["root", "subdirectory", "absolute"] {
+            let mut fixture = setup(true).await;
+            link_mock_remotes(&mut fixture);
+            let root = fixture.env.temp_dir.path().canonicalize().unwrap();
+            fs::create_dir_all(root.join("analysis")).unwrap();
+            if invocation != "root" {
+                std::env::set_current_dir(root.join("analysis")).unwrap();
+            }
+            let arg = |key: &str| match invocation {
+                "root" => format!("./{}", key),
+                "subdirectory" => format!("../{}", key),
+                _ => root.join(key).to_string_lossy().to_string(),
+            };
+            let mut project = Project::new().unwrap();
+
+            // existing, nonexistent, and directory paths
+            for key in ["data/data.tsv", "data/not_yet/new.tsv", "data/raw"] {
+                assert_eq!(
+                    project.path_key(&PathBuf::from(arg(key))).unwrap(),
+                    key,
+                    "{}",
+                    invocation
+                );
+            }
+            // a path that doesn't exist yet, under a symlinked directory,
+            // has the same key as an existing file there would
+            std::os::unix::fs::symlink(root.join("data/raw"), root.join("raw_link")).unwrap();
+            assert_eq!(
+                project
+                    .path_key(&PathBuf::from(arg("raw_link/missing.tsv")))
+                    .unwrap(),
+                "data/raw/missing.tsv"
+            );
+            check_error(
+                project.path_key(&PathBuf::from(arg("../elsewhere.tsv"))),
+                "not within the project directory",
+            );
+
+            // add
+            fs::write(root.join("data/new.tsv"), "new\n").unwrap();
+            project.add(&vec![arg("data/new.tsv")]).await.unwrap();
+            assert!(project.data.files.contains_key("data/new.tsv"));
+
+            // untrack and track
+            let tracked = [arg("data/supplement/big_1.tsv.gz")];
+            project.untrack(&tracked, false).unwrap();
+            assert!(!project.data.files["data/supplement/big_1.tsv.gz"].tracked);
+            project.track(&tracked, false, false).await.unwrap();
+            assert!(project.data.files["data/supplement/big_1.tsv.gz"].tracked);
+
+            // update, of a file and a directory
+            fs::write(root.join("data/new.tsv"), "newer\n").unwrap();
+            project
+                .update(Some(&vec![arg("data/new.tsv")]), false)
+                .await
+                .unwrap();
+            assert_eq!(project.data.files["data/new.tsv"].size, 6);
+            project
+                .update(Some(&vec![arg("data/supplement")]), false)
+                .await
+                .unwrap();
+
+            // remote settings of a linked directory
+            project
+                .remote_exclude(&arg("data/supplement/"), "*.tmp")
+                .unwrap();
+            project.remote_readonly(&arg("data/raw"), true).unwrap();
+
+            // mv, into a directory
+            project
+                .mv(&arg("data/new.tsv"), &arg("data/raw"), false, false)
+                .await
+                .unwrap();
+            assert!(project.data.files.contains_key("data/raw/new.tsv"));
+            assert!(root.join("data/raw/new.tsv").exists());
+
+            // rm, of a file already deleted
+            fs::remove_file(root.join("data/data.tsv")).unwrap();
+            project.remove(&vec![arg("data/data.tsv")]).await.unwrap();
+            assert!(!project.data.files.contains_key("data/data.tsv"));
+
+            std::env::set_current_dir(&root).unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_equivalent_path_spellings() {
+        let mut fixture = setup(false).await;
+        let spellings = [
+            "./data/data.tsv",
+            "data/./data.tsv",
+            "data//data.tsv",
+            "data/../data/data.tsv",
+        ];
+        fixture
+            .project
+            .add(&vec![spellings[0].to_string()])
+            .await
+            .unwrap();
+        assert!(fixture.project.data.files.contains_key("data/data.tsv"));
+        for spelling in &spellings[1..] {
+            let result = fixture.project.add(&vec![spelling.to_string()]).await;
+            check_error(result, "already registered");
+        }
+        assert_eq!(fixture.project.data.files.len(), 1);
+
+        // a file that no longer exists is still removed by any spelling
+        fs::remove_file("data/data.tsv").unwrap();
+        fixture
+            .project
+            .remove(&vec![spellings[3].to_string()])
+            .await
+            .unwrap();
+        assert!(fixture.project.data.files.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_report() {
+        let mut fixture = setup(true).await;
+        let output = fixture.env.get_file_path("report.md");
+        fixture
+            .project
+            .report("markdown", true, Some(&output))
+            .await
+            .unwrap();
+        let report = std::fs::read_to_string(&output).unwrap();
+        for section in ["## Remotes", "## Tracked files", "## Totals"] {
+            assert!(report.contains(section), "missing section {}", section);
+        }
+        assert!(report.contains("- 4 files in the manifest"));
+        assert!(report.contains("verified as pushed and current on"));
+
+        // a modified file can't be verified
+        let _ = generate_random_tsv(
+            &PathBuf::from("data/data.tsv"),
+            5,
+            false,
+            &mut fixture.env.rng,
+        );
+        check_error(
+            fixture
+                .project
+                .report("markdown", true, Some(&output))
+                .await,
+            "data/data.tsv is not current locally",
+        );
+        check_error(
+            fixture.project.report("html", false, None).await,
+            "Unsupported report format",
+        );
+    }
+
+    #[tokio::test]
+    async fn test_report_counts_nested_files() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        fs::create_dir_all("data/supplement/nested").unwrap();
+        fs::write("data/supplement/nested/extra.tsv", "extra\n").unwrap();
+        let nested = vec!["data/supplement/nested/extra.tsv".to_string()];
+        fixture.project.add(&nested).await.unwrap();
+        // sdf track only tracks files directly in a linked directory, but
+        // manifests can be edited by hand
+        fixture
+            .project
+            .data
+            .files
+            .get_mut(&nested[0])
+            .unwrap()
+            .tracked = true;
+
+        let output = fixture.env.get_file_path("report.md");
+        fixture
+            .project
+            .report("markdown", false, Some(&output))
+            .await
+            .unwrap();
+        let report = std::fs::read_to_string(&output).unwrap();
+        let row = |dir: &str| {
+            report
+                .lines()
+                .find(|line| line.starts_with(&format!("| `{}`", dir)))
+                .unwrap()
+                .to_string()
+        };
+        // the nested file counts toward its linked directory
+        assert!(row("data/supplement").ends_with("| 3 |"), "{}", report);
+        assert!(row("data/raw").ends_with("| 1 |"), "{}", report);
+    }
+
+    // get, bulk, and pull --urls all skip existing files unless
+    // overwriting, and succeed either way (see overwrite.rs); push and
+    // pull from remotes are covered by test_push_remote_status() and
+    // test_pull_remote_status().
+    #[tokio::test]
+    async fn test_overwrite_consistent() {
+        let mut fixture = setup(false).await;
+        let server = httpmock::MockServer::start();
+        for name in ["got.tsv", "bulk.tsv"] {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path(format!("/{}", name));
+                then.status(200).body("remote\n");
+            });
+        }
+        let read = |path: &str| fs::read_to_string(path).unwrap();
+        let url = server.url("/got.tsv");
+
+        // sdf get
+        fs::write("got.tsv", "local\n").unwrap();
+        fixture
+            .project
+            .get(&url, None, false, false, None, None)
+            .await
+            .unwrap();
+        assert_eq!(read("got.tsv"), "local\n");
+        // a skipped file is not registered either
+        assert!(!fixture.project.data.files.contains_key("got.tsv"));
+        fixture
+            .project
+            .get(&url, None, true, false, None, None)
+            .await
+            .unwrap();
+        assert_eq!(read("got.tsv"), "remote\n");
+        assert!(fixture.project.data.files.contains_key("got.tsv"));
+
+        // sdf bulk
+        fs::write("urls.tsv", format!("{}\n", server.url("/bulk.tsv"))).unwrap();
+        fs::write("bulk.tsv", "local\n").unwrap();
+        fixture
+            .project
+            .bulk("urls.tsv", &BulkOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(read("bulk.tsv"), "local\n");
+        assert!(!fixture.project.data.files.contains_key("bulk.tsv"));
+        fixture
+            .project
+            .bulk(
+                "urls.tsv",
+                &BulkOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(read("bulk.tsv"), "remote\n");
+        assert!(fixture.project.data.files.contains_key("bulk.tsv"));
+
+        // sdf pull --urls, for the file added by get
+        fs::write("got.tsv", "local\n").unwrap();
+        let filter = PathFilter::new();
+        fixture
+            .project
+            .pull(
+                &[],
+                &filter,
+                &PullOptions {
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(read("got.tsv"), "local\n");
+        fixture
+            .project
+            .pull(
+                &[],
+                &filter,
+                &PullOptions {
+                    overwrite: true,
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(read("got.tsv"), "remote\n");
+
+        // sdf push and sdf pull, for a tracked file whose copy on the
+        // remote differs
+        link_server_remotes(&mut fixture, &server.url(""));
+        fs::write("data/raw/tracked.tsv", "local\n").unwrap();
+        let tracked = vec!["data/raw/tracked.tsv".to_string()];
+        fixture.project.add(&tracked).await.unwrap();
+        fixture.project.track(&tracked, false, false).await.unwrap();
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/account/articles/42/files");
+            then.status(200).json_body(serde_json::json!([]));
+        });
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/deposit/depositions/7/files");
+            then.status(200).json_body(serde_json::json!([{
+                "checksum": format!("{:x}", md5::compute("remote\n")),
+                "filename": "tracked.tsv",
+                "filesize": 7,
+                "id": "1",
+                "links": { "download": server.url("/files/1") }
+            }]));
+        });
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/files/1");
+            then.status(200).body("remote\n");
+        });
+        let delete = server.mock(|when, then| {
+            when.method(httpmock::Method::DELETE)
+                .path("/deposit/depositions/7/files/1");
+            then.status(204);
+        });
+        let upload = server.mock(|when, then| {
+            when.method(httpmock::Method::PUT)
+                .path("/files/bucket/tracked.tsv");
+            then.status(201).json_body(serde_json::json!({
+                "key": "tracked.tsv",
+                "mimetype": "text/tab-separated-values",
+                "checksum": format!("md5:{:x}", md5::compute("local\n")),
+                "version_id": "1",
+                "size": 6,
+                "created": "2026-01-01T00:00:00+00:00",
+                "updated": "2026-01-01T00:00:00+00:00",
+                "links": {},
+                "is_head": true,
+                "delete_marker": false
+            }));
+        });
+        let filter = PathFilter::new();
+        fixture
+            .project
+            .push(&filter, &PushOptions::default())
+            .await
+            .unwrap();
+        delete.assert_hits(0);
+        upload.assert_hits(0);
+        fixture
+            .project
+            .push(
+                &filter,
+                &PushOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        delete.assert_hits(1);
+        upload.assert_hits(1);
+
+        // the remote listing still has the remote version
+        fixture
+            .project
+            .pull(&[], &filter, &PullOptions::default())
+            .await
+            .unwrap();
+        assert_eq!(read("data/raw/tracked.tsv"), "local\n");
+        fixture
+            .project
+            .pull(
+                &[],
+                &filter,
+                &PullOptions {
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(read("data/raw/tracked.tsv"), "remote\n");
+    }
+
+    #[tokio::test]
+    async fn test_get_bulk_sha256() {
+        let mut fixture = setup(false).await;
+        let server = httpmock::MockServer::start();
+        for name in ["ref.fa", "a.tsv", "b.tsv"] {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path(format!("/{}", name));
+                then.status(200).body("abc");
+            });
+        }
+        let abc = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+        let other = "0".repeat(64);
+
+        // a download that doesn't match is discarded and not registered
+        let result = fixture
+            .project
+            .get(
+                &server.url("/ref.fa"),
+                None,
+                false,
+                false,
+                None,
+                Some(&other),
+            )
+            .await;
+        check_error(result, "does not match the expected SHA-256");
+        assert!(!PathBuf::from("ref.fa").exists());
+        assert!(fixture.project.data.files.is_empty());
+        fixture
+            .project
+            .get(&server.url("/ref.fa"), None, false, false, None, Some(abc))
+            .await
+            .unwrap();
+        assert!(fixture.project.data.files.contains_key("ref.fa"));
+
+        // with overwrite, a download that doesn't match leaves the
+        // existing file as it was
+        fs::write("ref.fa", "my reference").unwrap();
+        let result = fixture
+            .project
+            .get(
+                &server.url("/ref.fa"),
+                None,
+                true,
+                false,
+                None,
+                Some(&other),
+            )
+            .await;
+        check_error(result, "does not match the expected SHA-256");
+        assert_eq!(fs::read_to_string("ref.fa").unwrap(), "my reference");
+        assert!(!PathBuf::from("ref.fa.unverified").exists());
+
+        // invalid digests are rejected before downloading
+        let result = fixture
+            .project
+            .get(&server.url("/a.tsv"), None, false, false, None, Some("abc"))
+            .await;
+        check_error(result, "is not a SHA-256 digest");
+        assert!(!PathBuf::from("a.tsv").exists());
+
+        // bulk registers the matching downloads, and discards the rest
+        fs::write(
+            "urls.tsv",
+            format!(
+                "{}\t{}\n{}\t{}\n",
+                server.url("/a.tsv"),
+                abc,
+                server.url("/b.tsv"),
+                other
+            ),
+        )
+        .unwrap();
+        let result = fixture
+            .project
+            .bulk(
+                "urls.tsv",
+                &BulkOptions {
+                    sha256_column: Some(2),
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "1 download did not match the expected SHA-256");
+        assert!(fixture.project.data.files.contains_key("a.tsv"));
+        assert!(!PathBuf::from("b.tsv").exists());
+        assert!(!fixture.project.data.files.contains_key("b.tsv"));
+
+        // existing files are only replaced by verified downloads
+        fs::write("b.tsv", "my b").unwrap();
+        let result = fixture
+            .project
+            .bulk(
+                "urls.tsv",
+                &BulkOptions {
+                    sha256_column: Some(2),
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "1 download did not match the expected SHA-256");
+        assert_eq!(fs::read_to_string("b.tsv").unwrap(), "my b");
+        assert_eq!(fs::read_to_string("a.tsv").unwrap(), "abc");
+
+        // columns are numbered from 1
+        let result = fixture
+            .project
+            .bulk(
+                "urls.tsv",
+                &BulkOptions {
+                    sha256_column: Some(0),
+                    overwrite: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "column 0 is invalid");
+    }
+
+    #[tokio::test]
+    async fn test_get_no_download() {
+        let mut fixture = setup(false).await;
+        // nothing is served, so any download would fail
+        let url = "http://data.example.invalid/raw/huge.fastq.gz";
+        let md5 = format!("{:x}", md5::compute("reads\n"));
+
+        // the file must exist
+        let err = fixture
+            .project
+            .get(url, Some("huge.fastq.gz"), false, true, None, None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not exist"), "{}", err);
+
+        fs::write("huge.fastq.gz", "reads\n").unwrap();
+        let err = fixture
+            .project
+            .get(url, None, false, true, Some("0123456789abcdef"), None)
+            .await
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("does not match the expected MD5"), "{}", err);
+        assert!(!fixture.project.data.files.contains_key("huge.fastq.gz"));
+
+        // the default path is from the URL
+        fixture
+            .project
+            .get(url, None, false, true, Some(&md5), None)
+            .await
+            .unwrap();
+        let data_file = &fixture.project.data.files["huge.fastq.gz"];
+        assert_eq!(data_file.url(), Some(url));
+        assert_eq!(data_file.md5, md5);
+
+        // without --md5, the file is just hashed
+        fs::write("other.fastq.gz", "other\n").unwrap();
+        fixture
+            .project
+            .get(url, Some("other.fastq.gz"), false, true, None, None)
+            .await
+            .unwrap();
+        assert_eq!(
+            fixture.project.data.files["other.fastq.gz"].md5,
+            format!("{:x}", md5::compute("other\n"))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_url_mirrors() {
+        let mut fixture = setup(false).await;
+        let server = httpmock::MockServer::start();
+        let mut primary = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/primary/ref.fa");
+            then.status(200).body(">ref\n");
+        });
+        let mirror = server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/mirror/ref.fa");
+            then.status(200).body(">ref\n");
+        });
+        let mirror_url = server.url("/mirror/ref.fa");
+        fixture
+            .project
+            .get(
+                &server.url("/primary/ref.fa"),
+                None,
+                false,
+                false,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        fixture
+            .project
+            .set_url("ref.fa", &mirror_url, true)
+            .unwrap();
+        fixture
+            .project
+            .set_url("./ref.fa", &mirror_url, true)
+            .unwrap();
+        assert_eq!(
+            fixture.project.data.files["ref.fa"].urls,
+            vec![server.url("/primary/ref.fa"), mirror_url.clone()]
+        );
+        check_error(
+            fixture.project.set_url("ref.fa", "not a url", true),
+            "is not valid",
+        );
+
+        // the mirrors are saved as a list
+        let project = Project::new().unwrap();
+        assert_eq!(project.data.files["ref.fa"].urls.len(), 2);
+
+        // pull --urls falls back on the mirror when the primary URL fails
+        primary.delete();
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET).path("/primary/ref.fa");
+            then.status(404);
+        });
+        fs::remove_file("ref.fa").unwrap();
+        fixture
+            .project
+            .pull(
+                &[],
+                &PathFilter::new(),
+                &PullOptions {
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string("ref.fa").unwrap(), ">ref\n");
+        mirror.assert();
+
+        // without --append, the URL replaces the file's URLs
+        fixture
+            .project
+            .set_url("ref.fa", &mirror_url, false)
+            .unwrap();
+        assert_eq!(fixture.project.data.files["ref.fa"].urls, vec![mirror_url]);
+    }
+
+    #[tokio::test]
+    async fn test_url_login_not_saved() {
+        let mut fixture = setup(false).await;
+        let server = httpmock::MockServer::start();
+        for name in ["got.tsv", "bulk.tsv"] {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path(format!("/{}", name));
+                then.status(200).body("remote\n");
+            });
+        }
+        let with_login = |path: &str| server.url(path).replace("http://", "http://joan:secret@");
+
+        fixture
+            .project
+            .get(&with_login("/got.tsv"), None, false, false, None, None)
+            .await
+            .unwrap();
+        fs::write("urls.tsv", format!("{}\n", with_login("/bulk.tsv"))).unwrap();
+        fixture
+            .project
+            .bulk("urls.tsv", &BulkOptions::default())
+            .await
+            .unwrap();
+        fixture
+            .project
+            .set_url("got.tsv", &with_login("/mirror.tsv"), true)
+            .unwrap();
+
+        let manifest = fs::read_to_string("data_manifest.yml").unwrap();
+        assert!(!manifest.contains("secret"), "{}", manifest);
+        assert_eq!(
+            fixture.project.data.files["got.tsv"].urls,
+            vec![server.url("/got.tsv"), server.url("/mirror.tsv")]
+        );
+        assert_eq!(
+            fixture.project.data.files["bulk.tsv"].urls,
+            vec![server.url("/bulk.tsv")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pull_urls_from_subdirectory() {
+        let fixture = setup(false).await;
+        let root = fixture.project.path_context();
+        let server = httpmock::MockServer::start();
+        for name in ["remote.tsv", "other.tsv"] {
+            server.mock(|when, then| {
+                when.method(httpmock::Method::GET)
+                    .path(format!("/{}", name));
+                then.status(200).body("remote\n");
+            });
+        }
+
+        // register a URL file, then remove it and its directory
+        fs::create_dir_all("data/urls").unwrap();
+        let mut project = Project::new().unwrap();
+        project
+            .get(
+                &server.url("/remote.tsv"),
+                Some("data/urls/remote.tsv"),
+                false,
+                false,
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        fs::remove_dir_all("data/urls").unwrap();
+
+        // pull --urls from a nested directory restores it at its manifest path
+        fs::create_dir_all("analysis/nested").unwrap();
+        std::env::set_current_dir(root.join("analysis/nested")).unwrap();
+        let mut project = Project::new().unwrap();
+        project
+            .pull(
+                &[],
+                &PathFilter::new(),
+                &PullOptions {
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert!(root.join("data/urls/remote.tsv").exists());
+        assert!(!root.join("analysis/nested/data").exists());
+
+        // sdf get saves to the working directory, keyed relative to the root
+        project
+            .get(&server.url("/other.tsv"), None, false, false, None, None)
+            .await
+            .unwrap();
+        assert!(root.join("analysis/nested/other.tsv").exists());
+        assert!(project.data.files.contains_key("analysis/nested/other.tsv"));
+        std::env::set_current_dir(&root).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pull_limit_invalid_directory() {
+        let mut fixture = setup(true).await;
+
+        // no remotes are linked
+        let result = fixture
+            .project
+            .pull(
+                &[PathBuf::from("data/supplement")],
+                &PathFilter::new(),
+                &PullOptions::default(),
+            )
+            .await;
+        check_error(result, "is not linked to a remote");
+
+        // no files under this directory in the manifest
+        let result = fixture
+            .project
+            .pull(
+                &[PathBuf::from("data/nonexistent")],
+                &PathFilter::new(),
+                &PullOptions {
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await;
+        check_error(result, "has no files in the manifest");
+    }
+
+    #[tokio::test]
+    async fn test_pull_urls_limit() {
+        let mut fixture = setup(true).await;
+        let server = httpmock::MockServer::start();
+        let mocks: Vec<_> = ["inside.tsv", "outside.tsv"]
+            .iter()
+            .map(|name| {
+                server.mock(|when, then| {
+                    when.method(httpmock::Method::GET)
+                        .path(format!("/{}", name));
+                    then.status(200).body("remote\n");
+                })
+            })
+            .collect();
+
+        // a URL file in data/supplement and one outside it, both deleted
+        let paths = ["data/supplement/inside.tsv", "data/outside.tsv"];
+        for (name, path) in ["inside.tsv", "outside.tsv"].iter().zip(paths) {
+            fixture
+                .project
+                .get(
+                    &server.url(format!("/{}", name)),
+                    Some(path),
+                    false,
+                    false,
+                    None,
+                    None,
+                )
+                .await
+                .unwrap();
+            fs::remove_file(path).unwrap();
+        }
+
+        // only the file in the directory is downloaded again
+        fixture
+            .project
+            .pull(
+                &[PathBuf::from("data/supplement")],
+                &PathFilter::new(),
+                &PullOptions {
+                    urls: true,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        assert_eq!(fs::read_to_string(paths[0]).unwrap(), "remote\n");
+        assert!(!Path::new(paths[1]).exists());
+        mocks[0].assert_hits(2);
+        mocks[1].assert_hits(1);
+    }
+
+    #[tokio::test]
+    async fn test_mv_to_unlinked_directory_untracks() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        let file = "data/supplement/big_1.tsv.gz";
+        assert!(fixture.project.data.files[file].tracked);
+
+        fixture
+            .project
+            .mv(file, "data/", false, false)
+            .await
+            .unwrap();
+        let moved = &fixture.project.data.files["data/big_1.tsv.gz"];
+        assert!(!moved.tracked);
+        assert!(!fixture.project.data.files.contains_key(file));
+    }
+
+    #[tokio::test]
+    async fn test_mv_within_linked_directory_stays_tracked() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+
+        fixture
+            .project
+            .mv(
+                "data/supplement/big_1.tsv.gz",
+                "data/supplement/big_1_renamed.tsv.gz",
+                true,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(fixture.project.data.files["data/supplement/big_1_renamed.tsv.gz"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_mv_between_remotes_stays_tracked() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+
+        // data/supplement is linked to FigShare, data/raw to Zenodo
+        fixture
+            .project
+            .mv("data/supplement/big_2.tsv.gz", "data/raw", false, false)
+            .await
+            .unwrap();
+        let moved = &fixture.project.data.files["data/raw/big_2.tsv.gz"];
+        assert!(moved.tracked);
+        assert_eq!(
+            fixture.project.data.get_this_files_remote(moved).unwrap(),
+            Some("Zenodo".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_mv_between_remotes_untrack() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+
+        fixture
+            .project
+            .mv("data/supplement/big_2.tsv.gz", "data/raw", true, false)
+            .await
+            .unwrap();
+        let moved = &fixture.project.data.files["data/raw/big_2.tsv.gz"];
+        assert!(!moved.tracked);
+
+        // moving into an unlinked directory doesn't check any remote
+        fixture
+            .project
+            .mv("data/supplement/big_1.tsv.gz", "data/", false, true)
+            .await
+            .unwrap();
+        assert!(!fixture.project.data.files["data/big_1.tsv.gz"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_track_check_remote() {
+        let mut fixture = setup(true).await;
+        let server = httpmock::MockServer::start();
+        link_server_remotes(&mut fixture, &server.url(""));
+        let mut listing = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/account/articles/42/files");
+            then.status(200).json_body(serde_json::json!([]));
+        });
+
+        // only --check-remote lists the remote's files
+        let files = vec!["data/supplement/big_1.tsv.gz".to_string()];
+        fixture.project.track(&files, false, false).await.unwrap();
+        listing.assert_hits(0);
+        fixture.project.track(&files, true, true).await.unwrap();
+        listing.assert_hits(1);
+
+        // a failed check is an error, but the file is still tracked
+        listing.delete();
+        server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/account/articles/42/files");
+            then.status(500);
+        });
+        let files = vec!["data/supplement/big_2.tsv.gz".to_string()];
+        let result = fixture.project.track(&files, true, false).await;
+        check_error(result, "HTTP Error: 500");
+        let project = Project::new().unwrap();
+        assert!(project.data.files["data/supplement/big_2.tsv.gz"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_mv_check_remote() {
+        let mut fixture = setup(true).await;
+        let server = httpmock::MockServer::start();
+        link_server_remotes(&mut fixture, &server.url(""));
+        let files = vec![
+            "data/supplement/big_1.tsv.gz".to_string(),
+            "data/supplement/big_2.tsv.gz".to_string(),
+        ];
+        fixture.project.track(&files, false, false).await.unwrap();
+        let source_listing = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/account/articles/42/files");
+            then.status(200).json_body(serde_json::json!([]));
+        });
+        let destination_listing = server.mock(|when, then| {
+            when.method(httpmock::Method::GET)
+                .path("/deposit/depositions/7/files");
+            then.status(200).json_body(serde_json::json!([]));
+        });
+
+        // moving within the linked directory doesn't check the remote
+        fixture
+            .project
+            .mv(
+                "data/supplement/big_1.tsv.gz",
+                "data/supplement/big_1_renamed.tsv.gz",
+                false,
+                true,
+            )
+            .await
+            .unwrap();
+        source_listing.assert_hits(0);
+
+        // moving to a directory linked to another remote checks that remote
+        fixture
+            .project
+            .mv("data/supplement/big_2.tsv.gz", "data/raw", false, true)
+            .await
+            .unwrap();
+        destination_listing.assert_hits(1);
+        source_listing.assert_hits(0);
+        assert!(fixture.project.data.files["data/raw/big_2.tsv.gz"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_add_auto_track_min_size() {
+        let mut fixture = setup(false).await;
+        let figshare =
+            FigShareAPI::new("test_project", Some("http://127.0.0.1".to_string())).unwrap();
+        fixture
+            .project
+            .data
+            .register_remote("data/supplement", Remote::FigShareAPI(figshare))
+            .unwrap();
+        fixture.project.config =
+            serde_yaml::from_str("user:\n  name: Joan\nauto_track_min_size: 1048576\n").unwrap();
+        fs::write("data/supplement/README.md", "A small file.\n").unwrap();
+
+        // only large files in linked directories are tracked
+        let files = vec![
+            "data/supplement/big_1.tsv.gz".to_string(),
+            "data/supplement/README.md".to_string(),
+            "data/data.tsv".to_string(),
+        ];
+        fixture.project.add(&files).await.unwrap();
+        let data = &fixture.project.data;
+        assert!(data.files["data/supplement/big_1.tsv.gz"].tracked);
+        assert!(!data.files["data/supplement/README.md"].tracked);
+        assert!(!data.files["data/data.tsv"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_undo() {
+        let fixture = setup(true).await;
+        assert!(fixture.project.data.files.contains_key("data/data.tsv"));
+
+        // each command is its own change, as with separate sdf processes
+        let mut project = Project::new().unwrap();
+        project
+            .remove(&vec!["data/data.tsv".to_string()])
+            .await
+            .unwrap();
+        let mut project = Project::new().unwrap();
+        project
+            .mv("data/supplement/big_1.tsv.gz", "data/", false, false)
+            .await
+            .unwrap();
+        assert!(PathBuf::from("data/big_1.tsv.gz").exists());
+
+        // the move is undone first, moving the file back
+        let undone = Project::undo().unwrap();
+        assert_eq!(undone.moves.len(), 1);
+        assert!(PathBuf::from("data/supplement/big_1.tsv.gz").exists());
+        assert!(!PathBuf::from("data/big_1.tsv.gz").exists());
+        let project = Project::new().unwrap();
+        assert!(project
+            .data
+            .files
+            .contains_key("data/supplement/big_1.tsv.gz"));
+        assert!(!project.data.files.contains_key("data/data.tsv"));
+
+        // then the removal
+        Project::undo().unwrap();
+        let project = Project::new().unwrap();
+        assert!(project.data.files.contains_key("data/data.tsv"));
+    }
+
+    #[tokio::test]
+    async fn test_init_hidden() {
+        let fixture = setup(false).await;
+        let root = fixture.env.temp_dir.path().to_path_buf();
+        let nested = root.join("analysis/hidden");
+        fs::create_dir_all(nested.join("data")).unwrap();
+        fs::write(nested.join("data/a.tsv"), "a\n").unwrap();
+        std::env::set_current_dir(&nested).unwrap();
+
+        Project::init(None, true, true).unwrap();
+        assert!(nested.join(".scidataflow/manifest.yml").exists());
+        assert!(!nested.join("data_manifest.yml").exists());
+        assert!(Project::init(None, true, false).is_err());
+
+        // the hidden manifest is found from subdirectories, with paths
+        // relative to the project root
+        std::env::set_current_dir(nested.join("data")).unwrap();
+        let mut project = Project::new().unwrap();
+        assert_eq!(project.path_context(), nested.canonicalize().unwrap());
+        project.add(&vec!["a.tsv".to_string()]).await.unwrap();
+        let project = Project::new().unwrap();
+        assert!(project.data.files.contains_key("data/a.tsv"));
+        assert_eq!(project.name().unwrap(), "hidden");
+
+        // both layouts at once are ambiguous
+        fs::write(nested.join("data_manifest.yml"), "").unwrap();
+        let result = Project::new();
+        std::env::set_current_dir(&root).unwrap();
+        let err = format!("{:#}", result.err().unwrap());
+        assert!(err.contains("remove one of them"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_migrate_to_hidden() {
+        let fixture = setup(true).await;
+        let root = fixture.env.temp_dir.path().to_path_buf();
+        let before = Project::new().unwrap().data;
+
+        let mut project = Project::new().unwrap();
+        project
+            .remove(&vec!["data/data.tsv".to_string()])
+            .await
+            .unwrap();
+
+        Project::migrate_to_hidden().unwrap();
+        assert!(!root.join("data_manifest.yml").exists());
+        assert!(root.join(".scidataflow/manifest.yml").exists());
+        assert!(Project::migrate_to_hidden().is_err());
+        let project = Project::new().unwrap();
+        assert!(!project.data.files.contains_key("data/data.tsv"));
+
+        // the migration is undone first, moving the manifest back
+        let undone = Project::undo().unwrap();
+        assert_eq!(undone.moves.len(), 1);
+        assert!(root.join("data_manifest.yml").exists());
+        assert!(!root.join(".scidataflow/manifest.yml").exists());
+        let project = Project::new().unwrap();
+        assert!(!project.data.files.contains_key("data/data.tsv"));
+
+        // then the removal, restoring the manifest to the old location
+        Project::undo().unwrap();
+        assert!(!root.join(".scidataflow/manifest.yml").exists());
+        assert_eq!(Project::new().unwrap().data, before);
+    }
+
+    #[tokio::test]
+    async fn test_add_with_checksums() {
+        let mut fixture = setup(false).await;
+        let md5 = |path: &str| format!("{:x}", md5::compute(fs::read(path).unwrap()));
+        let checksums = PathBuf::from("data/supplement/CHECKSUMS.md5");
+        let big_1 = md5("data/supplement/big_1.tsv.gz");
+        fs::write(
+            &checksums,
+            format!(
+                "{}  big_1.tsv.gz\n{}  big_2.tsv.gz\n",
+                big_1, "00000000000000000000000000000000"
+            ),
+        )
+        .unwrap();
+
+        // a mismatch is reported, and nothing is added
+        let result = fixture.project.add_with_checksums(&[], &checksums).await;
+        check_error(result, "data/supplement/big_2.tsv.gz");
+        assert!(fixture.project.data.files.is_empty());
+
+        fs::write(
+            &checksums,
+            format!(
+                "{}  big_1.tsv.gz\n{}  big_2.tsv.gz\n",
+                big_1,
+                md5("data/supplement/big_2.tsv.gz")
+            ),
+        )
+        .unwrap();
+        fixture
+            .project
+            .add_with_checksums(&["data/supplement/big_1.tsv.gz".to_string()], &checksums)
+            .await
+            .unwrap();
+        assert_eq!(fixture.project.data.files.len(), 1);
+        assert_eq!(
+            fixture.project.data.files["data/supplement/big_1.tsv.gz"].md5,
+            big_1
+        );
+
+        // without files, everything listed is added
+        fixture.project.data.files.clear();
+        fixture
+            .project
+            .add_with_checksums(&[], &checksums)
+            .await
+            .unwrap();
+        assert_eq!(fixture.project.data.files.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_track_without_remote_check() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        let file = "data/raw/medium.tsv.gz".to_string();
+        let files = vec![file.clone()];
+        fixture.project.untrack(&files, false).unwrap();
+        assert!(!fixture.project.data.files[&file].tracked);
+
+        // the mock remotes are unreachable, so this also checks
+        // that no network access happens without --check-remote
+        fixture.project.track(&files, false, false).await.unwrap();
+        assert!(fixture.project.data.files[&file].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_push_size_budget() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                size_budget: Some("1KB".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(fixture.project.data.metadata.size_budget, Some(1024));
+
+        // the check happens before any remote is contacted
+        let result = fixture
+            .project
+            .push(&PathFilter::new(), &PushOptions::default())
+            .await;
+        check_error(result, "over the project's size budget of 1.00 KB");
+
+        fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                size_budget: Some("0".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        assert_eq!(fixture.project.data.metadata.size_budget, None);
+    }
+
+    #[tokio::test]
+    async fn test_require_metadata() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        fixture.project.data.metadata.title = None;
+        fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                require_metadata: Some(true),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+
+        // the check happens before any remote is contacted
+        let result = fixture
+            .project
+            .push(&PathFilter::new(), &PushOptions::default())
+            .await;
+        let err = format!("{:#}", result.unwrap_err());
+        assert!(err.contains("sdf metadata --title <TITLE>"), "{}", err);
+        assert!(err.contains("sdf metadata --license <SPDX-ID>"), "{}", err);
+        // the fixture's config has an author
+        assert!(!err.contains("sdf config --name"), "{}", err);
+
+        fixture
+            .project
+            .set_metadata(&MetadataUpdate {
+                title: Some("A project".to_string()),
+                license: Some("CC0-1.0".to_string()),
+                ..Default::default()
+            })
+            .await
+            .unwrap();
+        fixture.project.check_required_metadata().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_manifest_hash_header() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        fixture.project.save().unwrap();
+        let header = |project: &Project| {
+            format!(
+                "{} {}",
+                MANIFEST_HASH_HEADER,
+                project.data.manifest_hash().unwrap()
+            )
+        };
+        let manifest = fs::read_to_string("data_manifest.yml").unwrap();
+        assert_eq!(manifest.lines().next().unwrap(), header(&fixture.project));
+
+        // the header is updated with the contents, and reloads unchanged
+        let hash = fixture.project.data.manifest_hash().unwrap();
+        fixture
+            .project
+            .untrack(&["data/raw/medium.tsv.gz".to_string()], false)
+            .unwrap();
+        let reloaded = Project::new().unwrap();
+        assert_ne!(reloaded.data.manifest_hash().unwrap(), hash);
+        let manifest = fs::read_to_string("data_manifest.yml").unwrap();
+        assert_eq!(manifest.lines().next().unwrap(), header(&reloaded));
+    }
+
+    #[tokio::test]
+    async fn test_track_if_needed() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        let file = "data/raw/medium.tsv.gz".to_string();
+        let files = vec![file.clone()];
+        assert!(fixture.project.data.files[&file].tracked);
+
+        // tracking a tracked file errors, unless --if-needed
+        let result = fixture.project.track(&files, false, false).await;
+        check_error(result, "already tracked");
+        fixture.project.track(&files, false, true).await.unwrap();
+        assert!(fixture.project.data.files[&file].tracked);
+
+        fixture.project.untrack(&files, true).unwrap();
+        fixture.project.untrack(&files, true).unwrap();
+        check_error(
+            fixture.project.untrack(&files, false),
+            "already not tracked",
+        );
+        assert!(!fixture.project.data.files[&file].tracked);
+
+        // files not in the manifest still error
+        let result = fixture
+            .project
+            .track(&["data/raw/missing.tsv".to_string()], false, true)
+            .await;
+        check_error(result, "not in the data manifest");
+    }
+
+    #[tokio::test]
+    async fn test_track_multiple_files() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        let files = vec![
+            "data/supplement/big_1.tsv.gz".to_string(),
+            "data/supplement/big_2.tsv.gz".to_string(),
+        ];
+        fixture.project.untrack(&files, false).unwrap();
+        assert!(files.iter().all(|f| !fixture.project.data.files[f].tracked));
+
+        // nothing is saved if any file fails
+        let mut with_missing = files.clone();
+        with_missing.push("data/raw/missing.tsv".to_string());
+        let result = fixture.project.track(&with_missing, false, false).await;
+        check_error(result, "$ sdf add data/raw/missing.tsv");
+        let reloaded = Project::new().unwrap();
+        assert!(files.iter().all(|f| !reloaded.data.files[f].tracked));
+
+        // with --if-needed, already tracked files are skipped
+        let mut project = reloaded;
+        let mut with_tracked = files.clone();
+        with_tracked.push("data/raw/medium.tsv.gz".to_string());
+        project.track(&with_tracked, false, true).await.unwrap();
+        let reloaded = Project::new().unwrap();
+        assert!(with_tracked.iter().all(|f| reloaded.data.files[f].tracked));
+    }
+
+    #[tokio::test]
+    async fn test_manifest_includes() {
+        let mut fixture = setup(true).await;
+        link_mock_remotes(&mut fixture);
+        let files_before = fixture.project.data.files.clone();
+
+        // split data/raw off into its own manifest
+        let include = "data/raw/data_manifest.yml".to_string();
+        fixture.project.data.includes.push(include.clone());
+        fixture.project.save().unwrap();
+
+        let sub_manifest = fs::read_to_string(&include).unwrap();
+        assert!(sub_manifest.contains("path: medium.tsv.gz"));
+        let root_manifest = fs::read_to_string("data_manifest.yml").unwrap();
+        assert!(root_manifest.contains(&include));
+        assert!(!root_manifest.contains("medium.tsv.gz"));
+
+        // loading merges the sub-manifest back in, with the same keys
+        let project = Project::new().unwrap();
+        assert_eq!(project.data.files, files_before);
+        // the root manifest's header is the merged hash
+        let header = format!(
+            "{} {}",
+            MANIFEST_HASH_HEADER,
+            project.data.manifest_hash().unwrap()
+        );
+        assert_eq!(root_manifest.lines().next().unwrap(), header);
+        assert!(project.data.remotes.contains_key("data/raw"));
+        assert!(project.data.remotes.contains_key("data/supplement"));
+        assert!(project.data.files["data/raw/medium.tsv.gz"].tracked);
+    }
+
+    #[tokio::test]
+    async fn test_manifest_includes_conflict() {
+        let mut fixture = setup(true).await;
+        let include = "data/raw/data_manifest.yml".to_string();
+        fixture.project.data.includes.push(include.clone());
+        fixture.project.save().unwrap();
+
+        // declare data/raw/medium.tsv.gz in the root manifest too
+        let root = serde_yaml::to_string(&fixture.project.data).unwrap();
+        fs::write("data_manifest.yml", root).unwrap();
+
+        let err = Project::new().err().unwrap();
+        let msg = format!("{:?}", err);
+        assert!(msg.contains("is declared in both"));
+        assert!(msg.contains("data/raw/medium.tsv.gz"));
+        assert!(msg.contains(&include));
+        assert!(msg.contains("data_manifest.yml' and"));
+    }
 }